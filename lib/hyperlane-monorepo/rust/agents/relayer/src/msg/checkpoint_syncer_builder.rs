@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ethers::prelude::Address;
+use eyre::{bail, eyre, Context, Result};
+use prometheus::IntGaugeVec;
+use tokio::sync::RwLock;
+use tracing::{debug, instrument, warn};
+
+use hyperlane_base::{
+    CheckpointSyncer, CheckpointSyncerConf, MultisigCheckpointSyncer, ReplicatedStorage,
+};
+use hyperlane_core::{HyperlaneDomain, ValidatorAnnounce, H160, H256};
+
+/// How long the checkpoint syncers built for a validator set are reused
+/// before the announced storage locations are looked up again.
+const VALIDATOR_SET_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Builds the checkpoint syncers for a validator set by looking up the
+/// storage locations each validator has announced on the origin chain.
+///
+/// Statically configured checkpoint syncers take precedence over announced
+/// storage locations. When a validator has announced several usable storage
+/// locations, reads fall back across them, most recently announced first.
+#[derive(Debug, Clone)]
+pub struct CheckpointSyncerBuilder {
+    origin: HyperlaneDomain,
+    origin_mailbox: H256,
+    validator_announce: Arc<dyn ValidatorAnnounce>,
+    static_checkpoint_syncers: HashMap<Address, Arc<dyn CheckpointSyncer>>,
+    allow_local_checkpoint_syncers: bool,
+    validator_checkpoint_index: IntGaugeVec,
+    /// Syncers that have already been built and verified, keyed by validator
+    /// and storage location.
+    cache: Arc<RwLock<HashMap<(Address, String), Arc<dyn CheckpointSyncer>>>>,
+    /// Syncers built for each validator set, and when they were built.
+    validator_sets: Arc<RwLock<HashMap<Vec<H256>, (Instant, MultisigCheckpointSyncer)>>>,
+}
+
+impl CheckpointSyncerBuilder {
+    pub fn new(
+        origin: HyperlaneDomain,
+        origin_mailbox: H256,
+        validator_announce: Arc<dyn ValidatorAnnounce>,
+        static_checkpoint_syncers: HashMap<Address, Arc<dyn CheckpointSyncer>>,
+        allow_local_checkpoint_syncers: bool,
+        validator_checkpoint_index: IntGaugeVec,
+    ) -> Self {
+        Self {
+            origin,
+            origin_mailbox,
+            validator_announce,
+            static_checkpoint_syncers,
+            allow_local_checkpoint_syncers,
+            validator_checkpoint_index,
+            cache: Default::default(),
+            validator_sets: Default::default(),
+        }
+    }
+
+    /// Build a MultisigCheckpointSyncer for the provided validators. Validators
+    /// without a usable storage location are left out.
+    ///
+    /// The result is reused for the same validator set for
+    /// `VALIDATOR_SET_CACHE_TTL`, so new announcements are picked up after at
+    /// most that long. Validator sets without any usable storage location are
+    /// not cached.
+    #[instrument(err, skip(self))]
+    pub async fn build(&self, validators: &[H256]) -> Result<MultisigCheckpointSyncer> {
+        if let Some((built_at, checkpoint_syncer)) =
+            self.validator_sets.read().await.get(validators)
+        {
+            if built_at.elapsed() < VALIDATOR_SET_CACHE_TTL {
+                return Ok(checkpoint_syncer.clone());
+            }
+        }
+
+        let checkpoint_syncers = self.build_checkpoint_syncers(validators).await?;
+        let usable = !checkpoint_syncers.is_empty();
+        let checkpoint_syncer = MultisigCheckpointSyncer::new(checkpoint_syncers);
+        if usable {
+            self.validator_sets.write().await.insert(
+                validators.to_vec(),
+                (Instant::now(), checkpoint_syncer.clone()),
+            );
+        }
+        Ok(checkpoint_syncer)
+    }
+
+    /// Build the checkpoint syncer of each validator from the currently
+    /// announced storage locations
+    async fn build_checkpoint_syncers(
+        &self,
+        validators: &[H256],
+    ) -> Result<HashMap<Address, Arc<dyn CheckpointSyncer>>> {
+        let storage_locations = self
+            .validator_announce
+            .get_announced_storage_locations(validators)
+            .await?;
+
+        let mut checkpoint_syncers = HashMap::new();
+        for (validator, validator_storage_locations) in validators.iter().zip(storage_locations) {
+            let validator = H160::from(*validator);
+            if let Some(checkpoint_syncer) = self.static_checkpoint_syncers.get(&validator) {
+                checkpoint_syncers.insert(validator, checkpoint_syncer.clone());
+                continue;
+            }
+
+            // Read from the most recently announced storage locations first
+            let mut announced = Vec::new();
+            for storage_location in validator_storage_locations.iter().rev() {
+                match self.build_announced(validator, storage_location).await {
+                    Ok(checkpoint_syncer) => {
+                        announced.push((storage_location.clone(), checkpoint_syncer));
+                    }
+                    Err(err) => {
+                        warn!(
+                            ?validator,
+                            ?storage_location,
+                            error = ?err,
+                            "Unable to use announced storage location"
+                        );
+                    }
+                }
+            }
+
+            match announced.len() {
+                0 => debug!(?validator, "No usable storage location for validator"),
+                1 => {
+                    checkpoint_syncers.insert(validator, announced.remove(0).1);
+                }
+                _ => {
+                    checkpoint_syncers
+                        .insert(validator, Arc::new(ReplicatedStorage::new(announced, None)));
+                }
+            }
+        }
+        Ok(checkpoint_syncers)
+    }
+
+    /// Build the checkpoint syncer for an announced storage location and
+    /// verify that the announcement it holds was signed by the validator.
+    async fn build_announced(
+        &self,
+        validator: Address,
+        storage_location: &str,
+    ) -> Result<Arc<dyn CheckpointSyncer>> {
+        let key = (validator, storage_location.to_owned());
+        if let Some(checkpoint_syncer) = self.cache.read().await.get(&key) {
+            return Ok(checkpoint_syncer.clone());
+        }
+
+        let conf: CheckpointSyncerConf = storage_location.parse()?;
+        if matches!(conf, CheckpointSyncerConf::LocalStorage { .. })
+            && !self.allow_local_checkpoint_syncers
+        {
+            bail!("Local checkpoint syncers are not allowed");
+        }
+
+        let gauge = self
+            .validator_checkpoint_index
+            .with_label_values(&[self.origin.name(), &format!("{validator:#x}")]);
+        let checkpoint_syncer: Arc<dyn CheckpointSyncer> = conf.build(Some(gauge))?.into();
+
+        let announcement = checkpoint_syncer
+            .fetch_announcement()
+            .await
+            .context("Fetching announcement")?
+            .ok_or_else(|| eyre!("No announcement found at storage location"))?;
+        announcement
+            .verify(validator)
+            .context("Announcement was not signed by the validator")?;
+        if announcement.value.validator != validator
+            || announcement.value.mailbox_address != self.origin_mailbox
+            || announcement.value.mailbox_domain != self.origin.id()
+            || announcement.value.storage_location != storage_location
+        {
+            bail!(
+                "Announcement does not match the announced storage location: {}",
+                announcement.value
+            );
+        }
+
+        self.cache
+            .write()
+            .await
+            .insert(key, checkpoint_syncer.clone());
+        Ok(checkpoint_syncer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prometheus::opts;
+    use tempfile::{tempdir, TempDir};
+
+    use hyperlane_base::LocalStorage;
+    use hyperlane_core::{
+        Announcement, FailureKind, HyperlaneSigner, HyperlaneSignerExt, KnownHyperlaneDomain,
+        SignedAnnouncement,
+    };
+    use hyperlane_test::mock_chain::{MockCall, MockChain, MockChainValidatorAnnounce};
+    use hyperlane_test::test_utils::TestSigner;
+
+    use super::*;
+
+    const MAILBOX: H256 = H256::repeat_byte(1);
+
+    struct Setup {
+        chain: Arc<MockChain>,
+        validator_announce: Arc<MockChainValidatorAnnounce>,
+        builder: CheckpointSyncerBuilder,
+        storage: LocalStorage,
+        dir: TempDir,
+    }
+
+    fn setup(allow_local_checkpoint_syncers: bool) -> Setup {
+        let origin = HyperlaneDomain::Known(KnownHyperlaneDomain::Test1);
+        let chain = Arc::new(MockChain::new(origin.clone()));
+        let validator_announce = Arc::new(MockChainValidatorAnnounce::new(
+            chain.clone(),
+            H256::repeat_byte(2),
+        ));
+        let builder = CheckpointSyncerBuilder::new(
+            origin,
+            MAILBOX,
+            validator_announce.clone(),
+            HashMap::new(),
+            allow_local_checkpoint_syncers,
+            IntGaugeVec::new(
+                opts!("validator_checkpoint_index", "test"),
+                &["origin", "validator"],
+            )
+            .unwrap(),
+        );
+        let dir = tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path().to_str().unwrap(), None);
+        Setup {
+            chain,
+            validator_announce,
+            builder,
+            storage,
+            dir,
+        }
+    }
+
+    fn announcement(storage_location: String) -> Announcement {
+        Announcement {
+            validator: TestSigner::default().eth_address(),
+            mailbox_address: MAILBOX,
+            mailbox_domain: KnownHyperlaneDomain::Test1 as u32,
+            storage_location,
+        }
+    }
+
+    async fn write_announcement(setup: &Setup, signed_announcement: &SignedAnnouncement) {
+        setup
+            .storage
+            .write_announcement(signed_announcement)
+            .await
+            .unwrap();
+    }
+
+    fn validator() -> Address {
+        TestSigner::default().eth_address()
+    }
+
+    #[tokio::test]
+    async fn verifies_announced_storage_location() {
+        let setup = setup(true);
+        let location = setup.storage.announcement_location();
+        let signed = TestSigner::default()
+            .sign(announcement(location.clone()))
+            .await
+            .unwrap();
+        write_announcement(&setup, &signed).await;
+
+        assert!(setup
+            .builder
+            .build_announced(validator(), &location)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatched_announcements() {
+        let setup = setup(true);
+        let location = setup.storage.announcement_location();
+        let signer = TestSigner::default();
+
+        let mut wrong_location = announcement("file:///somewhere/else".to_owned());
+        let mut wrong_mailbox = announcement(location.clone());
+        wrong_mailbox.mailbox_address = H256::repeat_byte(9);
+        let mut wrong_domain = announcement(location.clone());
+        wrong_domain.mailbox_domain = 1;
+        for mismatched in [wrong_location.clone(), wrong_mailbox, wrong_domain] {
+            write_announcement(&setup, &signer.sign(mismatched).await.unwrap()).await;
+            assert!(setup
+                .builder
+                .build_announced(validator(), &location)
+                .await
+                .is_err());
+        }
+
+        // Signed by someone other than the validator
+        wrong_location.storage_location = location.clone();
+        let other = TestSigner::from_key(
+            "2222222222222222222222222222222222222222222222222222222222222222",
+        );
+        write_announcement(&setup, &other.sign(wrong_location).await.unwrap()).await;
+        assert!(setup
+            .builder
+            .build_announced(validator(), &location)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_and_unreadable_announcements() {
+        let setup = setup(true);
+        let location = setup.storage.announcement_location();
+
+        assert!(setup.storage.fetch_announcement().await.unwrap().is_none());
+        assert!(setup
+            .builder
+            .build_announced(validator(), &location)
+            .await
+            .is_err());
+
+        let path = format!("{}/announcement.json", setup.dir.path().display());
+        std::fs::write(path, "not an announcement").unwrap();
+        assert!(setup.storage.fetch_announcement().await.is_err());
+        assert!(setup
+            .builder
+            .build_announced(validator(), &location)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_local_storage_unless_allowed() {
+        let setup = setup(false);
+        let location = setup.storage.announcement_location();
+        let signed = TestSigner::default()
+            .sign(announcement(location.clone()))
+            .await
+            .unwrap();
+        write_announcement(&setup, &signed).await;
+
+        assert!(setup
+            .builder
+            .build_announced(validator(), &location)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn caches_checkpoint_syncers_per_validator_set() {
+        let setup = setup(true);
+        let location = setup.storage.announcement_location();
+        let signed = TestSigner::default()
+            .sign(announcement(location))
+            .await
+            .unwrap();
+        write_announcement(&setup, &signed).await;
+        let validators = [H256::from(validator())];
+
+        // Nothing is announced on chain yet, so nothing is cached
+        setup.builder.build(&validators).await.unwrap();
+        setup
+            .validator_announce
+            .announce(signed, None)
+            .await
+            .unwrap();
+        setup.builder.build(&validators).await.unwrap();
+
+        // The cached syncers are used without looking up the announcements
+        setup
+            .chain
+            .fail(MockCall::AnnouncedLocations, FailureKind::Transient, 1);
+        setup.builder.build(&validators).await.unwrap();
+        assert!(setup.builder.build(&[H256::repeat_byte(7)]).await.is_err());
+    }
+}
@@ -3,30 +3,31 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info, instrument};
 
-use hyperlane_base::{CachingMailbox, ChainSetup, CoreMetrics, MultisigCheckpointSyncer};
+use hyperlane_base::{CachingMailbox, ChainSetup, CoreMetrics};
 use hyperlane_core::{HyperlaneChain, HyperlaneMessage, Mailbox, MultisigIsm};
 
 use crate::merkle_tree_builder::MerkleTreeBuilder;
+use crate::msg::checkpoint_syncer_builder::CheckpointSyncerBuilder;
 
 #[derive(Debug, Clone)]
 pub struct MetadataBuilder {
     metrics: Arc<CoreMetrics>,
     chain_setup: ChainSetup,
-    checkpoint_syncer: MultisigCheckpointSyncer,
+    checkpoint_syncer_builder: CheckpointSyncerBuilder,
     prover_sync: Arc<RwLock<MerkleTreeBuilder>>,
 }
 
 impl MetadataBuilder {
     pub fn new(
         chain_setup: ChainSetup,
-        checkpoint_syncer: MultisigCheckpointSyncer,
+        checkpoint_syncer_builder: CheckpointSyncerBuilder,
         prover_sync: Arc<RwLock<MerkleTreeBuilder>>,
         metrics: Arc<CoreMetrics>,
     ) -> Self {
         MetadataBuilder {
             metrics,
             chain_setup,
-            checkpoint_syncer,
+            checkpoint_syncer_builder,
             prover_sync,
         }
     }
@@ -59,9 +60,9 @@ impl MetadataBuilder {
             .await?;
 
         let (validators, threshold) = multisig_ism.validators_and_threshold(message).await?;
+        let checkpoint_syncer = self.checkpoint_syncer_builder.build(&validators).await?;
         let highest_known_nonce = self.prover_sync.read().await.count() - 1;
-        if let Some(checkpoint) = self
-            .checkpoint_syncer
+        if let Some(checkpoint) = checkpoint_syncer
             .fetch_checkpoint_in_range(
                 &validators,
                 threshold.into(),
@@ -108,3 +109,152 @@ impl MetadataBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use prometheus::{opts, IntGaugeVec};
+    use tempfile::tempdir;
+
+    use hyperlane_base::{ChainConf, CheckpointSyncer, CoreContractAddresses, LocalStorage};
+    use hyperlane_core::db::HyperlaneDB;
+    use hyperlane_core::{
+        Announcement, Finality, HyperlaneContract, HyperlaneSigner, HyperlaneSignerExt,
+        ValidatorAnnounce, H256,
+    };
+    use hyperlane_test::mock_chain::{self as h_mock, MockChain, MockChainMailbox};
+    use hyperlane_test::test_utils::{run_test_db, TestSigner};
+
+    use super::*;
+
+    const MAILBOX: &str = "0x0101010101010101010101010101010101010101";
+    const INTERCHAIN_GAS_PAYMASTER: &str = "0x0202020202020202020202020202020202020202";
+    const VALIDATOR_ANNOUNCE: &str = "0x0303030303030303030303030303030303030303";
+    const ISM: H256 = H256::repeat_byte(4);
+    const RECIPIENT: H256 = H256::repeat_byte(5);
+
+    /// Settings for a mock chain shared under `chain` within this process
+    fn chain_setup(name: &str, domain: &str, chain: &str) -> ChainSetup {
+        ChainSetup {
+            name: name.into(),
+            domain: domain.into(),
+            finality_blocks: "0".into(),
+            addresses: CoreContractAddresses {
+                mailbox: MAILBOX.into(),
+                interchain_gas_paymaster: INTERCHAIN_GAS_PAYMASTER.into(),
+                validator_announce: VALIDATOR_ANNOUNCE.into(),
+            },
+            chain: ChainConf::Mock(h_mock::ConnectionConf {
+                chain: Some(chain.into()),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn relays_messages_signed_by_validators() {
+        run_test_db(|db| async move {
+            let origin = chain_setup("test1", "13371", "metadata_builder_origin");
+            let destination = chain_setup("test2", "13372", "metadata_builder_destination");
+            let metrics = Arc::new(
+                CoreMetrics::new("relayer_test", None, prometheus::Registry::new()).unwrap(),
+            );
+            let origin_mailbox = origin.build_mailbox(&metrics).await.unwrap();
+            let (message, _) = origin_mailbox
+                .dispatch(13372, RECIPIENT, &[1, 2, 3])
+                .await
+                .unwrap();
+
+            // The validator signs the latest checkpoint and announces where
+            // it is stored, like the validator agent does
+            let validator = TestSigner::default();
+            let dir = tempdir().unwrap();
+            let storage = LocalStorage::new(dir.path().to_str().unwrap(), None);
+            let checkpoint = origin_mailbox
+                .latest_checkpoint(Finality::Blocks(0))
+                .await
+                .unwrap();
+            storage
+                .write_checkpoint(&validator.sign(checkpoint).await.unwrap())
+                .await
+                .unwrap();
+            let announcement = validator
+                .sign(Announcement {
+                    validator: validator.eth_address(),
+                    mailbox_address: origin_mailbox.address(),
+                    mailbox_domain: 13371,
+                    storage_location: storage.announcement_location(),
+                })
+                .await
+                .unwrap();
+            storage.write_announcement(&announcement).await.unwrap();
+            let validator_announce: Arc<dyn ValidatorAnnounce> = origin
+                .build_validator_announce(&metrics)
+                .await
+                .unwrap()
+                .into();
+            validator_announce
+                .announce(announcement, None)
+                .await
+                .unwrap();
+
+            // The destination trusts the validator for messages from the origin
+            let destination_chain = MockChain::named(
+                "metadata_builder_destination",
+                destination.domain().unwrap(),
+            );
+            destination_chain.set_validators(13371, vec![validator.eth_address().into()], 1);
+            destination_chain.set_default_ism(ISM);
+            // Any mock contract will do as a recipient
+            MockChainMailbox::new(destination_chain, RECIPIENT);
+
+            // The relayer finds the checkpoint through the announcement and
+            // proves the message against it
+            let origin_db = HyperlaneDB::new("test1", db.clone());
+            origin_db.store_message(&message).unwrap();
+            let mut prover_sync = MerkleTreeBuilder::new(origin_db);
+            prover_sync.update_to_index(message.nonce).await.unwrap();
+            let checkpoint_syncer_builder = CheckpointSyncerBuilder::new(
+                origin.domain().unwrap(),
+                origin_mailbox.address(),
+                validator_announce,
+                HashMap::new(),
+                true,
+                IntGaugeVec::new(
+                    opts!("validator_checkpoint_index", "test"),
+                    &["origin", "validator"],
+                )
+                .unwrap(),
+            );
+            let metadata_builder = MetadataBuilder::new(
+                destination.clone(),
+                checkpoint_syncer_builder,
+                Arc::new(RwLock::new(prover_sync)),
+                metrics.clone(),
+            );
+            let destination_mailbox = CachingMailbox::new(
+                destination.build_mailbox(&metrics).await.unwrap().into(),
+                HyperlaneDB::new("test2", db),
+                destination
+                    .build_mailbox_indexer(&metrics)
+                    .await
+                    .unwrap()
+                    .into(),
+            );
+            let metadata = metadata_builder
+                .fetch_metadata(&message, destination_mailbox.clone())
+                .await
+                .unwrap()
+                .expect("no metadata for a signed checkpoint");
+
+            let outcome = destination_mailbox
+                .process(&message, &metadata, None)
+                .await
+                .unwrap();
+            assert!(outcome.executed);
+            assert!(destination_mailbox.delivered(message.id()).await.unwrap());
+        })
+        .await
+    }
+}
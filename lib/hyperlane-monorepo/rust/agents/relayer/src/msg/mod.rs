@@ -1,8 +1,9 @@
 use std::cmp::Ordering;
 use std::time::Instant;
 
-use hyperlane_core::HyperlaneMessage;
+use hyperlane_core::{HyperlaneMessage, RevertReason};
 
+pub mod checkpoint_syncer_builder;
 pub mod gas_payment;
 pub mod gelato_submitter;
 pub mod metadata_builder;
@@ -33,6 +34,8 @@ pub struct SubmitMessageArgs {
     pub message: HyperlaneMessage,
     num_retries: u32,
     last_attempted_at: Instant,
+    /// Why the last attempt to deliver the message reverted, if it did
+    last_revert_reason: Option<RevertReason>,
 }
 
 impl SubmitMessageArgs {
@@ -41,6 +44,7 @@ impl SubmitMessageArgs {
             message,
             num_retries: 0,
             last_attempted_at: Instant::now(),
+            last_revert_reason: None,
         }
     }
 }
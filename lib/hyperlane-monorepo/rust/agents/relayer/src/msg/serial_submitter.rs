@@ -3,14 +3,19 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use eyre::{bail, Result};
-use prometheus::{IntCounter, IntGauge};
+use prometheus::{Counter, IntCounter, IntCounterVec, IntGauge};
 use tokio::sync::mpsc::{self, error::TryRecvError};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tracing::{debug, info, info_span, instrument, instrument::Instrumented, warn, Instrument};
+use tracing::{
+    debug, error, info, info_span, instrument, instrument::Instrumented, warn, Instrument,
+};
 
 use hyperlane_base::{CachingMailbox, CoreMetrics};
-use hyperlane_core::{db::HyperlaneDB, HyperlaneChain, HyperlaneDomain, Mailbox, U256};
+use hyperlane_core::{
+    db::HyperlaneDB, FailureKind, HyperlaneChain, HyperlaneDomain, Mailbox, RevertReason,
+    TxOutcome, U256,
+};
 
 use super::metadata_builder::MetadataBuilder;
 use super::{gas_payment::GasPaymentEnforcer, SubmitMessageArgs};
@@ -98,6 +103,14 @@ use super::{gas_payment::GasPaymentEnforcer, SubmitMessageArgs};
 /// source chain. If eligible for delivery, the message is promoted to the runnable queue and
 /// prioritized accordingly. These messages that have never been tried before are pushed to
 /// the front of the runnable queue.
+///
+/// Errors are classified by their `FailureKind`. Transient failures and nonce conflicts keep
+/// the message at the front of the run queue and pause submissions for a backoff which doubles
+/// with every consecutive failure. Rate limits, insufficient funds and invalid configuration
+/// pause all submissions to the destination for a while, since every other message would fail
+/// the same way; the latter two are logged as errors as they need an operator to step in.
+/// Reverts and unclassified errors send a message to the back of the run queue. Every failed
+/// attempt counts against the message.
 
 // TODO(webbhorn): Do we also want to await finality_blocks on source chain before attempting
 // submission? Does this already happen?
@@ -125,6 +138,12 @@ pub(crate) struct SerialSubmitter {
     gas_payment_enforcer: Arc<GasPaymentEnforcer>,
     /// Hard limit on transaction gas when submitting a transaction.
     transaction_gas_limit: Option<U256>,
+    /// No messages are submitted until this time, e.g. after being rate limited or running
+    /// out of funds.
+    paused_until: Option<Instant>,
+    /// The number of submissions in a row which failed with an error, used to back off from
+    /// transient failures.
+    consecutive_errors: u32,
 }
 
 impl SerialSubmitter {
@@ -147,6 +166,8 @@ impl SerialSubmitter {
             metrics,
             gas_payment_enforcer,
             transaction_gas_limit,
+            paused_until: None,
+            consecutive_errors: 0,
         }
     }
 
@@ -205,6 +226,10 @@ impl SerialSubmitter {
             .run_queue_length_gauge
             .set(self.run_queue.len() as i64);
 
+        if matches!(self.paused_until, Some(until) if Instant::now() < until) {
+            return Ok(());
+        }
+
         // Pick the next message to try processing.
         let mut msg = match self.run_queue.pop_front() {
             Some(m) => m,
@@ -226,19 +251,40 @@ impl SerialSubmitter {
             }
         }
 
-        match self.process_message(&msg).await {
+        let result = self.process_message(&mut msg).await;
+        if result.is_ok() {
+            self.consecutive_errors = 0;
+        }
+        match result {
             Ok(true) => {
                 info!(id=?msg.message.id(), nonce=msg.message.nonce, "Message processed");
                 self.record_message_process_success(&msg)?;
                 return Ok(());
             }
             Ok(false) => {
-                info!(id=?msg.message.id(), nonce=msg.message.nonce, "Message not processed");
+                info!(id=?msg.message.id(), nonce=msg.message.nonce, last_revert_reason=?msg.last_revert_reason.as_ref().map(ToString::to_string), "Message not processed");
             }
             // We expect this branch to be hit when there is unexpected behavior -
             // defined behavior like gas estimation failing will not hit this branch.
             Err(err) => {
-                warn!(id=?msg.message.id(), nonce=msg.message.nonce, error=?err, "Error occurred when attempting to process message");
+                let kind = FailureKind::of(&*err);
+                if kind.needs_attention() {
+                    error!(id=?msg.message.id(), nonce=msg.message.nonce, error=?err, %kind, "Error occurred when attempting to process message, pausing submission");
+                } else {
+                    warn!(id=?msg.message.id(), nonce=msg.message.nonce, error=?err, %kind, "Error occurred when attempting to process message");
+                }
+                self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+                if let Some(pause) = pause_duration(kind, self.consecutive_errors) {
+                    self.paused_until = Some(Instant::now() + pause);
+                }
+                // These failures are not caused by the message itself, so retry it first
+                // once the pause is over.
+                if !matches!(kind, FailureKind::Reverted | FailureKind::Unknown) {
+                    msg.num_retries += 1;
+                    msg.last_attempted_at = Instant::now();
+                    self.run_queue.push_front(msg);
+                    return Ok(());
+                }
             }
         }
 
@@ -255,9 +301,10 @@ impl SerialSubmitter {
     /// in this fn or by a view call to the Mailbox contract discovering the message has already
     /// been processed, Ok(true) is returned. If this message is unable to
     /// be processed, either due to failed gas estimation or an insufficient gas payment,
-    /// Ok(false) is returned.
+    /// Ok(false) is returned. If estimation or the transaction reverted, the reason is kept
+    /// with the message.
     #[instrument(skip(self, msg), fields(msg_nonce=msg.message.nonce, msg_id=format!("{:x}", msg.message.id())))]
-    async fn process_message(&self, msg: &SubmitMessageArgs) -> Result<bool> {
+    async fn process_message(&self, msg: &mut SubmitMessageArgs) -> Result<bool> {
         // If the message has already been processed, e.g. due to another relayer having already
         // processed, then mark it as already-processed, and move on to the next tick.
         // TODO(webbhorn): Make this robust to re-orgs on mailbox.
@@ -279,10 +326,19 @@ impl SerialSubmitter {
                 .await
             {
                 Ok(tx_cost_estimate) => tx_cost_estimate,
-                Err(err) => {
-                    info!(msg=?msg, error=?err, "Error estimating process costs");
+                Err(err) if matches!(err.kind(), FailureKind::Reverted | FailureKind::Unknown) => {
+                    if let Some(reason) = err.revert_reason() {
+                        warn!(msg=?msg, reason=%reason, "Estimating process costs reverted");
+                        self.metrics.record_revert("estimate", reason);
+                        msg.last_revert_reason = Some(reason.clone());
+                    } else {
+                        info!(msg=?msg, error=?err, "Error estimating process costs");
+                    }
                     return Ok(false);
                 }
+                // Estimation can also fail for reasons unrelated to the message, e.g. the
+                // signer running out of funds, which the caller handles.
+                Err(err) => return Err(err.into()),
             };
 
             // If the gas payment requirement hasn't been met, move to the next tick.
@@ -298,11 +354,6 @@ impl SerialSubmitter {
             // Go ahead and attempt processing of message to destination chain.
             debug!(gas_payment=?gas_payment, msg=?msg, "Ready to process message");
 
-            // TODO: consider differentiating types of processing errors, and pushing to the front of the
-            // run queue for intermittent types of errors that can occur even if a message's processing isn't
-            // reverting, e.g. timeouts or txs being dropped from the mempool. To avoid consistently retrying
-            // only these messages, the number of retries could be considered.
-
             let gas_limit = tx_cost_estimate.gas_limit;
 
             if let Some(max_limit) = self.transaction_gas_limit {
@@ -328,13 +379,24 @@ impl SerialSubmitter {
 
                 // Only mark the message as processed if the transaction didn't revert.
                 Ok(outcome) if outcome.executed => {
+                    self.metrics.record_process_outcome(&outcome);
                     info!(hash=?outcome.txid,
+                    gas_used=?outcome.gas_used, cost=?outcome.cost(), block=?outcome.block_number,
                     wq_sz=?self.wait_queue.len(), rq_sz=?self.run_queue.len(),
                     "Message successfully processed by transaction");
                     Ok(true)
                 }
                 Ok(outcome) => {
-                    info!(hash=?outcome.txid, "Transaction attempting to process transaction reverted");
+                    self.metrics.record_process_outcome(&outcome);
+                    warn!(hash=?outcome.txid,
+                    gas_used=?outcome.gas_used, cost=?outcome.cost(), block=?outcome.block_number,
+                    reason=?outcome.revert_reason.as_ref().map(ToString::to_string),
+                    revert_data=?outcome.revert_data.as_ref().map(ethers::utils::hex::encode),
+                    "Transaction attempting to process transaction reverted");
+                    if let Some(reason) = &outcome.revert_reason {
+                        self.metrics.record_revert("process", reason);
+                    }
+                    msg.last_revert_reason = outcome.revert_reason;
                     Ok(false)
                 }
                 Err(e) => Err(e.into()),
@@ -362,12 +424,35 @@ impl SerialSubmitter {
     }
 }
 
+/// How long to pause all submissions after a failure of the given kind, if at all.
+/// `consecutive_errors` includes this failure.
+fn pause_duration(kind: FailureKind, consecutive_errors: u32) -> Option<Duration> {
+    match kind {
+        FailureKind::RateLimited => Some(Duration::from_secs(30)),
+        FailureKind::InsufficientFunds | FailureKind::InvalidConfig => {
+            Some(Duration::from_secs(60 * 5))
+        }
+        // Start at 1s and double up to a minute while the failures continue
+        FailureKind::Transient | FailureKind::NonceConflict => Some(
+            Duration::from_secs(1)
+                .saturating_mul(2u32.saturating_pow(consecutive_errors.saturating_sub(1)))
+                .min(Duration::from_secs(60)),
+        ),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct SerialSubmitterMetrics {
     run_queue_length_gauge: IntGauge,
     wait_queue_length_gauge: IntGauge,
     processed_gauge: IntGauge,
     messages_processed_count: IntCounter,
+    process_gas_used: IntCounter,
+    process_cost: Counter,
+    message_reverts: IntCounterVec,
+    origin: String,
+    destination: String,
 
     /// Private state used to update actual metrics each tick.
     max_submitted_nonce: u32,
@@ -400,7 +485,54 @@ impl SerialSubmitterMetrics {
                 origin,
                 destination,
             ]),
+            process_gas_used: metrics
+                .transaction_gas_used()
+                .with_label_values(&[destination, "process"]),
+            process_cost: metrics
+                .transaction_cost()
+                .with_label_values(&[destination, "process"]),
+            message_reverts: metrics.message_reverts(),
+            origin: origin.to_owned(),
+            destination: destination.to_owned(),
             max_submitted_nonce: 0,
         }
     }
+
+    /// Record that estimating or submitting a process transaction reverted
+    fn record_revert(&self, stage: &str, reason: &RevertReason) {
+        self.message_reverts
+            .with_label_values(&[&self.origin, &self.destination, stage, &reason.label()])
+            .inc();
+    }
+
+    /// Record the gas used by and the cost of a process transaction
+    fn record_process_outcome(&self, outcome: &TxOutcome) {
+        self.process_gas_used.inc_by(outcome.gas_used.low_u64());
+        if let Some(cost) = outcome.cost() {
+            self.process_cost.inc_by(cost.to_f64_lossy());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backs_off_from_transient_failures() {
+        let pauses: Vec<_> = (1..=8)
+            .map(|errors| pause_duration(FailureKind::Transient, errors).unwrap())
+            .map(|pause| pause.as_secs())
+            .collect();
+        assert_eq!(pauses, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(
+            pause_duration(FailureKind::NonceConflict, 2),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            pause_duration(FailureKind::RateLimited, 5),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(pause_duration(FailureKind::Reverted, 5), None);
+    }
 }
@@ -2,7 +2,7 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use eyre::{Context, Result};
+use eyre::{eyre, Context, Result};
 use tokio::sync::{
     mpsc::{self, UnboundedReceiver, UnboundedSender},
     RwLock,
@@ -13,13 +13,14 @@ use tracing::{info, info_span, instrument::Instrumented, Instrument};
 use hyperlane_base::{
     chains::{GelatoConf, TransactionSubmissionType},
     run_all, BaseAgent, CachingInterchainGasPaymaster, CachingMailbox, ContractSyncMetrics,
-    CoreMetrics, HyperlaneAgentCore, MultisigCheckpointSyncer,
+    CoreMetrics, HyperlaneAgentCore,
 };
-use hyperlane_core::{db::DB, HyperlaneChain, HyperlaneDomain, U256};
+use hyperlane_core::{db::DB, HyperlaneChain, HyperlaneContract, HyperlaneDomain, U256};
 
 use crate::{
     merkle_tree_builder::MerkleTreeBuilder,
     msg::{
+        checkpoint_syncer_builder::CheckpointSyncerBuilder,
         gas_payment::GasPaymentEnforcer,
         gelato_submitter::{GelatoSubmitter, GelatoSubmitterMetrics},
         metadata_builder::MetadataBuilder,
@@ -37,7 +38,7 @@ pub struct Relayer {
     core: HyperlaneAgentCore,
     mailboxes: HashMap<HyperlaneDomain, CachingMailbox>,
     interchain_gas_paymasters: HashMap<HyperlaneDomain, CachingInterchainGasPaymaster>,
-    multisig_checkpoint_syncer: MultisigCheckpointSyncer,
+    checkpoint_syncer_builder: CheckpointSyncerBuilder,
     gas_payment_enforcer: Arc<GasPaymentEnforcer>,
     whitelist: Arc<MatchingList>,
     blacklist: Arc<MatchingList>,
@@ -83,11 +84,6 @@ impl BaseAgent for Relayer {
             .build_all_interchain_gas_paymasters(chain_names.as_slice(), &metrics, db)
             .await?;
 
-        let multisig_checkpoint_syncer = settings.multisigcheckpointsyncer.build(
-            &settings.originchainname,
-            core.metrics.validator_checkpoint_index(),
-        )?;
-
         let whitelist = Arc::new(parse_matching_list(&settings.whitelist));
         let blacklist = Arc::new(parse_matching_list(&settings.blacklist));
 
@@ -121,6 +117,27 @@ impl BaseAgent for Relayer {
             .chain_setup(&settings.originchainname)
             .context("Relayer must run on a configured chain")?
             .domain()?;
+        let origin_mailbox = mailboxes
+            .get(&origin_chain)
+            .ok_or_else(|| eyre!("No mailbox configured for origin chain {origin_chain}"))?;
+
+        let static_checkpoint_syncers =
+            settings.multisigcheckpointsyncer.build_checkpoint_syncers(
+                &settings.originchainname,
+                core.metrics.validator_checkpoint_index(),
+            )?;
+        let validator_announce = settings
+            .build_validator_announce(&settings.originchainname, &metrics)
+            .await
+            .context("Relayer needs a validator announce contract on the origin chain")?;
+        let checkpoint_syncer_builder = CheckpointSyncerBuilder::new(
+            origin_chain.clone(),
+            origin_mailbox.address(),
+            validator_announce.into(),
+            static_checkpoint_syncers,
+            settings.allowlocalcheckpointsyncers,
+            core.metrics.validator_checkpoint_index(),
+        );
 
         let gas_enforcement_policy = settings.gaspaymentenforcement.policy;
         let gas_enforcement_whitelist =
@@ -135,7 +152,7 @@ impl BaseAgent for Relayer {
         let gas_payment_enforcer = Arc::new(GasPaymentEnforcer::new(
             gas_enforcement_policy,
             gas_enforcement_whitelist,
-            mailboxes.get(&origin_chain).unwrap().db().clone(),
+            origin_mailbox.db().clone(),
         ));
 
         Ok(Self {
@@ -143,7 +160,7 @@ impl BaseAgent for Relayer {
             core,
             mailboxes,
             interchain_gas_paymasters,
-            multisig_checkpoint_syncer,
+            checkpoint_syncer_builder,
             gas_payment_enforcer,
             whitelist,
             blacklist,
@@ -186,7 +203,7 @@ impl BaseAgent for Relayer {
             let txsubmission = chain_setup.txsubmission;
             let metadata_builder = MetadataBuilder::new(
                 chain_setup,
-                self.multisig_checkpoint_syncer.clone(),
+                self.checkpoint_syncer_builder.clone(),
                 prover_sync.clone(),
                 self.core.metrics.clone(),
             );
@@ -41,8 +41,14 @@ decl_settings!(Relayer {
     // Optional list of destination chains. If none are provided, ALL chains in chain_setup
     // will be used, excluding the origin chain.
     destinationchainnames: Option<String>,
-    /// The multisig checkpoint syncer configuration
+    /// Optional checkpoint syncer configuration per validator. Validators not
+    /// listed here are discovered from their on-chain announcements.
+    #[serde(default)]
     multisigcheckpointsyncer: hyperlane_base::MultisigCheckpointSyncerConf,
+    /// Whether announced `file://` storage locations may be used. Defaults to
+    /// false as these point at the relayer's own filesystem.
+    #[serde(default)]
+    allowlocalcheckpointsyncers: bool,
     /// The gas payment enforcement configuration
     gaspaymentenforcement: GasPaymentEnforcementConfig,
     /// This is optional. If no whitelist is provided ALL messages will be considered on the
@@ -11,7 +11,7 @@ use sea_orm::prelude::TimeDateTime;
 use tracing::trace;
 
 use hyperlane_base::chains::IndexSettings;
-use hyperlane_base::ContractSyncMetrics;
+use hyperlane_base::{ContractSyncMetrics, CursorConf};
 use hyperlane_core::{
     BlockInfo, HyperlaneContract, HyperlaneDomain, HyperlaneMessage, HyperlaneProvider, LogMeta,
     Mailbox, MailboxIndexer, H256, U256,
@@ -40,7 +40,7 @@ pub struct SqlChainScraper {
     db: ScraperDb,
     /// Contracts on this chain representing this chain (e.g. mailbox)
     contracts: Contracts,
-    chunk_size: u32,
+    cursor_conf: CursorConf,
     metrics: ContractSyncMetrics,
     cursor: Arc<BlockCursor>,
 }
@@ -63,7 +63,7 @@ impl SqlChainScraper {
         Ok(Self {
             db,
             contracts,
-            chunk_size: index_settings.chunk_size(),
+            cursor_conf: index_settings.cursor_conf(),
             metrics,
             cursor,
         })
@@ -231,14 +231,20 @@ impl SqlChainScraper {
         let mut txns_to_insert: Vec<(&H256, &mut (Option<i64>, i64))> =
             txns.iter_mut().filter(|(_, id)| id.0.is_none()).collect();
 
-        let mut storable: Vec<StorableTxn> = Vec::with_capacity(txns_to_insert.len());
         let as_f64 = U256::to_f64_lossy;
-        for (hash, (_, block_id)) in txns_to_insert.iter() {
-            storable.push(StorableTxn {
-                info: self.contracts.provider.get_txn_by_hash(hash).await?,
+        let hashes: Vec<H256> = txns_to_insert.iter().map(|(hash, _)| **hash).collect();
+        let storable: Vec<StorableTxn> = self
+            .contracts
+            .provider
+            .get_txns_by_hash(&hashes)
+            .await?
+            .into_iter()
+            .zip(txns_to_insert.iter())
+            .map(|(info, (_, (_, block_id)))| StorableTxn {
+                info,
                 block_id: *block_id,
-            });
-        }
+            })
+            .collect();
 
         if !storable.is_empty() {
             let mut cur_id = self.db.store_txns(storable.into_iter()).await?;
@@ -294,11 +300,13 @@ impl SqlChainScraper {
         // Block info is an option so we can move it, must always be Some before
         // inserted into db.
         let mut blocks_to_insert: Vec<(&mut BasicBlock, Option<BlockInfo>)> = vec![];
-        let blocks_to_fetch = blocks
+        let blocks_to_fetch: Vec<(&H256, &mut Option<BasicBlock>)> = blocks
             .iter_mut()
-            .filter(|(_, block_info)| block_info.is_none());
-        for (hash, block_info) in blocks_to_fetch {
-            let info = self.contracts.provider.get_block_by_hash(hash).await?;
+            .filter(|(_, block_info)| block_info.is_none())
+            .collect();
+        let hashes: Vec<H256> = blocks_to_fetch.iter().map(|(hash, _)| **hash).collect();
+        let infos = self.contracts.provider.get_blocks_by_hash(&hashes).await?;
+        for ((hash, block_info), info) in blocks_to_fetch.into_iter().zip(infos) {
             let basic_info_ref = block_info.insert(BasicBlock {
                 id: -1,
                 hash: *hash,
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Instant;
 
 use eyre::Result;
 use itertools::Itertools;
@@ -10,7 +11,7 @@ use tracing::{debug, info, instrument, warn};
 use hyperlane_base::last_message::validate_message_continuity;
 use hyperlane_base::RateLimitedSyncBlockRangeCursor;
 use hyperlane_core::{
-    KnownHyperlaneDomain, ListValidity, MailboxIndexer, SyncBlockRangeCursor, H256,
+    ChainResult, KnownHyperlaneDomain, ListValidity, MailboxIndexer, SyncBlockRangeCursor, H256,
 };
 
 use crate::chain_scraper::{Delivery, HyperlaneMessageWithMeta, SqlChainScraper, TxnWithIdAndTime};
@@ -79,14 +80,16 @@ impl Syncer {
             .with_label_values(&message_labels);
         let message_nonce = scraper.metrics.message_nonce.clone();
 
-        let chunk_size = scraper.chunk_size;
+        let cursor_conf = scraper.cursor_conf.clone();
         let initial_height = scraper.cursor.height().await as u32;
         let last_valid_range_start_block = initial_height;
         let last_nonce = scraper.last_message_nonce().await?.unwrap_or(0);
 
         let sync_cursor = RateLimitedSyncBlockRangeCursor::new(
             scraper.contracts.indexer.clone(),
-            chunk_size,
+            scraper.domain().clone(),
+            None,
+            cursor_conf,
             initial_height,
         )
         .await?;
@@ -106,7 +109,7 @@ impl Syncer {
     }
 
     /// Sync contract and other blockchain data with the current chain state.
-    #[instrument(skip(self), fields(domain = %self.domain(), chunk_size = self.chunk_size))]
+    #[instrument(skip(self), fields(domain = %self.domain(), chunk_size = self.cursor_conf.chunk_size))]
     pub async fn run(mut self) -> Result<()> {
         let start_block = self.sync_cursor.current_position();
         info!(from = start_block, "Resuming chain sync");
@@ -123,7 +126,21 @@ impl Syncer {
                 }
             };
 
-            let (sorted_messages, deliveries) = self.scrape_range(from, to).await?;
+            let fetch_start = Instant::now();
+            let (sorted_messages, deliveries) = match self.scrape_range(from, to).await {
+                Ok((sorted_messages, deliveries)) => {
+                    self.sync_cursor.range_fetched(
+                        sorted_messages.len() + deliveries.len(),
+                        fetch_start.elapsed(),
+                    );
+                    (sorted_messages, deliveries)
+                }
+                Err(err) => {
+                    warn!(from, to, error = %err, "failed to scrape block range");
+                    self.sync_cursor.range_failed(&err);
+                    continue;
+                }
+            };
 
             let validation = validate_message_continuity(
                 Some(self.last_nonce),
@@ -186,7 +203,7 @@ impl Syncer {
         &self,
         from: u32,
         to: u32,
-    ) -> Result<(Vec<HyperlaneMessageWithMeta>, Vec<Delivery>)> {
+    ) -> ChainResult<(Vec<HyperlaneMessageWithMeta>, Vec<Delivery>)> {
         let sorted_messages = self
             .contracts
             .indexer
@@ -0,0 +1,152 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use eyre::Result;
+use tokio::task::JoinHandle;
+use tracing::{info, info_span, instrument::Instrumented, warn, Instrument};
+use warp::http::StatusCode;
+use warp::reply::{self, Reply, Response};
+use warp::{Filter, Rejection};
+
+use hyperlane_base::CheckpointSyncer;
+
+/// Serves the validator's signed checkpoints and announcement over HTTP using
+/// the `index.json` / `{index}.json` / `announcement.json` layout, so they can
+/// be read by an `HttpStorage` checkpoint syncer.
+pub(crate) struct CheckpointServer {
+    port: u16,
+    checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+}
+
+impl CheckpointServer {
+    pub(crate) fn new(port: u16, checkpoint_syncer: Arc<dyn CheckpointSyncer>) -> Self {
+        Self {
+            port,
+            checkpoint_syncer,
+        }
+    }
+
+    pub(crate) fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!("CheckpointServer", port = self.port);
+        tokio::spawn(async move {
+            info!(port = self.port, "Starting checkpoint server");
+            warp::serve(routes(self.checkpoint_syncer))
+                .run(([0, 0, 0, 0], self.port))
+                .await;
+            Ok(())
+        })
+        .instrument(span)
+    }
+}
+
+fn routes(
+    checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::any().map(move || checkpoint_syncer.clone()))
+        .and_then(serve_file)
+}
+
+async fn serve_file(
+    file: String,
+    checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+) -> Result<Response, Infallible> {
+    Ok(match read_file(&file, checkpoint_syncer.as_ref()).await {
+        Ok(Some(body)) => {
+            reply::with_header(body, "Content-Type", "application/json").into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            warn!(?file, error = ?err, "Unable to read file from checkpoint syncer");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    })
+}
+
+async fn read_file(
+    file: &str,
+    checkpoint_syncer: &dyn CheckpointSyncer,
+) -> Result<Option<Vec<u8>>> {
+    let body = match file {
+        "index.json" => checkpoint_syncer
+            .latest_index()
+            .await?
+            .map(|index| serde_json::to_vec(&index))
+            .transpose()?,
+        "announcement.json" => checkpoint_syncer
+            .fetch_announcement()
+            .await?
+            .map(|announcement| serde_json::to_vec_pretty(&announcement))
+            .transpose()?,
+        _ => match file.strip_suffix(".json").and_then(|i| i.parse().ok()) {
+            Some(index) => checkpoint_syncer
+                .fetch_checkpoint(index)
+                .await?
+                .map(|checkpoint| serde_json::to_vec_pretty(&checkpoint))
+                .transpose()?,
+            None => None,
+        },
+    };
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use tempfile::tempdir;
+
+    use hyperlane_base::{HttpStorage, LocalStorage};
+    use hyperlane_core::{Announcement, HyperlaneSigner, HyperlaneSignerExt, H256};
+    use hyperlane_test::test_utils::{signed_checkpoint, TestSigner};
+
+    use super::*;
+
+    fn serve(checkpoint_syncer: Arc<dyn CheckpointSyncer>) -> SocketAddr {
+        let (addr, server) =
+            warp::serve(routes(checkpoint_syncer)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    async fn serves_checkpoints_to_http_storage() {
+        let dir = tempdir().unwrap();
+        let local = Arc::new(LocalStorage::new(dir.path().to_str().unwrap(), None));
+        let signer = TestSigner::default();
+
+        let signed_checkpoint = signed_checkpoint(7).await;
+        let checkpoint = signed_checkpoint.value;
+        local.write_checkpoint(&signed_checkpoint).await.unwrap();
+
+        let addr = serve(local.clone());
+        let url = format!("http://{addr}");
+        let announcement = Announcement {
+            validator: signer.eth_address(),
+            mailbox_address: H256::repeat_byte(1),
+            mailbox_domain: 13371,
+            storage_location: url.clone(),
+        };
+        let signed_announcement = signer.sign(announcement).await.unwrap();
+        local
+            .write_announcement(&signed_announcement)
+            .await
+            .unwrap();
+
+        let http = HttpStorage::new(&url, None).unwrap();
+        assert_eq!(http.latest_index().await.unwrap(), Some(7));
+        assert_eq!(
+            http.fetch_checkpoint(7).await.unwrap().unwrap().value,
+            checkpoint
+        );
+        assert!(http.fetch_checkpoint(8).await.unwrap().is_none());
+        assert_eq!(
+            http.fetch_announcement().await.unwrap().unwrap().value,
+            signed_announcement.value
+        );
+        assert_eq!(http.announcement_location(), url);
+        assert!(http.write_checkpoint(&signed_checkpoint).await.is_err());
+    }
+}
@@ -9,8 +9,14 @@ decl_settings!(Validator {
     validator: hyperlane_base::SignerConf,
     /// The checkpoint syncer configuration
     checkpointsyncer: hyperlane_base::CheckpointSyncerConf,
-    /// The reorg_period in blocks
+    /// The reorg_period in blocks. If the origin chain has a finality tag
+    /// configured, the tag is used instead and this is only the fallback.
     reorgperiod: String,
     /// How frequently to check for new checkpoints
     interval: String,
+    /// Optional port to serve signed checkpoints on over HTTP
+    checkpointserverport: Option<String>,
+    /// Optional public url of the checkpoint server. If set, this is announced
+    /// as the storage location instead of the checkpoint syncer's location.
+    checkpointserverurl: Option<String>,
 });
@@ -1,60 +1,94 @@
-use std::num::NonZeroU64;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use eyre::Result;
-use prometheus::IntGauge;
+use prometheus::{Counter, IntCounter, IntGauge};
 use tokio::{task::JoinHandle, time::sleep};
-use tracing::{debug, info, info_span, instrument::Instrumented, Instrument};
+use tracing::{debug, error, info, info_span, instrument::Instrumented, warn, Instrument};
 
 use hyperlane_base::{CheckpointSyncer, CoreMetrics};
-use hyperlane_core::{Announcement, HyperlaneDomain, HyperlaneSigner, HyperlaneSignerExt, Mailbox};
+use hyperlane_core::{
+    Announcement, ChainCommunicationError, FailureKind, Finality, HyperlaneDomain, HyperlaneSigner,
+    HyperlaneSignerExt, Mailbox, RevertReason, SignedAnnouncement, ValidatorAnnounce, H256,
+};
+
+/// Delay before the first retry of a failed on-chain announcement
+const ANNOUNCE_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Maximum delay between retries of a failed on-chain announcement
+const ANNOUNCE_MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// How many reverted announcement transactions are retried. Each one costs
+/// gas, so a revert which keeps happening is given up on.
+const ANNOUNCE_MAX_REVERTS: u32 = 3;
 
 pub(crate) struct ValidatorSubmitter {
     interval: Duration,
-    reorg_period: Option<NonZeroU64>,
+    reorg_period: Finality,
     signer: Arc<dyn HyperlaneSigner>,
     mailbox: Arc<dyn Mailbox>,
+    validator_announce: Option<Arc<dyn ValidatorAnnounce>>,
     checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+    storage_locations: Vec<String>,
     metrics: ValidatorSubmitterMetrics,
 }
 
 impl ValidatorSubmitter {
     pub(crate) fn new(
         interval: Duration,
-        reorg_period: u64,
+        reorg_period: Finality,
         mailbox: Arc<dyn Mailbox>,
+        validator_announce: Option<Arc<dyn ValidatorAnnounce>>,
         signer: Arc<dyn HyperlaneSigner>,
         checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+        storage_locations: Vec<String>,
         metrics: ValidatorSubmitterMetrics,
     ) -> Self {
         Self {
-            reorg_period: NonZeroU64::new(reorg_period),
+            reorg_period,
             interval,
             mailbox,
+            validator_announce,
             signer,
             checkpoint_syncer,
+            storage_locations,
             metrics,
         }
     }
 
     pub(crate) fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
         let span = info_span!("ValidatorSubmitter");
-        tokio::spawn(async move { self.main_task().await }).instrument(span)
+        tokio::spawn(async move { Arc::new(self).main_task().await }).instrument(span)
     }
 
-    async fn main_task(self) -> Result<()> {
-        // Sign and post the validator announcement
-        let announcement = Announcement {
-            validator: self.signer.eth_address(),
-            mailbox_address: self.mailbox.address(),
-            mailbox_domain: self.mailbox.domain().id(),
-            storage_location: self.checkpoint_syncer.announcement_location(),
-        };
-        let signed_announcement = self.signer.sign(announcement).await?;
-        self.checkpoint_syncer
-            .write_announcement(&signed_announcement)
-            .await?;
+    async fn main_task(self: Arc<Self>) -> Result<()> {
+        // Sign and store an announcement for each storage location
+        let mut signed_announcements = Vec::with_capacity(self.storage_locations.len());
+        for storage_location in &self.storage_locations {
+            let announcement = Announcement {
+                validator: self.signer.eth_address(),
+                mailbox_address: self.mailbox.address(),
+                mailbox_domain: self.mailbox.domain().id(),
+                storage_location: storage_location.clone(),
+            };
+            let signed_announcement = self.signer.sign(announcement).await?;
+            self.checkpoint_syncer
+                .write_announcement(&signed_announcement)
+                .await?;
+            signed_announcements.push(signed_announcement);
+        }
+
+        // Post the announcements on chain in the background, so checkpoints
+        // are signed even while announcing fails
+        let submitter = self.clone();
+        tokio::spawn(
+            async move {
+                for signed_announcement in signed_announcements {
+                    submitter
+                        .announce_on_chain_with_retry(signed_announcement)
+                        .await;
+                }
+            }
+            .instrument(info_span!("ValidatorAnnouncement")),
+        );
 
         // Ensure that the mailbox has > 0 messages before we enter the main
         // validator submit loop. This is to avoid an underflow / reverted
@@ -144,11 +178,118 @@ impl ValidatorSubmitter {
             sleep(self.interval).await;
         }
     }
+
+    /// Submits the announcement on chain, retrying with exponential backoff
+    /// until it succeeds or fails in a way retrying cannot fix. Returns
+    /// whether the storage location was announced.
+    async fn announce_on_chain_with_retry(&self, signed_announcement: SignedAnnouncement) -> bool {
+        let mut backoff = ANNOUNCE_INITIAL_BACKOFF;
+        let mut reverts = 0;
+        loop {
+            let Err(error) = self.announce_on_chain(signed_announcement.clone()).await else {
+                return true;
+            };
+            let kind = FailureKind::of(&*error);
+            if kind == FailureKind::Reverted {
+                reverts += 1;
+            }
+            let retry = kind.is_retryable()
+                || (kind == FailureKind::Reverted && reverts < ANNOUNCE_MAX_REVERTS);
+            if !retry {
+                if kind.needs_attention() {
+                    error!(
+                        ?error,
+                        %kind,
+                        "Failed to announce validator storage location on chain, giving up until restarted"
+                    );
+                } else {
+                    warn!(
+                        ?error,
+                        %kind,
+                        "Failed to announce validator storage location on chain, giving up"
+                    );
+                }
+                return false;
+            }
+            warn!(
+                ?error,
+                %kind,
+                ?backoff,
+                "Failed to announce validator storage location on chain, retrying"
+            );
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(ANNOUNCE_MAX_BACKOFF);
+        }
+    }
+
+    /// Submits the announcement to the ValidatorAnnounce contract, unless the
+    /// storage location has already been announced for this validator. A
+    /// reverted transaction is an error.
+    async fn announce_on_chain(&self, signed_announcement: SignedAnnouncement) -> Result<()> {
+        let Some(validator_announce) = &self.validator_announce else {
+            warn!("No signer configured for the origin chain, skipping on-chain announcement");
+            return Ok(());
+        };
+
+        let validator = H256::from(signed_announcement.value.validator);
+        let announced_locations = validator_announce
+            .get_announced_storage_locations(&[validator])
+            .await?;
+        let storage_location = &signed_announcement.value.storage_location;
+        if announced_locations
+            .first()
+            .map(|locations| locations.contains(storage_location))
+            .unwrap_or(false)
+        {
+            info!(
+                ?storage_location,
+                "Validator has already announced storage location"
+            );
+            return Ok(());
+        }
+
+        info!(
+            ?storage_location,
+            "Announcing validator storage location on chain"
+        );
+        let outcome = validator_announce
+            .announce(signed_announcement, None)
+            .await?;
+        self.metrics
+            .announce_gas_used
+            .inc_by(outcome.gas_used.low_u64());
+        if let Some(cost) = outcome.cost() {
+            self.metrics.announce_cost.inc_by(cost.to_f64_lossy());
+        }
+        if outcome.executed {
+            info!(
+                txid = ?outcome.txid,
+                gas_used = ?outcome.gas_used,
+                cost = ?outcome.cost(),
+                block = ?outcome.block_number,
+                "Announced validator storage location"
+            );
+            Ok(())
+        } else {
+            warn!(
+                txid = ?outcome.txid,
+                gas_used = ?outcome.gas_used,
+                revert_data = ?outcome.revert_data.as_ref().map(ethers::utils::hex::encode),
+                "Transaction attempting to announce validator reverted"
+            );
+            let reason = outcome
+                .revert_reason
+                .unwrap_or_else(|| RevertReason::Unknown(outcome.revert_data.unwrap_or_default()));
+            Err(ChainCommunicationError::Reverted(reason).into())
+        }
+    }
 }
 
 pub(crate) struct ValidatorSubmitterMetrics {
     latest_checkpoint_observed: IntGauge,
     latest_checkpoint_processed: IntGauge,
+    announce_gas_used: IntCounter,
+    announce_cost: Counter,
 }
 
 impl ValidatorSubmitterMetrics {
@@ -161,6 +302,196 @@ impl ValidatorSubmitterMetrics {
             latest_checkpoint_processed: metrics
                 .latest_checkpoint()
                 .with_label_values(&["validator_processed", chain_name]),
+            announce_gas_used: metrics
+                .transaction_gas_used()
+                .with_label_values(&[chain_name, "announce"]),
+            announce_cost: metrics
+                .transaction_cost()
+                .with_label_values(&[chain_name, "announce"]),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tempfile::{tempdir, TempDir};
+
+    use hyperlane_base::LocalStorage;
+    use hyperlane_core::{FailureKind, KnownHyperlaneDomain};
+    use hyperlane_test::mock_chain::{
+        MockCall, MockChain, MockChainMailbox, MockChainValidatorAnnounce,
+    };
+    use hyperlane_test::test_utils::TestSigner;
+
+    use super::*;
+
+    const STORAGE_LOCATION: &str = "file:///tmp/checkpoints";
+
+    struct Setup {
+        chain: Arc<MockChain>,
+        validator_announce: Arc<MockChainValidatorAnnounce>,
+        submitter: ValidatorSubmitter,
+        _dir: TempDir,
+    }
+
+    fn setup() -> Setup {
+        let chain = Arc::new(MockChain::new(HyperlaneDomain::Known(
+            KnownHyperlaneDomain::Test1,
+        )));
+        let validator_announce = Arc::new(MockChainValidatorAnnounce::new(
+            chain.clone(),
+            H256::repeat_byte(2),
+        ));
+        let dir = tempdir().unwrap();
+        let metrics = CoreMetrics::new("validator_test", None, prometheus::Registry::new())
+            .expect("could not make metrics");
+        let submitter = ValidatorSubmitter::new(
+            Duration::from_secs(1),
+            Finality::Blocks(0),
+            Arc::new(MockChainMailbox::new(chain.clone(), H256::repeat_byte(1))),
+            Some(validator_announce.clone() as Arc<dyn ValidatorAnnounce>),
+            Arc::new(TestSigner::default()),
+            Arc::new(LocalStorage::new(dir.path().to_str().unwrap(), None)),
+            vec![STORAGE_LOCATION.to_owned()],
+            ValidatorSubmitterMetrics::new(&metrics, chain.domain()),
+        );
+        Setup {
+            chain,
+            validator_announce,
+            submitter,
+            _dir: dir,
+        }
+    }
+
+    async fn signed_announcement(submitter: &ValidatorSubmitter) -> SignedAnnouncement {
+        submitter
+            .signer
+            .sign(Announcement {
+                validator: submitter.signer.eth_address(),
+                mailbox_address: submitter.mailbox.address(),
+                mailbox_domain: submitter.mailbox.domain().id(),
+                storage_location: STORAGE_LOCATION.to_owned(),
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn announced_locations(setup: &Setup) -> Vec<String> {
+        let validator = H256::from(setup.submitter.signer.eth_address());
+        setup
+            .validator_announce
+            .get_announced_storage_locations(&[validator])
+            .await
+            .unwrap()
+            .remove(0)
+    }
+
+    #[tokio::test]
+    async fn announces_storage_location_once() {
+        let setup = setup();
+        let announcement = signed_announcement(&setup.submitter).await;
+
+        setup
+            .submitter
+            .announce_on_chain(announcement.clone())
+            .await
+            .unwrap();
+        assert_eq!(announced_locations(&setup).await, vec![STORAGE_LOCATION]);
+        let head = setup.chain.head();
+
+        // Already announced, so no transaction is sent
+        setup
+            .submitter
+            .announce_on_chain(announcement)
+            .await
+            .unwrap();
+        assert_eq!(setup.chain.head(), head);
+        assert_eq!(announced_locations(&setup).await, vec![STORAGE_LOCATION]);
+    }
+
+    #[tokio::test]
+    async fn skips_announcement_without_validator_announce() {
+        let mut setup = setup();
+        setup.submitter.validator_announce = None;
+        let announcement = signed_announcement(&setup.submitter).await;
+        let head = setup.chain.head();
+
+        setup
+            .submitter
+            .announce_on_chain(announcement)
+            .await
+            .unwrap();
+        assert_eq!(setup.chain.head(), head);
+        assert!(announced_locations(&setup).await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_failed_announcements() {
+        let setup = setup();
+        let announcement = signed_announcement(&setup.submitter).await;
+        setup
+            .chain
+            .fail(MockCall::AnnouncedLocations, FailureKind::Transient, 1);
+        setup
+            .chain
+            .fail(MockCall::Announce, FailureKind::RateLimited, 2);
+
+        assert!(setup
+            .submitter
+            .announce_on_chain(announcement.clone())
+            .await
+            .is_err());
+
+        assert!(
+            setup
+                .submitter
+                .announce_on_chain_with_retry(announcement)
+                .await
+        );
+        assert_eq!(announced_locations(&setup).await, vec![STORAGE_LOCATION]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_on_permanent_announce_failures() {
+        let setup = setup();
+        let announcement = signed_announcement(&setup.submitter).await;
+        // Would succeed on the third attempt if it were retried
+        setup
+            .chain
+            .fail(MockCall::Announce, FailureKind::InsufficientFunds, 2);
+
+        assert!(
+            !setup
+                .submitter
+                .announce_on_chain_with_retry(announcement)
+                .await
+        );
+        assert!(announced_locations(&setup).await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn signs_checkpoints_while_announcing_fails() {
+        let setup = setup();
+        let checkpoint_syncer = setup.submitter.checkpoint_syncer.clone();
+        setup
+            .chain
+            .fail(MockCall::Announce, FailureKind::Transient, usize::MAX);
+        MockChainMailbox::new(setup.chain.clone(), H256::repeat_byte(1))
+            .dispatch(2000, H256::zero(), &[1])
+            .await
+            .unwrap();
+
+        let validator = H256::from(setup.submitter.signer.eth_address());
+        let _submitter = setup.submitter.spawn();
+        sleep(Duration::from_secs(5)).await;
+        assert_eq!(checkpoint_syncer.latest_index().await.unwrap(), Some(0));
+        assert_eq!(
+            setup
+                .validator_announce
+                .get_announced_storage_locations(&[validator])
+                .await
+                .unwrap(),
+            vec![Vec::<String>::new()]
+        );
+    }
+}
@@ -7,10 +7,11 @@ use tokio::task::JoinHandle;
 use tracing::instrument::Instrumented;
 
 use hyperlane_base::{run_all, BaseAgent, CheckpointSyncer, CoreMetrics, HyperlaneAgentCore};
-use hyperlane_core::{HyperlaneDomain, HyperlaneSigner, Mailbox};
+use hyperlane_core::{Finality, HyperlaneDomain, HyperlaneSigner, Mailbox, ValidatorAnnounce};
 
 use crate::{
-    settings::ValidatorSettings, submit::ValidatorSubmitter, submit::ValidatorSubmitterMetrics,
+    checkpoint_server::CheckpointServer, settings::ValidatorSettings, submit::ValidatorSubmitter,
+    submit::ValidatorSubmitterMetrics,
 };
 
 /// A validator agent
@@ -19,10 +20,16 @@ pub struct Validator {
     origin_chain: HyperlaneDomain,
     core: HyperlaneAgentCore,
     mailbox: Arc<dyn Mailbox>,
+    /// Used to announce the checkpoint storage location on chain. `None` if
+    /// the origin chain has no signer configured to submit transactions.
+    validator_announce: Option<Arc<dyn ValidatorAnnounce>>,
     signer: Arc<dyn HyperlaneSigner>,
-    reorg_period: u64,
+    reorg_period: Finality,
     interval: Duration,
     checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+    /// The storage locations announced for the signed checkpoints
+    storage_locations: Vec<String>,
+    checkpoint_server_port: Option<u16>,
 }
 
 impl AsRef<HyperlaneAgentCore> for Validator {
@@ -47,30 +54,60 @@ impl BaseAgent for Validator {
             .build::<hyperlane_ethereum::Signers>()
             .await
             .map(|validator| Arc::new(validator) as Arc<dyn HyperlaneSigner>)?;
-        let reorg_period = settings.reorgperiod.parse().expect("invalid uint");
         let interval = Duration::from_secs(settings.interval.parse().expect("invalid uint"));
         let core = settings.build_hyperlane_core(metrics.clone());
-        let checkpoint_syncer = settings.checkpointsyncer.build(None)?.into();
+        let checkpoint_syncer: Arc<dyn CheckpointSyncer> = settings
+            .checkpointsyncer
+            .build_with_metrics(None, Some(&*metrics))?
+            .into();
+        let checkpoint_server_port = settings
+            .checkpointserverport
+            .as_ref()
+            .map(|port| port.parse())
+            .transpose()
+            .context("Invalid checkpoint server port")?;
+        let storage_locations = match &settings.checkpointserverurl {
+            Some(url) => vec![url.clone()],
+            None => checkpoint_syncer.announcement_locations(),
+        };
 
         let mailbox = settings
             .build_mailbox(&settings.originchainname, &metrics)
             .await?
             .into();
 
-        let origin_chain = core
+        let origin_chain_setup = core
             .settings
             .chain_setup(&settings.originchainname)
-            .context("Validator must run on a configured chain")?
-            .domain()?;
+            .context("Validator must run on a configured chain")?;
+        let origin_chain = origin_chain_setup.domain()?;
+        let reorg_period = Finality::new(
+            settings.reorgperiod.parse().expect("invalid uint"),
+            origin_chain_setup.finality_tag()?,
+        );
+
+        let validator_announce = if origin_chain_setup.signer.is_some() {
+            Some(
+                settings
+                    .build_validator_announce(&settings.originchainname, &metrics)
+                    .await?
+                    .into(),
+            )
+        } else {
+            None
+        };
 
         Ok(Self {
             origin_chain,
             core,
             mailbox,
+            validator_announce,
             signer,
             reorg_period,
             interval,
             checkpoint_syncer,
+            storage_locations,
+            checkpoint_server_port,
         })
     }
 
@@ -80,12 +117,19 @@ impl BaseAgent for Validator {
             self.interval,
             self.reorg_period,
             self.mailbox.clone(),
+            self.validator_announce.clone(),
             self.signer.clone(),
             self.checkpoint_syncer.clone(),
+            self.storage_locations.clone(),
             ValidatorSubmitterMetrics::new(&self.core.metrics, &self.origin_chain),
         );
 
-        run_all(vec![submit.spawn()])
+        let mut tasks = vec![submit.spawn()];
+        if let Some(port) = self.checkpoint_server_port {
+            tasks.push(CheckpointServer::new(port, self.checkpoint_syncer.clone()).spawn());
+        }
+
+        run_all(tasks)
     }
 }
 
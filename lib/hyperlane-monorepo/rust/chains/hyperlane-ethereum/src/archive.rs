@@ -0,0 +1,217 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, ProviderError};
+use ethers::types::U64;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tracing::trace;
+
+/// How long the head block number is reused before it is requested again
+const HEAD_MAX_AGE: Duration = Duration::from_secs(10);
+
+/// The last known head block number of a chain and when it was learned
+#[derive(Debug, Default)]
+pub(crate) struct Head(Mutex<Option<(u64, Instant)>>);
+
+impl Head {
+    pub(crate) fn update(&self, number: u64) {
+        *self.0.lock().unwrap() = Some((number, Instant::now()));
+    }
+
+    /// The head block number, requested from `provider` if the last known
+    /// one is older than `HEAD_MAX_AGE`
+    pub(crate) async fn get<P: JsonRpcClient>(&self, provider: &P) -> Result<u64, P::Error> {
+        let cached = *self.0.lock().unwrap();
+        match cached {
+            Some((number, at)) if at.elapsed() < HEAD_MAX_AGE => Ok(number),
+            _ => {
+                let number: U64 = provider.request("eth_blockNumber", ()).await?;
+                self.update(number.as_u64());
+                Ok(number.as_u64())
+            }
+        }
+    }
+}
+
+/// Sends requests for blocks more than `depth` blocks below the head to an
+/// archive node and all other requests to the primary provider, which may be
+/// a pruned node.
+#[derive(Debug)]
+pub struct ArchiveRouter<P, A> {
+    primary: P,
+    archive: Option<A>,
+    depth: u64,
+    head: Head,
+}
+
+impl<P, A> ArchiveRouter<P, A> {
+    /// Route requests more than `depth` blocks below the head to `archive`.
+    /// Everything goes to `primary` if there is no archive.
+    pub fn new(primary: P, archive: Option<A>, depth: u64) -> Self {
+        Self {
+            primary,
+            archive,
+            depth,
+            head: Head::default(),
+        }
+    }
+}
+
+/// The block a request reads from, if it names one by number.
+fn requested_block(method: &str, params: &Value) -> Option<u64> {
+    let block = match method {
+        "eth_getLogs" => params.get(0)?.get("fromBlock")?,
+        "eth_getBlockByNumber" | "eth_getBlockTransactionCountByNumber" => params.get(0)?,
+        "eth_call"
+        | "eth_getBalance"
+        | "eth_getCode"
+        | "eth_getStorageAt"
+        | "eth_getTransactionCount" => params.as_array()?.last()?,
+        _ => return None,
+    };
+    // EIP-1898 block parameters name the block in an object
+    let block = block.get("blockNumber").unwrap_or(block);
+    match block.as_str()? {
+        "earliest" => Some(0),
+        number => u64::from_str_radix(number.strip_prefix("0x")?, 16).ok(),
+    }
+}
+
+#[async_trait]
+impl<P, A> JsonRpcClient for ArchiveRouter<P, A>
+where
+    P: JsonRpcClient + 'static,
+    A: JsonRpcClient + 'static,
+{
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        if let Some(archive) = &self.archive {
+            let params = serde_json::to_value(params)?;
+            if let Some(block) = requested_block(method, &params) {
+                let head = self.head.get(&self.primary).await.map_err(Into::into)?;
+                if head.saturating_sub(block) > self.depth {
+                    trace!(method, block, head, "Routing request to archive node");
+                    return archive.request(method, params).await.map_err(Into::into);
+                }
+            }
+            let response: Value = match params {
+                Value::Null => self.primary.request(method, ()).await,
+                _ => self.primary.request(method, params).await,
+            }
+            .map_err(Into::into)?;
+            if method == "eth_blockNumber" {
+                if let Ok(number) = serde_json::from_value::<U64>(response.clone()) {
+                    self.head.update(number.as_u64());
+                }
+            }
+            Ok(serde_json::from_value(response)?)
+        } else {
+            self.primary
+                .request(method, params)
+                .await
+                .map_err(Into::into)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::providers::MockProvider;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn finds_requested_blocks() {
+        let logs = |from: Value| json!([{"fromBlock": from, "toBlock": "latest"}]);
+        assert_eq!(
+            requested_block("eth_getLogs", &logs(json!("0x10"))),
+            Some(16)
+        );
+        assert_eq!(
+            requested_block("eth_getLogs", &logs(json!("earliest"))),
+            Some(0)
+        );
+        assert_eq!(requested_block("eth_getLogs", &logs(json!("latest"))), None);
+        assert_eq!(
+            requested_block("eth_getLogs", &json!([{"blockHash": "0x01"}])),
+            None
+        );
+        assert_eq!(
+            requested_block("eth_call", &json!([{"to": "0x01"}, "0xff"])),
+            Some(255)
+        );
+        assert_eq!(
+            requested_block("eth_call", &json!([{"to": "0x01"}, {"blockNumber": "0x2"}])),
+            Some(2)
+        );
+        assert_eq!(
+            requested_block("eth_getBlockByNumber", &json!(["0x3", false])),
+            Some(3)
+        );
+        assert_eq!(requested_block("eth_chainId", &json!([])), None);
+    }
+
+    #[tokio::test]
+    async fn routes_deep_requests_to_archive() {
+        let primary = MockProvider::new();
+        let archive = MockProvider::new();
+        let router = ArchiveRouter::new(primary.clone(), Some(archive.clone()), 100);
+
+        primary.push(U64::from(1000)).unwrap();
+        let head: U64 = router.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(head.as_u64(), 1000);
+
+        // The head is known, so only the request itself is sent
+        archive.push(json!(["old"])).unwrap();
+        let logs: Value = router
+            .request("eth_getLogs", [json!({"fromBlock": "0x10"})])
+            .await
+            .unwrap();
+        assert_eq!(logs, json!(["old"]));
+
+        primary.push(json!(["recent"])).unwrap();
+        let logs: Value = router
+            .request(
+                "eth_getLogs",
+                [json!({ "fromBlock": format!("{:#x}", 950) })],
+            )
+            .await
+            .unwrap();
+        assert_eq!(logs, json!(["recent"]));
+
+        primary.push(json!("0x01")).unwrap();
+        let result: Value = router
+            .request("eth_call", (json!({"to": "0x01"}), "latest"))
+            .await
+            .unwrap();
+        assert_eq!(result, json!("0x01"));
+
+        // Nothing is left over
+        assert!(router.request::<_, Value>("eth_chainId", ()).await.is_err());
+        assert!(archive
+            .request::<_, Value>("eth_chainId", ())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_everything_to_primary_without_archive() {
+        let primary = MockProvider::new();
+        let router = ArchiveRouter::<_, MockProvider>::new(primary.clone(), None, 100);
+        primary.push(json!(["old"])).unwrap();
+        let logs: Value = router
+            .request("eth_getLogs", [json!({"fromBlock": "0x0"})])
+            .await
+            .unwrap();
+        assert_eq!(logs, json!(["old"]));
+    }
+}
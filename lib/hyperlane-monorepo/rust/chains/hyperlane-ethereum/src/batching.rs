@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ethers::providers::{Http, HttpClientError, JsonRpcClient, JsonRpcError};
+use reqwest::{Client, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+/// Methods which are commonly requested in bulk, e.g. by the scraper when it
+/// backfills a chain, and are worth batching.
+const BATCHED_METHODS: &[&str] = &[
+    "eth_getBlockByHash",
+    "eth_getBlockByNumber",
+    "eth_getTransactionByHash",
+    "eth_getTransactionReceipt",
+];
+
+/// How long to collect requests before sending them as a batch
+const BATCH_WINDOW: Duration = Duration::from_millis(5);
+
+/// The most requests sent in a single batch
+pub(crate) const MAX_BATCH_SIZE: usize = 100;
+
+/// How many batches in a row may fail before batching is given up on
+const MAX_BATCH_FAILURES: u32 = 3;
+
+/// A request waiting to be sent in the next batch. The sender receives `None`
+/// if the request was not answered as part of a batch and should be sent on
+/// its own instead.
+struct PendingRequest {
+    id: u64,
+    body: Value,
+    sender: oneshot::Sender<Option<Result<Value, JsonRpcError>>>,
+}
+
+/// An HTTP transport which sends concurrent requests for blocks,
+/// transactions and receipts to the node as JSON-RPC batches. All other
+/// requests are sent on their own.
+#[derive(Clone)]
+pub struct BatchHttp {
+    inner: Arc<BatchHttpInner>,
+}
+
+struct BatchHttpInner {
+    http: Http,
+    client: Client,
+    url: Url,
+    next_id: AtomicU64,
+    pending: Mutex<Vec<PendingRequest>>,
+    /// Set if batching is turned off, the node said it does not support
+    /// batches or `MAX_BATCH_FAILURES` batches in a row failed
+    batching_disabled: AtomicBool,
+    /// The number of batches in a row which were not answered
+    batch_failures: AtomicU32,
+}
+
+impl Debug for BatchHttp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchHttp")
+            .field("http", &self.inner.http)
+            .finish()
+    }
+}
+
+impl BatchHttp {
+    /// Create a transport for the url using the given client
+    pub fn new_with_client(url: Url, client: Client) -> Self {
+        Self {
+            inner: Arc::new(BatchHttpInner {
+                http: Http::new_with_client(url.clone(), client.clone()),
+                client,
+                url,
+                next_id: AtomicU64::new(1),
+                pending: Default::default(),
+                batching_disabled: AtomicBool::new(false),
+                batch_failures: AtomicU32::new(0),
+            }),
+        }
+    }
+
+    /// Turn batching on or off; requests are always sent on their own if it
+    /// is off.
+    pub fn with_batching(self, enabled: bool) -> Self {
+        self.inner
+            .batching_disabled
+            .store(!enabled, Ordering::Relaxed);
+        self
+    }
+}
+
+impl BatchHttpInner {
+    /// Send all requests which arrive within the batch window.
+    async fn flush_after(self: Arc<Self>, window: Duration) {
+        sleep(window).await;
+        let mut requests = std::mem::take(&mut *self.pending.lock().unwrap());
+        while !requests.is_empty() {
+            let rest = requests.split_off(requests.len().min(MAX_BATCH_SIZE));
+            self.send_batch(requests).await;
+            requests = rest;
+        }
+    }
+
+    async fn send_batch(&self, requests: Vec<PendingRequest>) {
+        if requests.len() == 1 {
+            // Not worth a batch
+            for request in requests {
+                let _ = request.sender.send(None);
+            }
+            return;
+        }
+
+        let body: Vec<&Value> = requests.iter().map(|request| &request.body).collect();
+        debug!(size = body.len(), "Sending batch");
+        let responses = match self.post(&body).await {
+            Ok(Value::Array(responses)) => {
+                self.batch_failures.store(0, Ordering::Relaxed);
+                responses
+            }
+            Ok(response) if rejects_batches(&response) => {
+                warn!(
+                    ?response,
+                    "Node does not support batches, sending requests on their own"
+                );
+                self.batching_disabled.store(true, Ordering::Relaxed);
+                vec![]
+            }
+            Ok(response) => {
+                warn!(?response, "Unexpected response to batch");
+                self.record_batch_failure();
+                vec![]
+            }
+            Err(error) => {
+                warn!(%error, "Failed to send batch, sending requests on their own");
+                self.record_batch_failure();
+                vec![]
+            }
+        };
+
+        let mut responses: HashMap<u64, Value> = responses
+            .into_iter()
+            .filter_map(|response| Some((response.get("id")?.as_u64()?, response)))
+            .collect();
+        for request in requests {
+            let response = responses.remove(&request.id).and_then(parse_response);
+            let _ = request.sender.send(response);
+        }
+    }
+
+    /// Count a failed batch, giving up on batching after too many in a row
+    /// so that a node which mishandles batches does not fail every request
+    /// once before it is retried on its own.
+    fn record_batch_failure(&self) {
+        let failures = self.batch_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= MAX_BATCH_FAILURES {
+            warn!(
+                failures,
+                "Batches keep failing, sending requests on their own"
+            );
+            self.batching_disabled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    async fn post(&self, body: &[&Value]) -> Result<Value, reqwest::Error> {
+        self.client
+            .post(self.url.clone())
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
+
+/// Whether the node answered a batch with an error saying that it does not
+/// support batches
+fn rejects_batches(response: &Value) -> bool {
+    response
+        .get("error")
+        .and_then(|error| error.get("message"))
+        .and_then(Value::as_str)
+        .map_or(false, |message| message.to_lowercase().contains("batch"))
+}
+
+/// The result or error of a single response in a batch
+fn parse_response(mut response: Value) -> Option<Result<Value, JsonRpcError>> {
+    let response = response.as_object_mut()?;
+    if let Some(error) = response.remove("error") {
+        Some(Err(serde_json::from_value(error).ok()?))
+    } else {
+        response.remove("result").map(Ok)
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for BatchHttp {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        if !BATCHED_METHODS.contains(&method)
+            || self.inner.batching_disabled.load(Ordering::Relaxed)
+        {
+            return self.inner.http.request(method, params).await;
+        }
+
+        let params = serde_json::to_value(params).map_err(|err| HttpClientError::SerdeJson {
+            err,
+            text: String::new(),
+        })?;
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        let first = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            pending.push(PendingRequest {
+                id,
+                body: json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params.clone(),
+                }),
+                sender,
+            });
+            pending.len() == 1
+        };
+        if first {
+            tokio::spawn(self.inner.clone().flush_after(BATCH_WINDOW));
+        }
+
+        match receiver.await {
+            Ok(Some(Ok(result))) => {
+                serde_json::from_value(result.clone()).map_err(|err| HttpClientError::SerdeJson {
+                    err,
+                    text: result.to_string(),
+                })
+            }
+            Ok(Some(Err(error))) => Err(HttpClientError::JsonRpcError(error)),
+            // Not answered as part of a batch
+            _ => self.inner.http.request(method, params).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::atomic::AtomicUsize;
+
+    use futures_util::future::join_all;
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    use super::*;
+
+    /// How the test node answers batches
+    #[derive(Clone, Copy)]
+    enum Batches {
+        Supported,
+        Rejected,
+        Failing,
+    }
+
+    /// Serve eth_getBlockByHash, echoing the hash, and count the http requests
+    async fn serve(batches: Batches) -> (Url, Arc<AtomicUsize>) {
+        let http_requests = Arc::new(AtomicUsize::new(0));
+        let counter = http_requests.clone();
+        let answer = |request: &Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": {"hash": request["params"][0]},
+            })
+        };
+        let route = warp::post()
+            .and(warp::body::json())
+            .map(move |body: Value| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let (response, status) = match (body, batches) {
+                    (Value::Array(requests), Batches::Supported) => (
+                        Value::Array(requests.iter().map(answer).collect()),
+                        StatusCode::OK,
+                    ),
+                    (Value::Array(_), Batches::Rejected) => (
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": null,
+                            "error": {"code": -32600, "message": "batches not supported"},
+                        }),
+                        StatusCode::OK,
+                    ),
+                    (Value::Array(_), Batches::Failing) => {
+                        (Value::Null, StatusCode::SERVICE_UNAVAILABLE)
+                    }
+                    (request, _) => (answer(&request), StatusCode::OK),
+                };
+                warp::reply::with_status(warp::reply::json(&response), status)
+            });
+        let (addr, server) =
+            warp::serve(route).bind_ephemeral(SocketAddr::from(([127, 0, 0, 1], 0)));
+        tokio::spawn(server);
+        (format!("http://{addr}").parse().unwrap(), http_requests)
+    }
+
+    async fn get_blocks(http: &BatchHttp, count: usize) {
+        let hashes: Vec<String> = (0..count).map(|i| format!("0x{i:064x}")).collect();
+        let blocks = join_all(
+            hashes
+                .iter()
+                .map(|hash| http.request::<_, Value>("eth_getBlockByHash", (hash, false))),
+        )
+        .await;
+        for (block, hash) in blocks.into_iter().zip(&hashes) {
+            assert_eq!(block.unwrap()["hash"], json!(hash));
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_concurrent_requests() {
+        let (url, http_requests) = serve(Batches::Supported).await;
+        let http = BatchHttp::new_with_client(url, Client::new());
+        get_blocks(&http, 150).await;
+        assert_eq!(http_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn batching_can_be_turned_off() {
+        let (url, http_requests) = serve(Batches::Supported).await;
+        let http = BatchHttp::new_with_client(url, Client::new()).with_batching(false);
+        get_blocks(&http, 10).await;
+        assert_eq!(http_requests.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_single_requests() {
+        let (url, http_requests) = serve(Batches::Rejected).await;
+        let http = BatchHttp::new_with_client(url, Client::new());
+        get_blocks(&http, 10).await;
+        assert_eq!(http_requests.load(Ordering::SeqCst), 11);
+
+        // Batches are not attempted anymore
+        get_blocks(&http, 10).await;
+        assert_eq!(http_requests.load(Ordering::SeqCst), 21);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_failing_batches() {
+        let (url, http_requests) = serve(Batches::Failing).await;
+        let http = BatchHttp::new_with_client(url, Client::new());
+        // A failed batch is retried as single requests, and batches are
+        // attempted again until too many failed in a row
+        for round in 1..=MAX_BATCH_FAILURES as usize {
+            get_blocks(&http, 10).await;
+            assert_eq!(http_requests.load(Ordering::SeqCst), round * 11);
+        }
+        get_blocks(&http, 10).await;
+        assert_eq!(
+            http_requests.load(Ordering::SeqCst),
+            MAX_BATCH_FAILURES as usize * 11 + 10
+        );
+    }
+}
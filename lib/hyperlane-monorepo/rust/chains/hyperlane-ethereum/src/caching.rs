@@ -0,0 +1,513 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use ethers::prelude::Lazy;
+use ethers::providers::{JsonRpcClient, ProviderError};
+use prometheus::{IntCounterVec, IntGaugeVec};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{trace, warn};
+
+use ethers_prometheus::json_rpc_client::JsonRpcClientMetrics;
+use hyperlane_core::db::{DbError, TypedDB, DB};
+use hyperlane_core::{Decode, Encode, HyperlaneDomain, HyperlaneProtocolError};
+
+use crate::archive::Head;
+
+/// Methods which look up data by a block or transaction hash. Blocks do not
+/// change once they are known to the node, transactions and receipts once
+/// their block is final.
+const CACHEABLE_METHODS: &[&str] = &[
+    "eth_getBlockByHash",
+    "eth_getTransactionByHash",
+    "eth_getTransactionReceipt",
+];
+
+/// DB prefix of persisted responses
+const RESPONSE_PREFIX: &str = "rpc_response_";
+
+/// The cache of each domain by domain id, shared by all providers of the
+/// domain.
+static CACHES: Lazy<Mutex<HashMap<u32, Arc<RpcCache>>>> = Lazy::new(Default::default);
+
+/// Databases by path. A RocksDB database can only be opened once per process.
+static DATABASES: Lazy<Mutex<HashMap<String, DB>>> = Lazy::new(Default::default);
+
+/// Error building an `RpcCache`
+#[derive(Debug, Error)]
+pub enum RpcCacheError {
+    /// A configuration value could not be parsed
+    #[error("Invalid rpc cache configuration {0}: {1:?}")]
+    InvalidConf(&'static str, String),
+    /// The database could not be opened
+    #[error(transparent)]
+    DbError(#[from] DbError),
+    /// The cache of the domain was already created with a different value of
+    /// a configuration field
+    #[error("Conflicting rpc cache configuration {1} for domain {0}")]
+    ConflictingConf(u32, &'static str),
+}
+
+/// A size-limited cache of responses to requests for immutable chain data,
+/// kept in memory and optionally persisted to a RocksDB database.
+pub struct RpcCache {
+    chain: String,
+    max_bytes: usize,
+    max_entry_bytes: usize,
+    finality_blocks: u64,
+    path: Option<String>,
+    memory: Mutex<MemoryCache>,
+    db: Option<TypedDB>,
+    hits: Option<IntCounterVec>,
+    misses: Option<IntCounterVec>,
+    size: Option<IntGaugeVec>,
+}
+
+impl Debug for RpcCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RpcCache {{ chain: {}, max_bytes: {}, persistent: {} }}",
+            self.chain,
+            self.max_bytes,
+            self.db.is_some()
+        )
+    }
+}
+
+/// In-memory responses, evicting the least recently used ones first.
+#[derive(Default)]
+struct MemoryCache {
+    /// Responses and when they were last used
+    entries: HashMap<String, (Arc<[u8]>, u64)>,
+    /// Keys by when they were last used
+    recency: BTreeMap<u64, String>,
+    bytes: usize,
+    clock: u64,
+}
+
+impl MemoryCache {
+    fn get(&mut self, key: &str) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let (value, used) = self.entries.get_mut(key)?;
+        let key = self.recency.remove(used).expect("recency is consistent");
+        *used = self.clock;
+        self.recency.insert(self.clock, key);
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: Arc<[u8]>, max_bytes: usize) {
+        self.clock += 1;
+        self.bytes += value.len();
+        if let Some((previous, used)) = self.entries.insert(key.clone(), (value, self.clock)) {
+            self.bytes -= previous.len();
+            self.recency.remove(&used);
+        }
+        self.recency.insert(self.clock, key);
+        while self.bytes > max_bytes {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((value, _)) = self.entries.remove(&key) {
+                self.bytes -= value.len();
+            }
+        }
+    }
+}
+
+/// A response as persisted in the database
+struct CachedResponse(Vec<u8>);
+
+impl Encode for CachedResponse {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        writer.write_all(&self.0)?;
+        Ok(self.0.len())
+    }
+}
+
+impl Decode for CachedResponse {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        Ok(Self(buf))
+    }
+}
+
+impl RpcCache {
+    /// The cache of `domain`, creating it if there is none yet. `db_path` is
+    /// the RocksDB database to persist responses in, which must not be the
+    /// database of the agent itself. Transactions and receipts are only
+    /// cached once their block is `finality_blocks` below the head.
+    ///
+    /// Fails if the cache of the domain already exists with a different
+    /// configuration, since only one of them could take effect.
+    pub fn for_domain(
+        domain: &HyperlaneDomain,
+        max_bytes: usize,
+        max_entry_bytes: usize,
+        finality_blocks: u64,
+        db_path: Option<&str>,
+        metrics: Option<&JsonRpcClientMetrics>,
+    ) -> Result<Arc<Self>, RpcCacheError> {
+        let mut caches = CACHES.lock().unwrap();
+        if let Some(cache) = caches.get(&domain.id()) {
+            let conflict = if cache.max_bytes != max_bytes {
+                Some("maxbytes")
+            } else if cache.max_entry_bytes != max_entry_bytes {
+                Some("maxentrybytes")
+            } else if cache.finality_blocks != finality_blocks {
+                Some("finalityblocks")
+            } else if cache.path.as_deref() != db_path {
+                Some("path")
+            } else {
+                None
+            };
+            return match conflict {
+                Some(field) => Err(RpcCacheError::ConflictingConf(domain.id(), field)),
+                None => Ok(cache.clone()),
+            };
+        }
+        let db = match db_path {
+            Some(path) => {
+                let mut databases = DATABASES.lock().unwrap();
+                let db = match databases.get(path) {
+                    Some(db) => db.clone(),
+                    None => {
+                        let db = DB::from_path(path)?;
+                        databases.insert(path.to_owned(), db.clone());
+                        db
+                    }
+                };
+                Some(TypedDB::new(domain.id().to_string(), db))
+            }
+            None => None,
+        };
+        let cache = Arc::new(Self {
+            chain: domain.name().to_owned(),
+            max_bytes,
+            max_entry_bytes,
+            finality_blocks,
+            path: db_path.map(ToOwned::to_owned),
+            memory: Default::default(),
+            db,
+            hits: metrics.and_then(|m| m.rpc_cache_hits()).cloned(),
+            misses: metrics.and_then(|m| m.rpc_cache_misses()).cloned(),
+            size: metrics.and_then(|m| m.rpc_cache_size_bytes()).cloned(),
+        });
+        caches.insert(domain.id(), cache.clone());
+        Ok(cache)
+    }
+
+    fn get(&self, method: &str, key: &str) -> Option<Arc<[u8]>> {
+        let hit = |store: &str| {
+            if let Some(hits) = &self.hits {
+                hits.with_label_values(&[&self.chain, method, store]).inc();
+            }
+        };
+        if let Some(value) = self.memory.lock().unwrap().get(key) {
+            hit("memory");
+            return Some(value);
+        }
+        let persisted = self.db.as_ref().and_then(|db| {
+            db.retrieve_decodable::<CachedResponse>(RESPONSE_PREFIX, key)
+                .map_err(|error| warn!(%error, "Failed to read cached rpc response"))
+                .ok()
+                .flatten()
+        });
+        if let Some(CachedResponse(value)) = persisted {
+            hit("db");
+            let value: Arc<[u8]> = value.into();
+            self.insert_memory(key, value.clone());
+            return Some(value);
+        }
+        if let Some(misses) = &self.misses {
+            misses.with_label_values(&[&self.chain, method]).inc();
+        }
+        None
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) {
+        if value.len() > self.max_entry_bytes {
+            return;
+        }
+        if let Some(db) = &self.db {
+            if let Err(error) =
+                db.store_encodable(RESPONSE_PREFIX, key, &CachedResponse(value.clone()))
+            {
+                warn!(%error, "Failed to persist rpc response");
+            }
+        }
+        self.insert_memory(key, value.into());
+    }
+
+    fn insert_memory(&self, key: &str, value: Arc<[u8]>) {
+        let mut memory = self.memory.lock().unwrap();
+        memory.insert(key.to_owned(), value, self.max_bytes);
+        if let Some(size) = &self.size {
+            size.with_label_values(&[&self.chain])
+                .set(memory.bytes as i64);
+        }
+    }
+}
+
+/// The number of the block a transaction or receipt is included in, if any
+fn included_in(response: &Value) -> Option<u64> {
+    let number = response.get("blockNumber")?.as_str()?;
+    u64::from_str_radix(number.strip_prefix("0x")?, 16).ok()
+}
+
+/// A JsonRpcClient which answers requests for blocks, transactions and
+/// receipts by hash from an `RpcCache` when it can.
+#[derive(Debug, Clone)]
+pub struct CachingProvider<P> {
+    inner: P,
+    cache: Option<Arc<RpcCache>>,
+    head: Arc<Head>,
+}
+
+impl<P> CachingProvider<P> {
+    /// Wrap a provider; requests are passed through if there is no cache.
+    pub fn new(inner: P, cache: Option<Arc<RpcCache>>) -> Self {
+        Self {
+            inner,
+            cache,
+            head: Default::default(),
+        }
+    }
+}
+
+impl<P> CachingProvider<P>
+where
+    P: JsonRpcClient,
+{
+    /// Whether a response may be cached. Transactions and receipts could
+    /// still be dropped or moved to another block by a reorg until their
+    /// block is final.
+    async fn is_cacheable(&self, cache: &RpcCache, method: &str, response: &Value) -> bool {
+        if method == "eth_getBlockByHash" {
+            return !response.is_null();
+        }
+        let Some(block) = included_in(response) else {
+            return false;
+        };
+        match self.head.get(&self.inner).await {
+            Ok(head) => head
+                .checked_sub(cache.finality_blocks)
+                .map_or(false, |final_block| block <= final_block),
+            Err(error) => {
+                warn!(%error, "Failed to get head block, not caching response");
+                false
+            }
+        }
+    }
+}
+
+/// Error type for the CachingProvider
+#[derive(Error, Debug)]
+pub enum CachingProviderError<P>
+where
+    P: JsonRpcClient,
+{
+    /// An error of the inner provider
+    #[error(transparent)]
+    JsonRpcClientError(P::Error),
+    /// A request or response could not be (de)serialized
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+impl<P> From<CachingProviderError<P>> for ProviderError
+where
+    P: JsonRpcClient + 'static,
+    <P as JsonRpcClient>::Error: Send + Sync,
+{
+    fn from(src: CachingProviderError<P>) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+#[async_trait]
+impl<P> JsonRpcClient for CachingProvider<P>
+where
+    P: JsonRpcClient + 'static,
+    P::Error: Send + Sync,
+{
+    type Error = CachingProviderError<P>;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let cache = match &self.cache {
+            Some(cache) if CACHEABLE_METHODS.contains(&method) => cache,
+            _ => {
+                return self
+                    .inner
+                    .request(method, params)
+                    .await
+                    .map_err(CachingProviderError::JsonRpcClientError)
+            }
+        };
+
+        let key = format!("{method}:{}", serde_json::to_string(&params)?);
+        if let Some(cached) = cache.get(method, &key) {
+            trace!(%key, "Answering request from cache");
+            return Ok(serde_json::from_slice(&cached)?);
+        }
+
+        let response: Value = self
+            .inner
+            .request(method, params)
+            .await
+            .map_err(CachingProviderError::JsonRpcClientError)?;
+        if self.is_cacheable(cache, method, &response).await {
+            cache.insert(&key, serde_json::to_vec(&response)?);
+        }
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::providers::MockProvider;
+    use serde_json::json;
+
+    use hyperlane_core::{HyperlaneDomainProtocol, HyperlaneDomainType};
+
+    use super::*;
+
+    fn domain(domain_id: u32, name: &str) -> HyperlaneDomain {
+        HyperlaneDomain::Unknown {
+            domain_id,
+            chain_name: name.to_owned(),
+            domain_type: HyperlaneDomainType::LocalTestChain,
+            domain_protocol: HyperlaneDomainProtocol::Ethereum,
+        }
+    }
+
+    fn cache(domain_id: u32, max_bytes: usize, db_path: Option<&str>) -> Arc<RpcCache> {
+        RpcCache::for_domain(
+            &domain(domain_id, "unknown"),
+            max_bytes,
+            1024,
+            2,
+            db_path,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn caches_final_responses() {
+        let mock = MockProvider::new();
+        let cache = cache(800_001, 1 << 20, None);
+        let provider = CachingProvider::new(mock.clone(), Some(cache.clone()));
+        let hash = json!(["0x01"]);
+        let receipt = json!({"blockHash": "0x02", "blockNumber": "0x10", "status": "0x1"});
+
+        // The mock answers requests in reverse order of pushing them
+        mock.push(Value::Null).unwrap();
+        let pending: Value = provider
+            .request("eth_getTransactionReceipt", &hash)
+            .await
+            .unwrap();
+        assert!(pending.is_null());
+
+        // Included, but the block is not final yet
+        mock.push(json!("0x11")).unwrap();
+        mock.push(receipt.clone()).unwrap();
+        let unfinal: Value = provider
+            .request("eth_getTransactionReceipt", &hash)
+            .await
+            .unwrap();
+        assert_eq!(unfinal, receipt);
+        mock.push(receipt.clone()).unwrap();
+        let _: Value = provider
+            .request("eth_getTransactionReceipt", &hash)
+            .await
+            .unwrap();
+
+        // Once the block is final the receipt is cached
+        let provider = CachingProvider::new(mock.clone(), Some(cache));
+        mock.push(json!("0x12")).unwrap();
+        mock.push(receipt.clone()).unwrap();
+        for _ in 0..3 {
+            let cached: Value = provider
+                .request("eth_getTransactionReceipt", &hash)
+                .await
+                .unwrap();
+            assert_eq!(cached, receipt);
+        }
+
+        // Other methods always go to the node
+        mock.push(json!("0x10")).unwrap();
+        let _: Value = provider.request("eth_blockNumber", ()).await.unwrap();
+        assert!(provider
+            .request::<_, Value>("eth_blockNumber", ())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = cache(800_002, 10, None);
+        cache.insert("a", vec![0; 4]);
+        cache.insert("b", vec![0; 4]);
+        assert!(cache.get("m", "a").is_some());
+        cache.insert("c", vec![0; 4]);
+        assert!(cache.get("m", "a").is_some());
+        assert!(cache.get("m", "b").is_none());
+        assert!(cache.get("m", "c").is_some());
+
+        // Too large entries are not cached at all
+        cache.insert("d", vec![0; 2048]);
+        assert!(cache.get("m", "d").is_none());
+        assert_eq!(cache.memory.lock().unwrap().bytes, 8);
+    }
+
+    #[test]
+    fn persists_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        cache(800_003, 1024, Some(path)).insert("a", b"[1]".to_vec());
+        CACHES.lock().unwrap().remove(&800_003);
+
+        let cache = cache(800_003, 1024, Some(path));
+        assert_eq!(&*cache.get("m", "a").unwrap(), b"[1]");
+        // Responses are persisted per domain, even if the chain names match
+        assert!(self::cache(800_004, 1024, Some(path))
+            .get("m", "a")
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_conflicting_conf() {
+        let first = cache(800_005, 1024, None);
+        // The same configuration shares the cache, whatever the chain name
+        let same = RpcCache::for_domain(&domain(800_005, "other"), 1024, 1024, 2, None, None);
+        assert!(Arc::ptr_eq(&first, &same.unwrap()));
+
+        let conflicting = [
+            RpcCache::for_domain(&domain(800_005, "other"), 2048, 1024, 2, None, None),
+            RpcCache::for_domain(&domain(800_005, "other"), 1024, 512, 2, None, None),
+            RpcCache::for_domain(&domain(800_005, "other"), 1024, 1024, 64, None, None),
+        ];
+        for result in conflicting {
+            assert!(matches!(
+                result,
+                Err(RpcCacheError::ConflictingConf(800_005, _))
+            ));
+        }
+    }
+}
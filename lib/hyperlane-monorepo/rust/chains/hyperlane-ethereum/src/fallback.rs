@@ -1,27 +1,108 @@
+use std::cmp::Ordering as CmpOrdering;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use ethers::providers::{Http, HttpClientError, JsonRpcClient, ProviderError};
+use ethers::prelude::U64;
+use ethers::providers::{HttpClientError, JsonRpcClient, JsonRpcError, ProviderError};
+use futures_util::future::join_all;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use thiserror::Error;
-use tracing::warn;
+use tracing::{info, warn};
 
 use ethers_prometheus::json_rpc_client::PrometheusJsonRpcClient;
 
+use crate::{RetryingProvider, RetryingProviderError};
+
 const METHODS_TO_NOT_TO_FALLBACK_ON: &[&str] = &[
     "eth_estimateGas",
     "eth_sendTransaction",
     "eth_sendRawTransaction",
 ];
 
-/// A provider that bundles multiple providers and attempts to call the first,
-/// then the second, and so on until a response is received.
-#[derive(Debug, Clone)]
+/// Weight of the latest sample in the moving averages of error rate and
+/// latency.
+const EWMA_WEIGHT: f64 = 0.2;
+/// Score penalty, in seconds of latency, of a provider failing every request.
+const ERROR_PENALTY: f64 = 5.0;
+/// Score penalty, in seconds of latency, for each block a provider is behind
+/// the highest block any of the providers reported.
+const BLOCK_LAG_PENALTY: f64 = 0.5;
+/// Score penalty for each position down the configured order, so ties go to
+/// the provider configured first.
+const PRIORITY_PENALTY: f64 = 0.01;
+/// How much better another provider has to score before requests are moved
+/// over to it. Keeps requests on one provider so consecutive reads and nonces
+/// are consistent.
+const SWITCH_MARGIN: f64 = 1.0;
+const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A provider that bundles multiple providers and sends requests to the
+/// healthiest of them, falling back to the others in order of health until a
+/// response is received.
+///
+/// Each provider is scored by its error rate, latency, and how far its block
+/// height lags behind the other providers. Requests stick to the selected
+/// provider until it fails or another scores notably better. All providers
+/// are periodically probed so demoted providers can recover.
+#[derive(Debug)]
 pub struct FallbackProvider<T> {
-    /// Sorted list of providers this provider calls in order of most primary to
-    /// most fallback.
+    inner: Arc<FallbackProviderInner<T>>,
+}
+
+#[derive(Debug)]
+struct FallbackProviderInner<T> {
+    /// Providers in the configured order of priority.
     providers: Vec<T>,
+    health: Mutex<Vec<ProviderHealth>>,
+    /// Index of the provider requests are sent to first
+    selected: AtomicUsize,
+    last_probe: Mutex<Option<Instant>>,
+    probe_interval: Duration,
+}
+
+/// What is known about the health of a provider
+#[derive(Debug, Clone, Default)]
+struct ProviderHealth {
+    /// Moving average of the fraction of failed requests
+    error_rate: f64,
+    /// Moving average of the latency of successful requests in seconds
+    latency: Option<f64>,
+    /// The latest block number the provider reported
+    block_height: Option<u64>,
+}
+
+impl ProviderHealth {
+    fn record_success(&mut self, latency: Duration) {
+        let latency = latency.as_secs_f64();
+        self.error_rate *= 1. - EWMA_WEIGHT;
+        self.latency = Some(self.latency.map_or(latency, |average| {
+            average * (1. - EWMA_WEIGHT) + latency * EWMA_WEIGHT
+        }));
+    }
+
+    fn record_failure(&mut self) {
+        self.error_rate = self.error_rate * (1. - EWMA_WEIGHT) + EWMA_WEIGHT;
+    }
+
+    /// Lower is better
+    fn score(&self, priority: usize, block_lag: u64) -> f64 {
+        self.latency.unwrap_or_default()
+            + self.error_rate * ERROR_PENALTY
+            + block_lag as f64 * BLOCK_LAG_PENALTY
+            + priority as f64 * PRIORITY_PENALTY
+    }
+}
+
+impl<T> Clone for FallbackProvider<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
 }
 
 impl<T> FallbackProvider<T> {
@@ -37,16 +118,182 @@ impl<T> FallbackProvider<T> {
     }
 }
 
+impl<T> FallbackProviderInner<T> {
+    /// Score and block lag of each provider
+    fn scores(&self) -> Vec<(f64, u64)> {
+        let health = self.health.lock().unwrap();
+        let highest_block = health.iter().filter_map(|h| h.block_height).max();
+        health
+            .iter()
+            .enumerate()
+            .map(|(priority, h)| {
+                let block_lag = match (highest_block, h.block_height) {
+                    (Some(highest), Some(height)) => highest - height,
+                    _ => 0,
+                };
+                (h.score(priority, block_lag), block_lag)
+            })
+            .collect()
+    }
+
+    /// The order to try the providers in: the selected provider unless
+    /// another scores better by `SWITCH_MARGIN`, followed by the others from
+    /// healthiest to least healthy.
+    fn provider_order(&self) -> Vec<usize> {
+        let scores = self.scores();
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|&a, &b| {
+            scores[a]
+                .0
+                .partial_cmp(&scores[b].0)
+                .unwrap_or(CmpOrdering::Equal)
+        });
+        let Some(&best) = order.first() else {
+            return order;
+        };
+
+        let selected = self.selected.load(Ordering::Relaxed);
+        if best != selected && scores[best].0 + SWITCH_MARGIN < scores[selected].0 {
+            self.select(best);
+        } else {
+            order.retain(|&idx| idx != selected);
+            order.insert(0, selected);
+        }
+        order
+    }
+
+    fn select(&self, idx: usize) {
+        let previous = self.selected.swap(idx, Ordering::Relaxed);
+        if previous != idx {
+            info!(
+                previous_provider_index = previous,
+                provider_index = idx,
+                "Fallback provider switched to a different provider"
+            );
+        }
+    }
+
+    fn record_success(&self, idx: usize, latency: Duration, method: &str, response: &Value) {
+        let mut health = self.health.lock().unwrap();
+        health[idx].record_success(latency);
+        if method == "eth_blockNumber" {
+            if let Ok(height) = serde_json::from_value::<U64>(response.clone()) {
+                health[idx].block_height = Some(height.as_u64());
+            }
+        }
+    }
+
+    fn record_failure(&self, idx: usize) {
+        self.health.lock().unwrap()[idx].record_failure();
+    }
+}
+
+/// A provider a `FallbackProvider` can fall back from
+pub trait FallbackMember: JsonRpcClient {
+    /// Report the health the fallback provider assigned to this provider
+    fn report_health(&self, score: f64, block_lag: u64, selected: bool);
+
+    /// The JSON-RPC error the node answered with, if it did. Such errors say
+    /// nothing about the health of the node.
+    fn json_rpc_error(error: &Self::Error) -> Option<&JsonRpcError>;
+}
+
+impl<C> FallbackMember for PrometheusJsonRpcClient<C>
+where
+    C: JsonRpcClient<Error = HttpClientError>,
+{
+    fn report_health(&self, score: f64, block_lag: u64, selected: bool) {
+        PrometheusJsonRpcClient::report_health(self, score, block_lag, selected)
+    }
+
+    fn json_rpc_error(error: &HttpClientError) -> Option<&JsonRpcError> {
+        match error {
+            HttpClientError::JsonRpcError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<C> FallbackMember for RetryingProvider<PrometheusJsonRpcClient<C>>
+where
+    C: JsonRpcClient<Error = HttpClientError> + 'static,
+{
+    fn report_health(&self, score: f64, block_lag: u64, selected: bool) {
+        self.inner().report_health(score, block_lag, selected)
+    }
+
+    fn json_rpc_error(error: &Self::Error) -> Option<&JsonRpcError> {
+        match error {
+            RetryingProviderError::JsonRpcClientError(e)
+            | RetryingProviderError::MaxRequests(e) => {
+                PrometheusJsonRpcClient::<C>::json_rpc_error(e)
+            }
+        }
+    }
+}
+
+impl<P> FallbackProviderInner<P>
+where
+    P: FallbackMember,
+{
+    /// Query every provider for its block number to update their health.
+    async fn probe(&self) {
+        let results = join_all(self.providers.iter().map(|provider| async move {
+            let start = Instant::now();
+            let result = provider.request::<_, Value>("eth_blockNumber", ()).await;
+            (result, start.elapsed())
+        }))
+        .await;
+        for (idx, (result, latency)) in results.into_iter().enumerate() {
+            match result {
+                Ok(response) => self.record_success(idx, latency, "eth_blockNumber", &response),
+                Err(_) => self.record_failure(idx),
+            }
+        }
+        self.report_health();
+    }
+
+    fn report_health(&self) {
+        let selected = self.selected.load(Ordering::Relaxed);
+        for (idx, (provider, (score, block_lag))) in
+            self.providers.iter().zip(self.scores()).enumerate()
+        {
+            provider.report_health(score, block_lag, idx == selected);
+        }
+    }
+}
+
+impl<P> FallbackProvider<P>
+where
+    P: FallbackMember + 'static,
+{
+    /// Probe all providers in the background if they have not been probed
+    /// within the probe interval.
+    fn probe_if_due(&self) {
+        {
+            let mut last_probe = self.inner.last_probe.lock().unwrap();
+            if last_probe.map_or(false, |t| t.elapsed() < self.inner.probe_interval) {
+                return;
+            }
+            *last_probe = Some(Instant::now());
+        }
+        let inner = self.inner.clone();
+        tokio::spawn(async move { inner.probe().await });
+    }
+}
+
 /// Builder to create a new fallback provider.
 #[derive(Debug, Clone)]
 pub struct FallbackProviderBuilder<T> {
     providers: Vec<T>,
+    probe_interval: Duration,
 }
 
 impl<T> Default for FallbackProviderBuilder<T> {
     fn default() -> Self {
         Self {
             providers: Vec::new(),
+            probe_interval: DEFAULT_PROBE_INTERVAL,
         }
     }
 }
@@ -64,10 +311,23 @@ impl<T> FallbackProviderBuilder<T> {
         self
     }
 
+    /// How often to probe all providers to update their health.
+    pub fn probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
     /// Create a fallback provider.
     pub fn build(self) -> FallbackProvider<T> {
+        let health = vec![ProviderHealth::default(); self.providers.len()];
         FallbackProvider {
-            providers: self.providers,
+            inner: Arc::new(FallbackProviderInner {
+                providers: self.providers,
+                health: Mutex::new(health),
+                selected: AtomicUsize::new(0),
+                last_probe: Mutex::new(None),
+                probe_interval: self.probe_interval,
+            }),
         }
     }
 }
@@ -88,7 +348,10 @@ impl From<FallbackError> for ProviderError {
 
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
-impl JsonRpcClient for FallbackProvider<PrometheusJsonRpcClient<Http>> {
+impl<P> JsonRpcClient for FallbackProvider<P>
+where
+    P: FallbackMember + 'static,
+{
     type Error = ProviderError;
 
     async fn request<T: Serialize + Send + Sync, R: DeserializeOwned>(
@@ -96,38 +359,101 @@ impl JsonRpcClient for FallbackProvider<PrometheusJsonRpcClient<Http>> {
         method: &str,
         params: T,
     ) -> Result<R, Self::Error> {
+        self.probe_if_due();
         let params = serde_json::to_value(params).expect("valid");
 
         let mut errors = vec![];
-        for (idx, provider) in self.providers.iter().enumerate() {
+        for idx in self.inner.provider_order() {
+            let provider = &self.inner.providers[idx];
+            let start = Instant::now();
             let fut = match params {
                 Value::Null => provider.request(method, ()),
                 _ => provider.request(method, &params),
             };
 
             match fut.await {
-                Ok(v) => return Ok(serde_json::from_value(v)?),
-
-                Err(HttpClientError::ReqwestError(e)) => {
-                    warn!(error=%e, provider_index=%idx, ?provider, method, "ReqwestError in http provider; falling back to the next provider");
-                    errors.push(HttpClientError::ReqwestError(e).into())
-                }
-                Err(HttpClientError::SerdeJson { err, text }) => {
-                    warn!(error=%err, text, provider_index=%idx, ?provider, method, "ReqwestError in http provider; falling back to the next provider");
-                    errors.push(HttpClientError::SerdeJson { err, text }.into())
+                Ok(v) => {
+                    self.inner.record_success(idx, start.elapsed(), method, &v);
+                    // Stay with whichever provider answered
+                    self.inner.select(idx);
+                    return Ok(serde_json::from_value(v)?);
                 }
-                Err(HttpClientError::JsonRpcError(e)) => {
+
+                // The node answered, so this says nothing about its health
+                Err(e) if P::json_rpc_error(&e).is_some() => {
                     if METHODS_TO_NOT_TO_FALLBACK_ON.contains(&method) {
                         warn!(error = %e, provider_index=%idx, ?provider, method, "JsonRpcError in http provider; not falling back");
-                        return Err(HttpClientError::JsonRpcError(e).into());
+                        return Err(e.into());
                     } else {
                         warn!(error = %e, provider_index=%idx, ?provider, method, "JsonRpcError in http provider; falling back to the next provider");
-                        errors.push(HttpClientError::JsonRpcError(e).into())
+                        errors.push(e.into())
                     }
                 }
+                Err(e) => {
+                    self.inner.record_failure(idx);
+                    warn!(error = %e, provider_index=%idx, ?provider, method, "Error in http provider; falling back to the next provider");
+                    errors.push(e.into())
+                }
             }
         }
 
         Err(FallbackError::AllProvidersFailed(errors).into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn fallback(providers: usize) -> Arc<FallbackProviderInner<()>> {
+        FallbackProvider::new(vec![(); providers]).inner
+    }
+
+    fn record_block(fallback: &FallbackProviderInner<()>, idx: usize, height: u64) {
+        fallback.record_success(
+            idx,
+            Duration::from_millis(100),
+            "eth_blockNumber",
+            &json!(format!("{height:#x}")),
+        );
+    }
+
+    #[test]
+    fn prefers_configured_order_when_healthy() {
+        let fallback = fallback(3);
+        for idx in 0..3 {
+            record_block(&fallback, idx, 100);
+        }
+        assert_eq!(fallback.provider_order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn sticks_to_selected_provider_despite_small_differences() {
+        let fallback = fallback(2);
+        fallback.select(1);
+        record_block(&fallback, 0, 100);
+        record_block(&fallback, 1, 100);
+        fallback.record_success(1, Duration::from_millis(500), "eth_call", &json!("0x"));
+        assert_eq!(fallback.provider_order(), vec![1, 0]);
+    }
+
+    #[test]
+    fn demotes_lagging_and_failing_providers() {
+        let fallback = fallback(3);
+        record_block(&fallback, 0, 90);
+        record_block(&fallback, 1, 100);
+        record_block(&fallback, 2, 100);
+        assert_eq!(fallback.provider_order(), vec![1, 2, 0]);
+
+        for _ in 0..3 {
+            fallback.record_failure(1);
+        }
+        assert_eq!(fallback.provider_order(), vec![2, 1, 0]);
+
+        // Moves back up once it catches up, without taking over requests
+        record_block(&fallback, 0, 100);
+        assert_eq!(fallback.provider_order(), vec![2, 0, 1]);
+    }
+}
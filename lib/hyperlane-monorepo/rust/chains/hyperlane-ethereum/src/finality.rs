@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ethers::prelude::{Block, BlockNumber, Middleware};
+use tracing::warn;
+
+use hyperlane_core::{Finality, FinalityTag};
+
+/// How long to use the fallback block count after the node failed to resolve
+/// a finality tag before trying the tag again
+const TAG_RETRY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Resolves the latest final block of a chain. Falls back to a fixed number of
+/// blocks behind the head if the node does not support the finality tag.
+#[derive(Debug, Default)]
+pub(crate) struct FinalityResolver {
+    /// When the node last failed to resolve the finality tag
+    tag_failed_at: Mutex<Option<Instant>>,
+}
+
+impl FinalityResolver {
+    /// The number of the latest final block. `head` is used instead of
+    /// requesting the head block number if it is already known.
+    pub async fn finalized_block<M: Middleware>(
+        &self,
+        provider: &M,
+        finality: Finality,
+        head: Option<u64>,
+    ) -> Result<u64, M::Error> {
+        if let Finality::Tag { tag, .. } = finality {
+            if let Some(number) = self.tagged_block(provider, tag).await {
+                return Ok(number);
+            }
+        }
+        let head = match head {
+            Some(head) => head,
+            None => provider.get_block_number().await?.as_u64(),
+        };
+        Ok(head.saturating_sub(finality.blocks() as u64))
+    }
+
+    /// The number of the block the node resolves `tag` to, or `None` if it
+    /// could not.
+    async fn tagged_block<M: Middleware>(&self, provider: &M, tag: FinalityTag) -> Option<u64> {
+        let failed_recently = matches!(
+            *self.tag_failed_at.lock().unwrap(),
+            Some(at) if at.elapsed() < TAG_RETRY_INTERVAL
+        );
+        if failed_recently {
+            return None;
+        }
+
+        let block = match tag {
+            FinalityTag::Safe => BlockNumber::Safe,
+            FinalityTag::Finalized => BlockNumber::Finalized,
+        };
+        match provider.get_block(block).await {
+            Ok(Some(Block {
+                number: Some(number),
+                ..
+            })) => return Some(number.as_u64()),
+            Ok(_) => warn!(%tag, "Finality tag unsupported, using block count instead"),
+            Err(error) => warn!(%tag, %error, "Finality tag failed, using block count instead"),
+        }
+        *self.tag_failed_at.lock().unwrap() = Some(Instant::now());
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::prelude::{Provider, H256, U64};
+    use serde_json::Value;
+
+    use super::*;
+
+    fn block(number: u64) -> Block<H256> {
+        Block {
+            number: Some(number.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_finality_tags() {
+        let (provider, mock) = Provider::mocked();
+        let resolver = FinalityResolver::default();
+        let finality = Finality::new(20, Some(FinalityTag::Finalized));
+
+        mock.push(block(90)).unwrap();
+        let number = resolver
+            .finalized_block(&provider, finality, None)
+            .await
+            .unwrap();
+        assert_eq!(number, 90);
+
+        mock.push(U64::from(100)).unwrap();
+        let number = resolver
+            .finalized_block(&provider, Finality::Blocks(20), None)
+            .await
+            .unwrap();
+        assert_eq!(number, 80);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_block_count() {
+        let (provider, mock) = Provider::mocked();
+        let resolver = FinalityResolver::default();
+        let finality = Finality::new(20, Some(FinalityTag::Safe));
+
+        mock.push(Value::Null).unwrap();
+        let number = resolver
+            .finalized_block(&provider, finality, Some(100))
+            .await
+            .unwrap();
+        assert_eq!(number, 80);
+
+        // The tag is not requested again for a while
+        mock.push(block(104)).unwrap();
+        let number = resolver
+            .finalized_block(&provider, finality, Some(105))
+            .await
+            .unwrap();
+        assert_eq!(number, 85);
+    }
+}
@@ -5,21 +5,28 @@ use std::fmt::Display;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use ethers::prelude::Middleware;
-use tracing::instrument;
+use ethers::prelude::{Middleware, Provider};
+use ethers_contract::{parse_log, LogMeta as EthersLogMeta};
+use futures_util::StreamExt;
+use tracing::{instrument, warn};
 
 use hyperlane_core::{
-    ChainCommunicationError, ChainResult, ContractLocator, HyperlaneAbi, HyperlaneChain,
-    HyperlaneContract, HyperlaneDomain, HyperlaneProvider, Indexer, InterchainGasPaymaster,
-    InterchainGasPaymasterIndexer, InterchainGasPayment, InterchainGasPaymentMeta,
-    InterchainGasPaymentWithMeta, H160, H256,
+    ChainCommunicationError, ChainResult, ContractLocator, EventStream, Finality, HyperlaneAbi,
+    HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneProvider, Indexer,
+    InterchainGasPaymaster, InterchainGasPaymasterIndexer, InterchainGasPayment,
+    InterchainGasPaymentMeta, InterchainGasPaymentWithMeta, TxOutcome, H160, H256, U256,
 };
 
 use crate::contracts::interchain_gas_paymaster::{
-    InterchainGasPaymaster as EthereumInterchainGasPaymasterInternal, INTERCHAINGASPAYMASTER_ABI,
+    GasPaymentFilter, InterchainGasPaymaster as EthereumInterchainGasPaymasterInternal,
+    INTERCHAINGASPAYMASTER_ABI,
 };
+use crate::finality::FinalityResolver;
+use crate::subscription::subscribe_finalized_logs;
 use crate::trait_builder::BuildableWithProvider;
-use crate::EthereumProvider;
+use crate::tx::report_tx_outcome;
+use crate::RevertDecoder;
+use crate::{EthereumProvider, ReconnectingWs};
 
 impl<M> Display for EthereumInterchainGasPaymasterInternal<M>
 where
@@ -32,7 +39,7 @@ where
 
 pub struct InterchainGasPaymasterIndexerBuilder {
     pub mailbox_address: H160,
-    pub finality_blocks: u32,
+    pub finality: Finality,
 }
 
 #[async_trait]
@@ -47,9 +54,23 @@ impl BuildableWithProvider for InterchainGasPaymasterIndexerBuilder {
         Box::new(EthereumInterchainGasPaymasterIndexer::new(
             Arc::new(provider),
             locator,
-            self.finality_blocks,
+            self.finality,
         ))
     }
+
+    async fn build_with_subscriber<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        subscriber: Option<Provider<ReconnectingWs>>,
+        locator: &ContractLocator,
+    ) -> Self::Output {
+        let mut indexer =
+            EthereumInterchainGasPaymasterIndexer::new(Arc::new(provider), locator, self.finality);
+        if let Some(subscriber) = subscriber {
+            indexer = indexer.with_subscriber(subscriber);
+        }
+        Box::new(indexer)
+    }
 }
 
 #[derive(Debug)]
@@ -60,7 +81,10 @@ where
 {
     contract: Arc<EthereumInterchainGasPaymasterInternal<M>>,
     provider: Arc<M>,
-    finality_blocks: u32,
+    /// Websocket provider used to subscribe to new gas payments
+    subscriber: Option<Provider<ReconnectingWs>>,
+    finality: Finality,
+    finality_resolver: FinalityResolver,
 }
 
 impl<M> EthereumInterchainGasPaymasterIndexer<M>
@@ -68,16 +92,24 @@ where
     M: Middleware + 'static,
 {
     /// Create new EthereumInterchainGasPaymasterIndexer
-    pub fn new(provider: Arc<M>, locator: &ContractLocator, finality_blocks: u32) -> Self {
+    pub fn new(provider: Arc<M>, locator: &ContractLocator, finality: Finality) -> Self {
         Self {
             contract: Arc::new(EthereumInterchainGasPaymasterInternal::new(
                 locator.address,
                 provider.clone(),
             )),
             provider,
-            finality_blocks,
+            subscriber: None,
+            finality,
+            finality_resolver: FinalityResolver::default(),
         }
     }
+
+    /// Subscribe to new gas payments through the provided websocket provider
+    pub fn with_subscriber(mut self, subscriber: Provider<ReconnectingWs>) -> Self {
+        self.subscriber = Some(subscriber);
+        self
+    }
 }
 
 #[async_trait]
@@ -87,13 +119,12 @@ where
 {
     #[instrument(err, ret, skip(self))]
     async fn get_finalized_block_number(&self) -> ChainResult<u32> {
-        Ok(self
-            .provider
-            .get_block_number()
+        let number = self
+            .finality_resolver
+            .finalized_block(&*self.provider, self.finality, None)
             .await
-            .map_err(ChainCommunicationError::from_other)?
-            .as_u32()
-            .saturating_sub(self.finality_blocks))
+            .map_err(ChainCommunicationError::from_other)?;
+        Ok(number as u32)
     }
 }
 
@@ -118,18 +149,47 @@ where
 
         Ok(events
             .into_iter()
-            .map(|(log, log_meta)| InterchainGasPaymentWithMeta {
-                payment: InterchainGasPayment {
-                    message_id: H256::from(log.message_id),
-                    payment: log.payment,
-                },
-                meta: InterchainGasPaymentMeta {
-                    transaction_hash: log_meta.transaction_hash,
-                    log_index: log_meta.log_index,
-                },
-            })
+            .map(|(log, log_meta)| gas_payment_with_meta(log, &log_meta))
             .collect())
     }
+
+    async fn subscribe_gas_payments(
+        &self,
+    ) -> ChainResult<Option<EventStream<InterchainGasPaymentWithMeta>>> {
+        let Some(subscriber) = self.subscriber.clone() else {
+            return Ok(None);
+        };
+        let filter = self.contract.gas_payment_filter().filter;
+        let payments = subscribe_finalized_logs(subscriber, filter, self.finality).filter_map(
+            |log| async move {
+                let log_meta = EthersLogMeta::from(&log);
+                match parse_log::<GasPaymentFilter>(log) {
+                    Ok(event) => Some(gas_payment_with_meta(event, &log_meta)),
+                    Err(err) => {
+                        warn!(error = %err, "Unable to decode gas payment log");
+                        None
+                    }
+                }
+            },
+        );
+        Ok(Some(payments.boxed()))
+    }
+}
+
+fn gas_payment_with_meta(
+    event: GasPaymentFilter,
+    log_meta: &EthersLogMeta,
+) -> InterchainGasPaymentWithMeta {
+    InterchainGasPaymentWithMeta {
+        payment: InterchainGasPayment {
+            message_id: H256::from(event.message_id),
+            payment: event.payment,
+        },
+        meta: InterchainGasPaymentMeta {
+            transaction_hash: log_meta.transaction_hash,
+            log_index: log_meta.log_index,
+        },
+    }
 }
 
 pub struct InterchainGasPaymasterBuilder {}
@@ -158,6 +218,7 @@ where
 {
     contract: Arc<EthereumInterchainGasPaymasterInternal<M>>,
     domain: HyperlaneDomain,
+    provider: Arc<M>,
 }
 
 impl<M> EthereumInterchainGasPaymaster<M>
@@ -170,9 +231,10 @@ where
         Self {
             contract: Arc::new(EthereumInterchainGasPaymasterInternal::new(
                 locator.address,
-                provider,
+                provider.clone(),
             )),
             domain: locator.domain.clone(),
+            provider,
         }
     }
 }
@@ -187,7 +249,7 @@ where
 
     fn provider(&self) -> Box<dyn HyperlaneProvider> {
         Box::new(EthereumProvider::new(
-            self.contract.client(),
+            self.provider.clone(),
             self.domain.clone(),
         ))
     }
@@ -203,7 +265,67 @@ where
 }
 
 #[async_trait]
-impl<M> InterchainGasPaymaster for EthereumInterchainGasPaymaster<M> where M: Middleware + 'static {}
+impl<M> InterchainGasPaymaster for EthereumInterchainGasPaymaster<M>
+where
+    M: Middleware + 'static,
+{
+    #[instrument(err, ret, skip(self))]
+    async fn quote_gas_payment(
+        &self,
+        destination_domain: u32,
+        gas_amount: U256,
+    ) -> ChainResult<U256> {
+        Ok(self
+            .contract
+            .quote_gas_payment(destination_domain, gas_amount)
+            .call()
+            .await?)
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn pay_for_gas(
+        &self,
+        message_id: H256,
+        destination_domain: u32,
+        gas_amount: U256,
+        payment: U256,
+        refund_address: H256,
+    ) -> ChainResult<TxOutcome> {
+        let contract_call = self
+            .contract
+            .pay_for_gas(
+                message_id.into(),
+                destination_domain,
+                gas_amount,
+                H160::from(refund_address),
+            )
+            .value(payment);
+        report_tx_outcome(&*self.provider, &RevertDecoder::default(), contract_call).await
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn balance(&self) -> ChainResult<U256> {
+        self.provider
+            .get_balance(self.contract.address(), None)
+            .await
+            .map_err(ChainCommunicationError::from_other)
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn beneficiary(&self) -> ChainResult<H256> {
+        // The contract sends claimed fees to its owner
+        Ok(self.contract.owner().call().await?.into())
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn claim(&self, tx_gas_limit: Option<U256>) -> ChainResult<TxOutcome> {
+        let mut contract_call = self.contract.claim();
+        if let Some(gas_limit) = tx_gas_limit {
+            contract_call = contract_call.gas(gas_limit);
+        }
+        report_tx_outcome(&*self.provider, &RevertDecoder::default(), contract_call).await
+    }
+}
 
 pub struct EthereumInterchainGasPaymasterAbi;
 
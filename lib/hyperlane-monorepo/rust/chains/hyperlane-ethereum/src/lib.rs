@@ -4,17 +4,29 @@
 #![warn(missing_docs)]
 
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use ethers::abi::FunctionExt;
 use ethers::prelude::{abi, BlockId, BlockNumber, Http, Lazy, Middleware, NameOrAddress, Provider};
+use ethers_prometheus::json_rpc_client::JsonRpcClientMetrics;
 
 use hyperlane_core::*;
-pub use retrying::{RetryingProvider, RetryingProviderError};
+pub use archive::ArchiveRouter;
+pub use batching::BatchHttp;
+pub use caching::{CachingProvider, CachingProviderError, RpcCache, RpcCacheError};
+pub use reconnecting_ws::{NotificationStream, ReconnectingWs, ReconnectingWsError};
+pub use quorum::{InvalidQuorumRule, QuorumRouter, QuorumRouterBuilder, QuorumRule};
+pub use revert::{RevertAbiError, RevertDecoder};
+pub use retrying::{
+    RetryClass, RetryPolicy, RetryableError, RetryingProvider, RetryingProviderError, TokenBucket,
+};
 
 #[cfg(not(doctest))]
 pub use crate::{
     fallback::*, interchain_gas::*, mailbox::*, multisig_ism::*, provider::*, signers::*,
-    trait_builder::*,
+    trait_builder::*, validator_announce::*,
 };
 
 #[cfg(not(doctest))]
@@ -39,16 +51,45 @@ mod interchain_gas;
 #[cfg(not(doctest))]
 mod multisig_ism;
 
+/// ValidatorAnnounce abi
+#[cfg(not(doctest))]
+mod validator_announce;
+
 /// Generated contract bindings.
 #[cfg(not(doctest))]
 mod contracts;
 
+/// Websocket log subscriptions
+#[cfg(not(doctest))]
+mod subscription;
+
+/// Websocket transport which reconnects on failure
+mod reconnecting_ws;
+
 /// Retrying Provider
 mod retrying;
 
 /// Fallback provider
 mod fallback;
 
+/// Cache of responses for immutable chain data
+mod caching;
+
+/// HTTP transport batching requests for blocks and transactions
+mod batching;
+
+/// Decoding of revert reasons
+mod revert;
+
+/// Routing of historical requests to archive nodes
+mod archive;
+
+/// Resolution of the latest final block
+mod finality;
+
+/// Quorum provider with per-method rules
+mod quorum;
+
 mod signers;
 
 /// Ethereum connection configuration
@@ -59,21 +100,78 @@ pub enum ConnectionConf {
     HttpQuorum {
         /// List of fully qualified strings to connect to
         urls: String,
+        /// Comma separated weights of the urls, in the same order. Every url
+        /// has a weight of 1 by default.
+        weights: Option<String>,
+        /// How many providers have to agree on a response: `majority` (the
+        /// default), `all`, `percentage:<n>`, `weight:<n>` or `count:<n>`, or
+        /// `single` to ask one provider at a time.
+        quorum: Option<String>,
+        /// Overrides of `quorum` by method, e.g. `eth_blockNumber: single`
+        #[serde(default)]
+        methods: HashMap<String, String>,
+        /// How requests are retried and rate limited
+        #[serde(default)]
+        retry: RetryConf,
+        /// Whether concurrent requests for blocks, transactions and receipts
+        /// are sent as JSON-RPC batches, `true` by default
+        batch: Option<String>,
+        /// How responses for immutable chain data are cached
+        #[serde(default)]
+        cache: RpcCacheConf,
+        /// Archive nodes for requests far below the head
+        #[serde(default)]
+        archive: ArchiveConf,
     },
     /// An HTTP-only fallback set.
     HttpFallback {
         /// List of fully qualified strings to connect to in order of priority
         urls: String,
+        /// How requests are rate limited. Failed requests fall back to the
+        /// next url instead of being retried, so only `ratelimit` and `burst`
+        /// apply.
+        #[serde(default)]
+        retry: RetryConf,
+        /// Whether concurrent requests for blocks, transactions and receipts
+        /// are sent as JSON-RPC batches, `true` by default
+        batch: Option<String>,
+        /// How responses for immutable chain data are cached
+        #[serde(default)]
+        cache: RpcCacheConf,
+        /// Archive nodes for requests far below the head
+        #[serde(default)]
+        archive: ArchiveConf,
     },
     /// HTTP connection details
     Http {
         /// Fully qualified string to connect to
         url: String,
+        /// How requests are retried and rate limited
+        #[serde(default)]
+        retry: RetryConf,
+        /// Whether concurrent requests for blocks, transactions and receipts
+        /// are sent as JSON-RPC batches, `true` by default
+        batch: Option<String>,
+        /// How responses for immutable chain data are cached
+        #[serde(default)]
+        cache: RpcCacheConf,
+        /// Archive nodes for requests far below the head
+        #[serde(default)]
+        archive: ArchiveConf,
     },
     /// Websocket connection details
     Ws {
         /// Fully qualified string to connect to
         url: String,
+        /// How requests are retried and rate limited
+        #[serde(default)]
+        retry: RetryConf,
+        /// How responses for immutable chain data are cached
+        #[serde(default)]
+        cache: RpcCacheConf,
+        /// Archive nodes for requests far below the head
+        #[serde(default)]
+        archive: ArchiveConf,
     },
 }
 
@@ -81,10 +179,219 @@ impl Default for ConnectionConf {
     fn default() -> Self {
         Self::Http {
             url: Default::default(),
+            retry: Default::default(),
+            batch: Default::default(),
+            cache: Default::default(),
+            archive: Default::default(),
+        }
+    }
+}
+
+impl ConnectionConf {
+    /// How responses for immutable chain data are cached
+    pub fn cache(&self) -> &RpcCacheConf {
+        match self {
+            Self::HttpQuorum { cache, .. }
+            | Self::HttpFallback { cache, .. }
+            | Self::Http { cache, .. }
+            | Self::Ws { cache, .. } => cache,
+        }
+    }
+
+    /// Whether requests to HTTP nodes, including archive nodes, are sent as
+    /// JSON-RPC batches, if configured
+    pub fn batch(&self) -> Option<&String> {
+        match self {
+            Self::HttpQuorum { batch, .. }
+            | Self::HttpFallback { batch, .. }
+            | Self::Http { batch, .. } => batch.as_ref(),
+            Self::Ws { .. } => None,
+        }
+    }
+
+    /// How requests are retried and rate limited, unless the connection
+    /// falls back between nodes instead
+    pub fn retry(&self) -> Option<&RetryConf> {
+        match self {
+            Self::HttpQuorum { retry, .. } | Self::Http { retry, .. } | Self::Ws { retry, .. } => {
+                Some(retry)
+            }
+            Self::HttpFallback { .. } => None,
+        }
+    }
+
+    /// Archive nodes for requests far below the head
+    pub fn archive(&self) -> &ArchiveConf {
+        match self {
+            Self::HttpQuorum { archive, .. }
+            | Self::HttpFallback { archive, .. }
+            | Self::Http { archive, .. }
+            | Self::Ws { archive, .. } => archive,
         }
     }
 }
 
+/// Archive nodes to send requests for old blocks to, so the primary
+/// connection may use pruned nodes.
+#[derive(Debug, Default, serde::Deserialize, Clone)]
+pub struct ArchiveConf {
+    /// Comma separated urls of HTTP archive nodes in order of priority
+    pub urls: Option<String>,
+    /// Requests for blocks more than this many blocks below the head go to
+    /// the archive nodes, 128 by default
+    pub depth: Option<String>,
+}
+
+impl ArchiveConf {
+    /// The default depth below which requests go to the archive nodes.
+    /// Full nodes usually keep the state of the last 128 blocks.
+    pub const DEFAULT_DEPTH: u64 = 128;
+
+    /// The depth below which requests go to the archive nodes
+    pub fn depth(&self) -> Result<u64, std::num::ParseIntError> {
+        self.depth
+            .as_ref()
+            .map_or(Ok(Self::DEFAULT_DEPTH), |depth| depth.trim().parse())
+    }
+}
+
+/// Configuration of the cache of blocks, transactions and receipts looked up
+/// by hash. Caching is disabled unless `maxbytes` is set.
+#[derive(Debug, Default, serde::Deserialize, Clone)]
+pub struct RpcCacheConf {
+    /// The number of bytes of responses to keep in memory
+    pub maxbytes: Option<String>,
+    /// The size in bytes of the largest response to cache, 1MiB by default
+    pub maxentrybytes: Option<String>,
+    /// Transactions and receipts are only cached once their block is this
+    /// many blocks below the head. Should be at least the finality of the
+    /// chain, 64 by default.
+    pub finalityblocks: Option<String>,
+    /// Path of a RocksDB database to also persist responses in, so they
+    /// survive restarts. Must differ from the agent's database.
+    pub path: Option<String>,
+}
+
+impl RpcCacheConf {
+    /// The cache of the domain if caching is enabled
+    pub fn cache(
+        &self,
+        domain: &HyperlaneDomain,
+        metrics: Option<&JsonRpcClientMetrics>,
+    ) -> Result<Option<Arc<RpcCache>>, RpcCacheError> {
+        let parse = |field: &'static str, value: &String| {
+            value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| RpcCacheError::InvalidConf(field, value.clone()))
+        };
+        let max_bytes = match &self.maxbytes {
+            Some(max_bytes) => parse("maxbytes", max_bytes)?,
+            None => return Ok(None),
+        };
+        let max_entry_bytes = self
+            .maxentrybytes
+            .as_ref()
+            .map(|v| parse("maxentrybytes", v))
+            .transpose()?
+            .unwrap_or(1 << 20);
+        let finality_blocks = self
+            .finalityblocks
+            .as_ref()
+            .map(|v| parse("finalityblocks", v))
+            .transpose()?
+            .unwrap_or(64);
+        RpcCache::for_domain(
+            domain,
+            max_bytes,
+            max_entry_bytes,
+            finality_blocks as u64,
+            self.path.as_deref(),
+            metrics,
+        )
+        .map(Some)
+    }
+}
+
+/// Retry and rate limit configuration of a connection. Unset values keep the
+/// defaults of the transport.
+#[derive(Debug, Default, serde::Deserialize, Clone)]
+pub struct RetryConf {
+    /// The maximum number of requests made for a call, including the first one
+    pub maxrequests: Option<String>,
+    /// The backoff in ms before the first retry, doubled for every further
+    /// retry
+    pub backoffms: Option<String>,
+    /// The longest backoff in ms between two requests
+    pub maxbackoffms: Option<String>,
+    /// The fraction by which backoffs are randomly varied, between 0 and 1
+    pub jitter: Option<String>,
+    /// The number of requests per second to send to each url at most.
+    /// Unlimited by default.
+    pub ratelimit: Option<String>,
+    /// The number of requests which may be sent to a url at once without
+    /// waiting for the rate limit. Defaults to the rate limit.
+    pub burst: Option<String>,
+}
+
+impl RetryConf {
+    /// The retry policy, falling back to `default` for unset values
+    pub fn policy(&self, default: RetryPolicy) -> Result<RetryPolicy, InvalidRetryConf> {
+        let mut policy = default;
+        if let Some(max_requests) = parse_retry_field("maxrequests", &self.maxrequests)? {
+            if max_requests == 0 {
+                return Err(InvalidRetryConf("maxrequests", "0".into()));
+            }
+            policy.max_requests = max_requests;
+        }
+        if let Some(ms) = parse_retry_field("backoffms", &self.backoffms)? {
+            policy.base_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = parse_retry_field("maxbackoffms", &self.maxbackoffms)? {
+            policy.max_backoff = Duration::from_millis(ms);
+        }
+        if let Some(jitter) = parse_retry_field::<f64>("jitter", &self.jitter)? {
+            if !(0. ..=1.).contains(&jitter) {
+                return Err(InvalidRetryConf("jitter", jitter.to_string()));
+            }
+            policy.jitter = jitter;
+        }
+        Ok(policy)
+    }
+
+    /// The token bucket of the url if a rate limit is configured
+    pub fn rate_limit(&self, url: &str) -> Result<Option<Arc<TokenBucket>>, InvalidRetryConf> {
+        let Some(rate) = parse_retry_field::<f64>("ratelimit", &self.ratelimit)? else {
+            return Ok(None);
+        };
+        if !(rate.is_finite() && rate > 0.) {
+            return Err(InvalidRetryConf("ratelimit", rate.to_string()));
+        }
+        let burst = parse_retry_field("burst", &self.burst)?.unwrap_or(rate.ceil() as u32);
+        Ok(Some(TokenBucket::for_url(url, rate, burst)))
+    }
+}
+
+fn parse_retry_field<T: FromStr>(
+    field: &'static str,
+    value: &Option<String>,
+) -> Result<Option<T>, InvalidRetryConf> {
+    value
+        .as_ref()
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| InvalidRetryConf(field, value.clone()))
+        })
+        .transpose()
+}
+
+/// A retry configuration value could not be parsed
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid retry configuration {0}: {1:?}")]
+pub struct InvalidRetryConf(&'static str, String);
+
 #[allow(dead_code)]
 /// A live connection to an ethereum-compatible chain.
 pub struct Chain {
@@ -2,26 +2,31 @@
 #![allow(missing_docs)]
 
 use std::collections::HashMap;
-use std::num::NonZeroU64;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use ethers::abi::AbiEncode;
-use ethers::prelude::Middleware;
+use ethers::prelude::{Middleware, Provider};
 use ethers_contract::builders::ContractCall;
-use tracing::instrument;
+use ethers_contract::{parse_log, LogMeta as EthersLogMeta};
+use futures_util::StreamExt;
+use tracing::{instrument, warn};
 
 use hyperlane_core::{
-    ChainCommunicationError, ChainResult, Checkpoint, ContractLocator, HyperlaneAbi,
-    HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneMessage, HyperlaneProtocolError,
-    HyperlaneProvider, Indexer, LogMeta, Mailbox, MailboxIndexer, RawHyperlaneMessage,
-    TxCostEstimate, TxOutcome, H256, U256,
+    ChainCommunicationError, ChainResult, Checkpoint, ContractLocator, EventStream, Finality,
+    HyperlaneAbi, HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneMessage,
+    HyperlaneProtocolError, HyperlaneProvider, Indexer, LogMeta, Mailbox, MailboxIndexer,
+    RawHyperlaneMessage, TxCostEstimate, TxOutcome, H256, U256,
 };
 
-use crate::contracts::mailbox::{Mailbox as EthereumMailboxInternal, ProcessCall, MAILBOX_ABI};
+use crate::contracts::mailbox::{
+    DispatchFilter, Mailbox as EthereumMailboxInternal, ProcessCall, MAILBOX_ABI,
+};
+use crate::finality::FinalityResolver;
+use crate::subscription::subscribe_finalized_logs;
 use crate::trait_builder::BuildableWithProvider;
-use crate::tx::report_tx;
-use crate::EthereumProvider;
+use crate::tx::{call_error, report_tx, report_tx_outcome};
+use crate::{EthereumProvider, ReconnectingWs, RevertDecoder};
 
 impl<M> std::fmt::Display for EthereumMailboxInternal<M>
 where
@@ -33,7 +38,7 @@ where
 }
 
 pub struct MailboxIndexerBuilder {
-    pub finality_blocks: u32,
+    pub finality: Finality,
 }
 
 #[async_trait]
@@ -48,9 +53,22 @@ impl BuildableWithProvider for MailboxIndexerBuilder {
         Box::new(EthereumMailboxIndexer::new(
             Arc::new(provider),
             locator,
-            self.finality_blocks,
+            self.finality,
         ))
     }
+
+    async fn build_with_subscriber<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        subscriber: Option<Provider<ReconnectingWs>>,
+        locator: &ContractLocator,
+    ) -> Self::Output {
+        let mut indexer = EthereumMailboxIndexer::new(Arc::new(provider), locator, self.finality);
+        if let Some(subscriber) = subscriber {
+            indexer = indexer.with_subscriber(subscriber);
+        }
+        Box::new(indexer)
+    }
 }
 
 #[derive(Debug)]
@@ -61,7 +79,10 @@ where
 {
     contract: Arc<EthereumMailboxInternal<M>>,
     provider: Arc<M>,
-    finality_blocks: u32,
+    /// Websocket provider used to subscribe to new messages
+    subscriber: Option<Provider<ReconnectingWs>>,
+    finality: Finality,
+    finality_resolver: FinalityResolver,
 }
 
 impl<M> EthereumMailboxIndexer<M>
@@ -69,7 +90,7 @@ where
     M: Middleware + 'static,
 {
     /// Create new EthereumMailboxIndexer
-    pub fn new(provider: Arc<M>, locator: &ContractLocator, finality_blocks: u32) -> Self {
+    pub fn new(provider: Arc<M>, locator: &ContractLocator, finality: Finality) -> Self {
         let contract = Arc::new(EthereumMailboxInternal::new(
             locator.address,
             provider.clone(),
@@ -77,9 +98,17 @@ where
         Self {
             contract,
             provider,
-            finality_blocks,
+            subscriber: None,
+            finality,
+            finality_resolver: FinalityResolver::default(),
         }
     }
+
+    /// Subscribe to new messages through the provided websocket provider
+    pub fn with_subscriber(mut self, subscriber: Provider<ReconnectingWs>) -> Self {
+        self.subscriber = Some(subscriber);
+        self
+    }
 }
 
 #[async_trait]
@@ -89,13 +118,12 @@ where
 {
     #[instrument(level = "debug", err, ret, skip(self))]
     async fn get_finalized_block_number(&self) -> ChainResult<u32> {
-        Ok(self
-            .provider
-            .get_block_number()
+        let number = self
+            .finality_resolver
+            .finalized_block(&*self.provider, self.finality, None)
             .await
-            .map_err(ChainCommunicationError::from_other)?
-            .as_u32()
-            .saturating_sub(self.finality_blocks))
+            .map_err(ChainCommunicationError::from_other)?;
+        Ok(number as u32)
     }
 }
 
@@ -142,9 +170,36 @@ where
             .map(|(event, meta)| (H256::from(event.message_id), meta.into()))
             .collect())
     }
+
+    async fn subscribe_messages(
+        &self,
+    ) -> ChainResult<Option<EventStream<(HyperlaneMessage, LogMeta)>>> {
+        let Some(subscriber) = self.subscriber.clone() else {
+            return Ok(None);
+        };
+        let filter = self.contract.dispatch_filter().filter;
+        let messages = subscribe_finalized_logs(subscriber, filter, self.finality).filter_map(
+            |log| async move {
+                let meta = EthersLogMeta::from(&log);
+                match parse_log::<DispatchFilter>(log) {
+                    Ok(event) => {
+                        Some((HyperlaneMessage::from(event.message.to_vec()), meta.into()))
+                    }
+                    Err(err) => {
+                        warn!(error = %err, "Unable to decode dispatch log");
+                        None
+                    }
+                }
+            },
+        );
+        Ok(Some(messages.boxed()))
+    }
 }
 
-pub struct MailboxBuilder {}
+pub struct MailboxBuilder {
+    /// Decodes the reasons for reverted deliveries
+    pub revert_decoder: Arc<RevertDecoder>,
+}
 
 #[async_trait]
 impl BuildableWithProvider for MailboxBuilder {
@@ -155,7 +210,11 @@ impl BuildableWithProvider for MailboxBuilder {
         provider: M,
         locator: &ContractLocator,
     ) -> Self::Output {
-        Box::new(EthereumMailbox::new(Arc::new(provider), locator))
+        Box::new(EthereumMailbox::new(
+            Arc::new(provider),
+            locator,
+            self.revert_decoder.clone(),
+        ))
     }
 }
 
@@ -168,6 +227,8 @@ where
     contract: Arc<EthereumMailboxInternal<M>>,
     domain: HyperlaneDomain,
     provider: Arc<M>,
+    finality_resolver: FinalityResolver,
+    revert_decoder: Arc<RevertDecoder>,
 }
 
 impl<M> EthereumMailbox<M>
@@ -176,7 +237,11 @@ where
 {
     /// Create a reference to a mailbox at a specific Ethereum address on some
     /// chain
-    pub fn new(provider: Arc<M>, locator: &ContractLocator) -> Self {
+    pub fn new(
+        provider: Arc<M>,
+        locator: &ContractLocator,
+        revert_decoder: Arc<RevertDecoder>,
+    ) -> Self {
         Self {
             contract: Arc::new(EthereumMailboxInternal::new(
                 locator.address,
@@ -184,6 +249,8 @@ where
             )),
             domain: locator.domain.clone(),
             provider,
+            finality_resolver: FinalityResolver::default(),
+            revert_decoder,
         }
     }
 
@@ -203,7 +270,10 @@ where
         let gas_limit = if let Some(gas_limit) = tx_gas_limit {
             gas_limit
         } else {
-            tx.estimate_gas().await?.saturating_add(U256::from(100000))
+            tx.estimate_gas()
+                .await
+                .map_err(|err| call_error(&self.revert_decoder, err))?
+                .saturating_add(U256::from(100000))
         };
         Ok(tx.gas(gas_limit))
     }
@@ -250,21 +320,19 @@ where
     }
 
     #[instrument(err, ret, skip(self))]
-    async fn latest_checkpoint(&self, maybe_lag: Option<NonZeroU64>) -> ChainResult<Checkpoint> {
+    async fn latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint> {
         let base_call = self.contract.latest_checkpoint();
-        let call_with_lag = match maybe_lag {
-            Some(lag) => {
-                let tip = self
-                    .provider
-                    .get_block_number()
-                    .await
-                    .map_err(ChainCommunicationError::from_other)?
-                    .as_u64();
-                base_call.block(tip.saturating_sub(lag.get()))
-            }
-            None => base_call,
+        let call_at_finality = if finality.is_head() {
+            base_call
+        } else {
+            let block = self
+                .finality_resolver
+                .finalized_block(&*self.provider, finality, None)
+                .await
+                .map_err(ChainCommunicationError::from_other)?;
+            base_call.block(block)
         };
-        let (root, index) = call_with_lag.call().await?;
+        let (root, index) = call_at_finality.call().await?;
         Ok(Checkpoint {
             mailbox_address: self.address(),
             mailbox_domain: self.domain.id(),
@@ -288,6 +356,29 @@ where
             .into())
     }
 
+    #[instrument(err, ret, skip(self))]
+    async fn dispatch(
+        &self,
+        destination_domain: u32,
+        recipient: H256,
+        body: &[u8],
+    ) -> ChainResult<(HyperlaneMessage, TxOutcome)> {
+        let contract_call =
+            self.contract
+                .dispatch(destination_domain, recipient.into(), body.to_vec().into());
+        let receipt = report_tx(contract_call).await?;
+        let message = receipt
+            .logs
+            .iter()
+            .filter(|log| log.address == self.contract.address())
+            .find_map(|log| parse_log::<DispatchFilter>(log.clone()).ok())
+            .map(|event| HyperlaneMessage::from(event.message.to_vec()))
+            .ok_or(ChainCommunicationError::MessageNotDispatched(
+                receipt.transaction_hash,
+            ))?;
+        Ok((message, receipt.into()))
+    }
+
     #[instrument(err, ret, skip(self))]
     async fn process(
         &self,
@@ -298,8 +389,7 @@ where
         let contract_call = self
             .process_contract_call(message, metadata, tx_gas_limit)
             .await?;
-        let receipt = report_tx(contract_call).await?;
-        Ok(receipt.into())
+        report_tx_outcome(&*self.provider, &self.revert_decoder, contract_call).await
     }
 
     #[instrument(err, ret, skip(self), fields(metadata=format!("{:x?}", metadata)))]
@@ -5,6 +5,7 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use ethers::prelude::Middleware;
+use futures_util::future::try_join_all;
 use tokio::time::sleep;
 use tracing::instrument;
 
@@ -14,6 +15,7 @@ use hyperlane_core::{
     H256,
 };
 
+use crate::batching::MAX_BATCH_SIZE;
 use crate::BuildableWithProvider;
 
 /// Connection to an ethereum provider. Useful for querying information about
@@ -101,6 +103,29 @@ where
         })
     }
 
+    #[instrument(err, skip(self), fields(count = hashes.len()))]
+    async fn get_blocks_by_hash(&self, hashes: &[H256]) -> ChainResult<Vec<BlockInfo>> {
+        // Concurrent requests are sent as batches by the transport, so at
+        // most a batch is requested at once
+        let mut blocks = Vec::with_capacity(hashes.len());
+        for chunk in hashes.chunks(MAX_BATCH_SIZE) {
+            blocks
+                .extend(try_join_all(chunk.iter().map(|hash| self.get_block_by_hash(hash))).await?);
+        }
+        Ok(blocks)
+    }
+
+    #[instrument(err, skip(self), fields(count = hashes.len()))]
+    async fn get_txns_by_hash(&self, hashes: &[H256]) -> ChainResult<Vec<TxnInfo>> {
+        // Concurrent requests are sent as batches by the transport, so at
+        // most a batch is requested at once
+        let mut txns = Vec::with_capacity(hashes.len());
+        for chunk in hashes.chunks(MAX_BATCH_SIZE) {
+            txns.extend(try_join_all(chunk.iter().map(|hash| self.get_txn_by_hash(hash))).await?);
+        }
+        Ok(txns)
+    }
+
     #[instrument(err, skip(self))]
     async fn is_contract(&self, address: &H256) -> ChainResult<bool> {
         let code = self
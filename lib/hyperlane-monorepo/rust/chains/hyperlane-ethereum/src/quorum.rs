@@ -0,0 +1,489 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, ProviderError, Quorum, QuorumProvider, WeightedProvider};
+use prometheus::IntCounterVec;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::warn;
+
+tokio::task_local! {
+    /// Responses of the individual providers to the quorum request being made
+    /// on the current task, as `(provider index, method, response)`. Includes
+    /// the requests the quorum provider makes itself, e.g. to resolve the
+    /// latest block.
+    static RESPONSES: RefCell<Vec<(usize, String, Value)>>;
+}
+
+/// How many providers have to agree on the response to a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuorumRule {
+    /// Ask a single provider, falling back to the next one in order if it
+    /// fails.
+    Single,
+    /// Ask all providers and wait for the quorum to agree.
+    Quorum(Quorum),
+}
+
+impl Default for QuorumRule {
+    fn default() -> Self {
+        Self::Quorum(Quorum::Majority)
+    }
+}
+
+/// A quorum rule could not be parsed
+#[derive(Error, Debug)]
+#[error("Invalid quorum rule {0:?}, expected one of `single`, `majority`, `all`, `percentage:<n>`, `weight:<n>` or `count:<n>`")]
+pub struct InvalidQuorumRule(String);
+
+impl FromStr for QuorumRule {
+    type Err = InvalidQuorumRule;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidQuorumRule(s.to_owned());
+        let (kind, value) = match s.trim().split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s.trim(), None),
+        };
+        Ok(match (kind.to_lowercase().as_str(), value) {
+            ("single", None) => Self::Single,
+            ("majority", None) => Self::Quorum(Quorum::Majority),
+            ("all", None) => Self::Quorum(Quorum::All),
+            ("percentage", Some(value)) => {
+                let percentage: u8 = value.parse().map_err(|_| invalid())?;
+                if percentage > 100 {
+                    return Err(invalid());
+                }
+                Self::Quorum(Quorum::Percentage(percentage))
+            }
+            ("weight", Some(value)) => {
+                Self::Quorum(Quorum::Weight(value.parse().map_err(|_| invalid())?))
+            }
+            ("count", Some(value)) => {
+                Self::Quorum(Quorum::ProviderCount(value.parse().map_err(|_| invalid())?))
+            }
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+/// A provider which sends requests to several providers and applies a
+/// `QuorumRule` to their responses, which may be overridden per method.
+///
+/// Responses which disagree with the value the quorum agreed on are counted,
+/// as are requests for which no quorum was reached. Only the responses which
+/// arrived before the quorum was reached can be compared, since the requests
+/// still in flight at that point are dropped rather than awaited, so a
+/// provider which is consistently slower than the others may disagree without
+/// ever being counted.
+#[derive(Debug)]
+pub struct QuorumRouter<P> {
+    providers: Vec<RecordingProvider<P>>,
+    /// A quorum provider for each distinct quorum in use
+    quorums: Vec<(Quorum, QuorumProvider<RecordingProvider<P>>)>,
+    default_route: Route,
+    /// Routes by lowercase method name
+    method_routes: HashMap<String, Route>,
+    metrics: Option<QuorumMetrics>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Route {
+    Single,
+    /// Index into `QuorumRouter::quorums`
+    Quorum(usize),
+}
+
+#[derive(Debug, Clone)]
+struct QuorumMetrics {
+    chain: String,
+    disagreements: Option<IntCounterVec>,
+    failures: Option<IntCounterVec>,
+}
+
+/// A provider of a `QuorumRouter`, recording its responses to quorum requests
+/// so disagreements can be found.
+#[derive(Debug)]
+struct RecordingProvider<P> {
+    index: usize,
+    name: String,
+    inner: Arc<P>,
+}
+
+impl<P> Clone for RecordingProvider<P> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            name: self.name.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<P> JsonRpcClient for RecordingProvider<P>
+where
+    P: JsonRpcClient,
+{
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let response: Value = self
+            .inner
+            .request(method, params)
+            .await
+            .map_err(Into::into)?;
+        let _ = RESPONSES.try_with(|responses| {
+            responses
+                .borrow_mut()
+                .push((self.index, method.to_owned(), response.clone()))
+        });
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+impl<P> QuorumRouter<P> {
+    /// Create a `QuorumRouterBuilder`
+    pub fn builder() -> QuorumRouterBuilder<P> {
+        QuorumRouterBuilder::default()
+    }
+
+    fn route(&self, method: &str) -> Route {
+        self.method_routes
+            .get(&method.to_lowercase())
+            .copied()
+            .unwrap_or(self.default_route)
+    }
+
+    /// Count the `responses` which disagree with the quorum's `value`. These
+    /// are the responses received before the quorum was reached.
+    fn count_disagreements(
+        &self,
+        method: &str,
+        value: &Value,
+        responses: &[(usize, String, Value)],
+    ) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        for (idx, response_method, response) in responses {
+            if response_method != method || response == value {
+                continue;
+            }
+            let provider = &self.providers[*idx].name;
+            warn!(method, %provider, ?response, quorum_response = ?value, "Provider disagreed with the quorum");
+            if let Some(disagreements) = &metrics.disagreements {
+                disagreements
+                    .with_label_values(&[&metrics.chain, method, provider])
+                    .inc();
+            }
+        }
+    }
+
+    fn count_failure(&self, method: &str) {
+        if let Some(QuorumMetrics {
+            chain,
+            failures: Some(failures),
+            ..
+        }) = &self.metrics
+        {
+            failures.with_label_values(&[chain, method]).inc();
+        }
+    }
+}
+
+#[async_trait]
+impl<P> JsonRpcClient for QuorumRouter<P>
+where
+    P: JsonRpcClient + 'static,
+{
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params)?;
+        match self.route(method) {
+            Route::Single => {
+                let mut last_error = None;
+                for provider in &self.providers {
+                    let result = match params {
+                        Value::Null => provider.request(method, ()).await,
+                        _ => provider.request(method, &params).await,
+                    };
+                    match result {
+                        Ok(response) => return Ok(response),
+                        Err(err) => {
+                            warn!(provider = %provider.name, method, error = %err, "Provider failed, trying the next one");
+                            last_error = Some(err);
+                        }
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| {
+                    ProviderError::CustomError("Quorum router has no providers".into())
+                }))
+            }
+            Route::Quorum(idx) => {
+                let quorum = &self.quorums[idx].1;
+                let (result, responses) = RESPONSES
+                    .scope(RefCell::new(Vec::new()), async {
+                        let result = match params {
+                            Value::Null => quorum.request::<_, Value>(method, ()).await,
+                            _ => quorum.request::<_, Value>(method, &params).await,
+                        };
+                        (result, RESPONSES.with(|responses| responses.take()))
+                    })
+                    .await;
+                match result {
+                    Ok(value) => {
+                        self.count_disagreements(method, &value, &responses);
+                        Ok(serde_json::from_value(value)?)
+                    }
+                    Err(err) => {
+                        self.count_failure(method);
+                        Err(err.into())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Builder for a `QuorumRouter`
+#[derive(Debug)]
+pub struct QuorumRouterBuilder<P> {
+    providers: Vec<(String, P, u64)>,
+    rule: QuorumRule,
+    method_rules: HashMap<String, QuorumRule>,
+    metrics: Option<QuorumMetrics>,
+}
+
+impl<P> Default for QuorumRouterBuilder<P> {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            rule: QuorumRule::default(),
+            method_rules: HashMap::new(),
+            metrics: None,
+        }
+    }
+}
+
+impl<P> QuorumRouterBuilder<P> {
+    /// Add a provider with the given weight. The name identifies it in logs
+    /// and metrics, and providers are asked in the order they are added for
+    /// `QuorumRule::Single`.
+    pub fn add_provider(mut self, name: impl Into<String>, provider: P, weight: u64) -> Self {
+        self.providers.push((name.into(), provider, weight));
+        self
+    }
+
+    /// The rule for methods without an override. Defaults to a majority.
+    pub fn rule(mut self, rule: QuorumRule) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    /// Override the rule for a method
+    pub fn method_rule(mut self, method: &str, rule: QuorumRule) -> Self {
+        self.method_rules.insert(method.to_lowercase(), rule);
+        self
+    }
+
+    /// Count responses which disagree with the quorum, by chain, method, and
+    /// provider, and requests which did not reach a quorum, by chain and
+    /// method.
+    pub fn metrics(
+        mut self,
+        chain: impl Into<String>,
+        disagreements: Option<IntCounterVec>,
+        failures: Option<IntCounterVec>,
+    ) -> Self {
+        self.metrics = Some(QuorumMetrics {
+            chain: chain.into(),
+            disagreements,
+            failures,
+        });
+        self
+    }
+
+    /// Create the quorum router
+    pub fn build(self) -> QuorumRouter<P>
+    where
+        P: JsonRpcClient,
+    {
+        let providers: Vec<_> = self
+            .providers
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, provider, weight))| {
+                let provider = RecordingProvider {
+                    index,
+                    name,
+                    inner: Arc::new(provider),
+                };
+                (provider, weight)
+            })
+            .collect();
+
+        let mut quorums: Vec<(Quorum, QuorumProvider<RecordingProvider<P>>)> = Vec::new();
+        let mut route = |rule: QuorumRule| match rule {
+            QuorumRule::Single => Route::Single,
+            QuorumRule::Quorum(quorum) => {
+                if let Some(idx) = quorums.iter().position(|(q, _)| *q == quorum) {
+                    return Route::Quorum(idx);
+                }
+                let mut builder = QuorumProvider::builder().quorum(quorum);
+                for (provider, weight) in &providers {
+                    builder = builder
+                        .add_provider(WeightedProvider::with_weight(provider.clone(), *weight));
+                }
+                quorums.push((quorum, builder.build()));
+                Route::Quorum(quorums.len() - 1)
+            }
+        };
+        let default_route = route(self.rule);
+        let method_routes = self
+            .method_rules
+            .into_iter()
+            .map(|(method, rule)| (method, route(rule)))
+            .collect();
+
+        QuorumRouter {
+            providers: providers
+                .into_iter()
+                .map(|(provider, _)| provider)
+                .collect(),
+            quorums,
+            default_route,
+            method_routes,
+            metrics: self.metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ethers::prelude::U64;
+    use prometheus::opts;
+
+    use super::*;
+
+    /// Responds to every request with a fixed block number
+    #[derive(Debug)]
+    struct FixedProvider {
+        block: u64,
+        requests: AtomicUsize,
+    }
+
+    impl FixedProvider {
+        fn new(block: u64) -> Self {
+            Self {
+                block,
+                requests: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for FixedProvider {
+        type Error = ProviderError;
+
+        async fn request<T, R>(&self, _method: &str, _params: T) -> Result<R, Self::Error>
+        where
+            T: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(serde_json::from_value(serde_json::to_value(U64::from(
+                self.block,
+            ))?)?)
+        }
+    }
+
+    #[test]
+    fn parses_quorum_rules() {
+        assert_eq!("single".parse::<QuorumRule>().unwrap(), QuorumRule::Single);
+        assert_eq!(
+            "Majority".parse::<QuorumRule>().unwrap(),
+            QuorumRule::Quorum(Quorum::Majority)
+        );
+        assert_eq!(
+            "percentage:66".parse::<QuorumRule>().unwrap(),
+            QuorumRule::Quorum(Quorum::Percentage(66))
+        );
+        assert_eq!(
+            "weight:3".parse::<QuorumRule>().unwrap(),
+            QuorumRule::Quorum(Quorum::Weight(3))
+        );
+        assert_eq!(
+            "count:2".parse::<QuorumRule>().unwrap(),
+            QuorumRule::Quorum(Quorum::ProviderCount(2))
+        );
+        assert!("percentage:101".parse::<QuorumRule>().is_err());
+        assert!("weight".parse::<QuorumRule>().is_err());
+        assert!("sometimes".parse::<QuorumRule>().is_err());
+    }
+
+    #[tokio::test]
+    async fn routes_methods_and_counts_disagreements() {
+        let disagreements = IntCounterVec::new(
+            opts!("quorum_disagreements", "test"),
+            &["chain", "method", "provider_node"],
+        )
+        .unwrap();
+        let failures =
+            IntCounterVec::new(opts!("quorum_failures", "test"), &["chain", "method"]).unwrap();
+        let router = QuorumRouter::builder()
+            .add_provider("a", FixedProvider::new(10), 1)
+            .add_provider("b", FixedProvider::new(10), 1)
+            .add_provider("c", FixedProvider::new(9), 1)
+            .method_rule("eth_chainId", QuorumRule::Single)
+            .method_rule("eth_gasPrice", QuorumRule::Quorum(Quorum::All))
+            .metrics("test", Some(disagreements.clone()), Some(failures.clone()))
+            .build();
+
+        let block: U64 = router.request("eth_chainId", ()).await.unwrap();
+        assert_eq!(block.as_u64(), 10);
+        let requests: Vec<_> = router
+            .providers
+            .iter()
+            .map(|p| p.inner.requests.load(Ordering::SeqCst))
+            .collect();
+        assert_eq!(requests, vec![1, 0, 0]);
+
+        let block: U64 = router.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(block.as_u64(), 10);
+
+        assert!(router.request::<_, U64>("eth_gasPrice", ()).await.is_err());
+        assert_eq!(
+            failures.with_label_values(&["test", "eth_gasPrice"]).get(),
+            1
+        );
+        assert_eq!(
+            disagreements
+                .with_label_values(&["test", "eth_blockNumber", "a"])
+                .get(),
+            0
+        );
+        assert_eq!(
+            disagreements
+                .with_label_values(&["test", "eth_blockNumber", "c"])
+                .get(),
+            1
+        );
+    }
+}
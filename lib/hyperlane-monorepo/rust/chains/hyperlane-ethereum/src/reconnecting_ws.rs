@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ethers::prelude::{JsonRpcClient, ProviderError, PubsubClient, Ws, WsClientError, U256};
+use futures_util::{Stream, StreamExt};
+use prometheus::IntCounterVec;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, value::RawValue, Value};
+use thiserror::Error;
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::time::sleep;
+use tracing::{debug, info, instrument, warn};
+
+/// The delay before the first reconnection attempt. Doubles with every failed
+/// attempt up to `RECONNECT_MAX_BACKOFF`.
+const RECONNECT_BASE_BACKOFF: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How many times reconnecting is attempted before giving up on the
+/// connection. Requests then fail and subscriptions end until a later request
+/// succeeds in reconnecting.
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+/// How many times a request is sent before giving up, reconnecting in between
+const MAX_REQUEST_ATTEMPTS: u32 = 3;
+
+/// A websocket transport which reconnects when the connection drops.
+///
+/// Requests that fail because of the dropped connection are sent again once
+/// reconnected, and active subscriptions are re-established on the new
+/// connection. Subscriptions keep the id they were first given, so their
+/// `SubscriptionStream`s continue receiving notifications.
+///
+/// If the endpoint cannot be reached for `MAX_RECONNECT_ATTEMPTS` attempts,
+/// pending requests fail with a connection error and subscriptions end.
+#[derive(Clone)]
+pub struct ReconnectingWs {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    url: String,
+    chain: String,
+    connection: RwLock<Connection>,
+    /// Held while reconnecting so only one reconnection happens at a time.
+    /// Holds the generation of the connection which was last given up on,
+    /// and when.
+    reconnecting: Mutex<Option<(u64, Instant)>>,
+    subscriptions: std::sync::Mutex<HashMap<U256, Subscription>>,
+    next_subscription_id: AtomicU64,
+    reconnects: Option<IntCounterVec>,
+}
+
+#[derive(Clone)]
+struct Connection {
+    ws: Ws,
+    /// Incremented with every reconnection
+    generation: u64,
+}
+
+struct Subscription {
+    /// The `eth_subscribe` params, to subscribe again after reconnecting
+    params: Value,
+    /// The id assigned by the node on the current connection
+    server_id: Option<U256>,
+    sender: mpsc::UnboundedSender<Box<RawValue>>,
+    /// Handed out by `PubsubClient::subscribe`
+    receiver: Option<mpsc::UnboundedReceiver<Box<RawValue>>>,
+}
+
+impl Debug for ReconnectingWs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectingWs")
+            .field("chain", &self.inner.chain)
+            .finish()
+    }
+}
+
+impl ReconnectingWs {
+    /// Connect to the websocket at `url`. Reconnection attempts are counted
+    /// with `reconnects` if provided, see
+    /// `MiddlewareMetrics::websocket_reconnects`.
+    pub async fn connect(
+        url: &str,
+        chain: &str,
+        reconnects: Option<IntCounterVec>,
+    ) -> Result<Self, WsClientError> {
+        let ws = Ws::connect(url).await?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                url: url.to_owned(),
+                chain: chain.to_owned(),
+                connection: RwLock::new(Connection { ws, generation: 0 }),
+                reconnecting: Mutex::new(None),
+                subscriptions: Default::default(),
+                next_subscription_id: AtomicU64::new(1),
+                reconnects,
+            }),
+        })
+    }
+
+    async fn connection(&self) -> Connection {
+        self.inner.connection.read().await.clone()
+    }
+
+    /// Replace the connection of the given generation, unless that has
+    /// already happened, and re-establish all subscriptions on it. Fails if
+    /// no connection could be established, ending all subscriptions.
+    #[instrument(skip(self), fields(chain = %self.inner.chain))]
+    async fn reconnect(&self, mut generation: u64) -> Result<(), ReconnectingWsError> {
+        let mut given_up = self.inner.reconnecting.lock().await;
+        if self.inner.connection.read().await.generation != generation {
+            return Ok(());
+        }
+        // Fail the callers which were waiting for the attempt that just gave
+        // up, rather than having each of them try again
+        if let Some((given_up_generation, at)) = *given_up {
+            if given_up_generation == generation && at.elapsed() < RECONNECT_MAX_BACKOFF {
+                return Err(ReconnectingWsError::Disconnected(MAX_RECONNECT_ATTEMPTS));
+            }
+        }
+
+        let mut backoff = RECONNECT_BASE_BACKOFF;
+        for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+            let ws = match Ws::connect(self.inner.url.as_str()).await {
+                Ok(ws) => {
+                    self.count_reconnect("success");
+                    ws
+                }
+                Err(err) => {
+                    self.count_reconnect("failure");
+                    warn!(attempt, error = %err, ?backoff, "Failed to reconnect websocket");
+                    if attempt < MAX_RECONNECT_ATTEMPTS {
+                        sleep(backoff).await;
+                        backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+                    }
+                    continue;
+                }
+            };
+            generation += 1;
+            let connection = Connection { ws, generation };
+            *self.inner.connection.write().await = connection.clone();
+
+            let ids: Vec<U256> = {
+                let mut subscriptions = self.inner.subscriptions.lock().unwrap();
+                subscriptions
+                    .values_mut()
+                    .for_each(|subscription| subscription.server_id = None);
+                subscriptions.keys().copied().collect()
+            };
+            let mut dropped = false;
+            for id in ids {
+                match self.start_subscription(id, &connection).await {
+                    Ok(()) => {}
+                    Err(err) if is_connection_error(&err) => {
+                        dropped = true;
+                        break;
+                    }
+                    Err(err) => {
+                        warn!(%id, error = %err, "Failed to resubscribe, dropping subscription");
+                        self.inner.subscriptions.lock().unwrap().remove(&id);
+                    }
+                }
+            }
+            if !dropped {
+                info!(generation, "Reconnected websocket");
+                *given_up = None;
+                return Ok(());
+            }
+        }
+
+        warn!(
+            attempts = MAX_RECONNECT_ATTEMPTS,
+            "Giving up reconnecting websocket, ending subscriptions"
+        );
+        // Dropping the senders ends the notification streams
+        self.inner.subscriptions.lock().unwrap().clear();
+        *given_up = Some((generation, Instant::now()));
+        Err(ReconnectingWsError::Disconnected(MAX_RECONNECT_ATTEMPTS))
+    }
+
+    fn count_reconnect(&self, result: &str) {
+        if let Some(reconnects) = &self.inner.reconnects {
+            reconnects
+                .with_label_values(&[&self.inner.chain, result])
+                .inc();
+        }
+    }
+
+    /// Send a request, reconnecting and sending it again if the connection
+    /// dropped.
+    async fn request_with_reconnect<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &Value,
+    ) -> Result<R, ReconnectingWsError> {
+        let mut attempt = 1;
+        loop {
+            let connection = self.connection().await;
+            let result = match params {
+                Value::Null => connection.ws.request(method, ()).await,
+                _ => connection.ws.request(method, params).await,
+            };
+            match result {
+                Err(err) if is_connection_error(&err) && attempt < MAX_REQUEST_ATTEMPTS => {
+                    warn!(method, attempt, error = %err, "Websocket request failed, reconnecting");
+                    self.reconnect(connection.generation).await?;
+                    attempt += 1;
+                }
+                result => return result.map_err(Into::into),
+            }
+        }
+    }
+
+    /// Register a subscription under a new local id and subscribe to it on
+    /// the current connection.
+    async fn subscribe_with_reconnect(&self, params: Value) -> Result<U256, ReconnectingWsError> {
+        let id = U256::from(
+            self.inner
+                .next_subscription_id
+                .fetch_add(1, Ordering::Relaxed),
+        );
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.inner.subscriptions.lock().unwrap().insert(
+            id,
+            Subscription {
+                params,
+                server_id: None,
+                sender,
+                receiver: Some(receiver),
+            },
+        );
+
+        let connection = self.connection().await;
+        match self.start_subscription(id, &connection).await {
+            Ok(()) => Ok(id),
+            Err(err) if is_connection_error(&err) => {
+                // Reconnecting subscribes again, including to this subscription
+                if let Err(reconnect_err) = self.reconnect(connection.generation).await {
+                    self.inner.subscriptions.lock().unwrap().remove(&id);
+                    return Err(reconnect_err);
+                }
+                let subscribed = self
+                    .inner
+                    .subscriptions
+                    .lock()
+                    .unwrap()
+                    .get(&id)
+                    .map_or(false, |subscription| subscription.server_id.is_some());
+                if subscribed {
+                    Ok(id)
+                } else {
+                    Err(err.into())
+                }
+            }
+            Err(err) => {
+                self.inner.subscriptions.lock().unwrap().remove(&id);
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Subscribe on the given connection and forward its notifications to
+    /// the subscription with the given local id.
+    async fn start_subscription(
+        &self,
+        id: U256,
+        connection: &Connection,
+    ) -> Result<(), WsClientError> {
+        let subscription = self
+            .inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map(|subscription| (subscription.params.clone(), subscription.sender.clone()));
+        let Some((params, sender)) = subscription else {
+            return Ok(());
+        };
+
+        let server_id: U256 = connection.ws.request("eth_subscribe", params).await?;
+        let notifications = connection.ws.subscribe(server_id)?;
+        if let Some(subscription) = self.inner.subscriptions.lock().unwrap().get_mut(&id) {
+            subscription.server_id = Some(server_id);
+        }
+        tokio::spawn(self.clone().forward_notifications(
+            id,
+            notifications,
+            sender,
+            connection.generation,
+        ));
+        Ok(())
+    }
+
+    async fn forward_notifications(
+        self,
+        id: U256,
+        mut notifications: <Ws as PubsubClient>::NotificationStream,
+        sender: mpsc::UnboundedSender<Box<RawValue>>,
+        generation: u64,
+    ) {
+        while let Some(notification) = notifications.next().await {
+            if sender.send(notification).is_err() {
+                // The subscriber went away
+                return;
+            }
+        }
+        // The notifications end when the connection drops or the subscription
+        // was removed
+        let active = self.inner.subscriptions.lock().unwrap().contains_key(&id);
+        if active {
+            // Giving up on reconnecting ends this subscription too
+            let _ = self.reconnect(generation).await;
+        }
+    }
+
+    async fn unsubscribe_request<R: DeserializeOwned>(
+        &self,
+        params: &Value,
+    ) -> Result<R, ReconnectingWsError> {
+        let id: U256 = serde_json::from_value(params.get(0).cloned().unwrap_or_default())?;
+        let server_id = self
+            .inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .and_then(|subscription| subscription.server_id);
+        match server_id {
+            Some(server_id) => Ok(self
+                .connection()
+                .await
+                .ws
+                .request("eth_unsubscribe", [server_id])
+                .await?),
+            None => Ok(serde_json::from_value(Value::Bool(false))?),
+        }
+    }
+}
+
+/// Whether the error was caused by the connection rather than the request
+fn is_connection_error(err: &WsClientError) -> bool {
+    !matches!(
+        err,
+        WsClientError::JsonRpcError(_) | WsClientError::JsonError(_)
+    )
+}
+
+#[async_trait]
+impl JsonRpcClient for ReconnectingWs {
+    type Error = ReconnectingWsError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params)?;
+        match method {
+            "eth_subscribe" => {
+                let id = self.subscribe_with_reconnect(params).await?;
+                Ok(serde_json::from_value(json!(id))?)
+            }
+            "eth_unsubscribe" => self.unsubscribe_request(&params).await,
+            _ => self.request_with_reconnect(method, &params).await,
+        }
+    }
+}
+
+impl PubsubClient for ReconnectingWs {
+    type NotificationStream = NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        let id = id.into();
+        self.inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get_mut(&id)
+            .and_then(|subscription| subscription.receiver.take())
+            .map(NotificationStream)
+            .ok_or(ReconnectingWsError::UnknownSubscription(id))
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        let id = id.into();
+        let server_id = self
+            .inner
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .and_then(|subscription| subscription.server_id);
+        if let Some(server_id) = server_id {
+            // The connection may be locked for reconnecting, so unsubscribe
+            // once it is available rather than leaking the subscription on the
+            // node
+            let ws = self.clone();
+            tokio::spawn(async move {
+                let connection = ws.connection().await;
+                let _ = connection.ws.unsubscribe(server_id);
+                if let Err(err) = connection
+                    .ws
+                    .request::<_, bool>("eth_unsubscribe", [server_id])
+                    .await
+                {
+                    debug!(%server_id, error = %err, "Failed to unsubscribe on the node");
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Notifications for a subscription, across reconnections
+#[derive(Debug)]
+pub struct NotificationStream(mpsc::UnboundedReceiver<Box<RawValue>>);
+
+impl Stream for NotificationStream {
+    type Item = Box<RawValue>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// Error type for the ReconnectingWs
+#[derive(Error, Debug)]
+pub enum ReconnectingWsError {
+    /// An error from the underlying websocket client
+    #[error(transparent)]
+    WsClientError(#[from] WsClientError),
+    /// Params or a response could not be (de)serialized
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    /// `PubsubClient::subscribe` was called with an unknown id, or twice
+    #[error("Unknown subscription {0}")]
+    UnknownSubscription(U256),
+    /// The connection dropped and could not be re-established
+    #[error("Websocket connection lost and not re-established after {0} attempts")]
+    Disconnected(u32),
+}
+
+impl From<ReconnectingWsError> for ProviderError {
+    fn from(src: ReconnectingWsError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+    use ethers::prelude::{Middleware, Provider};
+    use futures_util::SinkExt;
+    use prometheus::opts;
+    use warp::ws::{Message, WebSocket};
+    use warp::Filter;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Server {
+        connections: AtomicUsize,
+        /// Refuse all connections after the first
+        refuse_reconnects: AtomicBool,
+        /// Number of `eth_unsubscribe` requests received
+        unsubscribes: Arc<AtomicUsize>,
+    }
+
+    /// A JSON-RPC websocket server which drops the first connection as soon
+    /// as it is asked for the block number. `test_notify` sends a
+    /// notification carrying the connection number to every subscription.
+    fn serve(server: Arc<Server>) -> SocketAddr {
+        let routes = warp::ws().and_then(move |ws: warp::ws::Ws| {
+            let server = server.clone();
+            async move {
+                let connection = server.connections.fetch_add(1, Ordering::SeqCst) + 1;
+                if connection > 1 && server.refuse_reconnects.load(Ordering::SeqCst) {
+                    return Err(warp::reject::not_found());
+                }
+                let unsubscribes = server.unsubscribes.clone();
+                Ok(ws.on_upgrade(move |socket| handle(socket, connection, unsubscribes)))
+            }
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn handle(socket: WebSocket, connection: usize, unsubscribes: Arc<AtomicUsize>) {
+        let (mut sink, mut stream) = socket.split();
+        let mut subscriptions = Vec::new();
+        while let Some(Ok(message)) = stream.next().await {
+            let Ok(text) = message.to_str() else {
+                continue;
+            };
+            let request: Value = serde_json::from_str(text).unwrap();
+            let id = request["id"].clone();
+            let responses = match request["method"].as_str().unwrap() {
+                "eth_blockNumber" if connection == 1 => return,
+                "eth_blockNumber" => vec![json!({ "jsonrpc": "2.0", "id": id, "result": "0x2a" })],
+                "eth_subscribe" => {
+                    let subscription = format!("0x{:x}", connection * 100 + subscriptions.len());
+                    subscriptions.push(subscription.clone());
+                    vec![json!({ "jsonrpc": "2.0", "id": id, "result": subscription })]
+                }
+                "eth_unsubscribe" => {
+                    unsubscribes.fetch_add(1, Ordering::SeqCst);
+                    vec![json!({ "jsonrpc": "2.0", "id": id, "result": true })]
+                }
+                "test_notify" => subscriptions
+                    .iter()
+                    .map(|subscription| {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "method": "eth_subscription",
+                            "params": { "subscription": subscription, "result": connection },
+                        })
+                    })
+                    .chain([json!({ "jsonrpc": "2.0", "id": id, "result": true })])
+                    .collect(),
+                method => panic!("Unexpected method {method}"),
+            };
+            for response in responses {
+                sink.send(Message::text(response.to_string()))
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_resubscribes() {
+        let addr = serve(Default::default());
+        let reconnects =
+            IntCounterVec::new(opts!("websocket_reconnects", "test"), &["chain", "result"])
+                .unwrap();
+        let ws = ReconnectingWs::connect(&format!("ws://{addr}"), "test", Some(reconnects.clone()))
+            .await
+            .unwrap();
+        let provider = Provider::new(ws);
+
+        let mut notifications = provider.subscribe::<_, u64>(["newHeads"]).await.unwrap();
+        provider
+            .request::<_, bool>("test_notify", ())
+            .await
+            .unwrap();
+        assert_eq!(notifications.next().await, Some(1));
+
+        // The first connection is dropped by this request
+        assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 42);
+        assert_eq!(reconnects.with_label_values(&["test", "success"]).get(), 1);
+
+        provider
+            .request::<_, bool>("test_notify", ())
+            .await
+            .unwrap();
+        assert_eq!(notifications.next().await, Some(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_requests_when_reconnecting_fails() {
+        let server = Arc::new(Server::default());
+        server.refuse_reconnects.store(true, Ordering::SeqCst);
+        let addr = serve(server.clone());
+        let ws = ReconnectingWs::connect(&format!("ws://{addr}"), "test", None)
+            .await
+            .unwrap();
+        let provider = Provider::new(ws);
+        let mut notifications = provider.subscribe::<_, u64>(["newHeads"]).await.unwrap();
+
+        // The first connection is dropped by this request
+        let err = provider.get_block_number().await.unwrap_err();
+        assert!(err.to_string().contains("not re-established"), "{err}");
+        assert_eq!(
+            server.connections.load(Ordering::SeqCst),
+            1 + MAX_RECONNECT_ATTEMPTS as usize
+        );
+        assert_eq!(notifications.next().await, None);
+
+        // Requests fail right away instead of each trying again
+        assert!(provider.get_block_number().await.is_err());
+        assert_eq!(
+            server.connections.load(Ordering::SeqCst),
+            1 + MAX_RECONNECT_ATTEMPTS as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn unsubscribes_on_the_node_while_reconnecting() {
+        let server = Arc::new(Server::default());
+        let ws = ReconnectingWs::connect(&format!("ws://{}", serve(server.clone())), "test", None)
+            .await
+            .unwrap();
+        let provider = Provider::new(ws.clone());
+        let notifications = provider.subscribe::<_, u64>(["newHeads"]).await.unwrap();
+
+        // Hold the connection like reconnecting does while the subscription
+        // is dropped
+        let connection = ws.inner.connection.write().await;
+        drop(notifications);
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.unsubscribes.load(Ordering::SeqCst), 0);
+        drop(connection);
+
+        for _ in 0..100 {
+            if server.unsubscribes.load(Ordering::SeqCst) == 1 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(server.unsubscribes.load(Ordering::SeqCst), 1);
+    }
+}
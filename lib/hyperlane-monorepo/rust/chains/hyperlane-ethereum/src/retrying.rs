@@ -1,14 +1,23 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{fmt::Debug, str::FromStr, time::Duration};
 
 use async_trait::async_trait;
-use ethers::prelude::HttpClientError;
-use ethers::providers::{Http, JsonRpcClient, ProviderError};
+use ethers::prelude::{HttpClientError, Lazy, WsClientError};
+use ethers::providers::{JsonRpcClient, JsonRpcError, ProviderError};
 use ethers_prometheus::json_rpc_client::PrometheusJsonRpcClient;
+use rand::{thread_rng, Rng};
+use reqwest::StatusCode;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 use tokio::time::sleep;
-use tracing::{debug, error, instrument, trace, warn};
+use tracing::{debug, instrument, trace, warn};
+
+use hyperlane_core::FailureKind;
+
+use crate::ReconnectingWsError;
 
 const METHODS_TO_NOT_RETRY: &[&str] = &[
     "eth_estimateGas",
@@ -16,45 +25,290 @@ const METHODS_TO_NOT_RETRY: &[&str] = &[
     "eth_sendRawTransaction",
 ];
 
-/// An HTTP Provider with a simple naive exponential backoff built-in
+/// Token buckets by url, shared by all providers talking to the same url.
+static TOKEN_BUCKETS: Lazy<Mutex<HashMap<String, Arc<TokenBucket>>>> = Lazy::new(Default::default);
+
+/// How a `RetryingProvider` retries failed requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of requests made, including the first one
+    pub max_requests: u32,
+    /// The backoff before the first retry, doubled for every further retry
+    pub base_backoff: Duration,
+    /// The longest backoff between two requests
+    pub max_backoff: Duration,
+    /// The fraction by which backoffs are randomly varied, so that clients
+    /// which failed together do not retry together
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_requests: 6,
+            base_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.25,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The backoff before the given retry, where the first retry is 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0., 1.);
+        if jitter == 0. {
+            return backoff;
+        }
+        backoff.mul_f64(thread_rng().gen_range(1. - jitter..=1. + jitter))
+    }
+}
+
+/// A client-side token bucket limiting the rate of requests sent to a url.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Tokens added per second
+    rate: f64,
+    /// The most tokens the bucket holds, i.e. the largest burst of requests
+    capacity: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// Create a full bucket allowing `rate` requests per second on average
+    /// and bursts of up to `burst` requests.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        assert!(rate > 0.);
+        let capacity = f64::from(burst.max(1));
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// The bucket of the given url. The first bucket created for a url
+    /// determines its rate.
+    pub fn for_url(url: &str, rate: f64, burst: u32) -> Arc<Self> {
+        TOKEN_BUCKETS
+            .lock()
+            .unwrap()
+            .entry(url.to_owned())
+            .or_insert_with(|| Arc::new(Self::new(rate, burst)))
+            .clone()
+    }
+
+    /// Wait until a request may be sent.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let (tokens, last_refill) = &mut *state;
+                let now = Instant::now();
+                *tokens = (*tokens + now.duration_since(*last_refill).as_secs_f64() * self.rate)
+                    .min(self.capacity);
+                *last_refill = now;
+                if *tokens >= 1. {
+                    *tokens -= 1.;
+                    return;
+                }
+                Duration::from_secs_f64((1. - *tokens) / self.rate)
+            };
+            trace!(?wait, "Waiting for rate limit");
+            sleep(wait).await;
+        }
+    }
+}
+
+/// How an error should be retried
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryClass {
+    /// The request did not reach the node or no valid response came back
+    Transport,
+    /// The node asked us to slow down, optionally saying for how long
+    RateLimited(Option<Duration>),
+    /// The node failed to handle the request but may succeed later, e.g.
+    /// because it has not seen the requested block yet
+    Retryable,
+    /// Retrying will not help
+    Permanent,
+}
+
+/// Errors of a transport which a `RetryingProvider` can retry.
+pub trait RetryableError: std::error::Error {
+    /// How this error should be retried
+    fn retry_class(&self) -> RetryClass;
+}
+
+impl RetryableError for HttpClientError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            HttpClientError::ReqwestError(e) => match e.status() {
+                Some(StatusCode::TOO_MANY_REQUESTS) => RetryClass::RateLimited(None),
+                _ => RetryClass::Transport,
+            },
+            HttpClientError::JsonRpcError(e) => classify_json_rpc_error(e),
+            // Rate limited responses usually come with a body that is not a
+            // JSON-RPC response at all.
+            HttpClientError::SerdeJson { text, .. } => {
+                let text = text.to_lowercase();
+                if text.contains("429") || is_rate_limit_message(&text) {
+                    RetryClass::RateLimited(backoff_hint_from_text(&text))
+                } else {
+                    RetryClass::Transport
+                }
+            }
+        }
+    }
+}
+
+impl RetryableError for ReconnectingWsError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            ReconnectingWsError::WsClientError(WsClientError::JsonRpcError(e)) => {
+                classify_json_rpc_error(e)
+            }
+            ReconnectingWsError::WsClientError(_) => RetryClass::Transport,
+            ReconnectingWsError::SerdeJson(_) | ReconnectingWsError::UnknownSubscription(_) => {
+                RetryClass::Permanent
+            }
+        }
+    }
+}
+
+fn is_rate_limit_message(message: &str) -> bool {
+    FailureKind::from_message(message) == FailureKind::RateLimited
+}
+
+/// Decide whether a JSON-RPC error returned by a node is worth retrying,
+/// based on the kind of failure it is.
+pub fn classify_json_rpc_error(error: &JsonRpcError) -> RetryClass {
+    match FailureKind::from(error) {
+        FailureKind::RateLimited => RetryClass::RateLimited(
+            error
+                .data
+                .as_ref()
+                .and_then(backoff_hint_from_data)
+                .or_else(|| backoff_hint_from_text(&error.message.to_lowercase())),
+        ),
+        FailureKind::Transient | FailureKind::Unknown => RetryClass::Retryable,
+        // Sending the same request again will not resolve nonce conflicts or
+        // make the signer any richer, and ranges which are too large are
+        // shrunk by the caller instead
+        FailureKind::NonceConflict
+        | FailureKind::InsufficientFunds
+        | FailureKind::Reverted
+        | FailureKind::InvalidConfig
+        | FailureKind::RangeTooLarge => RetryClass::Permanent,
+    }
+}
+
+/// Backoff requested in the data of a rate limit error, e.g. Infura's
+/// `{"rate": {"backoff_seconds": 30}}`.
+fn backoff_hint_from_data(data: &Value) -> Option<Duration> {
+    ["/rate/backoff_seconds", "/backoff_seconds", "/retry_after"]
+        .iter()
+        .find_map(|pointer| data.pointer(pointer))
+        .and_then(|seconds| match seconds {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        })
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.)
+        .map(Duration::from_secs_f64)
+}
+
+/// Backoff requested in the text of a rate limit response, e.g. the echoed
+/// `Retry-After: 2` header or "try again in 2s".
+fn backoff_hint_from_text(text: &str) -> Option<Duration> {
+    ["retry-after:", "retry after", "try again in"]
+        .iter()
+        .find_map(|prefix| {
+            let rest = &text[text.find(prefix)? + prefix.len()..];
+            let seconds: String = rest
+                .trim_start()
+                .chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect();
+            seconds.parse::<f64>().ok()
+        })
+        .filter(|seconds| seconds.is_finite())
+        .map(Duration::from_secs_f64)
+}
+
+/// A Provider with jittered exponential backoff built-in which respects rate
+/// limit responses and optionally limits the rate of requests it sends.
 #[derive(Debug, Clone)]
 pub struct RetryingProvider<P> {
     inner: P,
-    max_requests: u32,
-    base_retry_ms: u64,
+    policy: RetryPolicy,
+    rate_limit: Option<Arc<TokenBucket>>,
 }
 
 impl<P> RetryingProvider<P> {
     /// Instantiate a RetryingProvider
     pub fn new(inner: P, max_requests: Option<u32>, base_retry_ms: Option<u64>) -> Self {
+        let mut policy = RetryPolicy::default();
+        if let Some(max_requests) = max_requests {
+            policy.max_requests = max_requests;
+        }
+        if let Some(base_retry_ms) = base_retry_ms {
+            policy.base_backoff = Duration::from_millis(base_retry_ms);
+        }
+        Self::with_policy(inner, policy)
+    }
+
+    /// Instantiate a RetryingProvider with the given retry policy
+    pub fn with_policy(inner: P, policy: RetryPolicy) -> Self {
+        assert!(policy.max_requests >= 1);
         Self {
             inner,
-            max_requests: max_requests.unwrap_or(6),
-            base_retry_ms: base_retry_ms.unwrap_or(50),
+            policy,
+            rate_limit: None,
         }
     }
 
+    /// Wait for a token of the bucket before sending each request.
+    pub fn with_rate_limit(mut self, bucket: Arc<TokenBucket>) -> Self {
+        self.rate_limit = Some(bucket);
+        self
+    }
+
     /// Set the max_requests (and by extension the total time a request can
     /// take).
     pub fn set_max_requests(&mut self, max_requests: u32) {
         assert!(max_requests >= 1);
-        self.max_requests = max_requests;
+        self.policy.max_requests = max_requests;
     }
 
     /// Set what the base amount of backoff time there should be.
     pub fn set_base_retry_ms(&mut self, base_retry_ms: u64) {
         assert!(base_retry_ms >= 1);
-        self.base_retry_ms = base_retry_ms;
+        self.policy.base_backoff = Duration::from_millis(base_retry_ms);
     }
 
     /// Get the max_requests
     pub fn max_requests(&self) -> u32 {
-        self.max_requests
+        self.policy.max_requests
     }
 
     /// Get the base retry duration in ms.
     pub fn base_retry_ms(&self) -> u64 {
-        self.base_retry_ms
+        self.policy.base_backoff.as_millis() as u64
+    }
+
+    /// Get the retry policy
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Get the wrapped provider
+    pub fn inner(&self) -> &P {
+        &self.inner
     }
 }
 
@@ -63,6 +317,9 @@ enum HandleMethod<R, PE> {
     Accept(R),
     Halt(PE),
     Retry(PE),
+    /// Retry after at least the given duration, up to the policy's maximum
+    /// backoff
+    RetryAfter(PE, Duration),
 }
 
 impl<P> RetryingProvider<P>
@@ -82,8 +339,8 @@ where
             Result<R, P::Error>,
             // which attempt this is
             u32,
-            // what the next backoff will be in ms
-            u64,
+            // what the next backoff will be
+            Duration,
         ) -> HandleMethod<R, P::Error>,
     ) -> Result<R, RetryingProviderError<P>>
     where
@@ -95,7 +352,10 @@ where
         let mut last_err;
         let mut i = 1;
         loop {
-            let backoff_ms = self.base_retry_ms * 2u64.pow(i - 1);
+            let mut backoff = self.policy.backoff(i);
+            if let Some(bucket) = &self.rate_limit {
+                bucket.acquire().await;
+            }
             trace!(params = %serde_json::to_string(&params).unwrap_or_default(), "Dispatching request with params");
             debug!(attempt = i, "Dispatching request");
 
@@ -104,7 +364,7 @@ where
                 _ => self.inner.request(method, &params),
             };
 
-            match matcher(fut.await, i, backoff_ms) {
+            match matcher(fut.await, i, backoff) {
                 HandleMethod::Accept(v) => {
                     return Ok(v);
                 }
@@ -114,15 +374,21 @@ where
                 HandleMethod::Retry(e) => {
                     last_err = e;
                 }
+                HandleMethod::RetryAfter(e, min_backoff) => {
+                    // Don't let the node make us wait longer than we would
+                    // for any other error
+                    backoff = backoff.max(min_backoff.min(self.policy.max_backoff));
+                    last_err = e;
+                }
             }
 
             i += 1;
-            if i <= self.max_requests {
-                trace!(backoff_ms, "Retrying provider going to sleep.");
-                sleep(Duration::from_millis(backoff_ms)).await;
+            if i <= self.policy.max_requests {
+                trace!(?backoff, "Retrying provider going to sleep.");
+                sleep(backoff).await;
             } else {
                 trace!(
-                    requests_made = self.max_requests,
+                    requests_made = self.policy.max_requests,
                     "Retrying provider reached max requests."
                 );
                 return Err(RetryingProviderError::MaxRequests(last_err));
@@ -157,8 +423,12 @@ where
 }
 
 #[async_trait]
-impl JsonRpcClient for RetryingProvider<PrometheusJsonRpcClient<Http>> {
-    type Error = RetryingProviderError<PrometheusJsonRpcClient<Http>>;
+impl<C> JsonRpcClient for RetryingProvider<PrometheusJsonRpcClient<C>>
+where
+    C: JsonRpcClient + 'static,
+    C::Error: RetryableError + Send + Sync,
+{
+    type Error = RetryingProviderError<PrometheusJsonRpcClient<C>>;
 
     #[instrument(skip(self), fields(provider_host = %self.inner.node_host(), chain_name = %self.inner.chain_name()))]
     async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
@@ -166,27 +436,39 @@ impl JsonRpcClient for RetryingProvider<PrometheusJsonRpcClient<Http>> {
         T: Debug + Serialize + Send + Sync,
         R: DeserializeOwned,
     {
-        self.request_with_retry::<T, R>(method, params, |res, attempt, next_backoff_ms| match res {
-            Ok(res) => HandleMethod::Accept(res),
-            Err(HttpClientError::ReqwestError(e)) => {
-                warn!(next_backoff_ms, retries_remaining = self.max_requests - attempt, error = %e, "ReqwestError in http provider.");
-                HandleMethod::Retry(HttpClientError::ReqwestError(e))
-            }
-            Err(HttpClientError::JsonRpcError(e)) => {
+        self.request_with_retry::<T, R>(method, params, |res, attempt, next_backoff| {
+            let e = match res {
+                Ok(res) => return HandleMethod::Accept(res),
+                Err(e) => e,
+            };
+            let retries_remaining = self.policy.max_requests - attempt;
+            match e.retry_class() {
+                RetryClass::Transport => {
+                    warn!(attempt, retries_remaining, ?next_backoff, error = %e, "Transport error in provider.");
+                    HandleMethod::Retry(e)
+                }
+                RetryClass::RateLimited(hint) => {
+                    warn!(attempt, retries_remaining, ?next_backoff, ?hint, error = %e, "Rate limited by provider.");
+                    match hint {
+                        Some(hint) => HandleMethod::RetryAfter(e, hint),
+                        None => HandleMethod::Retry(e),
+                    }
+                }
                 // We don't want to retry errors that are probably not going to work if we keep
                 // retrying them or that indicate an error in higher-order logic and not
                 // transient provider (connection or other) errors.
-                if METHODS_TO_NOT_RETRY.contains(&method) {
-                    warn!(attempt, next_backoff_ms, error = %e, "JsonRpcError in http provider; not retrying.");
-                    HandleMethod::Halt(HttpClientError::JsonRpcError(e))
-                } else {
-                    warn!(attempt, next_backoff_ms, error = %e, "JsonRpcError in http provider.");
-                    HandleMethod::Retry(HttpClientError::JsonRpcError(e))
+                RetryClass::Retryable if METHODS_TO_NOT_RETRY.contains(&method) => {
+                    warn!(attempt, error = %e, "JsonRpcError in provider; not retrying.");
+                    HandleMethod::Halt(e)
+                }
+                RetryClass::Retryable => {
+                    warn!(attempt, retries_remaining, ?next_backoff, error = %e, "JsonRpcError in provider.");
+                    HandleMethod::Retry(e)
+                }
+                RetryClass::Permanent => {
+                    debug!(attempt, error = %e, "Permanent error in provider; not retrying.");
+                    HandleMethod::Halt(e)
                 }
-            }
-            Err(HttpClientError::SerdeJson { err, text }) => {
-                warn!(attempt, next_backoff_ms, error = %err, text = text,  "SerdeJson error in http provider");
-                HandleMethod::Retry(HttpClientError::SerdeJson { err, text })
             }
         })
         .await
@@ -203,3 +485,136 @@ where
         Ok(Self::new(src.parse()?, None, None))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ethers::prelude::U64;
+    use ethers::providers::{MockError, MockProvider};
+    use serde_json::json;
+
+    use super::*;
+
+    fn json_rpc_error(code: i64, message: &str, data: Option<Value>) -> JsonRpcError {
+        JsonRpcError {
+            code,
+            message: message.to_owned(),
+            data,
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy {
+            max_requests: 10,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter: 0.,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(8), Duration::from_secs(1));
+
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        for _ in 0..100 {
+            let backoff = policy.backoff(2);
+            assert!(backoff >= Duration::from_millis(100) && backoff <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn classifies_json_rpc_errors() {
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(-32000, "header not found", None)),
+            RetryClass::Retryable
+        );
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(3, "execution reverted: nope", None)),
+            RetryClass::Permanent
+        );
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(-32602, "invalid params", None)),
+            RetryClass::Permanent
+        );
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(
+                -32005,
+                "query returned more than 10000 results",
+                None
+            )),
+            RetryClass::Permanent
+        );
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(
+                -32005,
+                "project ID request rate exceeded",
+                Some(json!({"rate": {"backoff_seconds": 30}}))
+            )),
+            RetryClass::RateLimited(Some(Duration::from_secs(30)))
+        );
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(
+                -32005,
+                "daily request count exceeded, request rate limited",
+                None
+            )),
+            RetryClass::RateLimited(None)
+        );
+        assert_eq!(
+            classify_json_rpc_error(&json_rpc_error(
+                429,
+                "Too many requests, try again in 1.5s",
+                None
+            )),
+            RetryClass::RateLimited(Some(Duration::from_millis(1500)))
+        );
+    }
+
+    #[tokio::test]
+    async fn clamps_requested_backoff() {
+        let mock = MockProvider::new();
+        mock.push(U64::from(1)).unwrap();
+        mock.push(U64::from(1)).unwrap();
+        let provider = RetryingProvider::with_policy(
+            mock,
+            RetryPolicy {
+                max_requests: 2,
+                base_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(10),
+                jitter: 0.,
+            },
+        );
+
+        let start = Instant::now();
+        let result: Result<U64, _> = provider
+            .request_with_retry("eth_blockNumber", (), |res, attempt, _| match res {
+                Ok(_) if attempt == 1 => {
+                    HandleMethod::RetryAfter(MockError::EmptyResponses, Duration::from_secs(3600))
+                }
+                Ok(v) => HandleMethod::Accept(v),
+                Err(e) => HandleMethod::Halt(e),
+            })
+            .await;
+        assert_eq!(result.unwrap(), U64::from(1));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn token_bucket_limits_rate() {
+        let bucket = TokenBucket::new(50., 2);
+        let start = Instant::now();
+        // The first two requests are the burst, the other two wait 20ms each
+        for _ in 0..4 {
+            bucket.acquire().await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(35), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(500), "{elapsed:?}");
+
+        let first = TokenBucket::for_url("http://token-bucket-test", 1., 1);
+        let second = TokenBucket::for_url("http://token-bucket-test", 100., 10);
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use ethers::abi::{self, Abi, AbiError, ParamType, Token};
+use ethers::utils::id;
+use serde_json::Value;
+use thiserror::Error;
+
+use hyperlane_core::{RevertReason, U256};
+
+/// Selector of `Error(string)`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Errors loading an ABI into a `RevertDecoder`
+#[derive(Debug, Error)]
+pub enum RevertAbiError {
+    /// The ABI file could not be read
+    #[error("Unable to read ABI file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file is neither an ABI nor a build artifact containing one
+    #[error("Invalid ABI: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Decodes revert data into a `RevertReason`. `Error(string)` and
+/// `Panic(uint256)` are always decoded; custom errors are decoded if they are
+/// declared in one of the registered ABIs.
+#[derive(Debug, Clone, Default)]
+pub struct RevertDecoder {
+    /// Custom errors by selector
+    errors: HashMap<[u8; 4], AbiError>,
+}
+
+impl RevertDecoder {
+    /// Register the custom errors declared in an ABI
+    pub fn with_abi(mut self, abi: &Abi) -> Self {
+        for error in abi.errors() {
+            self.errors.insert(selector(error), error.clone());
+        }
+        self
+    }
+
+    /// Register the custom errors declared in an ABI file. The file may hold
+    /// the ABI itself or a build artifact with the ABI under `abi`, as
+    /// produced by Foundry and Hardhat.
+    pub fn with_abi_file(self, path: impl AsRef<Path>) -> Result<Self, RevertAbiError> {
+        let json: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        let abi: Abi = match json {
+            Value::Object(mut artifact) if artifact.contains_key("abi") => {
+                serde_json::from_value(artifact.remove("abi").unwrap())?
+            }
+            abi => serde_json::from_value(abi)?,
+        };
+        Ok(self.with_abi(&abi))
+    }
+
+    /// Decode revert data
+    pub fn decode(&self, data: &[u8]) -> RevertReason {
+        self.try_decode(data)
+            .unwrap_or_else(|| RevertReason::Unknown(data.to_vec()))
+    }
+
+    fn try_decode(&self, data: &[u8]) -> Option<RevertReason> {
+        let selector: [u8; 4] = data.get(..4)?.try_into().ok()?;
+        let args = &data[4..];
+        match selector {
+            ERROR_SELECTOR => match abi::decode(&[ParamType::String], args).ok()?.pop()? {
+                Token::String(message) => Some(RevertReason::Message(message)),
+                _ => None,
+            },
+            PANIC_SELECTOR => match abi::decode(&[ParamType::Uint(256)], args).ok()?.pop()? {
+                Token::Uint(code) => Some(RevertReason::Panic(U256::from(code))),
+                _ => None,
+            },
+            selector => {
+                let error = self.errors.get(&selector)?;
+                let kinds: Vec<ParamType> = error.inputs.iter().map(|p| p.kind.clone()).collect();
+                let args = abi::decode(&kinds, args).ok()?;
+                Some(RevertReason::Custom {
+                    name: error.name.clone(),
+                    args: args.iter().map(ToString::to_string).collect(),
+                })
+            }
+        }
+    }
+}
+
+/// The selector of a custom error
+fn selector(error: &AbiError) -> [u8; 4] {
+    let kinds: Vec<String> = error.inputs.iter().map(|p| p.kind.to_string()).collect();
+    let hash = id(format!("{}({})", error.name, kinds.join(",")));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    const ABI: &str = r#"[{
+        "type": "error",
+        "name": "InsufficientCollateral",
+        "inputs": [
+            {"name": "required", "type": "uint256"},
+            {"name": "available", "type": "uint256"}
+        ]
+    }]"#;
+
+    fn encode(selector: [u8; 4], args: &[Token]) -> Vec<u8> {
+        [selector.to_vec(), abi::encode(args)].concat()
+    }
+
+    #[test]
+    fn decodes_builtin_errors() {
+        let decoder = RevertDecoder::default();
+        let data = encode(ERROR_SELECTOR, &[Token::String("!threshold".into())]);
+        assert_eq!(
+            decoder.decode(&data),
+            RevertReason::Message("!threshold".into())
+        );
+
+        let data = encode(PANIC_SELECTOR, &[Token::Uint(0x11.into())]);
+        assert_eq!(decoder.decode(&data), RevertReason::Panic(0x11.into()));
+
+        assert_eq!(decoder.decode(&[]), RevertReason::Unknown(vec![]));
+        assert_eq!(
+            decoder.decode(&[1, 2, 3, 4, 5]),
+            RevertReason::Unknown(vec![1, 2, 3, 4, 5])
+        );
+    }
+
+    #[test]
+    fn decodes_custom_errors() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let error = abi.errors().next().unwrap();
+        let data = encode(
+            selector(error),
+            &[Token::Uint(2.into()), Token::Uint(1.into())],
+        );
+
+        assert!(matches!(
+            RevertDecoder::default().decode(&data),
+            RevertReason::Unknown(_)
+        ));
+        assert_eq!(
+            RevertDecoder::default().with_abi(&abi).decode(&data),
+            RevertReason::Custom {
+                name: "InsufficientCollateral".into(),
+                args: vec!["2".into(), "1".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn loads_build_artifacts() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, r#"{{"abi": {ABI}, "bytecode": "0x"}}"#).unwrap();
+        let decoder = RevertDecoder::default().with_abi_file(file.path()).unwrap();
+        assert_eq!(decoder.errors.len(), 1);
+    }
+}
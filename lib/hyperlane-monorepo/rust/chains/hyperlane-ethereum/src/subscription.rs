@@ -0,0 +1,216 @@
+use ethers::prelude::{Block, Filter, Log, Middleware, Provider, TxHash};
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, warn};
+
+use hyperlane_core::Finality;
+
+use crate::finality::FinalityResolver;
+use crate::ReconnectingWs;
+
+/// Subscribe to the logs matching `filter` over a websocket, yielding them
+/// once their block has reached `finality`. Logs which are removed by a reorg
+/// before reaching finality are dropped.
+///
+/// The stream ends if either subscription fails or is closed by the node.
+pub(crate) fn subscribe_finalized_logs(
+    provider: Provider<ReconnectingWs>,
+    filter: Filter,
+    finality: Finality,
+) -> BoxStream<'static, Log> {
+    let (sender, receiver) = unbounded_channel();
+    tokio::spawn(async move {
+        let forwarded = async {
+            let logs = provider.subscribe_logs(&filter).await?;
+            let blocks = provider.subscribe_blocks().await?;
+            forward_finalized_logs(&provider, logs, blocks, finality, &sender).await
+        };
+        if let Err(err) = forwarded.await {
+            warn!(error = %err, "Log subscription failed");
+        }
+    });
+    receiver_stream(receiver)
+}
+
+fn receiver_stream<T: Send + 'static>(receiver: UnboundedReceiver<T>) -> BoxStream<'static, T> {
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+    .boxed()
+}
+
+/// Forward `logs` to `sender` once the latest block announced by `blocks` has
+/// made them final. Returns once either stream ends or the receiver is gone.
+async fn forward_finalized_logs<M: Middleware>(
+    provider: &M,
+    mut logs: impl Stream<Item = Log> + Unpin,
+    mut blocks: impl Stream<Item = Block<TxHash>> + Unpin,
+    finality: Finality,
+    sender: &UnboundedSender<Log>,
+) -> Result<(), M::Error> {
+    let finality_resolver = FinalityResolver::default();
+    let mut pending: Vec<Log> = Vec::new();
+    let mut finalized_block = 0;
+
+    loop {
+        // Logs are handled before blocks so that a log is never held back
+        // for a block that was announced after it
+        tokio::select! {
+            biased;
+            log = logs.next() => match log {
+                Some(log) if log.removed == Some(true) => {
+                    debug!(?log, "Dropping log removed by reorg");
+                    pending.retain(|p| {
+                        p.transaction_hash != log.transaction_hash || p.log_index != log.log_index
+                    });
+                }
+                Some(log) => pending.push(log),
+                None => break,
+            },
+            block = blocks.next() => match block {
+                Some(block) => {
+                    if let Some(head) = block.number {
+                        finalized_block = finality_resolver
+                            .finalized_block(provider, finality, Some(head.as_u64()))
+                            .await?;
+                    }
+                }
+                None => break,
+            },
+            // Nobody is listening anymore
+            _ = sender.closed() => break,
+        }
+
+        let (mut finalized, not_finalized): (Vec<_>, Vec<_>) = pending.drain(..).partition(|log| {
+            finality.is_head()
+                || log
+                    .block_number
+                    .map_or(false, |n| n.as_u64() <= finalized_block)
+        });
+        pending = not_finalized;
+        finalized.sort_by_key(|log| (log.block_number, log.log_index));
+        for log in finalized {
+            if sender.send(log).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use ethers::prelude::{ProviderError, H256};
+    use tokio::sync::mpsc::error::TryRecvError;
+    use tokio::time::timeout;
+
+    use super::*;
+
+    fn log(block_number: u64, log_index: u64) -> Log {
+        Log {
+            block_number: Some(block_number.into()),
+            log_index: Some(log_index.into()),
+            transaction_hash: Some(H256::from_low_u64_be(block_number)),
+            ..Default::default()
+        }
+    }
+
+    fn block(number: u64) -> Block<TxHash> {
+        Block {
+            number: Some(number.into()),
+            ..Default::default()
+        }
+    }
+
+    struct Subscription {
+        logs: UnboundedSender<Log>,
+        blocks: UnboundedSender<Block<TxHash>>,
+        forwarded: UnboundedReceiver<Log>,
+        task: tokio::task::JoinHandle<Result<(), ProviderError>>,
+    }
+
+    impl Subscription {
+        fn new(finality: Finality) -> Self {
+            let (logs, log_receiver) = unbounded_channel();
+            let (blocks, block_receiver) = unbounded_channel();
+            let (sender, forwarded) = unbounded_channel();
+            let task = tokio::spawn(async move {
+                let (provider, _) = Provider::mocked();
+                forward_finalized_logs(
+                    &provider,
+                    receiver_stream(log_receiver),
+                    receiver_stream(block_receiver),
+                    finality,
+                    &sender,
+                )
+                .await
+            });
+            Self {
+                logs,
+                blocks,
+                forwarded,
+                task,
+            }
+        }
+
+        async fn next_forwarded(&mut self) -> Log {
+            timeout(Duration::from_secs(1), self.forwarded.recv())
+                .await
+                .expect("no log was forwarded")
+                .expect("forwarding stopped")
+        }
+    }
+
+    #[tokio::test]
+    async fn forwards_logs_once_final() {
+        let mut sub = Subscription::new(Finality::Blocks(2));
+        sub.logs.send(log(5, 0)).unwrap();
+        sub.logs.send(log(6, 0)).unwrap();
+        sub.logs.send(log(8, 0)).unwrap();
+        sub.logs.send(log(5, 1)).unwrap();
+
+        // Block 5 is final at head 7
+        sub.blocks.send(block(7)).unwrap();
+        assert_eq!(sub.next_forwarded().await, log(5, 0));
+        assert_eq!(sub.next_forwarded().await, log(5, 1));
+        assert_eq!(sub.forwarded.try_recv(), Err(TryRecvError::Empty));
+
+        // A log removed by a reorg before it is final is never forwarded
+        sub.logs
+            .send(Log {
+                removed: Some(true),
+                ..log(6, 0)
+            })
+            .unwrap();
+        sub.logs.send(log(7, 0)).unwrap();
+
+        // Logs of a final block are all forwarded at once, so nothing else
+        // was final at head 9
+        sub.blocks.send(block(9)).unwrap();
+        assert_eq!(sub.next_forwarded().await, log(7, 0));
+        assert_eq!(sub.forwarded.try_recv(), Err(TryRecvError::Empty));
+
+        sub.blocks.send(block(10)).unwrap();
+        assert_eq!(sub.next_forwarded().await, log(8, 0));
+    }
+
+    #[tokio::test]
+    async fn forwards_logs_immediately_at_head() {
+        let mut sub = Subscription::new(Finality::Blocks(0));
+        sub.logs.send(log(5, 0)).unwrap();
+        assert_eq!(sub.next_forwarded().await, log(5, 0));
+    }
+
+    #[tokio::test]
+    async fn stops_when_subscription_ends() {
+        let mut sub = Subscription::new(Finality::Blocks(2));
+        sub.logs.send(log(5, 0)).unwrap();
+        drop(sub.logs);
+
+        sub.task.await.unwrap().unwrap();
+        // Logs that were not final yet are dropped with the subscription
+        assert_eq!(sub.forwarded.recv().await, None);
+    }
+}
@@ -4,8 +4,7 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use ethers::prelude::{
-    Http, JsonRpcClient, Middleware, NonceManagerMiddleware, Provider, Quorum, QuorumProvider,
-    SignerMiddleware, WeightedProvider, Ws, WsClientError,
+    JsonRpcClient, Middleware, NonceManagerMiddleware, Provider, SignerMiddleware, WsClientError,
 };
 use reqwest::{Client, Url};
 use thiserror::Error;
@@ -19,7 +18,11 @@ use ethers_prometheus::middleware::{
 };
 use hyperlane_core::{ChainCommunicationError, ChainResult, ContractLocator};
 
-use crate::{signers::Signers, ConnectionConf, FallbackProvider, RetryingProvider};
+use crate::{
+    signers::Signers, ArchiveRouter, BatchHttp, CachingProvider, ConnectionConf, FallbackProvider,
+    InvalidQuorumRule, InvalidRetryConf, QuorumRouter, ReconnectingWs, RetryConf, RetryPolicy,
+    RetryingProvider, RpcCache, RpcCacheError,
+};
 
 // This should be whatever the prometheus scrape interval is
 const METRICS_SCRAPE_INTERVAL: Duration = Duration::from_secs(60);
@@ -37,6 +40,24 @@ pub enum EthereumProviderConnectionError {
     /// Underlying websocket library threw an error
     #[error(transparent)]
     WebsocketClientError(#[from] WsClientError),
+    /// A quorum rule could not be parsed
+    #[error(transparent)]
+    InvalidQuorumRule(#[from] InvalidQuorumRule),
+    /// The quorum weights could not be parsed or do not match the urls
+    #[error("Invalid quorum weights {0:?}, expected one integer per url")]
+    InvalidWeights(String),
+    /// The retry configuration could not be parsed
+    #[error(transparent)]
+    InvalidRetryConf(#[from] InvalidRetryConf),
+    /// The response cache could not be set up
+    #[error(transparent)]
+    RpcCacheError(#[from] RpcCacheError),
+    /// The archive depth could not be parsed
+    #[error("Invalid archive depth {0:?}, expected a number of blocks")]
+    InvalidArchiveDepth(String),
+    /// Whether to batch requests could not be parsed
+    #[error("Invalid batch setting {0:?}, expected true or false")]
+    InvalidBatch(String),
 }
 
 impl From<EthereumProviderConnectionError> for ChainCommunicationError {
@@ -45,6 +66,13 @@ impl From<EthereumProviderConnectionError> for ChainCommunicationError {
     }
 }
 
+/// Layers wrapped around the transport of every connection.
+pub struct ProviderLayers {
+    cache: Option<Arc<RpcCache>>,
+    archive: Option<FallbackProvider<RetryingProvider<PrometheusJsonRpcClient<BatchHttp>>>>,
+    archive_depth: u64,
+}
+
 /// A trait for dynamic trait creation with provider initialization.
 #[async_trait]
 pub trait BuildableWithProvider {
@@ -62,19 +90,122 @@ pub trait BuildableWithProvider {
         rpc_metrics: Option<JsonRpcClientMetrics>,
         middleware_metrics: Option<(MiddlewareMetrics, PrometheusMiddlewareConf)>,
     ) -> ChainResult<Self::Output> {
+        let chain = middleware_metrics
+            .as_ref()
+            .and_then(|(_, conf)| conf.chain.as_ref())
+            .and_then(|chain| chain.name.clone())
+            .unwrap_or_else(|| locator.domain.name().to_owned());
+        let batch = match conn.batch() {
+            Some(batch) => batch
+                .trim()
+                .parse()
+                .map_err(|_| EthereumProviderConnectionError::InvalidBatch(batch.clone()))?,
+            None => true,
+        };
+        let archive_conf = conn.archive();
+        let default_retry = RetryConf::default();
+        let archive_retry = conn.retry().unwrap_or(&default_retry);
+        let layers = ProviderLayers {
+            cache: conn
+                .cache()
+                .cache(&locator.domain, rpc_metrics.as_ref())
+                .map_err(EthereumProviderConnectionError::from)?,
+            archive: archive_conf
+                .urls
+                .as_ref()
+                .map(|urls| {
+                    // Archive nodes are retried and rate limited like the
+                    // connection's own nodes
+                    self.build_fallback(
+                        urls,
+                        batch,
+                        &rpc_metrics,
+                        &middleware_metrics,
+                        |client, url| {
+                            build_retrying_provider(
+                                client,
+                                url,
+                                archive_retry,
+                                RetryPolicy::default(),
+                            )
+                        },
+                    )
+                })
+                .transpose()?,
+            archive_depth: archive_conf.depth().map_err(|_| {
+                EthereumProviderConnectionError::InvalidArchiveDepth(
+                    archive_conf.depth.clone().unwrap_or_default(),
+                )
+            })?,
+        };
         Ok(match conn {
-            ConnectionConf::HttpQuorum { urls } => {
-                let mut builder = QuorumProvider::builder().quorum(Quorum::Majority);
+            ConnectionConf::HttpQuorum {
+                urls,
+                weights,
+                quorum,
+                methods,
+                retry,
+                ..
+            } => {
+                let mut builder = QuorumRouter::builder();
+                if let Some(quorum) = quorum {
+                    builder = builder.rule(
+                        quorum
+                            .parse()
+                            .map_err(EthereumProviderConnectionError::from)?,
+                    );
+                }
+                for (method, rule) in methods {
+                    builder = builder.method_rule(
+                        method,
+                        rule.parse()
+                            .map_err(EthereumProviderConnectionError::from)?,
+                    );
+                }
+                if let Some(rpc_metrics) = &rpc_metrics {
+                    builder = builder.metrics(
+                        chain.clone(),
+                        rpc_metrics.quorum_disagreements().cloned(),
+                        rpc_metrics.quorum_failures().cloned(),
+                    );
+                }
+
+                let urls: Vec<&str> = urls.split(',').collect();
+                let weights = match weights {
+                    Some(weights) => {
+                        let parsed = weights
+                            .split(',')
+                            .map(|weight| weight.trim().parse::<u64>())
+                            .collect::<Result<Vec<_>, _>>();
+                        match parsed {
+                            Ok(parsed) if parsed.len() == urls.len() => parsed,
+                            _ => {
+                                return Err(EthereumProviderConnectionError::InvalidWeights(
+                                    weights.clone(),
+                                )
+                                .into())
+                            }
+                        }
+                    }
+                    None => vec![1; urls.len()],
+                };
+
+                let policy = RetryPolicy {
+                    max_requests: 5,
+                    base_backoff: Duration::from_millis(1000),
+                    ..Default::default()
+                };
                 let http_client = Client::builder()
                     .timeout(HTTP_CLIENT_TIMEOUT)
                     .build()
                     .map_err(EthereumProviderConnectionError::from)?;
-                for url in urls.split(',') {
+                for (url, weight) in urls.into_iter().zip(weights) {
                     let parsed_url = url.parse::<Url>().map_err(|e| {
                         EthereumProviderConnectionError::InvalidUrl(e, url.to_owned())
                     })?;
                     let http_provider =
-                        Http::new_with_client(parsed_url.clone(), http_client.clone());
+                        BatchHttp::new_with_client(parsed_url.clone(), http_client.clone())
+                            .with_batching(batch);
                     // Wrap the inner providers as RetryingProviders rather than the QuorumProvider.
                     // We've observed issues where the QuorumProvider will first get the latest
                     // block number and then submit an RPC at that block height,
@@ -90,43 +221,53 @@ pub trait BuildableWithProvider {
                         &rpc_metrics,
                         &middleware_metrics,
                     );
+                    let name = metrics_provider.node_host().to_owned();
                     let retrying_provider =
-                        RetryingProvider::new(metrics_provider, Some(5), Some(1000));
-                    let weighted_provider = WeightedProvider::new(retrying_provider);
-                    builder = builder.add_provider(weighted_provider);
+                        build_retrying_provider(metrics_provider, url, retry, policy.clone())?;
+                    builder = builder.add_provider(name, retrying_provider, weight);
                 }
                 let quorum_provider = builder.build();
-                self.wrap_with_metrics(quorum_provider, locator, signer, middleware_metrics)
-                    .await?
+                self.wrap_with_metrics(
+                    quorum_provider,
+                    layers,
+                    None,
+                    locator,
+                    signer,
+                    middleware_metrics,
+                )
+                .await?
             }
-            ConnectionConf::HttpFallback { urls } => {
-                let mut builder = FallbackProvider::builder();
-                let http_client = Client::builder()
-                    .timeout(HTTP_CLIENT_TIMEOUT)
-                    .build()
-                    .map_err(EthereumProviderConnectionError::from)?;
-                for url in urls.split(',') {
-                    let http_provider = Http::new_with_client(
-                        url.parse::<Url>().map_err(|e| {
-                            EthereumProviderConnectionError::InvalidUrl(e, url.to_owned())
-                        })?,
-                        http_client.clone(),
-                    );
-                    let metrics_provider = self.wrap_rpc_with_metrics(
-                        http_provider,
-                        Url::parse(url).map_err(|e| {
-                            EthereumProviderConnectionError::InvalidUrl(e, url.to_owned())
-                        })?,
-                        &rpc_metrics,
-                        &middleware_metrics,
-                    );
-                    builder = builder.add_provider(metrics_provider);
-                }
-                let fallback_provider = builder.build();
-                self.wrap_with_metrics(fallback_provider, locator, signer, middleware_metrics)
-                    .await?
+            ConnectionConf::HttpFallback { urls, retry, .. } => {
+                // Failed requests fall back to the next url rather than being
+                // retried, but each url is still rate limited
+                let policy = RetryPolicy {
+                    max_requests: 1,
+                    ..Default::default()
+                };
+                let fallback_provider = self.build_fallback(
+                    urls,
+                    batch,
+                    &rpc_metrics,
+                    &middleware_metrics,
+                    |client, url| {
+                        let provider = RetryingProvider::with_policy(client, policy.clone());
+                        Ok(match retry.rate_limit(url)? {
+                            Some(bucket) => provider.with_rate_limit(bucket),
+                            None => provider,
+                        })
+                    },
+                )?;
+                self.wrap_with_metrics(
+                    fallback_provider,
+                    layers,
+                    None,
+                    locator,
+                    signer,
+                    middleware_metrics,
+                )
+                .await?
             }
-            ConnectionConf::Http { url } => {
+            ConnectionConf::Http { url, retry, .. } => {
                 let http_client = Client::builder()
                     .timeout(HTTP_CLIENT_TIMEOUT)
                     .build()
@@ -134,23 +275,52 @@ pub trait BuildableWithProvider {
                 let parsed_url = url
                     .parse::<Url>()
                     .map_err(|e| EthereumProviderConnectionError::InvalidUrl(e, url.clone()))?;
-                let http_provider = Http::new_with_client(parsed_url.clone(), http_client);
+                let http_provider = BatchHttp::new_with_client(parsed_url.clone(), http_client)
+                    .with_batching(batch);
                 let metrics_provider = self.wrap_rpc_with_metrics(
                     http_provider,
                     parsed_url,
                     &rpc_metrics,
                     &middleware_metrics,
                 );
-                let retrying_http_provider = RetryingProvider::new(metrics_provider, None, None);
-                self.wrap_with_metrics(retrying_http_provider, locator, signer, middleware_metrics)
-                    .await?
+                let retrying_http_provider =
+                    build_retrying_provider(metrics_provider, url, retry, RetryPolicy::default())?;
+                self.wrap_with_metrics(
+                    retrying_http_provider,
+                    layers,
+                    None,
+                    locator,
+                    signer,
+                    middleware_metrics,
+                )
+                .await?
             }
-            ConnectionConf::Ws { url } => {
-                let ws = Ws::connect(url)
+            ConnectionConf::Ws { url, retry, .. } => {
+                let reconnects = middleware_metrics
+                    .as_ref()
+                    .and_then(|(metrics, _)| metrics.websocket_reconnects().cloned());
+                let ws = ReconnectingWs::connect(url, &chain, reconnects)
                     .await
                     .map_err(EthereumProviderConnectionError::from)?;
-                self.wrap_with_metrics(ws, locator, signer, middleware_metrics)
-                    .await?
+                // Subscriptions need a pubsub client, so they go straight to
+                // the websocket rather than through the middleware stack.
+                let subscriber = Provider::new(ws.clone());
+                let parsed_url = url
+                    .parse::<Url>()
+                    .map_err(|e| EthereumProviderConnectionError::InvalidUrl(e, url.clone()))?;
+                let metrics_provider =
+                    self.wrap_rpc_with_metrics(ws, parsed_url, &rpc_metrics, &middleware_metrics);
+                let retrying_ws_provider =
+                    build_retrying_provider(metrics_provider, url, retry, RetryPolicy::default())?;
+                self.wrap_with_metrics(
+                    retrying_ws_provider,
+                    layers,
+                    Some(subscriber),
+                    locator,
+                    signer,
+                    middleware_metrics,
+                )
+                .await?
             }
         })
     }
@@ -191,11 +361,45 @@ pub trait BuildableWithProvider {
         )
     }
 
-    /// Wrap the provider creation with metrics if provided; this is the second
-    /// step
+    /// Build a fallback provider of HTTP nodes from comma separated urls,
+    /// wrapping the client of each url with `wrap`.
+    fn build_fallback<T>(
+        &self,
+        urls: &str,
+        batch: bool,
+        rpc_metrics: &Option<JsonRpcClientMetrics>,
+        middleware_metrics: &Option<(MiddlewareMetrics, PrometheusMiddlewareConf)>,
+        wrap: impl Fn(
+            PrometheusJsonRpcClient<BatchHttp>,
+            &str,
+        ) -> Result<T, EthereumProviderConnectionError>,
+    ) -> Result<FallbackProvider<T>, EthereumProviderConnectionError> {
+        let mut builder = FallbackProvider::builder();
+        let http_client = Client::builder().timeout(HTTP_CLIENT_TIMEOUT).build()?;
+        for url in urls.split(',') {
+            let parsed_url = url
+                .parse::<Url>()
+                .map_err(|e| EthereumProviderConnectionError::InvalidUrl(e, url.to_owned()))?;
+            let http_provider = BatchHttp::new_with_client(parsed_url.clone(), http_client.clone())
+                .with_batching(batch);
+            let metrics_provider = self.wrap_rpc_with_metrics(
+                http_provider,
+                parsed_url,
+                rpc_metrics,
+                middleware_metrics,
+            );
+            builder = builder.add_provider(wrap(metrics_provider, url)?);
+        }
+        Ok(builder.build())
+    }
+
+    /// Wrap the provider creation with metrics if provided, the archive
+    /// routing and the response cache if configured; this is the second step
     async fn wrap_with_metrics<P>(
         &self,
         client: P,
+        layers: ProviderLayers,
+        subscriber: Option<Provider<ReconnectingWs>>,
         locator: &ContractLocator,
         signer: Option<Signers>,
         metrics: Option<(MiddlewareMetrics, PrometheusMiddlewareConf)>,
@@ -203,13 +407,16 @@ pub trait BuildableWithProvider {
     where
         P: JsonRpcClient + 'static,
     {
-        let provider = Provider::new(client);
+        let router = ArchiveRouter::new(client, layers.archive, layers.archive_depth);
+        let provider = Provider::new(CachingProvider::new(router, layers.cache));
         Ok(if let Some(metrics) = metrics {
             let provider = Arc::new(PrometheusMiddleware::new(provider, metrics.0, metrics.1));
             tokio::spawn(provider.start_updating_on_interval(METRICS_SCRAPE_INTERVAL));
-            self.wrap_with_signer(provider, locator, signer).await?
+            self.wrap_with_signer(provider, subscriber, locator, signer)
+                .await?
         } else {
-            self.wrap_with_signer(provider, locator, signer).await?
+            self.wrap_with_signer(provider, subscriber, locator, signer)
+                .await?
         })
     }
 
@@ -218,6 +425,7 @@ pub trait BuildableWithProvider {
     async fn wrap_with_signer<M>(
         &self,
         provider: M,
+        subscriber: Option<Provider<ReconnectingWs>>,
         locator: &ContractLocator,
         signer: Option<Signers>,
     ) -> ChainResult<Self::Output>
@@ -228,19 +436,51 @@ pub trait BuildableWithProvider {
             let signing_provider = build_signing_provider(provider, signer)
                 .await
                 .map_err(ChainCommunicationError::from_other)?;
-            self.build_with_provider(signing_provider, locator)
+            self.build_with_subscriber(signing_provider, subscriber, locator)
         } else {
-            self.build_with_provider(provider, locator)
+            self.build_with_subscriber(provider, subscriber, locator)
         }
         .await)
     }
 
+    /// Construct a new instance of the associated trait using a provider and,
+    /// for websocket connections, a provider to create subscriptions with.
+    /// Only needs to be implemented by builders that make use of
+    /// subscriptions.
+    async fn build_with_subscriber<M>(
+        &self,
+        provider: M,
+        _subscriber: Option<Provider<ReconnectingWs>>,
+        locator: &ContractLocator,
+    ) -> Self::Output
+    where
+        M: Middleware + 'static,
+    {
+        self.build_with_provider(provider, locator).await
+    }
+
     /// Construct a new instance of the associated trait using a provider.
     async fn build_with_provider<M>(&self, provider: M, locator: &ContractLocator) -> Self::Output
     where
         M: Middleware + 'static;
 }
 
+/// Wrap a client with retries, using `default_policy` for anything the
+/// connection does not configure, and with the rate limit of its url if one is
+/// configured.
+fn build_retrying_provider<C>(
+    client: PrometheusJsonRpcClient<C>,
+    url: &str,
+    retry: &RetryConf,
+    default_policy: RetryPolicy,
+) -> Result<RetryingProvider<PrometheusJsonRpcClient<C>>, EthereumProviderConnectionError> {
+    let mut provider = RetryingProvider::with_policy(client, retry.policy(default_policy)?);
+    if let Some(bucket) = retry.rate_limit(url)? {
+        provider = provider.with_rate_limit(bucket);
+    }
+    Ok(provider)
+}
+
 async fn build_signing_provider<M: Middleware>(
     provider: M,
     signer: Signers,
@@ -1,13 +1,17 @@
+use std::error::Error;
 use std::time::Duration;
 
 use ethers::abi::Detokenize;
-use ethers::prelude::{NameOrAddress, TransactionReceipt};
+use ethers::prelude::{BlockId, Bytes, NameOrAddress, TransactionReceipt, TypedTransaction};
+use ethers::providers::JsonRpcError;
 use ethers_contract::builders::ContractCall;
-use tracing::{error, info};
+use ethers_contract::ContractError;
+use serde_json::Value;
+use tracing::{debug, error, info};
 
-use hyperlane_core::{ChainCommunicationError, ChainResult, H256};
+use hyperlane_core::{ChainCommunicationError, ChainResult, TxOutcome, H256};
 
-use crate::Middleware;
+use crate::{Middleware, RevertDecoder};
 
 /// Dispatches a transaction, logs the tx id, and returns the result
 pub(crate) async fn report_tx<M, D>(tx: ContractCall<M, D>) -> ChainResult<TransactionReceipt>
@@ -77,3 +81,199 @@ where
         }
     }
 }
+
+/// Dispatches a transaction like `report_tx` and returns its outcome
+pub(crate) async fn report_tx_outcome<M, D>(
+    provider: &M,
+    decoder: &RevertDecoder,
+    tx: ContractCall<M, D>,
+) -> ChainResult<TxOutcome>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    let typed_tx = tx.tx.clone();
+    let receipt = report_tx(tx).await?;
+    Ok(tx_outcome(provider, decoder, typed_tx, receipt).await)
+}
+
+/// The outcome of a mined transaction. The revert data of a reverted
+/// transaction is found by replaying it on the state before the block it was
+/// included in. Transactions earlier in the same block are not part of that
+/// state, so a transaction which only reverted because of one of them may
+/// succeed when replayed, leaving the revert reason unknown.
+pub(crate) async fn tx_outcome<M>(
+    provider: &M,
+    decoder: &RevertDecoder,
+    mut tx: TypedTransaction,
+    receipt: TransactionReceipt,
+) -> TxOutcome
+where
+    M: Middleware + 'static,
+{
+    let block = receipt
+        .block_number
+        .map(|n| BlockId::Number(n.saturating_sub(1.into()).into()));
+    let from = receipt.from;
+    let mut outcome = TxOutcome::from(receipt);
+    if !outcome.executed {
+        tx.set_from(from);
+        match provider.call(&tx, block).await {
+            Ok(_) => debug!(tx_hash = ?outcome.txid, "Replay of reverted transaction succeeded"),
+            Err(err) => {
+                outcome.revert_data = revert_data(&err).map(|data| data.to_vec());
+                outcome.revert_reason = outcome.revert_data.as_deref().map(|d| decoder.decode(d));
+            }
+        }
+    }
+    outcome
+}
+
+/// Convert the error of a call or gas estimate, decoding the revert reason if
+/// it reverted
+pub(crate) fn call_error<M>(
+    decoder: &RevertDecoder,
+    err: ContractError<M>,
+) -> ChainCommunicationError
+where
+    M: Middleware + 'static,
+{
+    let data = match &err {
+        ContractError::Revert(data) => Some(data.clone()),
+        err => revert_data(err),
+    };
+    match data {
+        Some(data) => ChainCommunicationError::Reverted(decoder.decode(&data)),
+        None => err.into(),
+    }
+}
+
+/// Prefix of the revert data in the message of a JSON-RPC error
+const REVERT_DATA_PREFIX: &str = "data: Some(String(\"0x";
+
+/// The revert data in the JSON-RPC error response to a call or gas estimate,
+/// if any
+pub(crate) fn revert_data(error: &(dyn Error + 'static)) -> Option<Bytes> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(data) = error
+            .downcast_ref::<JsonRpcError>()
+            .and_then(|err| err.data.as_ref())
+        {
+            return match data {
+                Value::String(data) => data.parse().ok(),
+                // Some nodes nest the revert data in an object
+                data => data.get("data")?.as_str()?.parse().ok(),
+            };
+        }
+        source = error.source();
+    }
+
+    // The errors of the provider layers are transparent wrappers, which skip
+    // the JSON-RPC error in the source chain but keep its message
+    let message = error.to_string();
+    let start = message.find(REVERT_DATA_PREFIX)? + REVERT_DATA_PREFIX.len();
+    let data: String = message[start..]
+        .chars()
+        .take_while(char::is_ascii_hexdigit)
+        .collect();
+    hex::decode(data).ok().map(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use ethers::prelude::{Provider, U64};
+    use ethers::providers::{HttpClientError, ProviderError};
+    use ethers_prometheus::json_rpc_client::{
+        JsonRpcClientMetricsBuilder, PrometheusJsonRpcClient,
+    };
+    use reqwest::{Client, Url};
+    use serde_json::json;
+    use warp::Filter;
+
+    use super::*;
+    use crate::{ArchiveRouter, BatchHttp, CachingProvider, RetryingProvider};
+
+    fn json_rpc_error(data: Value) -> JsonRpcError {
+        JsonRpcError {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(data),
+        }
+    }
+
+    #[test]
+    fn finds_revert_data() {
+        let error = json_rpc_error(json!("0x08c379a0"));
+        assert_eq!(revert_data(&error).unwrap().to_vec(), [8, 195, 121, 160]);
+
+        let error = json_rpc_error(json!({"message": "reverted", "data": "0x01"}));
+        assert_eq!(revert_data(&error).unwrap().to_vec(), [1]);
+
+        let error = ProviderError::from(HttpClientError::JsonRpcError(json_rpc_error(json!(
+            "0x4e487b71"
+        ))));
+        assert_eq!(revert_data(&error).unwrap().to_vec(), [78, 72, 123, 113]);
+
+        let error = ProviderError::CustomError("execution reverted".into());
+        assert_eq!(revert_data(&error), None);
+    }
+
+    /// Serve a node which reverts every call, recording the blocks the calls
+    /// were made at
+    async fn serve_reverting_node(data: &'static str) -> (Url, Arc<Mutex<Vec<Value>>>) {
+        let blocks = Arc::new(Mutex::new(vec![]));
+        let recorded = blocks.clone();
+        let route = warp::post()
+            .and(warp::body::json())
+            .map(move |request: Value| {
+                recorded.lock().unwrap().push(request["params"][1].clone());
+                warp::reply::json(&json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": {"code": 3, "message": "execution reverted", "data": data},
+                }))
+            });
+        let (addr, server) =
+            warp::serve(route).bind_ephemeral(SocketAddr::from(([127, 0, 0, 1], 0)));
+        tokio::spawn(server);
+        (format!("http://{addr}").parse().unwrap(), blocks)
+    }
+
+    #[tokio::test]
+    async fn replays_reverted_transactions_before_their_block() {
+        let (url, blocks) = serve_reverting_node("0x4e487b71").await;
+        // The layers the connection builder wraps an HTTP node in
+        let client = PrometheusJsonRpcClient::new(
+            BatchHttp::new_with_client(url, Client::new()),
+            JsonRpcClientMetricsBuilder::default().build().unwrap(),
+            Default::default(),
+        );
+        let router = ArchiveRouter::new(
+            RetryingProvider::new(client, None, None),
+            None::<BatchHttp>,
+            128,
+        );
+        let provider = Provider::new(CachingProvider::new(router, None));
+
+        let receipt = TransactionReceipt {
+            block_number: Some(U64::from(10)),
+            status: Some(U64::zero()),
+            ..Default::default()
+        };
+        let outcome = tx_outcome(
+            &provider,
+            &RevertDecoder::default(),
+            TypedTransaction::default(),
+            receipt,
+        )
+        .await;
+        assert!(!outcome.executed);
+        // The revert data is found through the wrappers of all layers
+        assert_eq!(outcome.revert_data, Some(vec![78, 72, 123, 113]));
+        assert_eq!(*blocks.lock().unwrap(), vec![json!("0x9")]);
+    }
+}
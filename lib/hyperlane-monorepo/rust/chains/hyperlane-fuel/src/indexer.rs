@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use fuels::prelude::{ContractId, Provider};
+use fuels::tx::Receipt;
+use futures_util::{stream, StreamExt, TryStreamExt};
+
+use hyperlane_core::{
+    ChainCommunicationError, ChainResult, Finality, HyperlaneProviderError, LogMeta, H256, U256,
+};
+
+use crate::conversions::*;
+
+/// The most blocks whose receipts are fetched at once
+const MAX_CONCURRENT_BLOCKS: usize = 16;
+
+/// The receipts of contract `contract_id` in blocks `from` to `to`, with the
+/// metadata of each. Fuel has no log filters, so every transaction in the
+/// range is inspected.
+pub(crate) async fn contract_receipts(
+    provider: &Provider,
+    contract_id: &ContractId,
+    from: u32,
+    to: u32,
+) -> ChainResult<Vec<(Receipt, LogMeta)>> {
+    let blocks: Vec<_> = stream::iter(from..=to)
+        .map(|height| block_receipts(provider, contract_id, height))
+        .buffered(MAX_CONCURRENT_BLOCKS)
+        .try_collect()
+        .await?;
+    Ok(blocks.into_iter().flatten().collect())
+}
+
+/// The receipts of contract `contract_id` in the block at `height`. The block
+/// must exist, as the range is only indexed up to the finalized block.
+async fn block_receipts(
+    provider: &Provider,
+    contract_id: &ContractId,
+    height: u32,
+) -> ChainResult<Vec<(Receipt, LogMeta)>> {
+    let block = provider
+        .client
+        .block_by_height(height as u64)
+        .await
+        .map_err(ChainCommunicationError::from_other)?
+        .ok_or(HyperlaneProviderError::CouldNotFindBlockByNumber(
+            height as u64,
+        ))
+        .map_err(ChainCommunicationError::from_other)?;
+    let block_hash = parse_h256(&block.id.to_string())?;
+    let mut receipts = vec![];
+    for (transaction_index, transaction) in block.transactions.iter().enumerate() {
+        let transaction_id = transaction.id.to_string();
+        let transaction_hash = parse_h256(&transaction_id)?;
+        let transaction_receipts = provider
+            .client
+            .receipts(&transaction_id)
+            .await
+            .map_err(ChainCommunicationError::from_other)?;
+        receipts.extend(
+            transaction_receipts
+                .into_iter()
+                .enumerate()
+                .filter(|(_, receipt)| receipt.id() == Some(contract_id))
+                .map(|(log_index, receipt)| {
+                    let meta = LogMeta {
+                        address: contract_id.into_h256(),
+                        block_number: height as u64,
+                        block_hash,
+                        transaction_hash,
+                        transaction_index: transaction_index as u64,
+                        log_index: U256::from(log_index),
+                    };
+                    (receipt, meta)
+                }),
+        );
+    }
+    Ok(receipts)
+}
+
+/// Parse a hex encoded 32 byte hash from the Fuel client
+fn parse_h256(hash: &str) -> ChainResult<H256> {
+    H256::from_str(hash).map_err(ChainCommunicationError::from_other)
+}
+
+/// The latest block which is `finality` blocks behind the head
+pub(crate) async fn finalized_block_number(
+    provider: &Provider,
+    finality: Finality,
+) -> ChainResult<u32> {
+    let head = provider
+        .latest_block_height()
+        .await
+        .map_err(ChainCommunicationError::from_other)?;
+    Ok(head.saturating_sub(finality.blocks() as u64) as u32)
+}
@@ -1,51 +1,292 @@
+use std::fmt::{Debug, Formatter};
+
 use async_trait::async_trait;
-use hyperlane_core::{HyperlaneDomain, HyperlaneProvider, H256};
+use fuels::prelude::{
+    Address, Bech32ContractId, Bits256, CallParameters, ContractId, Identity, Provider,
+    TxParameters, WalletUnlocked, BASE_ASSET_ID,
+};
+use fuels::tx::Receipt;
+use tracing::instrument;
 
 use hyperlane_core::{
-    ChainResult, HyperlaneChain, HyperlaneContract, Indexer, InterchainGasPaymaster,
-    InterchainGasPaymasterIndexer, InterchainGasPaymentWithMeta,
+    ChainCommunicationError, ChainResult, ContractLocator, Finality, HyperlaneChain,
+    HyperlaneContract, HyperlaneDomain, HyperlaneProvider, Indexer, InterchainGasPaymaster,
+    InterchainGasPaymasterIndexer, InterchainGasPayment, InterchainGasPaymentMeta,
+    InterchainGasPaymentWithMeta, LogMeta, TxOutcome, H256, U256,
+};
+
+use crate::{
+    contracts::interchain_gas_paymaster::InterchainGasPaymaster as FuelInterchainGasPaymasterInner,
+    conversions::*,
+    indexer::{contract_receipts, finalized_block_number},
+    make_provider,
+    tx::{send_call, to_u64, tx_outcome},
+    ConnectionConf, FuelProvider,
 };
 
+/// Gas price used for transactions. Fuel nodes accept a price of zero unless
+/// configured otherwise.
+const GAS_PRICE: u64 = 1;
+
+/// Id of the `log` of a `GasPaymentEvent`, see the ABI's `loggedTypes`
+const GAS_PAYMENT_LOG_ID: u64 = 0;
+
+/// The length of an encoded `GasPaymentEvent`: the message id followed by the
+/// gas amount and payment as big endian u64s
+const GAS_PAYMENT_LEN: usize = 48;
+
 /// A reference to an IGP contract on some Fuel chain
-#[derive(Debug)]
-pub struct FuelInterchainGasPaymaster {}
+pub struct FuelInterchainGasPaymaster {
+    contract: FuelInterchainGasPaymasterInner,
+    domain: HyperlaneDomain,
+    provider: Provider,
+}
+
+impl FuelInterchainGasPaymaster {
+    /// Create a new fuel IGP
+    pub fn new(
+        conf: &ConnectionConf,
+        locator: ContractLocator,
+        mut wallet: WalletUnlocked,
+    ) -> ChainResult<Self> {
+        let provider = make_provider(conf)?;
+        wallet.set_provider(provider.clone());
+        let address = Bech32ContractId::from_h256(&locator.address);
+
+        Ok(FuelInterchainGasPaymaster {
+            contract: FuelInterchainGasPaymasterInner::new(address, wallet),
+            domain: locator.domain,
+            provider,
+        })
+    }
+}
 
 impl HyperlaneContract for FuelInterchainGasPaymaster {
     fn address(&self) -> H256 {
-        todo!()
+        self.contract.get_contract_id().into_h256()
     }
 }
 
 impl HyperlaneChain for FuelInterchainGasPaymaster {
     fn domain(&self) -> &HyperlaneDomain {
-        todo!()
+        &self.domain
     }
 
     fn provider(&self) -> Box<dyn HyperlaneProvider> {
-        todo!()
+        Box::new(FuelProvider::from_provider(
+            self.provider.clone(),
+            self.domain.clone(),
+        ))
+    }
+}
+
+impl Debug for FuelInterchainGasPaymaster {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self as &dyn HyperlaneContract)
+    }
+}
+
+#[async_trait]
+impl InterchainGasPaymaster for FuelInterchainGasPaymaster {
+    #[instrument(err, ret, skip(self))]
+    async fn quote_gas_payment(
+        &self,
+        destination_domain: u32,
+        gas_amount: U256,
+    ) -> ChainResult<U256> {
+        self.contract
+            .methods()
+            .quote_gas_payment(destination_domain, to_u64(gas_amount)?)
+            .simulate()
+            .await
+            .map(|r| U256::from(r.value))
+            .map_err(ChainCommunicationError::from_other)
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn pay_for_gas(
+        &self,
+        message_id: H256,
+        destination_domain: u32,
+        gas_amount: U256,
+        payment: U256,
+        refund_address: H256,
+    ) -> ChainResult<TxOutcome> {
+        let call = self
+            .contract
+            .methods()
+            .pay_for_gas(
+                Bits256::from_h256(&message_id),
+                destination_domain,
+                to_u64(gas_amount)?,
+                Identity::Address(Address::new(refund_address.0)),
+            )
+            .call_params(CallParameters::new(
+                Some(to_u64(payment)?),
+                Some(BASE_ASSET_ID),
+                None,
+            ))
+            // Overpayments are refunded to `refund_address`
+            .append_variable_outputs(1)
+            .tx_params(TxParameters::new(Some(GAS_PRICE), None, None));
+        let (txid, result) = send_call(&self.provider, call).await?;
+        tx_outcome(txid, result, GAS_PRICE)
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn balance(&self) -> ChainResult<U256> {
+        self.provider
+            .get_contract_asset_balance(self.contract.get_contract_id(), BASE_ASSET_ID)
+            .await
+            .map(U256::from)
+            .map_err(ChainCommunicationError::from_other)
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn beneficiary(&self) -> ChainResult<H256> {
+        let beneficiary = self
+            .contract
+            .methods()
+            .beneficiary()
+            .simulate()
+            .await
+            .map_err(ChainCommunicationError::from_other)?
+            .value;
+        Ok(match beneficiary {
+            Identity::Address(address) => H256::from(*address),
+            Identity::ContractId(contract_id) => contract_id.into_h256(),
+        })
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn claim(&self, tx_gas_limit: Option<U256>) -> ChainResult<TxOutcome> {
+        let gas_limit = tx_gas_limit.map(to_u64).transpose()?;
+        let call = self
+            .contract
+            .methods()
+            .claim()
+            .append_variable_outputs(1)
+            .tx_params(TxParameters::new(Some(GAS_PRICE), gas_limit, None));
+        let (txid, result) = send_call(&self.provider, call).await?;
+        tx_outcome(txid, result, GAS_PRICE)
     }
 }
 
-impl InterchainGasPaymaster for FuelInterchainGasPaymaster {}
+/// The gas payment made to `igp` in a receipt, if it is one
+fn gas_payment(
+    receipt: &Receipt,
+    igp: &ContractId,
+    meta: &LogMeta,
+) -> Option<InterchainGasPaymentWithMeta> {
+    match receipt {
+        Receipt::LogData { id, rb, data, .. }
+            if id == igp && *rb == GAS_PAYMENT_LOG_ID && data.len() == GAS_PAYMENT_LEN =>
+        {
+            let payment: [u8; 8] = data[40..48].try_into().unwrap();
+            Some(InterchainGasPaymentWithMeta {
+                payment: InterchainGasPayment {
+                    message_id: H256::from_slice(&data[..32]),
+                    payment: U256::from(u64::from_be_bytes(payment)),
+                },
+                meta: InterchainGasPaymentMeta {
+                    transaction_hash: meta.transaction_hash,
+                    log_index: meta.log_index,
+                },
+            })
+        }
+        _ => None,
+    }
+}
 
 /// Struct that retrieves event data for a Fuel IGP contract
 #[derive(Debug)]
-pub struct FuelInterchainGasPaymasterIndexer {}
+pub struct FuelInterchainGasPaymasterIndexer {
+    provider: Provider,
+    contract_id: ContractId,
+    finality: Finality,
+}
+
+impl FuelInterchainGasPaymasterIndexer {
+    /// Create a new fuel IGP indexer. Fuel has no finality tags, so only the
+    /// block count of `finality` is used.
+    pub fn new(
+        conf: &ConnectionConf,
+        locator: ContractLocator,
+        finality: Finality,
+    ) -> ChainResult<Self> {
+        Ok(Self {
+            provider: make_provider(conf)?,
+            contract_id: ContractId::from_h256(&locator.address),
+            finality,
+        })
+    }
+}
 
 #[async_trait]
 impl Indexer for FuelInterchainGasPaymasterIndexer {
+    #[instrument(level = "debug", err, ret, skip(self))]
     async fn get_finalized_block_number(&self) -> ChainResult<u32> {
-        todo!()
+        finalized_block_number(&self.provider, self.finality).await
     }
 }
 
 #[async_trait]
 impl InterchainGasPaymasterIndexer for FuelInterchainGasPaymasterIndexer {
+    #[instrument(err, skip(self))]
     async fn fetch_gas_payments(
         &self,
         from_block: u32,
         to_block: u32,
     ) -> ChainResult<Vec<InterchainGasPaymentWithMeta>> {
-        todo!()
+        Ok(
+            contract_receipts(&self.provider, &self.contract_id, from_block, to_block)
+                .await?
+                .iter()
+                .filter_map(|(receipt, meta)| gas_payment(receipt, &self.contract_id, meta))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_gas_payments() {
+        let igp = ContractId::new([1; 32]);
+        let data = [
+            [3; 32].to_vec(),
+            100u64.to_be_bytes().to_vec(),
+            7u64.to_be_bytes().to_vec(),
+        ]
+        .concat();
+        let receipt = Receipt::log_data_with_len(
+            igp,
+            0,
+            GAS_PAYMENT_LOG_ID,
+            0,
+            48,
+            Default::default(),
+            data,
+            0,
+            0,
+        );
+        let meta = LogMeta {
+            address: igp.into_h256(),
+            block_number: 1,
+            block_hash: H256::zero(),
+            transaction_hash: H256::repeat_byte(4),
+            transaction_index: 0,
+            log_index: U256::from(2),
+        };
+
+        let payment = gas_payment(&receipt, &igp, &meta).unwrap();
+        assert_eq!(payment.payment.message_id, H256::repeat_byte(3));
+        assert_eq!(payment.payment.payment, U256::from(7));
+        assert_eq!(payment.meta.transaction_hash, H256::repeat_byte(4));
+        assert_eq!(payment.meta.log_index, U256::from(2));
+
+        assert!(gas_payment(&receipt, &ContractId::new([2; 32]), &meta).is_none());
     }
 }
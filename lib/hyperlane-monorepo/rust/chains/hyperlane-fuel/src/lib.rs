@@ -2,8 +2,6 @@
 
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
-// TODO: Remove once we start filling things in
-#![allow(unused_variables)]
 
 pub use interchain_gas::*;
 pub use mailbox::*;
@@ -13,11 +11,13 @@ pub use trait_builder::*;
 
 mod contracts;
 mod conversions;
+mod indexer;
 mod interchain_gas;
 mod mailbox;
 mod multisig_ism;
 mod provider;
 mod trait_builder;
+mod tx;
 
 /// Safe default imports of commonly used traits/types.
 pub mod prelude {
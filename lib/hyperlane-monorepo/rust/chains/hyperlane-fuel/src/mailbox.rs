@@ -1,25 +1,55 @@
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
-use std::num::NonZeroU64;
 
 use async_trait::async_trait;
-use fuels::prelude::{Bech32ContractId, WalletUnlocked};
+use fuels::prelude::{
+    Bech32ContractId, Bits256, ContractId, Provider, TxParameters, WalletUnlocked,
+};
+use fuels::tx::Receipt;
 use tracing::instrument;
 
 use hyperlane_core::{
-    ChainCommunicationError, ChainResult, Checkpoint, ContractLocator, HyperlaneAbi,
+    ChainCommunicationError, ChainResult, Checkpoint, ContractLocator, Finality, HyperlaneAbi,
     HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneMessage, HyperlaneProvider,
-    Indexer, LogMeta, Mailbox, MailboxIndexer, TxCostEstimate, TxOutcome, H256, U256,
+    Indexer, LogMeta, Mailbox, MailboxIndexer, RawHyperlaneMessage, TxCostEstimate, TxOutcome,
+    H256, U256,
 };
 
 use crate::{
-    contracts::mailbox::Mailbox as FuelMailboxInner, conversions::*, make_provider, ConnectionConf,
+    contracts::mailbox::{Mailbox as FuelMailboxInner, Message as FuelMessage},
+    conversions::*,
+    indexer::{contract_receipts, finalized_block_number},
+    make_provider,
+    tx::{send_call, to_u64, tx_outcome},
+    ConnectionConf, FuelProvider,
 };
 
+/// Gas price used for transactions. Fuel nodes accept a price of zero unless
+/// configured otherwise.
+const GAS_PRICE: u64 = 1;
+
+/// Tolerance applied to gas estimates to account for changes in state between
+/// estimation and execution
+const GAS_ESTIMATE_TOLERANCE: f64 = 0.2;
+
+/// Id of the `log` of a processed message's id, the only `b256` in the ABI's
+/// `loggedTypes`. Checked against the ABI by the tests.
+const PROCESS_ID_LOG_ID: u64 = 6;
+
+/// The last id in the ABI's `loggedTypes`. Typed logs are numbered by the
+/// compiler, while the mailbox logs dispatched messages as raw `log_data`
+/// under an id past all of them, so that is how they are told apart. Checked
+/// against the ABI by the tests.
+const LAST_TYPED_LOG_ID: u64 = 9;
+
+/// The length of an encoded message without its body
+const MESSAGE_HEADER_LEN: usize = 77;
+
 /// A reference to a Mailbox contract on some Fuel chain
 pub struct FuelMailbox {
     contract: FuelMailboxInner,
     domain: HyperlaneDomain,
+    provider: Provider,
 }
 
 impl FuelMailbox {
@@ -30,14 +60,28 @@ impl FuelMailbox {
         mut wallet: WalletUnlocked,
     ) -> ChainResult<Self> {
         let provider = make_provider(conf)?;
-        wallet.set_provider(provider);
+        wallet.set_provider(provider.clone());
         let address = Bech32ContractId::from_h256(&locator.address);
 
         Ok(FuelMailbox {
             contract: FuelMailboxInner::new(address, wallet),
             domain: locator.domain,
+            provider,
         })
     }
+
+    /// The contracts `process` calls into, which have to be declared as
+    /// inputs of the transaction: the ISM and the recipient.
+    async fn process_contracts(
+        &self,
+        message: &HyperlaneMessage,
+    ) -> ChainResult<[Bech32ContractId; 2]> {
+        let ism = self.default_ism().await?;
+        Ok([
+            Bech32ContractId::from_h256(&ism),
+            Bech32ContractId::from_h256(&message.recipient),
+        ])
+    }
 }
 
 impl HyperlaneContract for FuelMailbox {
@@ -52,7 +96,10 @@ impl HyperlaneChain for FuelMailbox {
     }
 
     fn provider(&self) -> Box<dyn HyperlaneProvider> {
-        todo!()
+        Box::new(FuelProvider::from_provider(
+            self.provider.clone(),
+            self.domain.clone(),
+        ))
     }
 }
 
@@ -62,6 +109,20 @@ impl Debug for FuelMailbox {
     }
 }
 
+impl From<&HyperlaneMessage> for FuelMessage {
+    fn from(m: &HyperlaneMessage) -> Self {
+        Self {
+            version: m.version,
+            nonce: m.nonce,
+            origin: m.origin,
+            sender: Bits256::from_h256(&m.sender),
+            destination: m.destination,
+            recipient: Bits256::from_h256(&m.recipient),
+            body: m.body.clone(),
+        }
+    }
+}
+
 #[async_trait]
 impl Mailbox for FuelMailbox {
     #[instrument(level = "debug", err, ret, skip(self))]
@@ -77,13 +138,19 @@ impl Mailbox for FuelMailbox {
 
     #[instrument(err, ret, skip(self))]
     async fn delivered(&self, id: H256) -> ChainResult<bool> {
-        todo!()
+        self.contract
+            .methods()
+            .delivered(Bits256::from_h256(&id))
+            .simulate()
+            .await
+            .map(|r| r.value)
+            .map_err(ChainCommunicationError::from_other)
     }
 
     #[instrument(err, ret, skip(self))]
-    async fn latest_checkpoint(&self, lag: Option<NonZeroU64>) -> ChainResult<Checkpoint> {
+    async fn latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint> {
         assert!(
-            lag.is_none(),
+            finality.is_head(),
             "Fuel does not support querying point-in-time"
         );
         let (root, index) = self
@@ -105,12 +172,47 @@ impl Mailbox for FuelMailbox {
 
     #[instrument(err, ret, skip(self))]
     async fn default_ism(&self) -> ChainResult<H256> {
-        todo!()
+        self.contract
+            .methods()
+            .get_default_ism()
+            .simulate()
+            .await
+            .map(|r| r.value.into_h256())
+            .map_err(ChainCommunicationError::from_other)
     }
 
     #[instrument(err, ret, skip(self))]
-    async fn recipient_ism(&self, recipient: H256) -> ChainResult<H256> {
-        todo!()
+    async fn recipient_ism(&self, _recipient: H256) -> ChainResult<H256> {
+        // The Fuel mailbox verifies all messages with its default ISM
+        self.default_ism().await
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn dispatch(
+        &self,
+        destination_domain: u32,
+        recipient: H256,
+        body: &[u8],
+    ) -> ChainResult<(HyperlaneMessage, TxOutcome)> {
+        let call = self
+            .contract
+            .methods()
+            .dispatch(
+                destination_domain,
+                Bits256::from_h256(&recipient),
+                body.to_vec(),
+            )
+            .tx_params(TxParameters::new(Some(GAS_PRICE), None, None));
+        let (txid, result) = send_call(&self.provider, call).await?;
+        let response = result.map_err(ChainCommunicationError::from_other)?;
+        let id = response.value.into_h256();
+        let message = response
+            .receipts
+            .iter()
+            .filter_map(|receipt| dispatched_message(receipt, self.contract.get_contract_id()))
+            .find(|message| message.id() == id)
+            .ok_or(ChainCommunicationError::MessageNotDispatched(id))?;
+        Ok((message, tx_outcome(txid, Ok(response), GAS_PRICE)?))
     }
 
     #[instrument(err, ret, skip(self))]
@@ -120,7 +222,16 @@ impl Mailbox for FuelMailbox {
         metadata: &[u8],
         tx_gas_limit: Option<U256>,
     ) -> ChainResult<TxOutcome> {
-        todo!()
+        let contracts = self.process_contracts(message).await?;
+        let gas_limit = tx_gas_limit.map(to_u64).transpose()?;
+        let call = self
+            .contract
+            .methods()
+            .process(metadata.to_vec(), message.into())
+            .set_contracts(&contracts)
+            .tx_params(TxParameters::new(Some(GAS_PRICE), gas_limit, None));
+        let (txid, result) = send_call(&self.provider, call).await?;
+        tx_outcome(txid, result, GAS_PRICE)
     }
 
     #[instrument(err, ret, skip(self))]
@@ -129,41 +240,130 @@ impl Mailbox for FuelMailbox {
         message: &HyperlaneMessage,
         metadata: &[u8],
     ) -> ChainResult<TxCostEstimate> {
-        todo!()
+        let contracts = self.process_contracts(message).await?;
+        let cost = self
+            .contract
+            .methods()
+            .process(metadata.to_vec(), message.into())
+            .set_contracts(&contracts)
+            .tx_params(TxParameters::new(Some(GAS_PRICE), None, None))
+            .estimate_transaction_cost(Some(GAS_ESTIMATE_TOLERANCE))
+            .await
+            .map_err(ChainCommunicationError::from_other)?;
+
+        Ok(TxCostEstimate {
+            gas_limit: U256::from(cost.gas_used),
+            gas_price: U256::from(cost.gas_price.max(GAS_PRICE)),
+        })
     }
 
     fn process_calldata(&self, message: &HyperlaneMessage, metadata: &[u8]) -> Vec<u8> {
-        todo!()
+        let call = self
+            .contract
+            .methods()
+            .process(metadata.to_vec(), message.into())
+            .contract_call;
+        [call.encoded_selector.to_vec(), call.encoded_args].concat()
+    }
+}
+
+/// The message dispatched by `mailbox` in a receipt, if it is one
+fn dispatched_message(receipt: &Receipt, mailbox: &Bech32ContractId) -> Option<HyperlaneMessage> {
+    match receipt {
+        Receipt::LogData { id, rb, data, .. }
+            if *id == ContractId::from(mailbox)
+                && *rb > LAST_TYPED_LOG_ID
+                && data.len() >= MESSAGE_HEADER_LEN =>
+        {
+            Some(RawHyperlaneMessage::from(data.clone()).into())
+        }
+        _ => None,
+    }
+}
+
+/// The id of the message processed by `mailbox` in a receipt, if it is one
+fn processed_message_id(receipt: &Receipt, mailbox: &ContractId) -> Option<H256> {
+    match receipt {
+        Receipt::LogData { id, rb, data, .. }
+            if id == mailbox && *rb == PROCESS_ID_LOG_ID && data.len() == 32 =>
+        {
+            Some(H256::from_slice(data))
+        }
+        _ => None,
     }
 }
 
 /// Struct that retrieves event data for a Fuel Mailbox contract
 #[derive(Debug)]
-pub struct FuelMailboxIndexer {}
+pub struct FuelMailboxIndexer {
+    provider: Provider,
+    contract_id: ContractId,
+    finality: Finality,
+}
+
+impl FuelMailboxIndexer {
+    /// Create a new fuel mailbox indexer. Fuel has no finality tags, so only
+    /// the block count of `finality` is used.
+    pub fn new(
+        conf: &ConnectionConf,
+        locator: ContractLocator,
+        finality: Finality,
+    ) -> ChainResult<Self> {
+        Ok(Self {
+            provider: make_provider(conf)?,
+            contract_id: ContractId::from_h256(&locator.address),
+            finality,
+        })
+    }
+
+    /// The receipts of contract `contract_id` in blocks `from` to `to`, with
+    /// the metadata of each
+    async fn receipts(&self, from: u32, to: u32) -> ChainResult<Vec<(Receipt, LogMeta)>> {
+        contract_receipts(&self.provider, &self.contract_id, from, to).await
+    }
+}
 
 #[async_trait]
 impl Indexer for FuelMailboxIndexer {
+    #[instrument(level = "debug", err, ret, skip(self))]
     async fn get_finalized_block_number(&self) -> ChainResult<u32> {
-        todo!()
+        finalized_block_number(&self.provider, self.finality).await
     }
 }
 
 #[async_trait]
 impl MailboxIndexer for FuelMailboxIndexer {
+    #[instrument(err, skip(self))]
     async fn fetch_sorted_messages(
         &self,
         from: u32,
         to: u32,
     ) -> ChainResult<Vec<(HyperlaneMessage, LogMeta)>> {
-        todo!()
+        let mailbox = Bech32ContractId::from(self.contract_id);
+        let mut messages: Vec<(HyperlaneMessage, LogMeta)> = self
+            .receipts(from, to)
+            .await?
+            .into_iter()
+            .filter_map(|(receipt, meta)| Some((dispatched_message(&receipt, &mailbox)?, meta)))
+            .collect();
+        messages.sort_by(|a, b| a.0.nonce.cmp(&b.0.nonce));
+        Ok(messages)
     }
 
+    #[instrument(err, skip(self))]
     async fn fetch_delivered_messages(
         &self,
         from: u32,
         to: u32,
     ) -> ChainResult<Vec<(H256, LogMeta)>> {
-        todo!()
+        Ok(self
+            .receipts(from, to)
+            .await?
+            .into_iter()
+            .filter_map(|(receipt, meta)| {
+                Some((processed_message_id(&receipt, &self.contract_id)?, meta))
+            })
+            .collect())
     }
 }
 
@@ -173,7 +373,87 @@ impl HyperlaneAbi for FuelMailboxAbi {
     const SELECTOR_SIZE_BYTES: usize = 8;
 
     fn fn_map() -> HashMap<Vec<u8>, &'static str> {
-        // Can't support this without Fuels exporting it in the generated code
-        todo!()
+        // fuels only resolves selectors when building a call with a connected
+        // wallet, so calls to the mailbox are left unnamed
+        HashMap::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn log_data(id: ContractId, rb: u64, data: Vec<u8>) -> Receipt {
+        Receipt::log_data_with_len(
+            id,
+            0,
+            rb,
+            0,
+            data.len() as u64,
+            Default::default(),
+            data,
+            0,
+            0,
+        )
+    }
+
+    #[test]
+    fn decodes_mailbox_receipts() {
+        let mailbox = ContractId::new([1; 32]);
+        let message = HyperlaneMessage {
+            origin: 1000,
+            destination: 2000,
+            body: vec![1, 2, 3],
+            ..Default::default()
+        };
+        let encoded = RawHyperlaneMessage::from(&message);
+
+        let receipt = log_data(mailbox, LAST_TYPED_LOG_ID + 1, encoded.clone());
+        let dispatched = dispatched_message(&receipt, &mailbox.into()).unwrap();
+        assert_eq!(dispatched.id(), message.id());
+
+        // Logs of other contracts and typed logs are ignored
+        let receipt = log_data(ContractId::new([2; 32]), LAST_TYPED_LOG_ID + 1, encoded);
+        assert!(dispatched_message(&receipt, &mailbox.into()).is_none());
+        let receipt = log_data(mailbox, PROCESS_ID_LOG_ID, message.id().0.to_vec());
+        assert!(dispatched_message(&receipt, &mailbox.into()).is_none());
+
+        assert_eq!(processed_message_id(&receipt, &mailbox), Some(message.id()));
+    }
+
+    #[test]
+    fn log_ids_match_abi() {
+        let abi: Value = serde_json::from_str(include_str!("../abis/Mailbox.abi.json")).unwrap();
+        let type_name = |type_id: &Value| {
+            abi["types"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|t| &t["typeId"] == type_id)
+                .unwrap()["type"]
+                .clone()
+        };
+        let logged_types: Vec<(u64, Value)> = abi["loggedTypes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|log| {
+                (
+                    log["logId"].as_u64().unwrap(),
+                    type_name(&log["loggedType"]["type"]),
+                )
+            })
+            .collect();
+
+        let b256_logs: Vec<u64> = logged_types
+            .iter()
+            .filter(|(_, name)| name == "b256")
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(b256_logs, vec![PROCESS_ID_LOG_ID]);
+        let last = logged_types.iter().map(|(id, _)| *id).max().unwrap();
+        assert_eq!(last, LAST_TYPED_LOG_ID);
     }
 }
@@ -1,41 +1,108 @@
+use std::fmt::{Debug, Formatter};
+
 use async_trait::async_trait;
+use fuels::prelude::{Bech32ContractId, Provider, WalletUnlocked};
+use tracing::instrument;
 
 use hyperlane_core::{
-    accumulator::merkle::Proof, ChainResult, HyperlaneChain, HyperlaneContract, HyperlaneDomain,
-    HyperlaneMessage, HyperlaneProvider, MultisigIsm, MultisigSignedCheckpoint, H256,
+    accumulator::merkle::Proof, multisig_ism_metadata, ChainCommunicationError, ChainResult,
+    ContractLocator, HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneMessage,
+    HyperlaneProvider, MultisigIsm, MultisigSignedCheckpoint, H256,
+};
+
+use crate::{
+    contracts::multisig_ism::MultisigIsm as FuelMultisigIsmInner, conversions::*, make_provider,
+    ConnectionConf, FuelProvider,
 };
 
 /// A reference to a MultisigIsm contract on some Fuel chain
-#[derive(Debug)]
-pub struct FuelMultisigIsm {}
+pub struct FuelMultisigIsm {
+    contract: FuelMultisigIsmInner,
+    domain: HyperlaneDomain,
+    provider: Provider,
+}
+
+impl FuelMultisigIsm {
+    /// Create a new fuel multisig ISM
+    pub fn new(
+        conf: &ConnectionConf,
+        locator: ContractLocator,
+        mut wallet: WalletUnlocked,
+    ) -> ChainResult<Self> {
+        let provider = make_provider(conf)?;
+        wallet.set_provider(provider.clone());
+        let address = Bech32ContractId::from_h256(&locator.address);
+
+        Ok(FuelMultisigIsm {
+            contract: FuelMultisigIsmInner::new(address, wallet),
+            domain: locator.domain,
+            provider,
+        })
+    }
+}
 
 impl HyperlaneContract for FuelMultisigIsm {
     fn address(&self) -> H256 {
-        todo!()
+        self.contract.get_contract_id().into_h256()
     }
 }
 
 impl HyperlaneChain for FuelMultisigIsm {
     fn domain(&self) -> &HyperlaneDomain {
-        todo!()
+        &self.domain
     }
 
     fn provider(&self) -> Box<dyn HyperlaneProvider> {
-        todo!()
+        Box::new(FuelProvider::from_provider(
+            self.provider.clone(),
+            self.domain.clone(),
+        ))
+    }
+}
+
+impl Debug for FuelMultisigIsm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self as &dyn HyperlaneContract)
     }
 }
 
 #[async_trait]
 impl MultisigIsm for FuelMultisigIsm {
     /// Returns the validator and threshold needed to verify message
+    #[instrument(err, ret, skip(self))]
     async fn validators_and_threshold(
         &self,
         message: &HyperlaneMessage,
     ) -> ChainResult<(Vec<H256>, u8)> {
-        todo!()
+        let validators = self
+            .contract
+            .methods()
+            .validators(message.origin)
+            .simulate()
+            .await
+            .map_err(ChainCommunicationError::from_other)?
+            .value;
+        let threshold = self
+            .contract
+            .methods()
+            .threshold(message.origin)
+            .simulate()
+            .await
+            .map_err(ChainCommunicationError::from_other)?
+            .value;
+        Ok((
+            validators
+                .into_iter()
+                .map(FuelIntoH256::into_h256)
+                .collect(),
+            threshold,
+        ))
     }
 
-    /// Returns the metadata needed by the contract's verify function
+    /// Returns the metadata needed by the contract's verify function. The
+    /// layout is the same as on EVM chains: the checkpoint root and index, the
+    /// mailbox, the merkle proof, the threshold, the signatures in validator
+    /// order and the validators, without padding.
     fn format_metadata(
         &self,
         validators: &[H256],
@@ -43,6 +110,65 @@ impl MultisigIsm for FuelMultisigIsm {
         checkpoint: &MultisigSignedCheckpoint,
         proof: &Proof,
     ) -> Vec<u8> {
-        todo!()
+        multisig_ism_metadata(validators, threshold, checkpoint, proof)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::{Checkpoint, Decode, KnownHyperlaneDomain, SignatureWithSigner, H160};
+
+    use super::*;
+
+    fn signature(signer: H160, byte: u8) -> SignatureWithSigner {
+        let mut bytes = [byte; 65];
+        bytes[64] = 27;
+        SignatureWithSigner {
+            signature: Decode::read_from(&mut &bytes[..]).unwrap(),
+            signer,
+        }
+    }
+
+    #[test]
+    fn formats_metadata() {
+        let ism = FuelMultisigIsm::new(
+            &ConnectionConf::new("http://127.0.0.1:4000"),
+            ContractLocator {
+                domain: HyperlaneDomain::Known(KnownHyperlaneDomain::FuelTest1),
+                address: H256::repeat_byte(9),
+            },
+            WalletUnlocked::new_random(None),
+        )
+        .unwrap();
+        let (first, second) = (H160::repeat_byte(1), H160::repeat_byte(2));
+        let validators = [H256::from(first), H256::from(second)];
+        let checkpoint = MultisigSignedCheckpoint {
+            checkpoint: Checkpoint {
+                mailbox_address: H256::repeat_byte(3),
+                mailbox_domain: 1000,
+                root: H256::repeat_byte(4),
+                index: 5,
+            },
+            // Signatures are ordered by validator
+            signatures: vec![signature(second, 0xbb), signature(first, 0xaa)],
+        };
+        let proof = Proof {
+            leaf: H256::zero(),
+            index: 5,
+            path: [H256::repeat_byte(6); 32],
+        };
+
+        let metadata = ism.format_metadata(&validators, 2, &checkpoint, &proof);
+        assert_eq!(metadata.len(), 32 + 4 + 32 + 32 * 32 + 1 + 2 * 65 + 2 * 32);
+        assert_eq!(&metadata[..32], &[4; 32]);
+        assert_eq!(&metadata[32..36], &5u32.to_be_bytes());
+        assert_eq!(&metadata[36..68], &[3; 32]);
+        assert_eq!(&metadata[68..100], &[6; 32]);
+        let rest = &metadata[68 + 32 * 32..];
+        assert_eq!(rest[0], 2);
+        assert_eq!(&rest[1..65], &[0xaa; 64]);
+        assert_eq!(&rest[66..130], &[0xbb; 64]);
+        assert_eq!(&rest[131..163], validators[0].as_bytes());
+        assert_eq!(&rest[163..], validators[1].as_bytes());
     }
 }
@@ -1,34 +1,151 @@
 use async_trait::async_trait;
+use fuels::client::types::TransactionStatus;
+use fuels::prelude::Provider;
+use fuels::tx::field::{GasLimit, GasPrice, Inputs};
+use fuels::tx::{Input, Transaction};
+use tracing::instrument;
 
 use hyperlane_core::{
-    BlockInfo, ChainResult, HyperlaneChain, HyperlaneDomain, HyperlaneProvider, TxnInfo, H256,
+    BlockInfo, ChainCommunicationError, ChainResult, HyperlaneChain, HyperlaneDomain,
+    HyperlaneProvider, HyperlaneProviderError, TxnInfo, TxnReceiptInfo, H256, U256,
 };
 
+use crate::{make_provider, tx::gas_used, ConnectionConf};
+
 /// A wrapper around a fuel provider to get generic blockchain information.
-#[derive(Debug)]
-pub struct FuelProvider {}
+#[derive(Debug, Clone)]
+pub struct FuelProvider {
+    provider: Provider,
+    domain: HyperlaneDomain,
+}
+
+impl FuelProvider {
+    /// Create a new fuel provider
+    pub fn new(conf: &ConnectionConf, domain: HyperlaneDomain) -> ChainResult<Self> {
+        Ok(Self::from_provider(make_provider(conf)?, domain))
+    }
+
+    /// Wrap an existing fuel provider
+    pub(crate) fn from_provider(provider: Provider, domain: HyperlaneDomain) -> Self {
+        Self { provider, domain }
+    }
+}
 
 impl HyperlaneChain for FuelProvider {
     fn domain(&self) -> &HyperlaneDomain {
-        todo!()
+        &self.domain
     }
 
     fn provider(&self) -> Box<dyn HyperlaneProvider> {
-        todo!()
+        Box::new(self.clone())
     }
 }
 
+/// The gas price and gas limit of a transaction. Mint transactions are created
+/// by block producers and have neither.
+fn gas_price_and_limit(transaction: &Transaction) -> (u64, u64) {
+    match transaction {
+        Transaction::Script(script) => (*script.gas_price(), *script.gas_limit()),
+        Transaction::Create(create) => (*create.gas_price(), *create.gas_limit()),
+        Transaction::Mint(_) => (0, 0),
+    }
+}
+
+/// Fuel transactions have no sender, so the owner of the first coin spent by
+/// the transaction is used, and the first contract called as the recipient.
+fn sender_and_recipient(transaction: &Transaction) -> (H256, Option<H256>) {
+    let inputs: &[Input] = match transaction {
+        Transaction::Script(script) => script.inputs(),
+        Transaction::Create(create) => create.inputs(),
+        Transaction::Mint(_) => &[],
+    };
+    let sender = inputs
+        .iter()
+        .find_map(Input::input_owner)
+        .map(|owner| H256::from(**owner))
+        .unwrap_or_default();
+    let recipient = inputs
+        .iter()
+        .find_map(Input::contract_id)
+        .map(|contract_id| H256::from(**contract_id));
+    (sender, recipient)
+}
+
 #[async_trait]
 impl HyperlaneProvider for FuelProvider {
+    #[instrument(err, skip(self))]
     async fn get_block_by_hash(&self, hash: &H256) -> ChainResult<BlockInfo> {
-        todo!()
+        let block = self
+            .provider
+            .client
+            .block(&format!("{hash:x}"))
+            .await
+            .map_err(ChainCommunicationError::from_other)?
+            .ok_or(HyperlaneProviderError::CouldNotFindObjectByHash(*hash))
+            .map_err(ChainCommunicationError::from_other)?;
+
+        Ok(BlockInfo {
+            hash: *hash,
+            timestamp: block.header.time.0.to_unix() as u64,
+            number: block.header.height.0,
+        })
     }
 
+    #[instrument(err, skip(self))]
     async fn get_txn_by_hash(&self, hash: &H256) -> ChainResult<TxnInfo> {
-        todo!()
+        let id = format!("{hash:x}");
+        let response = self
+            .provider
+            .client
+            .transaction(&id)
+            .await
+            .map_err(ChainCommunicationError::from_other)?
+            .ok_or(HyperlaneProviderError::CouldNotFindObjectByHash(*hash))
+            .map_err(ChainCommunicationError::from_other)?;
+
+        let (gas_price, gas_limit) = gas_price_and_limit(&response.transaction);
+        let (sender, recipient) = sender_and_recipient(&response.transaction);
+        let receipt = match response.status {
+            TransactionStatus::Submitted { .. } | TransactionStatus::SqueezedOut { .. } => None,
+            TransactionStatus::Success { .. } | TransactionStatus::Failure { .. } => {
+                let receipts = self
+                    .provider
+                    .client
+                    .receipts(&id)
+                    .await
+                    .map_err(ChainCommunicationError::from_other)?;
+                let gas_used = U256::from(gas_used(&receipts));
+                Some(TxnReceiptInfo {
+                    gas_used,
+                    // Fuel does not report the gas used by preceding
+                    // transactions in the block
+                    cumulative_gas_used: gas_used,
+                    effective_gas_price: Some(U256::from(gas_price)),
+                })
+            }
+        };
+
+        Ok(TxnInfo {
+            hash: *hash,
+            gas_limit: U256::from(gas_limit),
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+            gas_price: Some(U256::from(gas_price)),
+            // Fuel is UTXO based and transactions have no nonce
+            nonce: 0,
+            sender,
+            recipient,
+            receipt,
+        })
     }
 
+    #[instrument(err, skip(self))]
     async fn is_contract(&self, address: &H256) -> ChainResult<bool> {
-        todo!()
+        self.provider
+            .client
+            .contract(&format!("{address:x}"))
+            .await
+            .map(|contract| contract.is_some())
+            .map_err(ChainCommunicationError::from_other)
     }
 }
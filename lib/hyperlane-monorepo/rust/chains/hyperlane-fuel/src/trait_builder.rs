@@ -10,6 +10,13 @@ pub struct ConnectionConf {
     url: String,
 }
 
+impl ConnectionConf {
+    /// Connect to the node at `url`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 #[error(transparent)]
 struct FuelNewConnectionError(#[from] anyhow::Error);
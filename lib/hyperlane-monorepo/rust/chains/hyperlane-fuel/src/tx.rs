@@ -0,0 +1,73 @@
+use std::fmt::Debug;
+
+use fuels::contract::contract::ContractCallHandler;
+use fuels::prelude::{Error as FuelsError, FuelCallResponse, Provider, Tokenizable};
+use fuels::tx::{Receipt, UniqueIdentifier};
+
+use hyperlane_core::{ChainCommunicationError, ChainResult, RevertReason, TxOutcome, H256, U256};
+
+/// Send a contract call like `ContractCallHandler::call`, which does not
+/// return the id of the transaction it sends, and return the id along with
+/// the result.
+pub(crate) async fn send_call<D>(
+    provider: &Provider,
+    call: ContractCallHandler<D>,
+) -> ChainResult<(H256, Result<FuelCallResponse<D>, FuelsError>)>
+where
+    D: Tokenizable + Debug,
+{
+    let executable = call
+        .get_executable_call()
+        .await
+        .map_err(ChainCommunicationError::from_other)?;
+    let txid = H256::from(*executable.tx.id());
+    let result = executable
+        .execute(provider)
+        .await
+        .and_then(|receipts| call.get_response(receipts));
+    Ok((txid, result))
+}
+
+/// The outcome of a contract call sent in transaction `txid`. Reverted calls
+/// are returned as an unexecuted outcome rather than an error, like on other
+/// chains.
+pub(crate) fn tx_outcome<D>(
+    txid: H256,
+    result: Result<FuelCallResponse<D>, FuelsError>,
+    gas_price: u64,
+) -> ChainResult<TxOutcome> {
+    match result {
+        Ok(response) => Ok(TxOutcome {
+            txid,
+            executed: true,
+            gas_used: U256::from(response.gas_used),
+            effective_gas_price: Some(U256::from(gas_price)),
+            ..Default::default()
+        }),
+        Err(FuelsError::RevertTransactionError(reason, receipts)) => Ok(TxOutcome {
+            txid,
+            executed: false,
+            gas_used: U256::from(gas_used(&receipts)),
+            effective_gas_price: Some(U256::from(gas_price)),
+            revert_reason: Some(RevertReason::Message(reason)),
+            ..Default::default()
+        }),
+        Err(err) => Err(ChainCommunicationError::from_other(err)),
+    }
+}
+
+/// The gas used by a script according to its receipts
+pub(crate) fn gas_used(receipts: &[Receipt]) -> u64 {
+    receipts
+        .iter()
+        .find_map(|receipt| match receipt {
+            Receipt::ScriptResult { gas_used, .. } => Some(*gas_used),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Convert an amount to the u64 Fuel uses for gas and native tokens
+pub(crate) fn to_u64(amount: U256) -> ChainResult<u64> {
+    u64::try_from(amount).map_err(ChainCommunicationError::from_other)
+}
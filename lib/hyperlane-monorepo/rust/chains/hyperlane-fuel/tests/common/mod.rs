@@ -0,0 +1,39 @@
+//! Helpers for the integration tests against a local fuel-core node.
+//!
+//! These need the `fuel-core` binary on the path and the compiled Sway
+//! contracts, whose paths are read from environment variables. Run them with
+//! `cargo test -p hyperlane-fuel -- --ignored`.
+
+use fuels::prelude::{Contract, StorageConfiguration, TxParameters, WalletUnlocked, BASE_ASSET_ID};
+use fuels::test_helpers::{setup_single_asset_coins, setup_test_client};
+
+use hyperlane_core::{ContractLocator, HyperlaneDomain, KnownHyperlaneDomain};
+use hyperlane_fuel::{prelude::*, ConnectionConf};
+
+/// Start a node with a funded wallet
+pub async fn start_node() -> (ConnectionConf, WalletUnlocked) {
+    let mut wallet = WalletUnlocked::new_random(None);
+    let coins = setup_single_asset_coins(wallet.address(), BASE_ASSET_ID, 1, 1_000_000_000);
+    let (client, address) = setup_test_client(coins, vec![], None, None).await;
+    wallet.set_provider(fuels::prelude::Provider::new(client));
+    (ConnectionConf::new(format!("http://{address}")), wallet)
+}
+
+/// Deploy the contract whose binary path is in the environment variable
+/// `binary_var`
+pub async fn deploy(wallet: &WalletUnlocked, binary_var: &str) -> ContractLocator {
+    let binary = std::env::var(binary_var).unwrap_or_else(|_| panic!("{binary_var} is not set"));
+    let contract_id = Contract::deploy(
+        &binary,
+        wallet,
+        TxParameters::default(),
+        StorageConfiguration::default(),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("Failed to deploy {binary}: {e}"));
+
+    ContractLocator {
+        domain: HyperlaneDomain::Known(KnownHyperlaneDomain::FuelTest1),
+        address: contract_id.into_h256(),
+    }
+}
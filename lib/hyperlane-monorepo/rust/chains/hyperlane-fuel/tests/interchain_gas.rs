@@ -0,0 +1,37 @@
+//! Integration tests of the IGP against a local fuel-core node, see `common`.
+
+use hyperlane_core::{
+    Finality, Indexer, InterchainGasPaymaster, InterchainGasPaymasterIndexer, H256, U256,
+};
+use hyperlane_fuel::{FuelInterchainGasPaymaster, FuelInterchainGasPaymasterIndexer};
+
+mod common;
+
+#[tokio::test]
+#[ignore = "requires fuel-core and the compiled IGP contract"]
+async fn pays_for_and_indexes_gas() {
+    let (conf, wallet) = common::start_node().await;
+    let locator = common::deploy(&wallet, "FUEL_IGP_BIN").await;
+    let igp = FuelInterchainGasPaymaster::new(&conf, locator.clone(), wallet).unwrap();
+    let indexer =
+        FuelInterchainGasPaymasterIndexer::new(&conf, locator, Finality::default()).unwrap();
+
+    let gas_amount = U256::from(100_000);
+    let payment = igp.quote_gas_payment(2000, gas_amount).await.unwrap();
+    let message_id = H256::repeat_byte(1);
+    let outcome = igp
+        .pay_for_gas(message_id, 2000, gas_amount, payment, H256::repeat_byte(2))
+        .await
+        .unwrap();
+    assert!(outcome.executed);
+    assert_eq!(igp.balance().await.unwrap(), payment);
+
+    let tip = indexer.get_finalized_block_number().await.unwrap();
+    let payments = indexer.fetch_gas_payments(0, tip).await.unwrap();
+    assert_eq!(payments.len(), 1);
+    assert_eq!(payments[0].payment.message_id, message_id);
+    assert_eq!(payments[0].payment.payment, payment);
+
+    assert!(igp.claim(None).await.unwrap().executed);
+    assert_eq!(igp.balance().await.unwrap(), U256::zero());
+}
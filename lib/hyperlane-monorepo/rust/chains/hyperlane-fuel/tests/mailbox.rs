@@ -0,0 +1,106 @@
+//! Integration tests of the mailbox against a local fuel-core node, see
+//! `common`.
+
+use fuels::prelude::WalletUnlocked;
+
+use hyperlane_core::{
+    ContractLocator, Finality, HyperlaneChain, HyperlaneContract, HyperlaneMessage,
+    HyperlaneProvider, Indexer, Mailbox, MailboxIndexer, H256, U256,
+};
+use hyperlane_fuel::{ConnectionConf, FuelMailbox, FuelMailboxIndexer};
+
+mod common;
+
+/// Start a node with a funded wallet and deploy the mailbox to it
+async fn deploy_mailbox() -> (ConnectionConf, ContractLocator, WalletUnlocked) {
+    let (conf, wallet) = common::start_node().await;
+    let locator = common::deploy(&wallet, "FUEL_MAILBOX_BIN").await;
+    (conf, locator, wallet)
+}
+
+#[tokio::test]
+#[ignore = "requires fuel-core and the compiled mailbox contract"]
+async fn dispatches_and_indexes_messages() {
+    let (conf, locator, wallet) = deploy_mailbox().await;
+    let mailbox = FuelMailbox::new(&conf, locator.clone(), wallet).unwrap();
+    let indexer = FuelMailboxIndexer::new(&conf, locator, Finality::default()).unwrap();
+
+    assert_eq!(mailbox.count().await.unwrap(), 0);
+    let recipient = H256::repeat_byte(2);
+    let (message, outcome) = mailbox.dispatch(2000, recipient, &[1, 2, 3]).await.unwrap();
+    assert!(outcome.executed);
+    let txn = mailbox
+        .provider()
+        .get_txn_by_hash(&outcome.txid)
+        .await
+        .unwrap();
+    assert_eq!(txn.recipient, Some(mailbox.address()));
+    assert_eq!(message.nonce, 0);
+    assert_eq!(message.destination, 2000);
+    assert_eq!(message.recipient, recipient);
+    assert_eq!(message.body, vec![1, 2, 3]);
+    assert_eq!(mailbox.count().await.unwrap(), 1);
+    assert!(!mailbox.delivered(message.id()).await.unwrap());
+
+    let tip = indexer.get_finalized_block_number().await.unwrap();
+    let messages = indexer.fetch_sorted_messages(0, tip).await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0.id(), message.id());
+    assert!(indexer
+        .fetch_delivered_messages(0, tip)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+#[ignore = "requires fuel-core and the compiled mailbox contract"]
+async fn checkpoints_follow_dispatches() {
+    let (conf, locator, wallet) = deploy_mailbox().await;
+    let mailbox = FuelMailbox::new(&conf, locator, wallet).unwrap();
+
+    mailbox
+        .dispatch(2000, H256::repeat_byte(2), &[1])
+        .await
+        .unwrap();
+    mailbox
+        .dispatch(2000, H256::repeat_byte(2), &[2])
+        .await
+        .unwrap();
+
+    let checkpoint = mailbox
+        .latest_checkpoint(Finality::default())
+        .await
+        .unwrap();
+    assert_eq!(checkpoint.index, 1);
+    assert_eq!(checkpoint.mailbox_address, mailbox.address());
+    assert_ne!(mailbox.default_ism().await.unwrap(), H256::zero());
+}
+
+#[tokio::test]
+#[ignore = "requires fuel-core and the compiled mailbox contract"]
+async fn rejects_messages_with_invalid_metadata() {
+    let (conf, locator, wallet) = deploy_mailbox().await;
+    let mailbox = FuelMailbox::new(&conf, locator, wallet).unwrap();
+    let message = HyperlaneMessage {
+        origin: 1000,
+        destination: mailbox.domain().id(),
+        recipient: mailbox.address(),
+        body: vec![1, 2, 3],
+        ..Default::default()
+    };
+
+    // Empty metadata does not verify against the default ISM
+    assert!(mailbox.process_estimate_costs(&message, &[]).await.is_err());
+    let outcome = mailbox.process(&message, &[], None).await.unwrap();
+    assert!(!outcome.executed);
+    assert!(outcome.revert_reason.is_some());
+    assert_ne!(outcome.txid, H256::zero());
+    assert!(!mailbox.delivered(message.id()).await.unwrap());
+
+    // Fuel gas limits are u64s, larger ones are rejected before sending
+    assert!(mailbox
+        .process(&message, &[], Some(U256::MAX))
+        .await
+        .is_err());
+}
@@ -0,0 +1,51 @@
+//! Integration tests of the multisig ISM against a local fuel-core node, see
+//! `common`.
+
+use hyperlane_core::accumulator::merkle::MerkleTree;
+use hyperlane_core::accumulator::TREE_DEPTH;
+use hyperlane_core::{
+    Checkpoint, HyperlaneChain, HyperlaneMessage, HyperlaneProvider, MultisigIsm,
+    MultisigSignedCheckpoint, H256,
+};
+use hyperlane_fuel::FuelMultisigIsm;
+
+mod common;
+
+#[tokio::test]
+#[ignore = "requires fuel-core and the compiled multisig ISM contract"]
+async fn reads_validators_and_threshold() {
+    let (conf, wallet) = common::start_node().await;
+    let locator = common::deploy(&wallet, "FUEL_MULTISIG_ISM_BIN").await;
+    let ism = FuelMultisigIsm::new(&conf, locator.clone(), wallet).unwrap();
+
+    // No validators are enrolled for any origin in a freshly deployed ISM
+    let message = HyperlaneMessage {
+        origin: 1000,
+        ..Default::default()
+    };
+    let (validators, threshold) = ism.validators_and_threshold(&message).await.unwrap();
+    assert!(validators.is_empty());
+    assert_eq!(threshold, 0);
+
+    // Metadata for an ISM without validators carries the checkpoint and proof
+    // only
+    let tree = MerkleTree::create(&[message.id()], TREE_DEPTH);
+    let proof = tree.prove_against_current(0);
+    let checkpoint = MultisigSignedCheckpoint {
+        checkpoint: Checkpoint {
+            mailbox_address: H256::repeat_byte(3),
+            mailbox_domain: message.origin,
+            root: tree.hash(),
+            index: 0,
+        },
+        signatures: vec![],
+    };
+    let metadata = ism.format_metadata(&validators, threshold, &checkpoint, &proof);
+    assert_eq!(metadata.len(), 32 + 4 + 32 + 32 * TREE_DEPTH + 1);
+    assert_eq!(&metadata[..32], tree.hash().as_bytes());
+    assert_eq!(&metadata[36..68], &[3; 32]);
+    assert_eq!(&metadata[68..100], proof.path[0].as_bytes());
+    assert_eq!(metadata[68 + 32 * TREE_DEPTH], 0);
+
+    assert!(ism.provider().is_contract(&locator.address).await.unwrap());
+}
@@ -0,0 +1,53 @@
+//! Integration tests of the provider against a local fuel-core node, see
+//! `common`.
+
+use hyperlane_core::{
+    Finality, HyperlaneDomain, HyperlaneProvider, Indexer, KnownHyperlaneDomain, Mailbox,
+    MailboxIndexer, H256,
+};
+use hyperlane_fuel::{FuelMailbox, FuelMailboxIndexer, FuelProvider};
+
+mod common;
+
+#[tokio::test]
+#[ignore = "requires fuel-core and the compiled mailbox contract"]
+async fn fetches_blocks_and_transactions() {
+    let (conf, wallet) = common::start_node().await;
+    let locator = common::deploy(&wallet, "FUEL_MAILBOX_BIN").await;
+    let mailbox = FuelMailbox::new(&conf, locator.clone(), wallet).unwrap();
+    let indexer = FuelMailboxIndexer::new(&conf, locator.clone(), Finality::default()).unwrap();
+    let provider = FuelProvider::new(
+        &conf,
+        HyperlaneDomain::Known(KnownHyperlaneDomain::FuelTest1),
+    )
+    .unwrap();
+
+    mailbox
+        .dispatch(2000, H256::repeat_byte(2), &[1])
+        .await
+        .unwrap();
+    let tip = indexer.get_finalized_block_number().await.unwrap();
+    let (_, meta) = indexer
+        .fetch_sorted_messages(0, tip)
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    let block = provider.get_block_by_hash(&meta.block_hash).await.unwrap();
+    assert_eq!(block.hash, meta.block_hash);
+    assert_eq!(block.number, meta.block_number);
+    assert!(block.timestamp > 0);
+
+    let txn = provider
+        .get_txn_by_hash(&meta.transaction_hash)
+        .await
+        .unwrap();
+    assert_eq!(txn.hash, meta.transaction_hash);
+    assert_eq!(txn.recipient, Some(locator.address));
+    let receipt = txn.receipt.unwrap();
+    assert!(receipt.gas_used > 0.into());
+
+    assert!(provider.is_contract(&locator.address).await.unwrap());
+    assert!(!provider.is_contract(&H256::repeat_byte(3)).await.unwrap());
+}
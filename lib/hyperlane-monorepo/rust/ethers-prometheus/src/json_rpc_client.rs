@@ -8,7 +8,7 @@ use async_trait::async_trait;
 use derive_builder::Builder;
 use ethers::prelude::JsonRpcClient;
 use maplit::hashmap;
-use prometheus::{CounterVec, IntCounterVec};
+use prometheus::{CounterVec, GaugeVec, IntCounterVec, IntGaugeVec};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
@@ -48,6 +48,95 @@ pub struct JsonRpcClientMetrics {
     ///   might still be an "error" but not one with the transport layer.
     #[builder(setter(into, strip_option), default)]
     request_duration_seconds: Option<CounterVec>,
+
+    /// Health score a fallback provider gives this node, lower is better.
+    /// - `provider_node`: node this is connecting to, e.g. `alchemy.com`,
+    ///   `quicknode.pro`, or `localhost:8545`.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the node is for.
+    #[builder(setter(into, strip_option), default)]
+    provider_health_score: Option<GaugeVec>,
+
+    /// Number of blocks this node is behind the highest block reported by the
+    /// other nodes of its fallback provider.
+    /// - `provider_node`: node this is connecting to.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the node is for.
+    #[builder(setter(into, strip_option), default)]
+    provider_block_lag: Option<IntGaugeVec>,
+
+    /// Whether this node is the one its fallback provider sends requests to
+    /// first, either `0` or `1`.
+    /// - `provider_node`: node this is connecting to.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the node is for.
+    #[builder(setter(into, strip_option), default)]
+    provider_selected: Option<IntGaugeVec>,
+
+    /// Responses which disagreed with the value the quorum agreed on.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the request was made on.
+    /// - `method`: request method string.
+    /// - `provider_node`: node which disagreed.
+    #[builder(setter(into, strip_option), default)]
+    quorum_disagreements: Option<IntCounterVec>,
+
+    /// Requests for which no quorum was reached.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the request was made on.
+    /// - `method`: request method string.
+    #[builder(setter(into, strip_option), default)]
+    quorum_failures: Option<IntCounterVec>,
+
+    /// Requests answered from the cache of immutable responses.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the request was made on.
+    /// - `method`: request method string.
+    /// - `store`: `memory` or `db` depending on where the response was found.
+    #[builder(setter(into, strip_option), default)]
+    rpc_cache_hits: Option<IntCounterVec>,
+
+    /// Cacheable requests which had to be sent to a node.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the request was made on.
+    /// - `method`: request method string.
+    #[builder(setter(into, strip_option), default)]
+    rpc_cache_misses: Option<IntCounterVec>,
+
+    /// Size in bytes of the responses cached in memory.
+    /// - `chain`: chain name (or chain id if the name is unknown) of the chain
+    ///   the responses are for.
+    #[builder(setter(into, strip_option), default)]
+    rpc_cache_size_bytes: Option<IntGaugeVec>,
+}
+
+impl JsonRpcClientMetrics {
+    /// Counter for responses which disagreed with their quorum, for use by
+    /// quorum providers.
+    pub fn quorum_disagreements(&self) -> Option<&IntCounterVec> {
+        self.quorum_disagreements.as_ref()
+    }
+
+    /// Counter for requests which did not reach a quorum, for use by quorum
+    /// providers.
+    pub fn quorum_failures(&self) -> Option<&IntCounterVec> {
+        self.quorum_failures.as_ref()
+    }
+
+    /// Counter for requests answered from the response cache.
+    pub fn rpc_cache_hits(&self) -> Option<&IntCounterVec> {
+        self.rpc_cache_hits.as_ref()
+    }
+
+    /// Counter for cacheable requests which were not cached yet.
+    pub fn rpc_cache_misses(&self) -> Option<&IntCounterVec> {
+        self.rpc_cache_misses.as_ref()
+    }
+
+    /// Gauge for the size of the in-memory response cache.
+    pub fn rpc_cache_size_bytes(&self) -> Option<&IntGaugeVec> {
+        self.rpc_cache_size_bytes.as_ref()
+    }
 }
 
 /// Expected label names for the metric.
@@ -61,6 +150,45 @@ pub const REQUEST_DURATION_SECONDS_LABELS: &[&str] =
 /// Help string for the metric.
 pub const REQUEST_DURATION_SECONDS_HELP: &str = "Total number of seconds spent making requests";
 
+/// Expected label names for the `provider_health_score`, `provider_block_lag`
+/// and `provider_selected` metrics.
+pub const PROVIDER_HEALTH_LABELS: &[&str] = &["provider_node", "chain"];
+/// Help string for the metric.
+pub const PROVIDER_HEALTH_SCORE_HELP: &str =
+    "Health score a fallback provider gives the node, lower is better";
+/// Help string for the metric.
+pub const PROVIDER_BLOCK_LAG_HELP: &str =
+    "Number of blocks the node is behind the other nodes of its fallback provider";
+/// Help string for the metric.
+pub const PROVIDER_SELECTED_HELP: &str =
+    "Whether the node is the one its fallback provider sends requests to first";
+
+/// Expected label names for the metric.
+pub const QUORUM_DISAGREEMENTS_LABELS: &[&str] = &["chain", "method", "provider_node"];
+/// Help string for the metric.
+pub const QUORUM_DISAGREEMENTS_HELP: &str =
+    "Responses which disagreed with the value the quorum agreed on";
+
+/// Expected label names for the metric.
+pub const QUORUM_FAILURES_LABELS: &[&str] = &["chain", "method"];
+/// Help string for the metric.
+pub const QUORUM_FAILURES_HELP: &str = "Requests for which no quorum was reached";
+
+/// Expected label names for the metric.
+pub const RPC_CACHE_HITS_LABELS: &[&str] = &["chain", "method", "store"];
+/// Help string for the metric.
+pub const RPC_CACHE_HITS_HELP: &str = "Requests answered from the cache of immutable responses";
+
+/// Expected label names for the metric.
+pub const RPC_CACHE_MISSES_LABELS: &[&str] = &["chain", "method"];
+/// Help string for the metric.
+pub const RPC_CACHE_MISSES_HELP: &str = "Cacheable requests which had to be sent to a node";
+
+/// Expected label names for the metric.
+pub const RPC_CACHE_SIZE_BYTES_LABELS: &[&str] = &["chain"];
+/// Help string for the metric.
+pub const RPC_CACHE_SIZE_BYTES_HELP: &str = "Size in bytes of the responses cached in memory";
+
 /// Configuration for the prometheus JsonRpcClioent. This can be loaded via
 /// serde.
 #[derive(Default, Clone, Debug)]
@@ -141,6 +269,20 @@ impl<C> PrometheusJsonRpcClient<C> {
     pub fn inner(&self) -> &C {
         &self.inner
     }
+
+    /// Report the health of this node as judged by a fallback provider.
+    pub fn report_health(&self, score: f64, block_lag: u64, selected: bool) {
+        let labels = [self.config.node_host(), self.config.chain_name()];
+        if let Some(gauge) = &self.metrics.provider_health_score {
+            gauge.with_label_values(&labels).set(score);
+        }
+        if let Some(gauge) = &self.metrics.provider_block_lag {
+            gauge.with_label_values(&labels).set(block_lag as i64);
+        }
+        if let Some(gauge) = &self.metrics.provider_selected {
+            gauge.with_label_values(&labels).set(selected as i64);
+        }
+    }
 }
 
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
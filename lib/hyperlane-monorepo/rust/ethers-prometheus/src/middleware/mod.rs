@@ -160,6 +160,11 @@ pub const WALLET_BALANCE_LABELS: &[&str] = &[
 /// Help string for the metric.
 pub const WALLET_BALANCE_HELP: &str = "Current balance of eth and other tokens in the `tokens` map for the wallet addresses in the `wallets` set";
 
+/// Expected label names for the `websocket_reconnects` metric.
+pub const WEBSOCKET_RECONNECTS_LABELS: &[&str] = &["chain", "result"];
+/// Help string for the metric.
+pub const WEBSOCKET_RECONNECTS_HELP: &str = "Number of attempts to reconnect a dropped websocket";
+
 /// Container for all the relevant middleware metrics.
 #[derive(Clone, Builder)]
 pub struct MiddlewareMetrics {
@@ -256,6 +261,22 @@ pub struct MiddlewareMetrics {
     /// - `token_name`: Full name of the token.
     #[builder(setter(into, strip_option), default)]
     wallet_balance: Option<GaugeVec>,
+
+    /// Number of attempts to reconnect a dropped websocket. These are reported
+    /// by the websocket transport rather than the middleware itself.
+    /// - `chain`: the chain name (or chain ID if the name is unknown) of the
+    ///   chain the websocket is connected to.
+    /// - `result`: `success` or `failure`.
+    #[builder(setter(into, strip_option), default)]
+    websocket_reconnects: Option<IntCounterVec>,
+}
+
+impl MiddlewareMetrics {
+    /// Counter for websocket reconnection attempts, for use by the websocket
+    /// transport underneath the middleware.
+    pub fn websocket_reconnects(&self) -> Option<&IntCounterVec> {
+        self.websocket_reconnects.as_ref()
+    }
 }
 
 /// An ethers-rs middleware that instruments calls with prometheus metrics. To
@@ -1,58 +1,185 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use eyre::Result;
+use once_cell::sync::Lazy;
 use tokio::time::sleep;
+use tracing::{error, info, warn};
 
-use hyperlane_core::{ChainResult, Indexer, SyncBlockRangeCursor};
+use hyperlane_core::db::HyperlaneDB;
+use hyperlane_core::{
+    ChainCommunicationError, ChainResult, FailureKind, HyperlaneDomain, Indexer,
+    SyncBlockRangeCursor,
+};
+
+use crate::contract_sync::schema::CursorContractSyncDB;
+
+/// The chunk size which last worked on each domain, keyed by domain id.
+///
+/// It is shared by all cursors on the domain, including those indexing
+/// different contracts, since the limit is set by the chain's provider rather
+/// than by the contract being indexed. Cursors given a db also persist it
+/// there, so after a restart they resume from the learnt size instead of
+/// relearning the provider's limit from its first rejections.
+static CHUNK_SIZES: Lazy<Mutex<HashMap<u32, Arc<AtomicU32>>>> = Lazy::new(Default::default);
+
+/// Configuration of a `RateLimitedSyncBlockRangeCursor`
+#[derive(Debug, Clone)]
+pub struct CursorConf {
+    /// The number of blocks to query at once, until the chain's effective
+    /// chunk size is known
+    pub chunk_size: u32,
+    /// The smallest chunk size to shrink to
+    pub min_chunk_size: u32,
+    /// The largest chunk size to grow to
+    pub max_chunk_size: u32,
+    /// Empty ranges fetched quicker than this grow the chunk size
+    pub fast_response: Duration,
+    /// How long to wait between ranges while catching up to the tip
+    pub catching_up_interval: Duration,
+    /// How long to wait between ranges once caught up to the tip
+    pub caught_up_interval: Duration,
+    /// How long after updating the tip we may be caught up with it
+    pub tip_max_age: Duration,
+    /// How long to wait after failing to get the tip or after being rate
+    /// limited
+    pub error_interval: Duration,
+}
+
+impl Default for CursorConf {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1999,
+            min_chunk_size: 10,
+            max_chunk_size: 1999,
+            fast_response: Duration::from_secs(1),
+            catching_up_interval: Duration::from_secs(1),
+            caught_up_interval: Duration::from_secs(10),
+            tip_max_age: Duration::from_secs(30),
+            error_interval: Duration::from_secs(10),
+        }
+    }
+}
 
 /// Tool for handling the logic of what the next block range that should be
 /// queried is and also handling rate limiting. Rate limiting is automatically
 /// performed by `next_range`.
+///
+/// The size of the ranges adapts to the provider: it is halved whenever the
+/// provider rejects a range for being too large, and grows again when ranges
+/// are fetched quickly without finding any events.
 pub struct RateLimitedSyncBlockRangeCursor<I> {
     indexer: I,
+    domain: HyperlaneDomain,
+    db: Option<HyperlaneDB>,
+    conf: CursorConf,
+    chunk_size: Arc<AtomicU32>,
     tip: u32,
     last_tip_update: Instant,
-    chunk_size: u32,
     from: u32,
+    last_range: Option<(u32, u32)>,
+    /// How long to wait before the next range because the last one failed
+    backoff: Duration,
 }
 
 impl<I> RateLimitedSyncBlockRangeCursor<I>
 where
     I: Indexer,
 {
-    /// Construct a new contract sync helper.
-    pub async fn new(indexer: I, chunk_size: u32, initial_height: u32) -> Result<Self> {
+    /// Construct a new contract sync helper. If a `db` is given, the chunk
+    /// size learnt for the domain is persisted to it and the cursor starts
+    /// from the stored size, unless another cursor on the domain has already
+    /// learnt a newer one.
+    pub async fn new(
+        indexer: I,
+        domain: HyperlaneDomain,
+        db: Option<HyperlaneDB>,
+        conf: CursorConf,
+        initial_height: u32,
+    ) -> Result<Self> {
         let tip = indexer.get_finalized_block_number().await?;
+        let chunk_size = CHUNK_SIZES
+            .lock()
+            .unwrap()
+            .entry(domain.id())
+            .or_insert_with(|| {
+                let stored = db
+                    .as_ref()
+                    .and_then(|db| db.retrieve_chunk_size(domain.id()));
+                Arc::new(AtomicU32::new(stored.unwrap_or(conf.chunk_size)))
+            })
+            .clone();
         Ok(Self {
             indexer,
-            tip,
+            domain,
+            db,
+            conf,
             chunk_size,
+            tip,
             last_tip_update: Instant::now(),
             from: initial_height,
+            last_range: None,
+            backoff: Duration::ZERO,
         })
     }
 
+    fn chunk_size(&self) -> u32 {
+        self.chunk_size
+            .load(Ordering::Relaxed)
+            .clamp(self.conf.min_chunk_size, self.conf.max_chunk_size)
+    }
+
+    fn set_chunk_size(&self, chunk_size: u32) {
+        let chunk_size = chunk_size.clamp(self.conf.min_chunk_size, self.conf.max_chunk_size);
+        let previous = self.chunk_size.swap(chunk_size, Ordering::Relaxed);
+        if previous != chunk_size {
+            info!(domain = %self.domain, previous, chunk_size, "Adjusted block range chunk size");
+            if let Some(db) = &self.db {
+                if let Err(err) = db.store_chunk_size(self.domain.id(), chunk_size) {
+                    warn!(domain = %self.domain, error = %err, "Failed to persist block range chunk size");
+                }
+            }
+        }
+    }
+
+    /// How long to wait after a failed request before the next one. Invalid
+    /// configuration is logged as an error since retrying cannot fix it.
+    fn backoff_after(&self, error: &ChainCommunicationError) -> Duration {
+        match error.kind() {
+            FailureKind::RateLimited => self.conf.error_interval * 2,
+            FailureKind::InvalidConfig => {
+                error!(domain = %self.domain, error = %error, "Indexing failed due to invalid configuration");
+                self.conf.error_interval
+            }
+            FailureKind::Transient => self.conf.catching_up_interval,
+            _ => Duration::ZERO,
+        }
+    }
+
     /// Wait based on how close we are to the tip and update the tip,
     /// i.e. the highest block we may scrape.
     async fn rate_limit(&mut self) -> ChainResult<()> {
-        if self.from + self.chunk_size < self.tip {
+        sleep(std::mem::take(&mut self.backoff)).await;
+        if self.from + self.chunk_size() < self.tip {
             // If doing the full chunk wouldn't exceed the already known tip,
             // we don't necessarily need to fetch the new tip. Sleep a tiny bit
             // so that we can catch up to the tip relatively quickly.
-            sleep(Duration::from_secs(1)).await;
+            sleep(self.conf.catching_up_interval).await;
             Ok(())
         } else {
             // We are close to the tip.
-            if self.last_tip_update.elapsed() < Duration::from_secs(30) {
+            if self.last_tip_update.elapsed() < self.conf.tip_max_age {
                 // Sleep a little longer because we have caught up.
-                sleep(Duration::from_secs(10)).await;
+                sleep(self.conf.caught_up_interval).await;
             } else {
                 // We are probably not caught up yet. This would happen if we
                 // started really far behind so now it is very likely the tip
                 // has moved a significant distance. We don't want to wait in
                 // this case any more than we normally would.
-                sleep(Duration::from_secs(1)).await;
+                sleep(self.conf.catching_up_interval).await;
             }
 
             match self.indexer.get_finalized_block_number().await {
@@ -64,7 +191,7 @@ where
                 }
                 Err(e) => {
                     // we are failing to make a basic query, we should wait before retrying.
-                    sleep(Duration::from_secs(10)).await;
+                    sleep(self.backoff_after(&e).max(self.conf.error_interval)).await;
                     Err(e)
                 }
             }
@@ -80,13 +207,189 @@ impl<I: Indexer> SyncBlockRangeCursor for RateLimitedSyncBlockRangeCursor<I> {
 
     async fn next_range(&mut self) -> ChainResult<(u32, u32)> {
         self.rate_limit().await?;
-        let to = u32::min(self.tip, self.from + self.chunk_size);
-        let from = to.saturating_sub(self.chunk_size);
+        let chunk_size = self.chunk_size();
+        let to = u32::min(self.tip, self.from + chunk_size);
+        let from = to.saturating_sub(chunk_size);
         self.from = to + 1;
+        self.last_range = Some((from, to));
         Ok((from, to))
     }
 
     fn backtrack(&mut self, start_from: u32) {
         self.from = u32::min(start_from, self.from);
     }
+
+    fn is_caught_up(&self) -> bool {
+        self.from > self.tip
+    }
+
+    fn range_fetched(&mut self, events: usize, elapsed: Duration) {
+        let Some((from, to)) = self.last_range.take() else {
+            return;
+        };
+        let chunk_size = self.chunk_size();
+        // Only ranges of the full chunk size tell us whether it may grow
+        if events == 0 && elapsed < self.conf.fast_response && to - from >= chunk_size {
+            self.set_chunk_size(
+                chunk_size
+                    .saturating_add(chunk_size / 2)
+                    .max(chunk_size + 1),
+            );
+        }
+    }
+
+    fn range_failed(&mut self, error: &ChainCommunicationError) {
+        let Some((from, to)) = self.last_range.take() else {
+            return;
+        };
+        self.backtrack(from);
+        if error.kind() == FailureKind::RangeTooLarge {
+            warn!(domain = %self.domain, from, to, error = %error, "Provider rejected block range, shrinking it");
+            self.set_chunk_size((to - from) / 2);
+        } else {
+            self.backoff = self.backoff_after(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use hyperlane_core::db::DB;
+    use hyperlane_core::{HyperlaneDomainProtocol, HyperlaneDomainType};
+    use hyperlane_test::mocks::indexer::MockHyperlaneIndexer;
+
+    use super::*;
+
+    fn conf() -> CursorConf {
+        CursorConf {
+            chunk_size: 100,
+            min_chunk_size: 10,
+            max_chunk_size: 200,
+            fast_response: Duration::from_secs(1),
+            catching_up_interval: Duration::ZERO,
+            caught_up_interval: Duration::ZERO,
+            tip_max_age: Duration::ZERO,
+            error_interval: Duration::ZERO,
+        }
+    }
+
+    /// A domain for a single test, since chunk sizes are shared by all
+    /// cursors on a domain
+    fn domain(domain_id: u32) -> HyperlaneDomain {
+        HyperlaneDomain::Unknown {
+            domain_id,
+            chain_name: format!("cursortest{domain_id}"),
+            domain_type: HyperlaneDomainType::LocalTestChain,
+            domain_protocol: HyperlaneDomainProtocol::Mock,
+        }
+    }
+
+    async fn cursor_with_db(
+        domain_id: u32,
+        db: Option<HyperlaneDB>,
+    ) -> RateLimitedSyncBlockRangeCursor<MockHyperlaneIndexer> {
+        let mut indexer = MockHyperlaneIndexer::new();
+        indexer
+            .expect__get_finalized_block_number()
+            .returning(|| Ok(10_000));
+        RateLimitedSyncBlockRangeCursor::new(indexer, domain(domain_id), db, conf(), 0)
+            .await
+            .unwrap()
+    }
+
+    async fn cursor(domain_id: u32) -> RateLimitedSyncBlockRangeCursor<MockHyperlaneIndexer> {
+        cursor_with_db(domain_id, None).await
+    }
+
+    fn error(message: &str) -> ChainCommunicationError {
+        ChainCommunicationError::from_other(io::Error::new(
+            io::ErrorKind::Other,
+            message.to_owned(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn shrinks_rejected_ranges() {
+        let mut cursor = cursor(900_001).await;
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 100));
+
+        cursor.range_failed(&error("query returned more than 10000 results"));
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 50));
+
+        // Other errors retry the same range
+        cursor.range_failed(&error("connection reset"));
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 50));
+
+        // Errors which merely mention a block range do not shrink it
+        cursor.range_failed(&error("block range extends beyond current head block"));
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 50));
+
+        for _ in 0..10 {
+            cursor.range_failed(&error("Block range too large"));
+            cursor.next_range().await.unwrap();
+        }
+        assert_eq!(cursor.chunk_size(), 10);
+    }
+
+    #[tokio::test]
+    async fn backs_off_when_rate_limited() {
+        let mut cursor = cursor(900_002).await;
+        cursor.conf.error_interval = Duration::from_millis(50);
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 100));
+
+        cursor.range_failed(&error("429 Too Many Requests"));
+        assert_eq!(cursor.backoff, Duration::from_millis(100));
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 100));
+        assert_eq!(cursor.backoff, Duration::ZERO);
+
+        cursor.range_failed(&error("something unexpected"));
+        assert_eq!(cursor.backoff, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn grows_on_fast_empty_ranges() {
+        let mut cursor = cursor(900_003).await;
+        assert_eq!(cursor.next_range().await.unwrap(), (0, 100));
+        cursor.range_fetched(0, Duration::from_millis(10));
+        assert_eq!(cursor.next_range().await.unwrap(), (101, 251));
+
+        // Slow or non-empty ranges leave the size alone
+        cursor.range_fetched(0, Duration::from_secs(2));
+        assert_eq!(cursor.next_range().await.unwrap(), (252, 402));
+        cursor.range_fetched(3, Duration::from_millis(10));
+        assert_eq!(cursor.next_range().await.unwrap(), (403, 553));
+
+        cursor.range_fetched(0, Duration::from_millis(10));
+        assert_eq!(cursor.chunk_size(), 200);
+    }
+
+    #[tokio::test]
+    async fn shares_chunk_size_per_domain() {
+        let mut first = cursor(900_004).await;
+        first.next_range().await.unwrap();
+        first.range_failed(&error("block range is too wide"));
+
+        let second = cursor(900_004).await;
+        assert_eq!(second.chunk_size(), 50);
+        let other = cursor(900_005).await;
+        assert_eq!(other.chunk_size(), 100);
+    }
+
+    #[tokio::test]
+    async fn persists_chunk_size_per_domain() {
+        let db = HyperlaneDB::new("mailbox", DB::in_memory());
+        let mut cursor = cursor_with_db(900_006, Some(db.clone())).await;
+        cursor.next_range().await.unwrap();
+        cursor.range_failed(&error("block range is too wide"));
+        assert_eq!(db.retrieve_chunk_size(900_006), Some(50));
+        assert_eq!(db.retrieve_chunk_size(900_007), None);
+
+        // A cursor on a domain not yet seen by this process, as after a
+        // restart, starts from the stored size
+        db.store_chunk_size(900_007, 30).unwrap();
+        let restarted = cursor_with_db(900_007, Some(db)).await;
+        assert_eq!(restarted.chunk_size(), 30);
+    }
 }
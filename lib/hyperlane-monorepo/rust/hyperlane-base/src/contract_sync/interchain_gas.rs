@@ -1,9 +1,12 @@
+use std::time::Instant;
+
 use tokio::task::JoinHandle;
 use tracing::{info, info_span, instrument::Instrumented, warn, Instrument};
 
 use hyperlane_core::{InterchainGasPaymasterIndexer, SyncBlockRangeCursor};
 
 use crate::contract_sync::cursor::RateLimitedSyncBlockRangeCursor;
+use crate::contract_sync::LiveEvents;
 use crate::{contract_sync::schema::InterchainGasPaymasterContractSyncDB, ContractSync};
 
 const GAS_PAYMENTS_LABEL: &str = "gas_payments";
@@ -36,7 +39,9 @@ where
                 .map_or(config_initial_height, |b| b + 1);
             RateLimitedSyncBlockRangeCursor::new(
                 indexer.clone(),
-                self.index_settings.chunk_size(),
+                self.domain.clone(),
+                Some(db.clone()),
+                self.index_settings.cursor_conf(),
                 initial_height,
             )
         };
@@ -48,8 +53,33 @@ where
             info!(from = start_block, "[GasPayments]: resuming indexer");
             indexed_height.set(start_block as i64);
 
+            // Once caught up, gas payments are also received through a
+            // subscription if the indexer supports it. Block ranges are still
+            // indexed to fill in anything the subscription missed.
+            let mut live_payments = LiveEvents::new("[GasPayments]");
+
             loop {
-                let (from, to) = match cursor.next_range().await {
+                live_payments
+                    .subscribe_if_caught_up(cursor.is_caught_up(), || {
+                        indexer.subscribe_gas_payments()
+                    })
+                    .await;
+
+                let next_range = {
+                    let next_range = cursor.next_range();
+                    tokio::pin!(next_range);
+                    loop {
+                        tokio::select! {
+                            range = &mut next_range => break range,
+                            Some(payment) = live_payments.next() => {
+                                if db.process_gas_payment(&payment)? {
+                                    stored_messages.inc();
+                                }
+                            }
+                        }
+                    }
+                };
+                let (from, to) = match next_range {
                     Ok(range) => range,
                     Err(err) => {
                         warn!(error = %err, "[GasPayments]: failed to get next block range");
@@ -57,7 +87,18 @@ where
                     }
                 };
 
-                let gas_payments = indexer.fetch_gas_payments(from, to).await?;
+                let fetch_start = Instant::now();
+                let gas_payments = match indexer.fetch_gas_payments(from, to).await {
+                    Ok(gas_payments) => {
+                        cursor.range_fetched(gas_payments.len(), fetch_start.elapsed());
+                        gas_payments
+                    }
+                    Err(err) => {
+                        warn!(from, to, error = %err, "[GasPayments]: failed to fetch block range");
+                        cursor.range_failed(&err);
+                        continue;
+                    }
+                };
 
                 info!(
                     from,
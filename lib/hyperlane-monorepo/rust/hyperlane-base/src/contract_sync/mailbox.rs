@@ -1,11 +1,16 @@
+use std::time::Instant;
+
 use tracing::{debug, info, info_span, warn};
 use tracing::{instrument::Instrumented, Instrument};
 
+use hyperlane_core::db::HyperlaneDB;
 use hyperlane_core::{
-    Indexer, KnownHyperlaneDomain, ListValidity, MailboxIndexer, SyncBlockRangeCursor,
+    HyperlaneDomain, HyperlaneMessage, Indexer, KnownHyperlaneDomain, ListValidity, LogMeta,
+    MailboxIndexer, SyncBlockRangeCursor,
 };
 
 use crate::contract_sync::last_message::validate_message_continuity;
+use crate::contract_sync::LiveEvents;
 use crate::{contract_sync::schema::MailboxContractSyncDB, ContractSync};
 
 const MESSAGES_LABEL: &str = "messages";
@@ -46,7 +51,9 @@ where
                 .map_or(config_initial_height, |b| b + 1);
             create_cursor(
                 indexer.clone(),
-                self.index_settings.chunk_size(),
+                self.domain.clone(),
+                db.clone(),
+                self.index_settings.cursor_conf(),
                 initial_height,
             )
         };
@@ -98,6 +105,13 @@ where
         // Note this means we only handle this case upon observing messages in some
         // range [C,D] that indicate a previously indexed range may have
         // missed some messages.
+        //
+        // Once caught up, messages are also received through a subscription if the
+        // indexer supports it, so they are stored as soon as they are final rather
+        // than when the next block range is indexed. Only messages that are a valid
+        // continuation are stored this way; anything else, as well as everything
+        // dispatched while the subscription was down, is left to the block range
+        // indexing above.
         tokio::spawn(async move {
             let mut cursor = cursor.await?;
 
@@ -106,9 +120,45 @@ where
             info!(from = start_block, "[Messages]: resuming indexer from latest valid message range start block");
             indexed_height.set(start_block as i64);
 
+            let mut live_messages = LiveEvents::new("[Messages]");
+
+            let store_live_message = |message: HyperlaneMessage, meta: LogMeta| -> eyre::Result<()> {
+                let last_nonce = db.retrieve_latest_nonce()?;
+                if last_nonce.map_or(false, |nonce| message.nonce <= nonce) {
+                    return Ok(());
+                }
+                if !matches!(validate_message_continuity(last_nonce, &[&message]), ListValidity::Valid) {
+                    debug!(last_nonce = ?last_nonce, nonce = message.nonce, "[Messages]: subscribed message is not a valid continuation, leaving it to block range indexing");
+                    return Ok(());
+                }
+
+                db.store_messages(std::slice::from_ref(&message))?;
+                stored_messages.inc();
+                let dst = KnownHyperlaneDomain::try_from(message.destination).map(|d| d.as_str()).unwrap_or("unknown");
+                message_nonce
+                    .with_label_values(&["dispatch", &chain_name, dst])
+                    .set(message.nonce as i64);
+                debug!(nonce = message.nonce, block = meta.block_number, "[Messages]: stored message from subscription");
+                Ok(())
+            };
+
             loop {
+                live_messages
+                    .subscribe_if_caught_up(cursor.is_caught_up(), || indexer.subscribe_messages())
+                    .await;
+
                 let start_block = cursor.current_position();
-                let (from, to) = match cursor.next_range().await {
+                let next_range = {
+                    let next_range = cursor.next_range();
+                    tokio::pin!(next_range);
+                    loop {
+                        tokio::select! {
+                            range = &mut next_range => break range,
+                            Some((message, meta)) = live_messages.next() => store_live_message(message, meta)?,
+                        }
+                    }
+                };
+                let (from, to) = match next_range {
                     Ok(range) => range,
                     Err(err) => {
                         warn!(error = %err, "[Messages]: failed to get next block range");
@@ -116,12 +166,18 @@ where
                     }
                 };
 
-                let mut sorted_messages: Vec<_> = indexer
-                    .fetch_sorted_messages(from, to)
-                    .await?
-                    .into_iter()
-                    .map(|(msg, _)| msg)
-                    .collect();
+                let fetch_start = Instant::now();
+                let mut sorted_messages: Vec<_> = match indexer.fetch_sorted_messages(from, to).await {
+                    Ok(messages) => {
+                        cursor.range_fetched(messages.len(), fetch_start.elapsed());
+                        messages.into_iter().map(|(msg, _)| msg).collect()
+                    }
+                    Err(err) => {
+                        warn!(from, to, error = %err, "[Messages]: failed to fetch block range");
+                        cursor.range_failed(&err);
+                        continue;
+                    }
+                };
 
                 info!(from, to, message_count = sorted_messages.len(), "[Messages]: indexed block range");
 
@@ -210,12 +266,15 @@ static mut MOCK_CURSOR: Option<hyperlane_test::mocks::cursor::MockSyncBlockRange
 #[cfg_attr(test, allow(unused_variables))]
 async fn create_cursor<I: Indexer>(
     indexer: I,
-    chunk_size: u32,
+    domain: HyperlaneDomain,
+    db: HyperlaneDB,
+    conf: crate::CursorConf,
     initial_height: u32,
 ) -> eyre::Result<impl SyncBlockRangeCursor> {
     #[cfg(not(test))]
     {
-        crate::RateLimitedSyncBlockRangeCursor::new(indexer, chunk_size, initial_height).await
+        crate::RateLimitedSyncBlockRangeCursor::new(indexer, domain, Some(db), conf, initial_height)
+            .await
     }
     #[cfg(test)]
     {
@@ -226,10 +285,12 @@ async fn create_cursor<I: Indexer>(
 
 #[cfg(test)]
 mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
     use std::time::Duration;
 
     use eyre::eyre;
+    use futures_util::{stream, StreamExt};
     use mockall::predicate::eq;
     use mockall::*;
     use tokio::select;
@@ -254,21 +315,41 @@ mod test {
         static ref TEST_MTX: Mutex<()> = Mutex::new(());
     }
 
+    fn message_gen(nonce: u32) -> HyperlaneMessage {
+        HyperlaneMessage {
+            version: 0,
+            nonce,
+            origin: 1000,
+            destination: 2000,
+            sender: H256::from([10; 32]),
+            recipient: H256::from([11; 32]),
+            body: [10u8; 5].to_vec(),
+        }
+    }
+
+    fn meta() -> LogMeta {
+        LogMeta {
+            address: Default::default(),
+            block_number: 0,
+            block_hash: Default::default(),
+            transaction_hash: Default::default(),
+            transaction_index: 0,
+            log_index: Default::default(),
+        }
+    }
+
+    fn sync_metrics() -> ContractSyncMetrics {
+        let metrics = Arc::new(
+            CoreMetrics::new("contract_sync_test", None, prometheus::Registry::new())
+                .expect("could not make metrics"),
+        );
+        ContractSyncMetrics::new(metrics)
+    }
+
     #[tokio::test]
     async fn handles_missing_rpc_messages() {
+        let _lock = TEST_MTX.lock().await;
         test_utils::run_test_db(|db| async move {
-            let message_gen = |nonce: u32| -> HyperlaneMessage {
-                HyperlaneMessage {
-                    version: 0,
-                    nonce,
-                    origin: 1000,
-                    destination: 2000,
-                    sender: H256::from([10; 32]),
-                    recipient: H256::from([11; 32]),
-                    body: [10u8; 5].to_vec(),
-                }
-            };
-
             let messages = (0..10).map(message_gen).collect::<Vec<HyperlaneMessage>>();
             let m0 = messages[0].clone();
             let m1 = messages[1].clone();
@@ -277,19 +358,13 @@ mod test {
             let m4 = messages[4].clone();
             let m5 = messages[5].clone();
 
-            let meta = || LogMeta {
-                address: Default::default(),
-                block_number: 0,
-                block_hash: Default::default(),
-                transaction_hash: Default::default(),
-                transaction_index: 0,
-                log_index: Default::default(),
-            };
-
             let latest_valid_message_range_start_block = 100;
 
             let mut mock_indexer = MockHyperlaneIndexer::new();
             let mut mock_cursor = MockSyncBlockRangeCursor::new();
+            // The cursor never catches up, so no subscription is set up
+            mock_cursor.expect__is_caught_up().returning(|| false);
+            mock_cursor.expect__range_fetched().returning(|_, _| ());
             {
                 let mut seq = Sequence::new();
 
@@ -397,14 +472,8 @@ mod test {
                 .unwrap();
 
             let indexer = Arc::new(mock_indexer);
-            let metrics = Arc::new(
-                CoreMetrics::new("contract_sync_test", None, prometheus::Registry::new())
-                    .expect("could not make metrics"),
-            );
             unsafe { MOCK_CURSOR = Some(mock_cursor) };
 
-            let sync_metrics = ContractSyncMetrics::new(metrics);
-
             let contract_sync = ContractSync::new(
                 HyperlaneDomain::Known(KnownHyperlaneDomain::Test1),
                 hyperlane_db.clone(),
@@ -412,8 +481,9 @@ mod test {
                 IndexSettings {
                     from: Some("0".to_string()),
                     chunk: Some("19".to_string()),
+                    ..Default::default()
                 },
-                sync_metrics,
+                sync_metrics(),
             );
 
             let sync_task = contract_sync.sync_dispatched_messages();
@@ -444,4 +514,105 @@ mod test {
         })
         .await
     }
+
+    #[tokio::test]
+    async fn falls_back_to_block_ranges_when_subscription_drops() {
+        let _lock = TEST_MTX.lock().await;
+        test_utils::run_test_db(|db| async move {
+            let m0 = message_gen(0);
+            let m1 = message_gen(1);
+
+            let mut mock_indexer = MockHyperlaneIndexer::new();
+            let mut mock_cursor = MockSyncBlockRangeCursor::new();
+            mock_cursor.expect__is_caught_up().returning(|| true);
+            mock_cursor.expect__current_position().returning(|| 1);
+            mock_cursor.expect__range_fetched().returning(|_, _| ());
+
+            let subscriptions = Arc::new(AtomicUsize::new(0));
+            {
+                let mut seq = Sequence::new();
+
+                // The subscription delivers m0 and is then dropped
+                let live = m0.clone();
+                let count = subscriptions.clone();
+                mock_indexer
+                    .expect__subscribe_messages()
+                    .times(1)
+                    .in_sequence(&mut seq)
+                    .return_once(move || {
+                        count.fetch_add(1, Ordering::SeqCst);
+                        Ok(Some(stream::iter([(live, meta())]).boxed()))
+                    });
+                // so m1 is only found by indexing the next block range
+                mock_cursor.expect__next_range().times(1).return_once(|| {
+                    Box::pin(async {
+                        sleep(Duration::from_millis(50)).await;
+                        Ok((1, 1))
+                    })
+                });
+                let ranged = vec![(m0.clone(), meta()), (m1.clone(), meta())];
+                mock_indexer
+                    .expect__fetch_sorted_messages()
+                    .times(1)
+                    .with(eq(1), eq(1))
+                    .return_once(move |_, _| Ok(ranged));
+                // Resubscribing finds that subscriptions are no longer
+                // supported, after which it is not attempted again
+                let count = subscriptions.clone();
+                mock_indexer
+                    .expect__subscribe_messages()
+                    .times(1)
+                    .in_sequence(&mut seq)
+                    .return_once(move || {
+                        count.fetch_add(1, Ordering::SeqCst);
+                        Ok(None)
+                    });
+                mock_cursor.expect__next_range().returning(|| {
+                    Box::pin(async {
+                        sleep(Duration::from_secs(100)).await;
+                        Ok((2, 2))
+                    })
+                });
+            }
+
+            let hyperlane_db = HyperlaneDB::new("mailbox_1", db);
+            unsafe { MOCK_CURSOR = Some(mock_cursor) };
+
+            let contract_sync = ContractSync::new(
+                HyperlaneDomain::Known(KnownHyperlaneDomain::Test1),
+                hyperlane_db.clone(),
+                Arc::new(mock_indexer),
+                IndexSettings {
+                    from: Some("0".to_string()),
+                    chunk: Some("19".to_string()),
+                    ..Default::default()
+                },
+                sync_metrics(),
+            );
+
+            let sync_task = contract_sync.sync_dispatched_messages();
+            let test_pass_fut = timeout(Duration::from_secs(5), async move {
+                let mut interval = interval(Duration::from_millis(20));
+                loop {
+                    if hyperlane_db.message_by_nonce(0).expect("!db").is_some()
+                        && hyperlane_db.message_by_nonce(1).expect("!db").is_some()
+                        && subscriptions.load(Ordering::SeqCst) == 2
+                    {
+                        break;
+                    }
+                    interval.tick().await;
+                }
+            });
+            let test_result = select! {
+                 err = sync_task => Err(eyre!(
+                    "sync task unexpectedly done before test: {:?}", err.unwrap_err())),
+                 tests_result = test_pass_fut =>
+                   if tests_result.is_ok() { Ok(()) } else { Err(eyre!("timed out")) }
+            };
+            if let Err(err) = test_result {
+                panic!("Test failed: {err}")
+            }
+        })
+        .await
+    }
 }
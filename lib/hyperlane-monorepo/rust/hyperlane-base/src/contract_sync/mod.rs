@@ -1,12 +1,17 @@
 // TODO: Reapply tip buffer
 // TODO: Reapply metrics
 
+use std::future::Future;
+
 pub use cursor::*;
+use futures_util::future::pending;
+use futures_util::StreamExt;
 use hyperlane_core::db::HyperlaneDB;
-use hyperlane_core::HyperlaneDomain;
+use hyperlane_core::{ChainResult, EventStream, HyperlaneDomain};
 pub use interchain_gas::*;
 pub use mailbox::*;
 pub use metrics::ContractSyncMetrics;
+use tracing::{info, warn};
 
 use crate::chains::IndexSettings;
 
@@ -49,3 +54,129 @@ impl<I> ContractSync<I> {
         }
     }
 }
+
+/// Events received through a subscription of the indexer. The subscription is
+/// only set up once the cursor has caught up with the chain and is dropped when
+/// its stream ends, leaving block range indexing to cover the gap until it is
+/// re-established.
+struct LiveEvents<T> {
+    label: &'static str,
+    events: Option<EventStream<T>>,
+    supported: bool,
+}
+
+impl<T> LiveEvents<T> {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            events: None,
+            supported: true,
+        }
+    }
+
+    /// Subscribe if the cursor is caught up and there is no subscription yet.
+    /// Once the indexer reports that it does not support subscriptions this
+    /// is never attempted again.
+    async fn subscribe_if_caught_up<F, Fut>(&mut self, caught_up: bool, subscribe: F)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = ChainResult<Option<EventStream<T>>>>,
+    {
+        if self.events.is_some() || !self.supported || !caught_up {
+            return;
+        }
+        match subscribe().await {
+            Ok(Some(events)) => {
+                info!("{}: caught up, subscribed to new events", self.label);
+                self.events = Some(events);
+            }
+            Ok(None) => self.supported = false,
+            Err(err) => warn!(error = %err, "{}: failed to subscribe to new events", self.label),
+        }
+    }
+
+    /// Wait for the next event delivered by the subscription. Never resolves
+    /// if there is no subscription, and returns `None` once the subscription
+    /// has ended.
+    async fn next(&mut self) -> Option<T> {
+        let Some(events) = &mut self.events else {
+            return pending().await;
+        };
+        let event = events.next().await;
+        if event.is_none() {
+            warn!(
+                "{}: subscription closed, relying on block range indexing",
+                self.label
+            );
+            self.events = None;
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_util::{stream, FutureExt};
+
+    use ethers::providers::ProviderError;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn subscribes_only_once_caught_up() {
+        let mut live = LiveEvents::new("[Test]");
+        let subscriptions = AtomicUsize::new(0);
+        let subscribe = || async {
+            subscriptions.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(stream::iter([1, 2]).boxed()))
+        };
+
+        live.subscribe_if_caught_up(false, subscribe).await;
+        assert_eq!(subscriptions.load(Ordering::SeqCst), 0);
+        assert!(live.next().now_or_never().is_none());
+
+        live.subscribe_if_caught_up(true, subscribe).await;
+        live.subscribe_if_caught_up(true, subscribe).await;
+        assert_eq!(subscriptions.load(Ordering::SeqCst), 1);
+        assert_eq!(live.next().await, Some(1));
+        assert_eq!(live.next().await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_polling_when_subscription_ends() {
+        let mut live = LiveEvents::new("[Test]");
+        live.subscribe_if_caught_up(true, || async { Ok(Some(stream::iter([1]).boxed())) })
+            .await;
+        assert_eq!(live.next().await, Some(1));
+
+        // The closed subscription is dropped, after which waiting for events
+        // never resolves so only block ranges are indexed
+        assert_eq!(live.next().await, None);
+        assert!(live.next().now_or_never().is_none());
+
+        // and it is re-established once caught up again
+        live.subscribe_if_caught_up(true, || async { Ok(Some(stream::iter([2]).boxed())) })
+            .await;
+        assert_eq!(live.next().await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn retries_failed_subscriptions_but_not_unsupported_ones() {
+        let mut live = LiveEvents::<u32>::new("[Test]");
+        live.subscribe_if_caught_up(true, || async {
+            Err(ProviderError::CustomError("connection dropped".into()).into())
+        })
+        .await;
+        assert!(live.next().now_or_never().is_none());
+
+        live.subscribe_if_caught_up(true, || async { Ok(None) })
+            .await;
+        live.subscribe_if_caught_up(true, || async {
+            panic!("subscribed although subscriptions are not supported")
+        })
+        .await;
+        assert!(live.next().now_or_never().is_none());
+    }
+}
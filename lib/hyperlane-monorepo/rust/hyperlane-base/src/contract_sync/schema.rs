@@ -9,6 +9,9 @@ use hyperlane_core::db::HyperlaneDB;
 /// valid range.
 static LATEST_VALID_MESSAGE_RANGE_START_BLOCK: &str = "latest_valid_message_range_start_block";
 static LATEST_INDEXED_GAS_PAYMENT_BLOCK: &str = "latest_indexed_gas_payment_block";
+/// The block range chunk size which last worked for a domain, keyed by the
+/// domain id.
+static CHUNK_SIZE: &str = "chunk_size_";
 
 pub(crate) trait MailboxContractSyncDB {
     fn store_latest_valid_message_range_start_block(&self, block_num: u32) -> Result<(), DbError>;
@@ -41,3 +44,19 @@ impl InterchainGasPaymasterContractSyncDB for HyperlaneDB {
             .expect("db failure")
     }
 }
+
+pub(crate) trait CursorContractSyncDB {
+    fn store_chunk_size(&self, domain: u32, chunk_size: u32) -> Result<(), DbError>;
+    fn retrieve_chunk_size(&self, domain: u32) -> Option<u32>;
+}
+
+impl CursorContractSyncDB for HyperlaneDB {
+    fn store_chunk_size(&self, domain: u32, chunk_size: u32) -> Result<(), DbError> {
+        self.store_keyed_encodable(CHUNK_SIZE, &domain, &chunk_size)
+    }
+
+    fn retrieve_chunk_size(&self, domain: u32) -> Option<u32> {
+        self.retrieve_keyed_decodable(CHUNK_SIZE, &domain)
+            .expect("db failure")
+    }
+}
@@ -1,5 +1,4 @@
 use std::fmt::Debug;
-use std::num::NonZeroU64;
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -10,8 +9,9 @@ use tracing::{info_span, Instrument};
 
 use hyperlane_core::db::HyperlaneDB;
 use hyperlane_core::{
-    ChainResult, Checkpoint, HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneMessage,
-    HyperlaneProvider, Mailbox, MailboxIndexer, TxCostEstimate, TxOutcome, H256, U256,
+    ChainResult, Checkpoint, Finality, HyperlaneChain, HyperlaneContract, HyperlaneDomain,
+    HyperlaneMessage, HyperlaneProvider, InterchainGasPaymaster, Mailbox, MailboxIndexer,
+    TxCostEstimate, TxOutcome, H256, U256,
 };
 
 use crate::chains::IndexSettings;
@@ -101,8 +101,8 @@ impl Mailbox for CachingMailbox {
         self.mailbox.delivered(id).await
     }
 
-    async fn latest_checkpoint(&self, maybe_lag: Option<NonZeroU64>) -> ChainResult<Checkpoint> {
-        self.mailbox.latest_checkpoint(maybe_lag).await
+    async fn latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint> {
+        self.mailbox.latest_checkpoint(finality).await
     }
 
     /// Fetch the current default interchain security module value
@@ -114,6 +114,17 @@ impl Mailbox for CachingMailbox {
         self.mailbox.recipient_ism(recipient).await
     }
 
+    async fn dispatch(
+        &self,
+        destination_domain: u32,
+        recipient: H256,
+        body: &[u8],
+    ) -> ChainResult<(HyperlaneMessage, TxOutcome)> {
+        self.mailbox
+            .dispatch(destination_domain, recipient, body)
+            .await
+    }
+
     async fn process(
         &self,
         message: &HyperlaneMessage,
@@ -151,3 +162,120 @@ impl HyperlaneContract for CachingMailbox {
         self.mailbox.address()
     }
 }
+
+/// A message which was dispatched and paid for
+#[derive(Debug, Clone)]
+pub struct PaidDispatch {
+    /// The dispatched message
+    pub message: HyperlaneMessage,
+    /// The outcome of the dispatch transaction
+    pub dispatch: TxOutcome,
+    /// The amount paid to the interchain gas paymaster
+    pub payment: U256,
+    /// The outcome of the gas payment transaction
+    pub payment_outcome: TxOutcome,
+}
+
+/// Dispatch a message through `mailbox` and pay `paymaster` the quoted amount
+/// for `gas_amount` of destination gas for it. Any overpayment is refunded to
+/// `refund_address`.
+pub async fn dispatch_and_pay(
+    mailbox: &dyn Mailbox,
+    paymaster: &dyn InterchainGasPaymaster,
+    destination_domain: u32,
+    recipient: H256,
+    body: &[u8],
+    gas_amount: U256,
+    refund_address: H256,
+) -> ChainResult<PaidDispatch> {
+    let (message, dispatch) = mailbox
+        .dispatch(destination_domain, recipient, body)
+        .await?;
+    let payment = paymaster
+        .quote_gas_payment(destination_domain, gas_amount)
+        .await?;
+    let payment_outcome = paymaster
+        .pay_for_gas(
+            message.id(),
+            destination_domain,
+            gas_amount,
+            payment,
+            refund_address,
+        )
+        .await?;
+    Ok(PaidDispatch {
+        message,
+        dispatch,
+        payment,
+        payment_outcome,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_test::mocks::{MockInterchainGasPaymasterContract, MockMailboxContract};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn pays_for_dispatched_message() {
+        let outcome = |txid: u64| TxOutcome {
+            txid: H256::from_low_u64_be(txid),
+            executed: true,
+            ..Default::default()
+        };
+        let message = HyperlaneMessage {
+            nonce: 7,
+            destination: 2,
+            recipient: H256::repeat_byte(1),
+            body: b"hello".to_vec(),
+            ..Default::default()
+        };
+        let message_id = message.id();
+
+        let mut mailbox = MockMailboxContract::new();
+        let dispatched = message.clone();
+        mailbox
+            .expect__dispatch()
+            .withf(|destination, recipient, body| {
+                *destination == 2 && *recipient == H256::repeat_byte(1) && body == b"hello"
+            })
+            .return_once(move |_, _, _| Ok((dispatched, outcome(1))));
+
+        let mut paymaster = MockInterchainGasPaymasterContract::new();
+        paymaster
+            .expect__quote_gas_payment()
+            .withf(|destination, gas_amount| {
+                *destination == 2 && *gas_amount == U256::from(100_000)
+            })
+            .return_once(|_, _| Ok(U256::from(42)));
+        paymaster
+            .expect__pay_for_gas()
+            .withf(
+                move |id, destination, gas_amount, payment, refund_address| {
+                    *id == message_id
+                        && *destination == 2
+                        && *gas_amount == U256::from(100_000)
+                        && *payment == U256::from(42)
+                        && *refund_address == H256::repeat_byte(2)
+                },
+            )
+            .return_once(|_, _, _, _, _| Ok(outcome(2)));
+
+        let paid = dispatch_and_pay(
+            &mailbox,
+            &paymaster,
+            2,
+            H256::repeat_byte(1),
+            b"hello",
+            U256::from(100_000),
+            H256::repeat_byte(2),
+        )
+        .await
+        .unwrap();
+        assert_eq!(paid.message.id(), message_id);
+        assert_eq!(paid.dispatch.txid, H256::from_low_u64_be(1));
+        assert_eq!(paid.payment, U256::from(42));
+        assert_eq!(paid.payment_outcome.txid, H256::from_low_u64_be(2));
+    }
+}
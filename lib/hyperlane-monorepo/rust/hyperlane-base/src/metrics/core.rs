@@ -41,9 +41,16 @@ pub struct CoreMetrics {
     submitter_queue_length: IntGaugeVec,
 
     messages_processed_count: IntCounterVec,
+    message_reverts: IntCounterVec,
+
+    transaction_gas_used: IntCounterVec,
+    transaction_cost: CounterVec,
 
     latest_checkpoint: IntGaugeVec,
 
+    checkpoint_syncer_writes: IntCounterVec,
+    checkpoint_syncer_lag: IntGaugeVec,
+
     /// Set of metrics that tightly wrap the JsonRpcClient for use with the
     /// quorum provider.
     json_rpc_client_metrics: OnceCell<JsonRpcClientMetrics>,
@@ -153,6 +160,56 @@ impl CoreMetrics {
             registry
         )?;
 
+        let message_reverts = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("message_reverts"),
+                "Number of times delivering a message reverted, by reason",
+                const_labels_ref
+            ),
+            &["origin", "remote", "stage", "reason"],
+            registry
+        )?;
+
+        let transaction_gas_used = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("transaction_gas_used"),
+                "Gas used by transactions submitted by this process",
+                const_labels_ref
+            ),
+            &["chain", "operation"],
+            registry
+        )?;
+
+        let transaction_cost = register_counter_vec_with_registry!(
+            opts!(
+                namespaced!("transaction_cost"),
+                "Native tokens paid for transactions submitted by this process",
+                const_labels_ref
+            ),
+            &["chain", "operation"],
+            registry
+        )?;
+
+        let checkpoint_syncer_writes = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("checkpoint_syncer_writes"),
+                "Number of writes to each checkpoint syncer backend",
+                const_labels_ref
+            ),
+            &["backend", "operation", "result"],
+            registry
+        )?;
+
+        let checkpoint_syncer_lag = register_int_gauge_vec_with_registry!(
+            opts!(
+                namespaced!("checkpoint_syncer_lag"),
+                "Number of checkpoint indices each checkpoint syncer backend is behind the latest",
+                const_labels_ref
+            ),
+            &["backend"],
+            registry
+        )?;
+
         Ok(Self {
             agent_name: for_agent.into(),
             registry,
@@ -168,9 +225,16 @@ impl CoreMetrics {
             submitter_queue_length,
 
             messages_processed_count,
+            message_reverts,
+
+            transaction_gas_used,
+            transaction_cost,
 
             latest_checkpoint,
 
+            checkpoint_syncer_writes,
+            checkpoint_syncer_lag,
+
             json_rpc_client_metrics: OnceCell::new(),
             provider_metrics: OnceCell::new(),
         })
@@ -309,6 +373,26 @@ impl CoreMetrics {
         self.latest_checkpoint.clone()
     }
 
+    /// Writes to the backends of a replicated checkpoint syncer
+    ///
+    /// Labels:
+    /// - `backend`: Name of the backend in the checkpoint syncer config.
+    /// - `operation`: `checkpoint` or `announcement`.
+    /// - `result`: `success` or `failure`.
+    pub fn checkpoint_syncer_writes(&self) -> IntCounterVec {
+        self.checkpoint_syncer_writes.clone()
+    }
+
+    /// How many checkpoint indices each backend of a replicated checkpoint
+    /// syncer is behind the latest index of any backend. Backends which
+    /// missed writes are not backfilled.
+    ///
+    /// Labels:
+    /// - `backend`: Name of the backend in the checkpoint syncer config.
+    pub fn checkpoint_syncer_lag(&self) -> IntGaugeVec {
+        self.checkpoint_syncer_lag.clone()
+    }
+
     /// Measure of the queue lengths in Submitter instances
     ///
     /// Labels:
@@ -336,6 +420,39 @@ impl CoreMetrics {
         self.messages_processed_count.clone()
     }
 
+    /// Number of times estimating or submitting the delivery of a message
+    /// reverted.
+    ///
+    /// Labels:
+    /// - `origin`: Chain the message came from.
+    /// - `remote`: Chain we tried to deliver the message to.
+    /// - `stage`: Either `estimate` or `process`.
+    /// - `reason`: Short form of the decoded revert reason, e.g. the
+    ///   `require` message or custom error name.
+    pub fn message_reverts(&self) -> IntCounterVec {
+        self.message_reverts.clone()
+    }
+
+    /// Gas used by the transactions this process submitted, including ones
+    /// which reverted.
+    ///
+    /// Labels:
+    /// - `chain`: Chain the transaction was submitted to.
+    /// - `operation`: What the transaction did, e.g. `process` or `announce`.
+    pub fn transaction_gas_used(&self) -> IntCounterVec {
+        self.transaction_gas_used.clone()
+    }
+
+    /// Native tokens paid for the transactions this process submitted, in the
+    /// smallest denomination of the chain's native token.
+    ///
+    /// Labels:
+    /// - `chain`: Chain the transaction was submitted to.
+    /// - `operation`: What the transaction did, e.g. `process` or `announce`.
+    pub fn transaction_cost(&self) -> CounterVec {
+        self.transaction_cost.clone()
+    }
+
     /// Measure of span durations provided by tracing.
     ///
     /// Labels:
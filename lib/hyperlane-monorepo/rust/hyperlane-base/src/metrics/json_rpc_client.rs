@@ -17,5 +17,45 @@ pub(crate) fn create_json_rpc_client_metrics(
             REQUEST_DURATION_SECONDS_HELP,
             REQUEST_DURATION_SECONDS_LABELS,
         )?)
+        .provider_health_score(metrics.new_gauge(
+            "provider_health_score",
+            PROVIDER_HEALTH_SCORE_HELP,
+            PROVIDER_HEALTH_LABELS,
+        )?)
+        .provider_block_lag(metrics.new_int_gauge(
+            "provider_block_lag",
+            PROVIDER_BLOCK_LAG_HELP,
+            PROVIDER_HEALTH_LABELS,
+        )?)
+        .provider_selected(metrics.new_int_gauge(
+            "provider_selected",
+            PROVIDER_SELECTED_HELP,
+            PROVIDER_HEALTH_LABELS,
+        )?)
+        .quorum_disagreements(metrics.new_int_counter(
+            "quorum_disagreements",
+            QUORUM_DISAGREEMENTS_HELP,
+            QUORUM_DISAGREEMENTS_LABELS,
+        )?)
+        .quorum_failures(metrics.new_int_counter(
+            "quorum_failures",
+            QUORUM_FAILURES_HELP,
+            QUORUM_FAILURES_LABELS,
+        )?)
+        .rpc_cache_hits(metrics.new_int_counter(
+            "rpc_cache_hits",
+            RPC_CACHE_HITS_HELP,
+            RPC_CACHE_HITS_LABELS,
+        )?)
+        .rpc_cache_misses(metrics.new_int_counter(
+            "rpc_cache_misses",
+            RPC_CACHE_MISSES_HELP,
+            RPC_CACHE_MISSES_LABELS,
+        )?)
+        .rpc_cache_size_bytes(metrics.new_int_gauge(
+            "rpc_cache_size_bytes",
+            RPC_CACHE_SIZE_BYTES_HELP,
+            RPC_CACHE_SIZE_BYTES_LABELS,
+        )?)
         .build()?)
 }
@@ -51,5 +51,10 @@ pub(crate) fn create_provider_metrics(metrics: &CoreMetrics) -> Result<Middlewar
             WALLET_BALANCE_HELP,
             WALLET_BALANCE_LABELS,
         )?)
+        .websocket_reconnects(metrics.new_int_counter(
+            "websocket_reconnects",
+            WEBSOCKET_RECONNECTS_HELP,
+            WEBSOCKET_RECONNECTS_LABELS,
+        )?)
         .build()?)
 }
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 use ethers::prelude::Selector;
 use eyre::{eyre, Context, Result};
@@ -8,17 +10,19 @@ use ethers_prometheus::middleware::{
     ChainInfo, ContractInfo, PrometheusMiddlewareConf, WalletInfo,
 };
 use hyperlane_core::{
-    ContractLocator, HyperlaneAbi, HyperlaneDomain, HyperlaneDomainProtocol, HyperlaneProvider,
-    HyperlaneSigner, InterchainGasPaymaster, InterchainGasPaymasterIndexer, Mailbox,
-    MailboxIndexer, MultisigIsm, H256,
+    ContractLocator, Finality, FinalityTag, HyperlaneAbi, HyperlaneDomain, HyperlaneDomainProtocol,
+    HyperlaneProvider, HyperlaneSigner, InterchainGasPaymaster, InterchainGasPaymasterIndexer,
+    Mailbox, MailboxIndexer, MultisigIsm, ValidatorAnnounce, H256,
 };
 use hyperlane_ethereum::{
     self as h_eth, BuildableWithProvider, EthereumInterchainGasPaymasterAbi, EthereumMailboxAbi,
+    EthereumValidatorAnnounceAbi, RevertDecoder,
 };
 use hyperlane_fuel::{self as h_fuel, prelude::*};
+use hyperlane_test::mock_chain::{self as h_mock, MockChain};
 
 use crate::settings::signers::BuildableWithSignerConf;
-use crate::{CoreMetrics, SignerConf};
+use crate::{CoreMetrics, CursorConf, SignerConf};
 
 /// A connection to _some_ blockchain.
 ///
@@ -30,6 +34,8 @@ pub enum ChainConf {
     Ethereum(h_eth::ConnectionConf),
     /// Fuel configuration
     Fuel(h_fuel::ConnectionConf),
+    /// In-memory mock chain configuration, for tests
+    Mock(h_mock::ConnectionConf),
 }
 
 impl ChainConf {
@@ -37,6 +43,7 @@ impl ChainConf {
         match self {
             ChainConf::Ethereum(_) => HyperlaneDomainProtocol::Ethereum,
             ChainConf::Fuel(_) => HyperlaneDomainProtocol::Fuel,
+            ChainConf::Mock(_) => HyperlaneDomainProtocol::Mock,
         }
     }
 }
@@ -75,6 +82,8 @@ pub struct CoreContractAddresses {
     pub mailbox: String,
     /// Address of the InterchainGasPaymaster contract
     pub interchain_gas_paymaster: String,
+    /// Address of the ValidatorAnnounce contract
+    pub validator_announce: String,
 }
 
 /// Indexing settings
@@ -84,8 +93,21 @@ pub struct IndexSettings {
     /// The height at which to start indexing the Outbox contract
     pub from: Option<String>,
     /// The number of blocks to query at once at which to start indexing the
-    /// Mailbox contract
+    /// Mailbox contract. The chunk size adapts to the provider, this is only
+    /// where it starts and the most it grows to by default.
     pub chunk: Option<String>,
+    /// The smallest number of blocks to query at once
+    pub minchunk: Option<String>,
+    /// The largest number of blocks to query at once
+    pub maxchunk: Option<String>,
+    /// Seconds to wait between block ranges while catching up to the tip
+    pub catchupinterval: Option<String>,
+    /// Seconds to wait between block ranges once caught up to the tip
+    pub caughtupinterval: Option<String>,
+    /// Seconds after which the tip is assumed to have moved on
+    pub tipmaxage: Option<String>,
+    /// Seconds to wait after failing to get the tip
+    pub errorinterval: Option<String>,
 }
 
 impl IndexSettings {
@@ -104,6 +126,34 @@ impl IndexSettings {
             .and_then(|s| s.parse::<u32>().ok())
             .unwrap_or(1999)
     }
+
+    /// Get the configuration for block range cursors
+    pub fn cursor_conf(&self) -> CursorConf {
+        let parse_u32 = |s: &Option<String>| s.as_ref().and_then(|s| s.parse::<u32>().ok());
+        let parse_secs = |s: &Option<String>| {
+            s.as_ref()
+                .and_then(|s| s.parse::<f64>().ok())
+                .map(Duration::from_secs_f64)
+        };
+        let default = CursorConf::default();
+        let chunk_size = self.chunk_size();
+        CursorConf {
+            chunk_size,
+            min_chunk_size: parse_u32(&self.minchunk)
+                .unwrap_or(default.min_chunk_size)
+                .min(chunk_size),
+            max_chunk_size: parse_u32(&self.maxchunk)
+                .unwrap_or(chunk_size)
+                .max(chunk_size),
+            catching_up_interval: parse_secs(&self.catchupinterval)
+                .unwrap_or(default.catching_up_interval),
+            caught_up_interval: parse_secs(&self.caughtupinterval)
+                .unwrap_or(default.caught_up_interval),
+            tip_max_age: parse_secs(&self.tipmaxage).unwrap_or(default.tip_max_age),
+            error_interval: parse_secs(&self.errorinterval).unwrap_or(default.error_interval),
+            ..default
+        }
+    }
 }
 
 /// A chain setup is a domain ID, an address on that chain (where the mailbox is
@@ -119,6 +169,10 @@ pub struct ChainSetup {
     pub signer: Option<SignerConf>,
     /// Number of blocks until finality
     pub finality_blocks: String,
+    /// Block tag used to determine finality, either `safe` or `finalized`.
+    /// `finality_blocks` is used where the node does not support the tag.
+    #[serde(default)]
+    pub finality_tag: Option<String>,
     /// Addresses of contracts on the chain
     pub addresses: CoreContractAddresses,
     /// The chain connection details
@@ -135,6 +189,10 @@ pub struct ChainSetup {
     /// Settings for event indexing
     #[serde(default)]
     pub index: IndexSettings,
+    /// Comma separated paths to ABIs, or build artifacts containing them,
+    /// whose custom errors are decoded when a call or transaction reverts
+    #[serde(default)]
+    pub revert_abis: Option<String>,
 }
 
 impl ChainSetup {
@@ -150,7 +208,13 @@ impl ChainSetup {
                     .await
             }
 
-            ChainConf::Fuel(_) => todo!(),
+            ChainConf::Fuel(conf) => h_fuel::FuelProvider::new(conf, self.domain()?)
+                .map(|p| Box::new(p) as Box<dyn HyperlaneProvider>)
+                .map_err(Into::into),
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainProvider::new(
+                self.mock_chain(conf)?,
+            )) as Box<dyn HyperlaneProvider>),
         }
         .context("Building provider")
     }
@@ -161,8 +225,15 @@ impl ChainSetup {
 
         match &self.chain {
             ChainConf::Ethereum(conf) => {
-                self.build_ethereum(conf, &locator, metrics, h_eth::MailboxBuilder {})
-                    .await
+                self.build_ethereum(
+                    conf,
+                    &locator,
+                    metrics,
+                    h_eth::MailboxBuilder {
+                        revert_decoder: self.revert_decoder()?,
+                    },
+                )
+                .await
             }
 
             ChainConf::Fuel(conf) => {
@@ -171,6 +242,11 @@ impl ChainSetup {
                     .map(|m| Box::new(m) as Box<dyn Mailbox>)
                     .map_err(Into::into)
             }
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainMailbox::new(
+                self.mock_chain(conf)?,
+                locator.address,
+            )) as Box<dyn Mailbox>),
         }
         .context("Building mailbox")
     }
@@ -189,13 +265,23 @@ impl ChainSetup {
                     &locator,
                     metrics,
                     h_eth::MailboxIndexerBuilder {
-                        finality_blocks: self.finality_blocks(),
+                        finality: self.finality()?,
                     },
                 )
                 .await
             }
 
-            ChainConf::Fuel(_) => todo!(),
+            ChainConf::Fuel(conf) => {
+                h_fuel::FuelMailboxIndexer::new(conf, locator, self.finality()?)
+                    .map(|i| Box::new(i) as Box<dyn MailboxIndexer>)
+                    .map_err(Into::into)
+            }
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainIndexer::new(
+                self.mock_chain(conf)?,
+                locator.address,
+                self.finality()?,
+            )) as Box<dyn MailboxIndexer>),
         }
         .context("Building mailbox indexer")
     }
@@ -219,7 +305,17 @@ impl ChainSetup {
                 .await
             }
 
-            ChainConf::Fuel(_) => todo!(),
+            ChainConf::Fuel(conf) => {
+                let wallet = self.fuel_signer().await?;
+                h_fuel::FuelInterchainGasPaymaster::new(conf, locator, wallet)
+                    .map(|m| Box::new(m) as Box<dyn InterchainGasPaymaster>)
+                    .map_err(Into::into)
+            }
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainInterchainGasPaymaster::new(
+                self.mock_chain(conf)?,
+                locator.address,
+            )) as Box<dyn InterchainGasPaymaster>),
         }
         .context("Building IGP")
     }
@@ -239,13 +335,23 @@ impl ChainSetup {
                     metrics,
                     h_eth::InterchainGasPaymasterIndexerBuilder {
                         mailbox_address: self.addresses.mailbox.parse()?,
-                        finality_blocks: self.finality_blocks(),
+                        finality: self.finality()?,
                     },
                 )
                 .await
             }
 
-            ChainConf::Fuel(_) => todo!(),
+            ChainConf::Fuel(conf) => {
+                h_fuel::FuelInterchainGasPaymasterIndexer::new(conf, locator, self.finality()?)
+                    .map(|i| Box::new(i) as Box<dyn InterchainGasPaymasterIndexer>)
+                    .map_err(Into::into)
+            }
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainIndexer::new(
+                self.mock_chain(conf)?,
+                locator.address,
+                self.finality()?,
+            )) as Box<dyn InterchainGasPaymasterIndexer>),
         }
         .context("Building IGP indexer")
     }
@@ -267,11 +373,44 @@ impl ChainSetup {
                     .await
             }
 
-            ChainConf::Fuel(_) => todo!(),
+            ChainConf::Fuel(conf) => {
+                let wallet = self.fuel_signer().await?;
+                h_fuel::FuelMultisigIsm::new(conf, locator, wallet)
+                    .map(|m| Box::new(m) as Box<dyn MultisigIsm>)
+                    .map_err(Into::into)
+            }
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainMultisigIsm::new(
+                self.mock_chain(conf)?,
+                locator.address,
+            )) as Box<dyn MultisigIsm>),
         }
         .context("Building multisig ISM")
     }
 
+    /// Try to convert the chain setting into a ValidatorAnnounce contract
+    pub async fn build_validator_announce(
+        &self,
+        metrics: &CoreMetrics,
+    ) -> Result<Box<dyn ValidatorAnnounce>> {
+        let locator = self.locator(&self.addresses.validator_announce)?;
+
+        match &self.chain {
+            ChainConf::Ethereum(conf) => {
+                self.build_ethereum(conf, &locator, metrics, h_eth::ValidatorAnnounceBuilder {})
+                    .await
+            }
+
+            ChainConf::Fuel(_) => Err(eyre!("validator announce is not supported on Fuel")),
+
+            ChainConf::Mock(conf) => Ok(Box::new(h_mock::MockChainValidatorAnnounce::new(
+                self.mock_chain(conf)?,
+                locator.address,
+            )) as Box<dyn ValidatorAnnounce>),
+        }
+        .context("Building validator announce")
+    }
+
     /// Get the domain for this chain setup
     pub fn domain(&self) -> Result<HyperlaneDomain> {
         HyperlaneDomain::from_config_strs(&self.domain, &self.name, self.chain.protocol())
@@ -285,6 +424,35 @@ impl ChainSetup {
             .expect("could not parse finality_blocks")
     }
 
+    /// Get the block tag used to determine finality, if any
+    pub fn finality_tag(&self) -> Result<Option<FinalityTag>> {
+        self.finality_tag
+            .as_deref()
+            .map(|tag| tag.parse().context("Invalid finality tag"))
+            .transpose()
+    }
+
+    /// Get how the latest final block of this chain is determined
+    pub fn finality(&self) -> Result<Finality> {
+        Ok(Finality::new(self.finality_blocks(), self.finality_tag()?))
+    }
+
+    /// Get the decoder for revert reasons, including the custom errors of
+    /// all configured ABIs
+    pub fn revert_decoder(&self) -> Result<Arc<RevertDecoder>> {
+        let paths = self.revert_abis.as_deref().unwrap_or_default();
+        paths
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .try_fold(RevertDecoder::default(), |decoder, path| {
+                decoder
+                    .with_abi_file(path)
+                    .with_context(|| format!("Loading revert ABI {path}"))
+            })
+            .map(Arc::new)
+    }
+
     async fn signer<S: BuildableWithSignerConf>(&self) -> Result<Option<S>> {
         if let Some(conf) = &self.signer {
             Ok(Some(conf.build::<S>().await?))
@@ -297,6 +465,12 @@ impl ChainSetup {
         self.signer().await
     }
 
+    /// Get the in-memory chain a mock connection refers to
+    fn mock_chain(&self, conf: &h_mock::ConnectionConf) -> Result<Arc<MockChain>> {
+        let name = conf.chain.as_deref().unwrap_or(&self.name);
+        Ok(MockChain::named(name, self.domain()?))
+    }
+
     async fn fuel_signer(&self) -> Result<fuels::prelude::WalletUnlocked> {
         self.signer().await.and_then(|opt| {
             opt.ok_or_else(|| eyre!("Fuel requires a signer to construct contract instances"))
@@ -344,6 +518,12 @@ impl ChainSetup {
                 functions: functions(EthereumInterchainGasPaymasterAbi::fn_map_owned()),
             });
         }
+        if let Ok(addr) = self.addresses.validator_announce.parse() {
+            cfg.contracts.entry(addr).or_insert_with(|| ContractInfo {
+                name: Some("validator_announce".into()),
+                functions: functions(EthereumValidatorAnnounceAbi::fn_map_owned()),
+            });
+        }
         cfg
     }
 
@@ -358,6 +538,11 @@ impl ChainSetup {
                 .parse::<fuels::tx::ContractId>()
                 .map_err(|e| eyre!("Invalid fuel contract id: {e}"))?
                 .into_h256(),
+            ChainConf::Mock(_) => address
+                .parse::<ethers::types::Address>()
+                .map(Into::into)
+                .or_else(|_| address.parse::<H256>())
+                .context("Invalid mock chain address")?,
         };
 
         Ok(ContractLocator { domain, address })
@@ -86,7 +86,7 @@ pub use chains::{ChainConf, ChainSetup, CoreContractAddresses};
 use hyperlane_core::{
     db::{HyperlaneDB, DB},
     HyperlaneChain, HyperlaneDomain, HyperlaneProvider, InterchainGasPaymaster,
-    InterchainGasPaymasterIndexer, Mailbox, MailboxIndexer, MultisigIsm, H256,
+    InterchainGasPaymasterIndexer, Mailbox, MailboxIndexer, MultisigIsm, ValidatorAnnounce, H256,
 };
 pub use signers::SignerConf;
 
@@ -285,4 +285,5 @@ impl Settings {
     delegate_fn!(build_mailbox -> dyn Mailbox);
     delegate_fn!(build_mailbox_indexer -> dyn MailboxIndexer);
     delegate_fn!(build_provider -> dyn HyperlaneProvider);
+    delegate_fn!(build_validator_announce -> dyn ValidatorAnnounce);
 }
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use ethers::prelude::AwsSigner;
+use ethers::prelude::{AwsSigner, LocalWallet};
 use eyre::{bail, Report};
 use rusoto_core::credential::EnvironmentProvider;
 use rusoto_core::HttpClient;
@@ -27,6 +27,13 @@ pub enum SignerConf {
         /// The AWS region
         region: String,
     },
+    /// An encrypted JSON keystore, as created by `forc wallet` or `geth`
+    Keystore {
+        /// Path to the keystore file
+        path: String,
+        /// The password the keystore is encrypted with
+        password: String,
+    },
     #[serde(other)]
     /// Assume node will sign on RPC calls
     Node,
@@ -71,6 +78,9 @@ impl BuildableWithSignerConf for hyperlane_ethereum::Signers {
                 let signer = AwsSigner::new(client, id, 0).await?;
                 hyperlane_ethereum::Signers::Aws(signer)
             }
+            SignerConf::Keystore { path, password } => {
+                hyperlane_ethereum::Signers::Local(LocalWallet::decrypt_keystore(path, password)?)
+            }
             SignerConf::Node => bail!("Node signer"),
         })
     }
@@ -84,8 +94,57 @@ impl BuildableWithSignerConf for fuels::prelude::WalletUnlocked {
                 let key = key.as_ref().parse()?;
                 fuels::prelude::WalletUnlocked::new_from_private_key(key, None)
             }
+            SignerConf::Keystore { path, password } => {
+                fuels::prelude::WalletUnlocked::load_keystore(path, password, None)?
+            }
             SignerConf::Aws { .. } => bail!("Aws signer is not supported by fuel"),
             SignerConf::Node => bail!("Node signer is not supported by fuel"),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ethers::core::rand::thread_rng;
+    use ethers::prelude::Signer;
+
+    use hyperlane_core::H256;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn builds_signers_from_keystores() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wallet, name) =
+            LocalWallet::new_keystore(dir.path(), &mut thread_rng(), "password", None).unwrap();
+        let keystore = |password: &str| SignerConf::Keystore {
+            path: dir.path().join(&name).to_str().unwrap().to_owned(),
+            password: password.to_owned(),
+        };
+        let hex_key = SignerConf::HexKey {
+            key: HexString::from_string(format!(
+                "{:x}",
+                H256::from_slice(&wallet.signer().to_bytes())
+            ))
+            .unwrap(),
+        };
+
+        let signer: hyperlane_ethereum::Signers = keystore("password").build().await.unwrap();
+        assert_eq!(signer.address(), wallet.address());
+
+        // Fuel wallets decrypt the same keystores
+        let fuel_wallet: fuels::prelude::WalletUnlocked =
+            keystore("password").build().await.unwrap();
+        let fuel_expected: fuels::prelude::WalletUnlocked = hex_key.build().await.unwrap();
+        assert_eq!(fuel_wallet.address(), fuel_expected.address());
+
+        assert!(keystore("wrong")
+            .build::<hyperlane_ethereum::Signers>()
+            .await
+            .is_err());
+        assert!(keystore("wrong")
+            .build::<fuels::prelude::WalletUnlocked>()
+            .await
+            .is_err());
+    }
+}
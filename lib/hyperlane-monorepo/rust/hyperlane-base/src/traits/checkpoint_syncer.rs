@@ -14,8 +14,15 @@ pub trait CheckpointSyncer: Debug + Send + Sync {
     async fn fetch_checkpoint(&self, index: u32) -> Result<Option<SignedCheckpoint>>;
     /// Write the signed checkpoint to this syncer
     async fn write_checkpoint(&self, signed_checkpoint: &SignedCheckpoint) -> Result<()>;
+    /// Attempt to fetch the signed announcement written to this syncer
+    async fn fetch_announcement(&self) -> Result<Option<SignedAnnouncement>>;
     /// Write the signed announcement to this syncer
     async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()>;
     /// Return the announcement storage location for this syncer
     fn announcement_location(&self) -> String;
+    /// Return every storage location this syncer writes to, each of which
+    /// should be announced
+    fn announcement_locations(&self) -> Vec<String> {
+        vec![self.announcement_location()]
+    }
 }
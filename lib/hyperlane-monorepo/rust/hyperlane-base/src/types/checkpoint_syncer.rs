@@ -1,12 +1,18 @@
 use core::str::FromStr;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 use ethers::types::Address;
-use eyre::{Report, Result};
+use eyre::{eyre, Context, Report, Result};
 use prometheus::{IntGauge, IntGaugeVec};
+use rusoto_core::Region;
 
-use crate::S3Storage;
-use crate::{CheckpointSyncer, LocalStorage, MultisigCheckpointSyncer};
+use crate::{
+    CheckpointSyncer, CoreMetrics, HttpStorage, LocalStorage, MultisigCheckpointSyncer,
+    ReplicatedStorage,
+};
+use crate::{S3Credentials, S3Storage};
 
 /// Checkpoint Syncer types
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -21,9 +27,94 @@ pub enum CheckpointSyncerConf {
     S3 {
         /// Bucket name
         bucket: String,
-        /// S3 Region
+        /// S3 Region. Used as the region name when a custom endpoint is set.
         region: String,
+        /// Endpoint of an S3-compatible store such as MinIO or R2. Requests
+        /// are always path-style addressed.
+        endpoint: Option<String>,
+        /// Prefix for all object keys, so several validators can share a
+        /// bucket
+        prefix: Option<String>,
+        /// Credentials for the bucket. Reads are anonymous if not set, and
+        /// writes use credentials from the environment.
+        credentials: Option<S3Credentials>,
+        /// Timeout of a single request in seconds
+        timeout: Option<String>,
+        /// Maximum number of times a failed request is retried
+        maxretries: Option<String>,
     },
+    /// A read-only checkpoint syncer served over HTTP(S)
+    Http {
+        /// Base url the checkpoint files are served from
+        url: String,
+    },
+    /// Writes to several checkpoint syncers, succeeding as long as one of
+    /// them accepts the write, and reads from the first one that can serve
+    /// a request
+    Replicated {
+        /// The backends keyed by a name used in logs and metrics. Backends are
+        /// read from in order of their names.
+        backends: HashMap<String, CheckpointSyncerConf>,
+    },
+}
+
+impl FromStr for CheckpointSyncerConf {
+    type Err = Report;
+
+    /// Parse an announced storage location, e.g.
+    /// `s3://bucket/region[/prefix][?endpoint=url]`, `file://path` or
+    /// `https://host/path`, back into a checkpoint syncer config.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, suffix) = s
+            .split_once("://")
+            .ok_or_else(|| eyre!("Missing storage location prefix in {s}"))?;
+
+        match prefix {
+            "s3" => {
+                let (suffix, endpoint) = match suffix.split_once('?') {
+                    Some((suffix, query)) => {
+                        let endpoint = query
+                            .strip_prefix("endpoint=")
+                            .filter(|endpoint| !endpoint.is_empty())
+                            .ok_or_else(|| eyre!("Invalid s3 storage location query in {s}"))?;
+                        (suffix, Some(endpoint.to_owned()))
+                    }
+                    None => (suffix, None),
+                };
+                let (bucket, region) = suffix
+                    .split_once('/')
+                    .ok_or_else(|| eyre!("Unable to parse s3 storage location {s}"))?;
+                let (region, prefix) = match region.split_once('/') {
+                    Some((region, prefix)) => (region, Some(prefix.to_owned())),
+                    None => (region, None),
+                };
+                // With a custom endpoint the region is only a name
+                if endpoint.is_none() {
+                    Region::from_str(region)
+                        .with_context(|| format!("Invalid s3 region in storage location {s}"))?;
+                }
+                Ok(CheckpointSyncerConf::S3 {
+                    bucket: bucket.into(),
+                    region: region.into(),
+                    endpoint,
+                    prefix,
+                    credentials: None,
+                    timeout: None,
+                    maxretries: None,
+                })
+            }
+            "file" => {
+                if suffix.is_empty() {
+                    return Err(eyre!("Missing path in storage location {s}"));
+                }
+                Ok(CheckpointSyncerConf::LocalStorage {
+                    path: suffix.into(),
+                })
+            }
+            "http" | "https" => Ok(CheckpointSyncerConf::Http { url: s.into() }),
+            _ => Err(eyre!("Unknown storage location prefix `{prefix}`")),
+        }
+    }
 }
 
 impl CheckpointSyncerConf {
@@ -31,25 +122,90 @@ impl CheckpointSyncerConf {
     pub fn build(
         &self,
         latest_index_gauge: Option<IntGauge>,
+    ) -> Result<Box<dyn CheckpointSyncer>, Report> {
+        self.build_with_metrics(latest_index_gauge, None)
+    }
+
+    /// Turn conf info a Checkpoint Syncer, reporting the writes to and lag
+    /// of each backend of a replicated checkpoint syncer to `metrics`
+    pub fn build_with_metrics(
+        &self,
+        latest_index_gauge: Option<IntGauge>,
+        metrics: Option<&CoreMetrics>,
     ) -> Result<Box<dyn CheckpointSyncer>, Report> {
         match self {
             CheckpointSyncerConf::LocalStorage { path } => {
                 Ok(Box::new(LocalStorage::new(path, latest_index_gauge)))
             }
-            CheckpointSyncerConf::S3 { bucket, region } => Ok(Box::new(S3Storage::new(
+            CheckpointSyncerConf::S3 {
                 bucket,
-                region.parse().expect("invalid s3 region"),
-                latest_index_gauge,
-            ))),
+                region,
+                endpoint,
+                prefix,
+                credentials,
+                timeout,
+                maxretries,
+            } => {
+                let region = match endpoint {
+                    Some(endpoint) => Region::Custom {
+                        name: region.clone(),
+                        endpoint: endpoint.clone(),
+                    },
+                    None => region.parse().context("Invalid s3 region")?,
+                };
+                let mut storage = S3Storage::new(bucket, region, latest_index_gauge);
+                if let Some(credentials) = credentials {
+                    storage = storage.with_credentials(credentials.clone());
+                }
+                if let Some(prefix) = prefix {
+                    storage = storage.with_prefix(prefix);
+                }
+                if let Some(timeout) = timeout {
+                    let timeout = timeout.parse().context("Invalid s3 timeout")?;
+                    storage = storage.with_timeout(Duration::from_secs(timeout));
+                }
+                if let Some(max_retries) = maxretries {
+                    storage = storage
+                        .with_max_retries(max_retries.parse().context("Invalid s3 maxretries")?);
+                }
+                Ok(Box::new(storage))
+            }
+            CheckpointSyncerConf::Http { url } => {
+                Ok(Box::new(HttpStorage::new(url, latest_index_gauge)?))
+            }
+            CheckpointSyncerConf::Replicated { backends } => {
+                if backends.is_empty() {
+                    return Err(eyre!("Replicated checkpoint syncer has no backends"));
+                }
+                let mut names: Vec<_> = backends.keys().collect();
+                names.sort();
+                let backends = names
+                    .into_iter()
+                    .map(|name| {
+                        let backend = backends[name]
+                            .build_with_metrics(None, metrics)
+                            .with_context(|| format!("Building checkpoint syncer {name}"))?;
+                        Ok((name.clone(), backend.into()))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let storage = ReplicatedStorage::new(backends, latest_index_gauge);
+                Ok(Box::new(match metrics {
+                    Some(metrics) => storage
+                        .with_write_metrics(metrics.checkpoint_syncer_writes())
+                        .with_lag_metrics(metrics.checkpoint_syncer_lag()),
+                    None => storage,
+                }))
+            }
         }
     }
 }
 
 /// Config for a MultisigCheckpointSyncer
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub struct MultisigCheckpointSyncerConf {
     /// The checkpoint syncer for each valid validator signer address
+    #[serde(default)]
     checkpointsyncers: HashMap<String, CheckpointSyncerConf>,
 }
 
@@ -60,12 +216,156 @@ impl MultisigCheckpointSyncerConf {
         origin: &str,
         validator_checkpoint_index: IntGaugeVec,
     ) -> Result<MultisigCheckpointSyncer, Report> {
+        Ok(MultisigCheckpointSyncer::new(
+            self.build_checkpoint_syncers(origin, validator_checkpoint_index)?,
+        ))
+    }
+
+    /// Get the checkpoint syncer for each configured validator address
+    pub fn build_checkpoint_syncers(
+        &self,
+        origin: &str,
+        validator_checkpoint_index: IntGaugeVec,
+    ) -> Result<HashMap<Address, Arc<dyn CheckpointSyncer>>, Report> {
         let mut checkpoint_syncers = HashMap::new();
         for (key, value) in self.checkpointsyncers.iter() {
             let gauge =
                 validator_checkpoint_index.with_label_values(&[origin, &key.to_lowercase()]);
             checkpoint_syncers.insert(Address::from_str(key)?, value.build(Some(gauge))?.into());
         }
-        Ok(MultisigCheckpointSyncer::new(checkpoint_syncers))
+        Ok(checkpoint_syncers)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_s3_storage_location() {
+        let conf: CheckpointSyncerConf = "s3://hyperlane-validator/us-east-1".parse().unwrap();
+        assert!(matches!(
+            conf,
+            CheckpointSyncerConf::S3 { bucket, region, prefix: None, .. }
+                if bucket == "hyperlane-validator" && region == "us-east-1"
+        ));
+    }
+
+    #[test]
+    fn parses_s3_storage_location_with_prefix() {
+        let conf: CheckpointSyncerConf = "s3://hyperlane-validator/us-east-1/validators/alice"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            conf,
+            CheckpointSyncerConf::S3 { bucket, region, prefix: Some(prefix), .. }
+                if bucket == "hyperlane-validator"
+                    && region == "us-east-1"
+                    && prefix == "validators/alice"
+        ));
+    }
+
+    #[test]
+    fn builds_s3_storage_with_custom_endpoint() {
+        let conf: CheckpointSyncerConf = serde_json::from_value(serde_json::json!({
+            "type": "s3",
+            "bucket": "hyperlane-validator",
+            "region": "minio",
+            "endpoint": "http://127.0.0.1:9000",
+            "prefix": "alice",
+            "credentials": {
+                "type": "static",
+                "accesskeyid": "minioadmin",
+                "secretaccesskey": "minioadmin"
+            },
+            "timeout": "5",
+            "maxretries": "3"
+        }))
+        .unwrap();
+        let syncer = conf.build(None).unwrap();
+        assert_eq!(
+            syncer.announcement_location(),
+            "s3://hyperlane-validator/minio/alice?endpoint=http://127.0.0.1:9000"
+        );
+    }
+
+    #[test]
+    fn parses_s3_storage_location_with_endpoint() {
+        let conf: CheckpointSyncerConf =
+            "s3://hyperlane-validator/minio/alice?endpoint=http://127.0.0.1:9000"
+                .parse()
+                .unwrap();
+        assert!(matches!(
+            conf,
+            CheckpointSyncerConf::S3 {
+                bucket,
+                region,
+                endpoint: Some(endpoint),
+                prefix: Some(prefix),
+                credentials: None,
+                ..
+            } if bucket == "hyperlane-validator"
+                && region == "minio"
+                && endpoint == "http://127.0.0.1:9000"
+                && prefix == "alice"
+        ));
+    }
+
+    #[test]
+    fn parses_file_storage_location() {
+        let conf: CheckpointSyncerConf = "file:///tmp/checkpoints".parse().unwrap();
+        assert!(matches!(
+            conf,
+            CheckpointSyncerConf::LocalStorage { path } if path == "/tmp/checkpoints"
+        ));
+    }
+
+    #[test]
+    fn parses_http_storage_location() {
+        let conf: CheckpointSyncerConf = "https://example.com/checkpoints".parse().unwrap();
+        assert!(matches!(
+            conf,
+            CheckpointSyncerConf::Http { url } if url == "https://example.com/checkpoints"
+        ));
+    }
+
+    #[test]
+    fn builds_replicated_storage() {
+        let conf: CheckpointSyncerConf = serde_json::from_value(serde_json::json!({
+            "type": "replicated",
+            "backends": {
+                "b": { "type": "localStorage", "path": "/tmp/b" },
+                "a": { "type": "s3", "bucket": "hyperlane-validator", "region": "us-east-1" }
+            }
+        }))
+        .unwrap();
+        let syncer = conf.build(None).unwrap();
+        assert_eq!(
+            syncer.announcement_locations(),
+            vec!["s3://hyperlane-validator/us-east-1", "file:///tmp/b"]
+        );
+
+        let empty: CheckpointSyncerConf =
+            serde_json::from_value(serde_json::json!({ "type": "replicated", "backends": {} }))
+                .unwrap();
+        assert!(empty.build(None).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_storage_locations() {
+        for location in [
+            "/tmp/checkpoints",
+            "file://",
+            "s3://hyperlane-validator",
+            "s3://hyperlane-validator/not-a-region",
+            "s3://hyperlane-validator/minio?endpoint=",
+            "s3://hyperlane-validator/minio?region=us-east-1",
+            "gcs://hyperlane-validator/us-east-1",
+        ] {
+            assert!(
+                location.parse::<CheckpointSyncerConf>().is_err(),
+                "{location} should not parse"
+            );
+        }
     }
 }
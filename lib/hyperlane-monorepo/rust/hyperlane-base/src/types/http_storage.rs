@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use eyre::{bail, Result};
+use prometheus::IntGauge;
+use reqwest::{Client, StatusCode};
+
+use hyperlane_core::{SignedAnnouncement, SignedCheckpoint};
+
+use crate::CheckpointSyncer;
+
+/// The timeout for HTTP requests made to read checkpoints.
+const HTTP_REQUEST_TIMEOUT_SECONDS: u64 = 30;
+
+#[derive(Debug, Clone)]
+/// Type for reading checkpoints served over HTTP(S). Uses the same file layout
+/// as [`LocalStorage`](crate::LocalStorage) and is read-only.
+pub struct HttpStorage {
+    /// Base url the checkpoint files are served from
+    url: String,
+    client: Client,
+    /// The latest seen signed checkpoint index.
+    latest_index: Option<IntGauge>,
+}
+
+impl HttpStorage {
+    /// Constructor
+    pub fn new(url: &str, latest_index: Option<IntGauge>) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(HTTP_REQUEST_TIMEOUT_SECONDS))
+            .build()?;
+        Ok(Self {
+            url: url.trim_end_matches('/').to_owned(),
+            client,
+            latest_index,
+        })
+    }
+
+    /// Reads a file relative to the base url. Returns None if it is not found.
+    async fn read(&self, file: &str) -> Result<Option<Vec<u8>>> {
+        let response = self
+            .client
+            .get(format!("{}/{file}", self.url))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.error_for_status()?.bytes().await?;
+        Ok(Some(body.to_vec()))
+    }
+
+    fn checkpoint_file(index: u32) -> String {
+        format!("{index}.json")
+    }
+
+    fn index_file() -> &'static str {
+        "index.json"
+    }
+
+    fn announcement_file() -> &'static str {
+        "announcement.json"
+    }
+}
+
+#[async_trait]
+impl CheckpointSyncer for HttpStorage {
+    async fn latest_index(&self) -> Result<Option<u32>> {
+        let ret = self
+            .read(HttpStorage::index_file())
+            .await?
+            .map(|data| serde_json::from_slice(&data))
+            .transpose()
+            .map_err(Into::into);
+
+        if let Ok(Some(latest_index)) = ret {
+            if let Some(gauge) = &self.latest_index {
+                gauge.set(latest_index as i64);
+            }
+        }
+
+        ret
+    }
+
+    async fn fetch_checkpoint(&self, index: u32) -> Result<Option<SignedCheckpoint>> {
+        self.read(&HttpStorage::checkpoint_file(index))
+            .await?
+            .map(|data| serde_json::from_slice(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn write_checkpoint(&self, _signed_checkpoint: &SignedCheckpoint) -> Result<()> {
+        bail!("HTTP checkpoint syncer is read-only")
+    }
+
+    async fn fetch_announcement(&self) -> Result<Option<SignedAnnouncement>> {
+        self.read(HttpStorage::announcement_file())
+            .await?
+            .map(|data| serde_json::from_slice(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn write_announcement(&self, _signed_announcement: &SignedAnnouncement) -> Result<()> {
+        bail!("HTTP checkpoint syncer is read-only")
+    }
+
+    fn announcement_location(&self) -> String {
+        self.url.clone()
+    }
+}
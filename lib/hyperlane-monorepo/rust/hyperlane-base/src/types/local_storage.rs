@@ -92,6 +92,19 @@ impl CheckpointSyncer for LocalStorage {
         Ok(())
     }
 
+    async fn fetch_announcement(&self) -> Result<Option<SignedAnnouncement>> {
+        let path = self.announcement_file_path();
+        match tokio::fs::read(&path).await {
+            Ok(data) => {
+                let announcement = serde_json::from_slice(&data)
+                    .with_context(|| format!("Parsing announcement at {path}"))?;
+                Ok(Some(announcement))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("Reading announcement from {path}")),
+        }
+    }
+
     async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()> {
         let serialized_announcement = serde_json::to_string_pretty(signed_announcement)?;
         let path = self.announcement_file_path();
@@ -103,7 +116,7 @@ impl CheckpointSyncer for LocalStorage {
 
     fn announcement_location(&self) -> String {
         let mut location: String = "file://".to_owned();
-        location.push_str(self.announcement_file_path().as_ref());
+        location.push_str(self.path.as_ref());
         location
     }
 }
@@ -1,9 +1,13 @@
 mod checkpoint_syncer;
+mod http_storage;
 mod local_storage;
 mod multisig;
+mod replicated_storage;
 mod s3_storage;
 
 pub use checkpoint_syncer::*;
+pub use http_storage::*;
 pub use local_storage::*;
 pub use multisig::*;
+pub use replicated_storage::*;
 pub use s3_storage::*;
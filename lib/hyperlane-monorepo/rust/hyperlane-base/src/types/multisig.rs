@@ -2,10 +2,12 @@ use std::collections::{hash_map::Entry, HashMap};
 use std::sync::Arc;
 
 use ethers::prelude::Address;
-use eyre::Result;
-use tracing::{debug, instrument};
+use eyre::{Report, Result};
+use tracing::{debug, instrument, warn};
 
-use hyperlane_core::{MultisigSignedCheckpoint, SignedCheckpointWithSigner, H160, H256};
+use hyperlane_core::{
+    FailureKind, MultisigSignedCheckpoint, SignedCheckpointWithSigner, H160, H256,
+};
 
 use crate::CheckpointSyncer;
 
@@ -31,6 +33,9 @@ impl MultisigCheckpointSyncer {
     /// backwards if unsuccessful, until the (optional) index is reached.
     ///
     /// Note it's possible to not find a quorum.
+    ///
+    /// Errors from individual validators' checkpoint syncers are tolerated, except
+    /// for being rate limited, which stops the search so the caller can back off.
     #[instrument(err, skip(self))]
     pub async fn fetch_checkpoint_in_range(
         &self,
@@ -45,8 +50,12 @@ impl MultisigCheckpointSyncer {
             let addr = H160::from(*validator);
             if let Some(checkpoint_syncer) = self.checkpoint_syncers.get(&addr) {
                 // Gracefully handle errors getting the latest_index
-                if let Ok(Some(index)) = checkpoint_syncer.latest_index().await {
-                    latest_indices.push(index);
+                match checkpoint_syncer.latest_index().await {
+                    Ok(Some(index)) => latest_indices.push(index),
+                    Ok(None) => {}
+                    Err(err) => {
+                        log_syncer_error(validator, &err);
+                    }
                 }
             }
         }
@@ -68,10 +77,13 @@ impl MultisigCheckpointSyncer {
                 return Ok(None);
             }
             for index in (minimum_index..=*start_index).rev() {
-                if let Ok(Some(checkpoint)) =
-                    self.fetch_checkpoint(index, validators, threshold).await
-                {
-                    return Ok(Some(checkpoint));
+                match self.fetch_checkpoint(index, validators, threshold).await {
+                    Ok(Some(checkpoint)) => return Ok(Some(checkpoint)),
+                    Ok(None) => {}
+                    Err(err) if FailureKind::of(&*err) == FailureKind::RateLimited => {
+                        return Err(err)
+                    }
+                    Err(_) => {}
                 }
             }
         }
@@ -98,8 +110,16 @@ impl MultisigCheckpointSyncer {
             if let Some(checkpoint_syncer) = self.checkpoint_syncers.get(&addr) {
                 // Gracefully ignore an error fetching the checkpoint from a validator's checkpoint syncer,
                 // which can happen if the validator has not signed the checkpoint at `index`.
-                if let Ok(Some(signed_checkpoint)) = checkpoint_syncer.fetch_checkpoint(index).await
-                {
+                let signed_checkpoint = match checkpoint_syncer.fetch_checkpoint(index).await {
+                    Ok(signed_checkpoint) => signed_checkpoint,
+                    Err(err) => {
+                        if log_syncer_error(validator, &err) == FailureKind::RateLimited {
+                            return Err(err);
+                        }
+                        None
+                    }
+                };
+                if let Some(signed_checkpoint) = signed_checkpoint {
                     // If the signed checkpoint is for a different index, ignore it
                     if signed_checkpoint.value.index != index {
                         continue;
@@ -149,3 +169,17 @@ impl MultisigCheckpointSyncer {
         Ok(None)
     }
 }
+
+/// Log an error from a validator's checkpoint syncer at a level depending on
+/// its kind and return the kind. Errors which need an operator, e.g. denied
+/// access to a bucket, are warned about; others are expected from time to time.
+fn log_syncer_error(validator: &H256, err: &Report) -> FailureKind {
+    let kind = FailureKind::of(&**err);
+    let validator = format!("{validator:#x}");
+    if kind.needs_attention() || kind == FailureKind::RateLimited {
+        warn!(validator, error = ?err, %kind, "Checkpoint syncer failed");
+    } else {
+        debug!(validator, error = ?err, %kind, "Checkpoint syncer failed");
+    }
+    kind
+}
@@ -0,0 +1,423 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use eyre::{bail, Result};
+use futures_util::future::join_all;
+use prometheus::{IntCounterVec, IntGauge, IntGaugeVec};
+use tracing::warn;
+
+use hyperlane_core::{SignedAnnouncement, SignedCheckpoint};
+
+use crate::CheckpointSyncer;
+
+/// Writes checkpoints and announcements to several checkpoint syncers, and
+/// reads from the first one able to serve a request.
+///
+/// Writes succeed as long as at least one backend accepted them. Backends
+/// which missed writes are not backfilled: they hold every checkpoint written
+/// after they recovered, but not the ones written while they were failing.
+/// The latest index is the highest of all backends and checkpoints are read
+/// from whichever backend has them, so reading through the replicated storage
+/// is unaffected, while a reader of only the lagging backend may find gaps.
+/// How far each backend is behind is reported with `with_lag_metrics`.
+#[derive(Debug, Clone)]
+pub struct ReplicatedStorage {
+    /// The backends, in the order they are read from, with a name used to
+    /// identify them in logs and metrics
+    backends: Vec<(String, Arc<dyn CheckpointSyncer>)>,
+    /// Counts writes to each backend by operation and result
+    writes: Option<IntCounterVec>,
+    /// How many checkpoint indices each backend is behind the latest one
+    lag: Option<IntGaugeVec>,
+    /// The latest seen signed checkpoint index
+    latest_index: Option<IntGauge>,
+}
+
+impl ReplicatedStorage {
+    /// Constructor
+    pub fn new(
+        backends: Vec<(String, Arc<dyn CheckpointSyncer>)>,
+        latest_index: Option<IntGauge>,
+    ) -> Self {
+        Self {
+            backends,
+            writes: None,
+            lag: None,
+            latest_index,
+        }
+    }
+
+    /// Count the writes to each backend, see
+    /// `CoreMetrics::checkpoint_syncer_writes`
+    pub fn with_write_metrics(mut self, writes: IntCounterVec) -> Self {
+        self.writes = Some(writes);
+        self
+    }
+
+    /// Report how far each backend is behind the latest index, see
+    /// `CoreMetrics::checkpoint_syncer_lag`
+    pub fn with_lag_metrics(mut self, lag: IntGaugeVec) -> Self {
+        self.lag = Some(lag);
+        self
+    }
+
+    /// Set the lag of each backend whose latest index could be read
+    fn report_lag(&self, latest_index: u32, indices: &[Option<Option<u32>>]) {
+        let Some(lag) = &self.lag else {
+            return;
+        };
+        for ((name, _), index) in self.backends.iter().zip(indices) {
+            if let Some(index) = index {
+                let behind = match index {
+                    Some(index) => latest_index.saturating_sub(*index) as i64,
+                    None => latest_index as i64 + 1,
+                };
+                lag.with_label_values(&[name.as_str()]).set(behind);
+            }
+        }
+    }
+
+    /// Run `write` against each of `backends` concurrently, failing only if
+    /// none of them succeeded.
+    async fn write_all<'a, F, Fut>(
+        &self,
+        operation: &str,
+        backends: Vec<&'a (String, Arc<dyn CheckpointSyncer>)>,
+        write: F,
+    ) -> Result<()>
+    where
+        F: Fn(&'a dyn CheckpointSyncer) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let results = join_all(
+            backends
+                .iter()
+                .copied()
+                .map(|(_, backend)| write(backend.as_ref())),
+        )
+        .await;
+
+        let mut succeeded = 0;
+        for ((name, _), result) in backends.into_iter().zip(results) {
+            let label = match result {
+                Ok(()) => {
+                    succeeded += 1;
+                    "success"
+                }
+                Err(err) => {
+                    warn!(
+                        backend = %name,
+                        operation,
+                        error = ?err,
+                        "Failed to write to checkpoint syncer backend"
+                    );
+                    "failure"
+                }
+            };
+            if let Some(writes) = &self.writes {
+                writes
+                    .with_label_values(&[name.as_str(), operation, label])
+                    .inc();
+            }
+        }
+
+        if succeeded == 0 {
+            bail!("Failed to write {operation} to any checkpoint syncer backend");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CheckpointSyncer for ReplicatedStorage {
+    /// The highest index any of the backends has reached
+    async fn latest_index(&self) -> Result<Option<u32>> {
+        let results = join_all(
+            self.backends
+                .iter()
+                .map(|(_, backend)| backend.latest_index()),
+        )
+        .await;
+
+        let mut latest_index = None;
+        let mut last_err = None;
+        let mut indices = Vec::with_capacity(results.len());
+        for ((name, _), result) in self.backends.iter().zip(results) {
+            match result {
+                Ok(index) => {
+                    latest_index = latest_index.max(index);
+                    indices.push(Some(index));
+                }
+                Err(err) => {
+                    warn!(backend = %name, error = ?err, "Failed to read latest index");
+                    last_err = Some(err);
+                    indices.push(None);
+                }
+            }
+        }
+        if let (Some(err), None) = (last_err, latest_index) {
+            return Err(err);
+        }
+
+        if let Some(index) = latest_index {
+            self.report_lag(index, &indices);
+            if let Some(gauge) = &self.latest_index {
+                gauge.set(index as i64);
+            }
+        }
+        Ok(latest_index)
+    }
+
+    async fn fetch_checkpoint(&self, index: u32) -> Result<Option<SignedCheckpoint>> {
+        let mut last_err = None;
+        for (name, backend) in &self.backends {
+            match backend.fetch_checkpoint(index).await {
+                Ok(Some(checkpoint)) => return Ok(Some(checkpoint)),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(backend = %name, index, error = ?err, "Failed to fetch checkpoint");
+                    last_err = Some(err);
+                }
+            }
+        }
+        last_err.map_or(Ok(None), Err)
+    }
+
+    /// Writes the checkpoint to all backends, and then updates their lag if
+    /// it is reported
+    async fn write_checkpoint(&self, signed_checkpoint: &SignedCheckpoint) -> Result<()> {
+        self.write_all("checkpoint", self.backends.iter().collect(), |backend| {
+            backend.write_checkpoint(signed_checkpoint)
+        })
+        .await?;
+        if self.lag.is_some() {
+            // Failing to read the indices back does not fail the write
+            let _ = self.latest_index().await;
+        }
+        Ok(())
+    }
+
+    async fn fetch_announcement(&self) -> Result<Option<SignedAnnouncement>> {
+        let mut last_err = None;
+        for (name, backend) in &self.backends {
+            match backend.fetch_announcement().await {
+                Ok(Some(announcement)) => return Ok(Some(announcement)),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(backend = %name, error = ?err, "Failed to fetch announcement");
+                    last_err = Some(err);
+                }
+            }
+        }
+        last_err.map_or(Ok(None), Err)
+    }
+
+    /// Writes the announcement to the backends whose location it announces,
+    /// or to all backends if it announces some other location.
+    async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()> {
+        let storage_location = &signed_announcement.value.storage_location;
+        let mut backends: Vec<_> = self
+            .backends
+            .iter()
+            .filter(|(_, backend)| backend.announcement_locations().contains(storage_location))
+            .collect();
+        if backends.is_empty() {
+            backends = self.backends.iter().collect();
+        }
+        self.write_all("announcement", backends, |backend| {
+            backend.write_announcement(signed_announcement)
+        })
+        .await
+    }
+
+    /// The location of the first backend
+    fn announcement_location(&self) -> String {
+        self.backends
+            .first()
+            .map(|(_, backend)| backend.announcement_location())
+            .unwrap_or_default()
+    }
+
+    fn announcement_locations(&self) -> Vec<String> {
+        self.backends
+            .iter()
+            .flat_map(|(_, backend)| backend.announcement_locations())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prometheus::opts;
+    use tempfile::tempdir;
+
+    use hyperlane_core::{Announcement, HyperlaneSigner, HyperlaneSignerExt, H256};
+    use hyperlane_test::test_utils::{signed_checkpoint, TestSigner};
+
+    use super::*;
+    use crate::LocalStorage;
+
+    fn local(path: &str) -> (String, Arc<dyn CheckpointSyncer>) {
+        (path.to_owned(), Arc::new(LocalStorage::new(path, None)))
+    }
+
+    fn writes() -> IntCounterVec {
+        IntCounterVec::new(
+            opts!("checkpoint_syncer_writes", "test"),
+            &["backend", "operation", "result"],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn writes_while_any_backend_succeeds() {
+        let dir = tempdir().unwrap();
+        let healthy = dir.path().to_str().unwrap();
+        let missing = dir.path().join("missing");
+        let missing = missing.to_str().unwrap();
+        let writes = writes();
+        let storage = ReplicatedStorage::new(vec![local(missing), local(healthy)], None)
+            .with_write_metrics(writes.clone());
+
+        storage
+            .write_checkpoint(&signed_checkpoint(4).await)
+            .await
+            .unwrap();
+        assert_eq!(storage.latest_index().await.unwrap(), Some(4));
+        assert_eq!(
+            storage
+                .fetch_checkpoint(4)
+                .await
+                .unwrap()
+                .unwrap()
+                .value
+                .index,
+            4
+        );
+        assert_eq!(
+            writes
+                .with_label_values(&[healthy, "checkpoint", "success"])
+                .get(),
+            1
+        );
+        assert_eq!(
+            writes
+                .with_label_values(&[missing, "checkpoint", "failure"])
+                .get(),
+            1
+        );
+
+        let broken = ReplicatedStorage::new(vec![local(missing)], None);
+        assert!(broken
+            .write_checkpoint(&signed_checkpoint(5).await)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_fall_back_across_backends() {
+        let (first, second) = (tempdir().unwrap(), tempdir().unwrap());
+        let first = local(first.path().to_str().unwrap());
+        let second = local(second.path().to_str().unwrap());
+        second
+            .1
+            .write_checkpoint(&signed_checkpoint(2).await)
+            .await
+            .unwrap();
+        first
+            .1
+            .write_checkpoint(&signed_checkpoint(1).await)
+            .await
+            .unwrap();
+
+        let storage = ReplicatedStorage::new(vec![first, second], None);
+        assert_eq!(storage.latest_index().await.unwrap(), Some(2));
+        assert!(storage.fetch_checkpoint(1).await.unwrap().is_some());
+        assert!(storage.fetch_checkpoint(2).await.unwrap().is_some());
+        assert!(storage.fetch_checkpoint(3).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_lag_of_backends_which_missed_writes() {
+        let (first, second) = (tempdir().unwrap(), tempdir().unwrap());
+        let first_path = first.path().to_str().unwrap();
+        let second_path = second.path().to_str().unwrap();
+        let lag = IntGaugeVec::new(opts!("checkpoint_syncer_lag", "test"), &["backend"]).unwrap();
+        let storage = ReplicatedStorage::new(vec![local(first_path), local(second_path)], None)
+            .with_lag_metrics(lag.clone());
+
+        storage
+            .write_checkpoint(&signed_checkpoint(1).await)
+            .await
+            .unwrap();
+        assert_eq!(lag.with_label_values(&[first_path]).get(), 0);
+        assert_eq!(lag.with_label_values(&[second_path]).get(), 0);
+
+        // Only the first backend receives the next checkpoints
+        let (_, first_backend) = local(first_path);
+        for index in [2, 3] {
+            first_backend
+                .write_checkpoint(&signed_checkpoint(index).await)
+                .await
+                .unwrap();
+        }
+        assert_eq!(storage.latest_index().await.unwrap(), Some(3));
+        assert_eq!(lag.with_label_values(&[first_path]).get(), 0);
+        assert_eq!(lag.with_label_values(&[second_path]).get(), 2);
+
+        // The second backend catches up on the latest index, but is not
+        // backfilled
+        storage
+            .write_checkpoint(&signed_checkpoint(4).await)
+            .await
+            .unwrap();
+        assert_eq!(lag.with_label_values(&[second_path]).get(), 0);
+        let (_, second_backend) = local(second_path);
+        assert!(second_backend.fetch_checkpoint(2).await.unwrap().is_none());
+        assert!(storage.fetch_checkpoint(2).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn writes_announcements_to_their_backends() {
+        let (first, second) = (tempdir().unwrap(), tempdir().unwrap());
+        let first = local(first.path().to_str().unwrap());
+        let second = local(second.path().to_str().unwrap());
+        let storage = ReplicatedStorage::new(vec![first.clone(), second.clone()], None);
+        let locations = storage.announcement_locations();
+        assert_eq!(
+            locations,
+            vec![
+                first.1.announcement_location(),
+                second.1.announcement_location()
+            ]
+        );
+
+        let signer = TestSigner::default();
+        for storage_location in locations {
+            let announcement = Announcement {
+                validator: signer.eth_address(),
+                mailbox_address: H256::repeat_byte(1),
+                mailbox_domain: 13371,
+                storage_location,
+            };
+            storage
+                .write_announcement(&signer.sign(announcement).await.unwrap())
+                .await
+                .unwrap();
+        }
+
+        for (_, backend) in [first, second] {
+            assert_eq!(
+                backend
+                    .fetch_announcement()
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .value
+                    .storage_location,
+                backend.announcement_location()
+            );
+        }
+    }
+}
@@ -1,32 +1,77 @@
-use std::{fmt, time::Duration};
+use std::{fmt, future::Future, time::Duration};
 
 use async_trait::async_trait;
-use eyre::{bail, Result};
+use eyre::{bail, Report, Result};
 use futures_util::TryStreamExt;
-use hyperlane_core::{SignedAnnouncement, SignedCheckpoint};
+use hyperlane_core::{FailureKind, SignedAnnouncement, SignedCheckpoint};
 use once_cell::sync::OnceCell;
 use prometheus::IntGauge;
 use rusoto_core::{
-    credential::{Anonymous, AwsCredentials, EnvironmentProvider, StaticProvider},
+    credential::{Anonymous, AwsCredentials, EnvironmentProvider, ProfileProvider, StaticProvider},
     HttpClient, Region, RusotoError,
 };
 use rusoto_s3::{GetObjectError, GetObjectRequest, PutObjectRequest, S3Client, S3};
-use tokio::time::timeout;
+use tokio::time::{sleep, timeout};
+use tracing::warn;
 
 use crate::CheckpointSyncer;
 
-/// The timeout for S3 requests. Rusoto doesn't offer timeout configuration
-/// out of the box, so S3 requests must be wrapped with a timeout.
+/// The default timeout for S3 requests. Rusoto doesn't offer timeout
+/// configuration out of the box, so S3 requests must be wrapped with a timeout.
 /// See https://github.com/rusoto/rusoto/issues/1795.
 const S3_REQUEST_TIMEOUT_SECONDS: u64 = 30;
 
+/// The delay before the first retry of a failed S3 request. Doubles with
+/// every subsequent retry.
+const S3_RETRY_BASE_BACKOFF_MS: u64 = 500;
+
+/// The longest delay between retries of a failed S3 request.
+const S3_RETRY_MAX_BACKOFF_MS: u64 = 30_000;
+
+/// Credentials used to sign authenticated S3 requests.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum S3Credentials {
+    /// Read credentials from the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+    /// and `AWS_SESSION_TOKEN` environment variables
+    #[default]
+    Environment,
+    /// Explicitly configured credentials
+    Static {
+        /// The access key id
+        accesskeyid: String,
+        /// The secret access key
+        secretaccesskey: String,
+        /// An optional session token
+        sessiontoken: Option<String>,
+    },
+    /// Read credentials from a profile in an AWS credentials file
+    Profile {
+        /// The profile name. Defaults to `AWS_PROFILE` or `default`.
+        profile: Option<String>,
+        /// The credentials file. Defaults to `~/.aws/credentials`.
+        path: Option<String>,
+    },
+}
+
 #[derive(Clone)]
-/// Type for reading/writing to S3
+/// Type for reading/writing to S3 or an S3-compatible store. Requests are
+/// always path-style addressed.
 pub struct S3Storage {
     /// The name of the bucket.
     bucket: String,
-    /// The region of the bucket.
+    /// The region of the bucket, or a custom endpoint.
     region: Region,
+    /// An optional prefix for all object keys.
+    prefix: Option<String>,
+    /// The credentials for authenticated requests. Reads are anonymous unless
+    /// credentials are configured, and writes default to credentials from the
+    /// environment.
+    credentials: Option<S3Credentials>,
+    /// The timeout for a single request.
+    timeout: Duration,
+    /// The maximum number of times a failed request is retried.
+    max_retries: u32,
     /// A client with AWS credentials.
     authenticated_client: OnceCell<S3Client>,
     /// A client without credentials for anonymous requests.
@@ -40,6 +85,7 @@ impl fmt::Debug for S3Storage {
         f.debug_struct("S3Storage")
             .field("bucket", &self.bucket)
             .field("region", &self.region)
+            .field("prefix", &self.prefix)
             .finish()
     }
 }
@@ -50,59 +96,143 @@ impl S3Storage {
         Self {
             bucket: bucket.to_owned(),
             region,
+            prefix: None,
+            credentials: None,
+            timeout: Duration::from_secs(S3_REQUEST_TIMEOUT_SECONDS),
+            max_retries: 0,
             authenticated_client: OnceCell::new(),
             anonymous_client: OnceCell::new(),
             latest_index,
         }
     }
 
+    /// Prepend `prefix` to all object keys, e.g. so several validators can
+    /// share a bucket.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        self.prefix = (!prefix.is_empty()).then(|| prefix.to_owned());
+        self
+    }
+
+    /// Use the provided credentials for authenticated requests, including
+    /// reads, e.g. for private buckets
+    pub fn with_credentials(mut self, credentials: S3Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Set the timeout for a single request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of times a failed request is retried
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
     async fn write_to_bucket(&self, key: String, body: &str) -> Result<()> {
-        let req = PutObjectRequest {
-            key,
-            bucket: self.bucket.clone(),
-            body: Some(Vec::from(body).into()),
-            content_type: Some("application/json".to_owned()),
-            ..Default::default()
-        };
-        timeout(
-            Duration::from_secs(S3_REQUEST_TIMEOUT_SECONDS),
-            self.authenticated_client().put_object(req),
-        )
-        .await??;
-        Ok(())
+        let client = self.authenticated_client()?;
+        self.with_retries(|| {
+            let req = PutObjectRequest {
+                key: key.clone(),
+                bucket: self.bucket.clone(),
+                body: Some(Vec::from(body).into()),
+                content_type: Some("application/json".to_owned()),
+                ..Default::default()
+            };
+            put_object(client, req)
+        })
+        .await
     }
 
-    /// Uses an anonymous client. This should only be used for publicly accessible buckets.
-    async fn anonymously_read_from_bucket(&self, key: String) -> Result<Option<Vec<u8>>> {
-        let req = GetObjectRequest {
-            key,
-            bucket: self.bucket.clone(),
-            ..Default::default()
+    /// Uses the authenticated client if credentials are configured, and an
+    /// anonymous client for publicly accessible buckets otherwise.
+    async fn read_from_bucket(&self, key: String) -> Result<Option<Vec<u8>>> {
+        let client = match self.credentials {
+            Some(_) => self.authenticated_client()?,
+            None => self.anonymous_client()?,
         };
-        let get_object_result = timeout(
-            Duration::from_secs(S3_REQUEST_TIMEOUT_SECONDS),
-            self.anonymous_client().get_object(req),
-        )
-        .await?;
+        self.with_retries(|| {
+            let req = GetObjectRequest {
+                key: key.clone(),
+                bucket: self.bucket.clone(),
+                ..Default::default()
+            };
+            get_object(client, req)
+        })
+        .await
+    }
 
-        match get_object_result {
-            Ok(res) => match res.body {
-                Some(body) => Ok(Some(body.map_ok(|b| b.to_vec()).try_concat().await?)),
-                None => Ok(None),
-            },
-            Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => Ok(None),
-            Err(e) => bail!(e),
+    /// Runs a request with the configured timeout, retrying it with an
+    /// exponential backoff if it fails in a way which retrying could fix.
+    async fn with_retries<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            let result = timeout(self.timeout, request())
+                .await
+                .map_err(Report::from)
+                .and_then(|res| res);
+            match result {
+                Err(err) if retries < self.max_retries && FailureKind::of(&*err).is_retryable() => {
+                    let backoff = retry_backoff(retries);
+                    retries += 1;
+                    warn!(
+                        bucket = %self.bucket,
+                        retries,
+                        ?backoff,
+                        error = ?err,
+                        "S3 request failed, retrying"
+                    );
+                    sleep(backoff).await;
+                }
+                result => return result,
+            }
         }
     }
 
     /// Gets an authenticated S3Client, creating it if it doesn't already exist.
-    fn authenticated_client(&self) -> &S3Client {
-        self.authenticated_client.get_or_init(|| {
-            S3Client::new_with(
-                HttpClient::new().unwrap(),
-                EnvironmentProvider::default(),
-                self.region.clone(),
-            )
+    fn authenticated_client(&self) -> Result<&S3Client> {
+        self.authenticated_client.get_or_try_init(|| {
+            let http_client = HttpClient::new()?;
+            let region = self.region.clone();
+            let credentials = self.credentials.clone().unwrap_or_default();
+            let client = match &credentials {
+                S3Credentials::Environment => {
+                    S3Client::new_with(http_client, EnvironmentProvider::default(), region)
+                }
+                S3Credentials::Static {
+                    accesskeyid,
+                    secretaccesskey,
+                    sessiontoken,
+                } => S3Client::new_with(
+                    http_client,
+                    StaticProvider::new(
+                        accesskeyid.clone(),
+                        secretaccesskey.clone(),
+                        sessiontoken.clone(),
+                        None,
+                    ),
+                    region,
+                ),
+                S3Credentials::Profile { profile, path } => {
+                    let mut provider = ProfileProvider::new()?;
+                    if let Some(profile) = profile {
+                        provider.set_profile(profile.clone());
+                    }
+                    if let Some(path) = path {
+                        provider.set_file_path(path);
+                    }
+                    S3Client::new_with(http_client, provider, region)
+                }
+            };
+            Ok(client)
         })
     }
 
@@ -112,30 +242,60 @@ impl S3Storage {
     /// We've experienced an inability to make GetObjectRequests to public
     /// S3 buckets when signing with credentials from an AWS account not from the
     /// S3 bucket's AWS account.
-    fn anonymous_client(&self) -> &S3Client {
-        self.anonymous_client.get_or_init(|| {
+    fn anonymous_client(&self) -> Result<&S3Client> {
+        self.anonymous_client.get_or_try_init(|| {
             // By default, these credentials are anonymous, see https://docs.rs/rusoto_credential/latest/rusoto_credential/struct.AwsCredentials.html#anonymous-example
             let credentials = AwsCredentials::default();
             assert!(credentials.is_anonymous(), "AWS credentials not anonymous");
 
-            S3Client::new_with(
-                HttpClient::new().unwrap(),
+            Ok(S3Client::new_with(
+                HttpClient::new()?,
                 StaticProvider::from(credentials),
                 self.region.clone(),
-            )
+            ))
         })
     }
 
-    fn checkpoint_key(index: u32) -> String {
-        format!("checkpoint_{}.json", index)
+    fn key(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}/{name}"),
+            None => name.to_owned(),
+        }
+    }
+
+    fn checkpoint_key(&self, index: u32) -> String {
+        self.key(&format!("checkpoint_{}.json", index))
     }
 
-    fn index_key() -> String {
-        "checkpoint_latest_index.json".to_owned()
+    fn index_key(&self) -> String {
+        self.key("checkpoint_latest_index.json")
     }
 
-    fn announcement_key() -> String {
-        "announcement.json".to_owned()
+    fn announcement_key(&self) -> String {
+        self.key("announcement.json")
+    }
+}
+
+/// The delay before retrying a request which has already been retried
+/// `retries` times
+fn retry_backoff(retries: u32) -> Duration {
+    let backoff = S3_RETRY_BASE_BACKOFF_MS.saturating_mul(2u64.saturating_pow(retries));
+    Duration::from_millis(backoff.min(S3_RETRY_MAX_BACKOFF_MS))
+}
+
+async fn put_object(client: &S3Client, req: PutObjectRequest) -> Result<()> {
+    client.put_object(req).await?;
+    Ok(())
+}
+
+async fn get_object(client: &S3Client, req: GetObjectRequest) -> Result<Option<Vec<u8>>> {
+    match client.get_object(req).await {
+        Ok(res) => match res.body {
+            Some(body) => Ok(Some(body.map_ok(|b| b.to_vec()).try_concat().await?)),
+            None => Ok(None),
+        },
+        Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => Ok(None),
+        Err(e) => bail!(e),
     }
 }
 
@@ -143,7 +303,7 @@ impl S3Storage {
 impl CheckpointSyncer for S3Storage {
     async fn latest_index(&self) -> Result<Option<u32>> {
         let ret = self
-            .anonymously_read_from_bucket(S3Storage::index_key())
+            .read_from_bucket(self.index_key())
             .await?
             .map(|data| serde_json::from_slice(&data))
             .transpose()
@@ -159,7 +319,7 @@ impl CheckpointSyncer for S3Storage {
     }
 
     async fn fetch_checkpoint(&self, index: u32) -> Result<Option<SignedCheckpoint>> {
-        self.anonymously_read_from_bucket(S3Storage::checkpoint_key(index))
+        self.read_from_bucket(self.checkpoint_key(index))
             .await?
             .map(|data| serde_json::from_slice(&data))
             .transpose()
@@ -169,30 +329,291 @@ impl CheckpointSyncer for S3Storage {
     async fn write_checkpoint(&self, signed_checkpoint: &SignedCheckpoint) -> Result<()> {
         let serialized_checkpoint = serde_json::to_string_pretty(signed_checkpoint)?;
         self.write_to_bucket(
-            S3Storage::checkpoint_key(signed_checkpoint.value.index),
+            self.checkpoint_key(signed_checkpoint.value.index),
             &serialized_checkpoint,
         )
         .await?;
 
-        self.write_to_bucket(
-            S3Storage::index_key(),
-            &signed_checkpoint.value.index.to_string(),
-        )
-        .await?;
+        self.write_to_bucket(self.index_key(), &signed_checkpoint.value.index.to_string())
+            .await?;
         Ok(())
     }
 
+    async fn fetch_announcement(&self) -> Result<Option<SignedAnnouncement>> {
+        self.read_from_bucket(self.announcement_key())
+            .await?
+            .map(|data| serde_json::from_slice(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+
     async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()> {
         let serialized_announcement = serde_json::to_string_pretty(signed_announcement)?;
-        self.write_to_bucket(S3Storage::announcement_key(), &serialized_announcement)
+        self.write_to_bucket(self.announcement_key(), &serialized_announcement)
             .await?;
         Ok(())
     }
+
+    /// `s3://bucket/region`, followed by `/prefix` if a key prefix is set and
+    /// `?endpoint=<url>` if a custom endpoint is used.
     fn announcement_location(&self) -> String {
         let mut location: String = "s3://".to_owned();
         location.push_str(self.bucket.as_ref());
         location.push('/');
         location.push_str(self.region.name());
+        if let Some(prefix) = &self.prefix {
+            location.push('/');
+            location.push_str(prefix);
+        }
+        if let Region::Custom { endpoint, .. } = &self.region {
+            location.push_str("?endpoint=");
+            location.push_str(endpoint);
+        }
         location
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use hyperlane_test::test_utils::signed_checkpoint;
+    use warp::http::StatusCode;
+    use warp::hyper::body::Bytes;
+    use warp::path::Tail;
+    use warp::reply::{self, Reply};
+    use warp::Filter;
+
+    use super::*;
+    use crate::CheckpointSyncerConf;
+
+    /// A minimal path-style S3 stand-in that keeps objects in memory. The
+    /// first `failures` requests are answered with an internal error, and all
+    /// requests are denied while `deny` is set.
+    #[derive(Default)]
+    struct FakeS3 {
+        objects: Mutex<HashMap<(String, String), Bytes>>,
+        failures: AtomicUsize,
+        deny: AtomicBool,
+        /// Number of requests received
+        requests: AtomicUsize,
+        /// Number of reads which were signed with credentials
+        signed_reads: AtomicUsize,
+    }
+
+    fn s3_error(status: StatusCode, code: &str) -> reply::Response {
+        let body = format!("<Error><Code>{code}</Code><Message>{code}</Message></Error>");
+        reply::with_status(
+            reply::with_header(body, "Content-Type", "application/xml"),
+            status,
+        )
+        .into_response()
+    }
+
+    fn serve(s3: Arc<FakeS3>) -> SocketAddr {
+        let failure = |s3: &FakeS3| {
+            s3.requests.fetch_add(1, Ordering::SeqCst);
+            if s3.deny.load(Ordering::SeqCst) {
+                Some(s3_error(StatusCode::FORBIDDEN, "AccessDenied"))
+            } else if s3
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |f| f.checked_sub(1))
+                .is_ok()
+            {
+                Some(s3_error(StatusCode::INTERNAL_SERVER_ERROR, "InternalError"))
+            } else {
+                None
+            }
+        };
+        let put = {
+            let s3 = s3.clone();
+            warp::put()
+                .and(warp::path::param::<String>())
+                .and(warp::path::tail())
+                .and(warp::body::bytes())
+                .map(move |bucket: String, key: Tail, body: Bytes| {
+                    if let Some(failure) = failure(&s3) {
+                        return failure;
+                    }
+                    s3.objects
+                        .lock()
+                        .unwrap()
+                        .insert((bucket, key.as_str().to_owned()), body);
+                    StatusCode::OK.into_response()
+                })
+        };
+        let get = warp::get()
+            .and(warp::path::param::<String>())
+            .and(warp::path::tail())
+            .and(warp::header::optional::<String>("authorization"))
+            .map(
+                move |bucket: String, key: Tail, authorization: Option<String>| {
+                    if authorization.is_some() {
+                        s3.signed_reads.fetch_add(1, Ordering::SeqCst);
+                    }
+                    if let Some(failure) = failure(&s3) {
+                        return failure;
+                    }
+                    match s3
+                        .objects
+                        .lock()
+                        .unwrap()
+                        .get(&(bucket, key.as_str().to_owned()))
+                    {
+                        Some(body) => body.clone().into_response(),
+                        None => s3_error(StatusCode::NOT_FOUND, "NoSuchKey"),
+                    }
+                },
+            );
+        let (addr, server) = warp::serve(put.or(get)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    fn storage(addr: SocketAddr) -> S3Storage {
+        let region = Region::Custom {
+            name: "local".to_owned(),
+            endpoint: format!("http://{addr}"),
+        };
+        S3Storage::new("checkpoints", region, None)
+            .with_credentials(S3Credentials::Static {
+                accesskeyid: "test".to_owned(),
+                secretaccesskey: "test".to_owned(),
+                sessiontoken: None,
+            })
+            .with_timeout(Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn reads_and_writes_checkpoints() {
+        let s3 = Arc::new(FakeS3::default());
+        let storage = storage(serve(s3.clone()));
+
+        assert_eq!(storage.latest_index().await.unwrap(), None);
+        assert!(storage.fetch_checkpoint(3).await.unwrap().is_none());
+
+        let signed_checkpoint = signed_checkpoint(3).await;
+        storage.write_checkpoint(&signed_checkpoint).await.unwrap();
+        assert_eq!(storage.latest_index().await.unwrap(), Some(3));
+        assert_eq!(
+            storage.fetch_checkpoint(3).await.unwrap().unwrap().value,
+            signed_checkpoint.value
+        );
+        assert!(s3
+            .objects
+            .lock()
+            .unwrap()
+            .contains_key(&("checkpoints".to_owned(), "checkpoint_3.json".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn prefixes_object_keys() {
+        let s3 = Arc::new(FakeS3::default());
+        let addr = serve(s3.clone());
+        let alice = storage(addr).with_prefix("/validators/alice/");
+        let bob = storage(addr).with_prefix("validators/bob");
+
+        alice
+            .write_checkpoint(&signed_checkpoint(1).await)
+            .await
+            .unwrap();
+        bob.write_checkpoint(&signed_checkpoint(2).await)
+            .await
+            .unwrap();
+
+        assert_eq!(alice.latest_index().await.unwrap(), Some(1));
+        assert_eq!(bob.latest_index().await.unwrap(), Some(2));
+        assert!(alice.fetch_checkpoint(2).await.unwrap().is_none());
+        assert!(s3.objects.lock().unwrap().contains_key(&(
+            "checkpoints".to_owned(),
+            "validators/alice/checkpoint_latest_index.json".to_owned()
+        )));
+        assert_eq!(
+            alice.announcement_location(),
+            format!("s3://checkpoints/local/validators/alice?endpoint=http://{addr}")
+        );
+    }
+
+    #[tokio::test]
+    async fn announced_location_round_trips_through_parser() {
+        let s3 = Arc::new(FakeS3::default());
+        let alice = storage(serve(s3.clone())).with_prefix("validators/alice");
+        let signed_checkpoint = signed_checkpoint(4).await;
+        alice.write_checkpoint(&signed_checkpoint).await.unwrap();
+
+        // This is how relayers find the checkpoints of a validator
+        let conf: CheckpointSyncerConf = alice.announcement_location().parse().unwrap();
+        let announced = conf.build(None).unwrap();
+        assert_eq!(
+            announced.announcement_location(),
+            alice.announcement_location()
+        );
+        assert_eq!(announced.latest_index().await.unwrap(), Some(4));
+        assert_eq!(
+            announced.fetch_checkpoint(4).await.unwrap().unwrap().value,
+            signed_checkpoint.value
+        );
+    }
+
+    #[tokio::test]
+    async fn signs_reads_only_with_credentials() {
+        let s3 = Arc::new(FakeS3::default());
+        let addr = serve(s3.clone());
+
+        let authenticated = storage(addr);
+        authenticated
+            .write_checkpoint(&signed_checkpoint(1).await)
+            .await
+            .unwrap();
+        assert_eq!(authenticated.latest_index().await.unwrap(), Some(1));
+        assert_eq!(s3.signed_reads.load(Ordering::SeqCst), 1);
+
+        let region = authenticated.region.clone();
+        let anonymous = S3Storage::new("checkpoints", region, None);
+        assert_eq!(anonymous.latest_index().await.unwrap(), Some(1));
+        assert_eq!(s3.signed_reads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_failed_requests() {
+        let s3 = Arc::new(FakeS3::default());
+        let addr = serve(s3.clone());
+
+        s3.failures.store(1, Ordering::SeqCst);
+        assert!(storage(addr).latest_index().await.is_err());
+
+        s3.failures.store(2, Ordering::SeqCst);
+        let storage = storage(addr).with_max_retries(2);
+        storage
+            .write_checkpoint(&signed_checkpoint(5).await)
+            .await
+            .unwrap();
+        assert_eq!(storage.latest_index().await.unwrap(), Some(5));
+        assert_eq!(s3.failures.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_denied_requests() {
+        let s3 = Arc::new(FakeS3::default());
+        let storage = storage(serve(s3.clone())).with_max_retries(3);
+        s3.deny.store(true, Ordering::SeqCst);
+
+        assert!(storage.latest_index().await.is_err());
+        assert_eq!(s3.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(retry_backoff(0), Duration::from_millis(500));
+        assert_eq!(retry_backoff(2), Duration::from_secs(2));
+        for retries in [10, 58, 64, u32::MAX] {
+            assert_eq!(
+                retry_backoff(retries),
+                Duration::from_millis(S3_RETRY_MAX_BACKOFF_MS)
+            );
+        }
+    }
+}
@@ -152,6 +152,8 @@ pub enum HyperlaneDomainProtocol {
     Ethereum,
     /// A Fuel-based chain type which uses hyperlane-fuel.
     Fuel,
+    /// An in-memory chain for tests which uses hyperlane-test.
+    Mock,
 }
 
 impl KnownHyperlaneDomain {
@@ -1,10 +1,11 @@
 use std::future::Future;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use tokio::time::sleep;
 use tracing::{debug, info, trace};
 
-use crate::db::{DbError, TypedDB, DB};
+use crate::db::{DbError, TypedBatch, TypedDB, DB};
 use crate::{
     HyperlaneMessage, InterchainGasPayment, InterchainGasPaymentMeta, InterchainGasPaymentWithMeta,
     H256, U256,
@@ -23,8 +24,12 @@ type Result<T> = std::result::Result<T, DbError>;
 /// DB handle for storing data tied to a specific Mailbox.
 ///
 /// Key structure: ```<entity>_<additional_prefix(es)>_<key>```
+///
+/// Clones of a handle share the lock serializing gas payment processing, so
+/// tasks processing gas payments concurrently must use clones of the same
+/// handle rather than handles created separately.
 #[derive(Debug, Clone)]
-pub struct HyperlaneDB(TypedDB);
+pub struct HyperlaneDB(TypedDB, Arc<Mutex<()>>);
 
 impl std::ops::Deref for HyperlaneDB {
     type Target = TypedDB;
@@ -49,10 +54,14 @@ impl AsRef<DB> for HyperlaneDB {
 impl HyperlaneDB {
     /// Instantiated new `HyperlaneDB`
     pub fn new(entity: impl AsRef<str>, db: DB) -> Self {
-        Self(TypedDB::new(entity.as_ref().to_owned(), db))
+        Self(
+            TypedDB::new(entity.as_ref().to_owned(), db),
+            Default::default(),
+        )
     }
 
-    /// Store list of messages
+    /// Store list of messages. Each message is stored atomically, so if
+    /// interrupted, a prefix of the list is stored.
     pub fn store_messages(&self, messages: &[HyperlaneMessage]) -> Result<u32> {
         let mut latest_nonce: u32 = 0;
         for message in messages {
@@ -80,12 +89,19 @@ impl HyperlaneDB {
         self.store_message(message)
     }
 
-    /// Store a raw committed message
+    /// Atomically store a raw committed message, along with the latest known
+    /// nonces
     ///
     /// Keys --> Values:
     /// - `nonce` --> `id`
     /// - `id` --> `message`
     pub fn store_message(&self, message: &HyperlaneMessage) -> Result<()> {
+        let batch = self.message_batch(message);
+        self.write(batch)
+    }
+
+    /// The writes storing a raw committed message
+    fn message_batch(&self, message: &HyperlaneMessage) -> TypedBatch {
         let id = message.id();
 
         info!(
@@ -95,21 +111,29 @@ impl HyperlaneDB {
             destination = &message.destination,
             "Storing new message in db.",
         );
-        self.store_message_id(message.nonce, message.destination, id)?;
-        self.store_keyed_encodable(MESSAGE, &id, message)?;
-        Ok(())
+        let mut batch = self.batch();
+        self.batch_message_id(&mut batch, message.nonce, message.destination, id);
+        batch.store_keyed_encodable(MESSAGE, &id, message);
+        batch
     }
 
     /// Store the latest known nonce
     ///
     /// Key --> value: `LATEST_NONCE` --> `nonce`
     pub fn update_latest_nonce(&self, nonce: u32) -> Result<()> {
+        let mut batch = self.batch();
+        self.batch_latest_nonce(&mut batch, nonce);
+        self.write(batch)
+    }
+
+    /// Add updating the latest known nonce to `batch`, if `nonce` is higher
+    fn batch_latest_nonce(&self, batch: &mut TypedBatch, nonce: u32) {
         if let Ok(Some(n)) = self.retrieve_latest_nonce() {
             if nonce <= n {
-                return Ok(());
+                return;
             }
         }
-        self.store_encodable("", LATEST_NONCE, &nonce)
+        batch.store_encodable("", LATEST_NONCE, &nonce)
     }
 
     /// Retrieve the highest known nonce
@@ -121,12 +145,25 @@ impl HyperlaneDB {
     ///
     /// Key --> value: `destination` --> `nonce`
     pub fn update_latest_nonce_for_destination(&self, destination: u32, nonce: u32) -> Result<()> {
+        let mut batch = self.batch();
+        self.batch_latest_nonce_for_destination(&mut batch, destination, nonce);
+        self.write(batch)
+    }
+
+    /// Add updating the latest known nonce for a destination to `batch`, if
+    /// `nonce` is higher
+    fn batch_latest_nonce_for_destination(
+        &self,
+        batch: &mut TypedBatch,
+        destination: u32,
+        nonce: u32,
+    ) {
         if let Ok(Some(n)) = self.retrieve_latest_nonce_for_destination(destination) {
             if nonce <= n {
-                return Ok(());
+                return;
             }
         }
-        self.store_keyed_encodable(LATEST_NONCE_FOR_DESTINATION, &destination, &nonce)
+        batch.store_keyed_encodable(LATEST_NONCE_FOR_DESTINATION, &destination, &nonce)
     }
 
     /// Retrieve the highest known nonce for a destination
@@ -134,16 +171,16 @@ impl HyperlaneDB {
         self.retrieve_keyed_decodable(LATEST_NONCE_FOR_DESTINATION, &destination)
     }
 
-    /// Store the message id keyed by nonce
-    fn store_message_id(&self, nonce: u32, destination: u32, id: H256) -> Result<()> {
+    /// Add storing the message id keyed by nonce to `batch`
+    fn batch_message_id(&self, batch: &mut TypedBatch, nonce: u32, destination: u32, id: H256) {
         debug!(
             nonce,
             id = ?id,
             "storing leaf hash keyed by index"
         );
-        self.store_keyed_encodable(MESSAGE_ID, &nonce, &id)?;
-        self.update_latest_nonce(nonce)?;
-        self.update_latest_nonce_for_destination(destination, nonce)
+        batch.store_keyed_encodable(MESSAGE_ID, &nonce, &id);
+        self.batch_latest_nonce(batch, nonce);
+        self.batch_latest_nonce_for_destination(batch, destination, nonce)
     }
 
     /// Retrieve a message by its id
@@ -198,29 +235,41 @@ impl HyperlaneDB {
         &self,
         gas_payment_with_meta: &InterchainGasPaymentWithMeta,
     ) -> Result<bool> {
+        // Otherwise two callers could both find the payment unprocessed and
+        // both add it to the total
+        let _guard = self.1.lock().unwrap();
+        match self.gas_payment_batch(gas_payment_with_meta)? {
+            // Return false to indicate the gas payment was already processed
+            None => Ok(false),
+            Some(batch) => {
+                self.write(batch)?;
+                // Return true to indicate the gas payment was processed for the first time
+                Ok(true)
+            }
+        }
+    }
+
+    /// The writes processing a gas payment, or None if it has already been
+    /// processed. Marking the payment as processed and adding it to the
+    /// total must happen atomically so it is counted exactly once.
+    fn gas_payment_batch(
+        &self,
+        gas_payment_with_meta: &InterchainGasPaymentWithMeta,
+    ) -> Result<Option<TypedBatch>> {
         let meta = &gas_payment_with_meta.meta;
         // If the gas payment has already been processed, do nothing
         if self.retrieve_gas_payment_meta_processed(meta)? {
             trace!(gas_payment_with_meta=?gas_payment_with_meta, "Attempted to process an already-processed gas payment");
-            // Return false to indicate the gas payment was already processed
-            return Ok(false);
+            return Ok(None);
         }
+        let mut batch = self.batch();
         // Set the gas payment as processed
-        self.store_gas_payment_meta_processed(meta)?;
+        batch.store_keyed_encodable(GAS_PAYMENT_META_PROCESSED, meta, &true);
 
         // Update the total gas payment for the message to include the payment
-        self.update_gas_payment_for_message_id(&gas_payment_with_meta.payment)?;
-
-        // Return true to indicate the gas payment was processed for the first time
-        Ok(true)
-    }
+        Self::batch_gas_payment_for_message_id(&mut batch, &gas_payment_with_meta.payment);
 
-    /// Record a gas payment, identified by its metadata, as processed
-    fn store_gas_payment_meta_processed(
-        &self,
-        gas_payment_meta: &InterchainGasPaymentMeta,
-    ) -> Result<()> {
-        self.store_keyed_encodable(GAS_PAYMENT_META_PROCESSED, gas_payment_meta, &true)
+        Ok(Some(batch))
     }
 
     /// Get whether a gas payment, identified by its metadata, has been
@@ -234,19 +283,20 @@ impl HyperlaneDB {
             .unwrap_or(false))
     }
 
-    /// Update the total gas payment for a message to include gas_payment
-    fn update_gas_payment_for_message_id(&self, gas_payment: &InterchainGasPayment) -> Result<()> {
+    /// Add updating the total gas payment for a message to include
+    /// gas_payment to `batch`. The total is accumulated by the DB's merge
+    /// operator rather than read and rewritten.
+    fn batch_gas_payment_for_message_id(
+        batch: &mut TypedBatch,
+        gas_payment: &InterchainGasPayment,
+    ) {
         let InterchainGasPayment {
             message_id,
             payment,
         } = gas_payment;
-        let existing_payment = self.retrieve_gas_payment_for_message_id(*message_id)?;
-        let total = existing_payment + payment;
-
-        info!(message_id=?message_id, gas_payment_amount=?payment, new_total_gas_payment=?total, "Storing gas payment");
-        self.store_keyed_encodable(GAS_PAYMENT_FOR_MESSAGE_ID, &gas_payment.message_id, &total)?;
 
-        Ok(())
+        info!(message_id=?message_id, gas_payment_amount=?payment, "Storing gas payment");
+        batch.add_keyed_u256(GAS_PAYMENT_FOR_MESSAGE_ID, message_id, *payment);
     }
 
     /// Retrieve the total gas payment for a message
@@ -256,3 +306,233 @@ impl HyperlaneDB {
             .unwrap_or(U256::zero()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::db::{BatchOp, DbBatch, InMemoryStore, KeyValueStore, KvIterator};
+    use crate::HyperlaneProtocolError;
+
+    /// A store which fails once it has applied a set number of writes, like a
+    /// process crashing partway through storing. Individual puts are applied
+    /// as they come, while a batch it fails on is not applied at all.
+    #[derive(Debug, Clone, Default)]
+    struct FailingStore {
+        inner: Arc<InMemoryStore>,
+        /// The number of writes left before failing, or None to never fail
+        writes_left: Arc<Mutex<Option<usize>>>,
+        /// The keys of the last batch which failed
+        failed_keys: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl FailingStore {
+        fn fail_after(&self, writes: usize) {
+            *self.writes_left.lock().unwrap() = Some(writes);
+        }
+
+        fn recover(&self) {
+            *self.writes_left.lock().unwrap() = None;
+        }
+
+        /// Use up one write, failing if there are none left
+        fn use_write(&self) -> Result<()> {
+            match &mut *self.writes_left.lock().unwrap() {
+                Some(0) => Err(HyperlaneProtocolError::IoError(io::Error::new(
+                    io::ErrorKind::Other,
+                    "simulated crash",
+                ))
+                .into()),
+                Some(left) => {
+                    *left -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+
+        /// Assert that none of the keys of the batch which failed are stored
+        fn assert_failed_batch_not_stored(&self) {
+            let failed_keys = self.failed_keys.lock().unwrap();
+            assert!(failed_keys.len() > 1);
+            for key in failed_keys.iter() {
+                assert_eq!(self.inner.get(key).unwrap(), None);
+            }
+        }
+    }
+
+    impl KeyValueStore for FailingStore {
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.inner.get(key)
+        }
+
+        fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+            self.use_write()?;
+            self.inner.put(key, value)
+        }
+
+        fn write(&self, batch: DbBatch) -> Result<()> {
+            let ops: Vec<BatchOp> = batch.into_iter().collect();
+            for _ in &ops {
+                if let Err(err) = self.use_write() {
+                    *self.failed_keys.lock().unwrap() = ops
+                        .into_iter()
+                        .map(|op| match op {
+                            BatchOp::Put(key, _) | BatchOp::AddU256(key, _) => key,
+                        })
+                        .collect();
+                    return Err(err);
+                }
+            }
+            self.inner.write(DbBatch(ops))
+        }
+
+        fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a> {
+            self.inner.prefix_iterator(prefix)
+        }
+    }
+
+    fn open(dir: &TempDir) -> HyperlaneDB {
+        HyperlaneDB::new(
+            "mailbox",
+            DB::from_path(dir.path().to_str().unwrap()).unwrap(),
+        )
+    }
+
+    fn open_store(store: &FailingStore) -> HyperlaneDB {
+        HyperlaneDB::new("mailbox", DB::new(store.clone()))
+    }
+
+    fn message(nonce: u32) -> HyperlaneMessage {
+        HyperlaneMessage {
+            nonce,
+            destination: 12,
+            body: vec![1, 2, 3],
+            ..HyperlaneMessage::default()
+        }
+    }
+
+    fn gas_payment(log_index: u64, payment: u64) -> InterchainGasPaymentWithMeta {
+        InterchainGasPaymentWithMeta {
+            payment: InterchainGasPayment {
+                message_id: H256::repeat_byte(1),
+                payment: payment.into(),
+            },
+            meta: InterchainGasPaymentMeta {
+                transaction_hash: H256::repeat_byte(2),
+                log_index: log_index.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn interrupted_message_write_stores_nothing() {
+        let store = FailingStore::default();
+        let db = open_store(&store);
+
+        // Crash after the first of the message's writes
+        store.fail_after(1);
+        assert!(db.store_latest_message(&message(0)).is_err());
+        store.assert_failed_batch_not_stored();
+        assert_eq!(db.message_id_by_nonce(0).unwrap(), None);
+        assert!(db.message_by_id(message(0).id()).unwrap().is_none());
+        assert_eq!(db.retrieve_latest_nonce().unwrap(), None);
+        assert_eq!(db.retrieve_latest_nonce_for_destination(12).unwrap(), None);
+
+        // Retrying after the restart stores every key
+        store.recover();
+        drop(db);
+        let db = open_store(&store);
+        db.store_latest_message(&message(0)).unwrap();
+        db.store_latest_message(&message(1)).unwrap();
+        assert!(db.message_by_nonce(0).unwrap().is_some());
+        assert!(db.message_by_nonce(1).unwrap().is_some());
+        assert_eq!(db.retrieve_latest_nonce().unwrap(), Some(1));
+        assert_eq!(
+            db.retrieve_latest_nonce_for_destination(12).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn interrupted_gas_payment_is_processed_once_after_restart() {
+        let store = FailingStore::default();
+        let db = open_store(&store);
+        let message_id = H256::repeat_byte(1);
+
+        // Crash after marking the payment as processed but before adding it
+        // to the total
+        store.fail_after(1);
+        assert!(db.process_gas_payment(&gas_payment(0, 10)).is_err());
+        store.assert_failed_batch_not_stored();
+        assert_eq!(
+            db.retrieve_gas_payment_for_message_id(message_id).unwrap(),
+            U256::zero()
+        );
+
+        store.recover();
+        drop(db);
+        let db = open_store(&store);
+        assert!(db.process_gas_payment(&gas_payment(0, 10)).unwrap());
+        assert!(!db.process_gas_payment(&gas_payment(0, 10)).unwrap());
+        assert!(db.process_gas_payment(&gas_payment(1, 5)).unwrap());
+        assert!(!db.process_gas_payment(&gas_payment(1, 5)).unwrap());
+        assert_eq!(
+            db.retrieve_gas_payment_for_message_id(message_id).unwrap(),
+            U256::from(15)
+        );
+    }
+
+    #[test]
+    fn concurrent_gas_payments_are_processed_once() {
+        let db = HyperlaneDB::new("mailbox", DB::in_memory());
+        let threads = 8;
+        let barrier = Arc::new(std::sync::Barrier::new(threads));
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let db = db.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    (0..100)
+                        .filter(|&i| db.process_gas_payment(&gas_payment(i, 1)).unwrap())
+                        .count()
+                })
+            })
+            .collect();
+        let processed: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(processed, 100);
+        assert_eq!(
+            db.retrieve_gas_payment_for_message_id(H256::repeat_byte(1))
+                .unwrap(),
+            U256::from(100)
+        );
+    }
+
+    #[test]
+    fn gas_payment_totals_merge_onto_stored_values() {
+        let dir = TempDir::new().unwrap();
+        let db = open(&dir);
+        let message_id = H256::repeat_byte(1);
+        // Totals stored before merges were used are added to
+        db.store_keyed_encodable(GAS_PAYMENT_FOR_MESSAGE_ID, &message_id, &U256::from(7))
+            .unwrap();
+
+        let mut batch = db.batch();
+        HyperlaneDB::batch_gas_payment_for_message_id(&mut batch, &gas_payment(0, 1).payment);
+        HyperlaneDB::batch_gas_payment_for_message_id(&mut batch, &gas_payment(1, 2).payment);
+        db.write(batch).unwrap();
+        drop(db);
+
+        let db = open(&dir);
+        assert_eq!(
+            db.retrieve_gas_payment_for_message_id(message_id).unwrap(),
+            U256::from(10)
+        );
+    }
+}
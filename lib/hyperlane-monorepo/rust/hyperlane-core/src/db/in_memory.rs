@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::sync::{PoisonError, RwLock};
+
+use crate::db::{BatchOp, DbBatch, DbError, KeyValueStore, KvIterator};
+use crate::{Decode, Encode, U256};
+
+type Result<T> = std::result::Result<T, DbError>;
+
+/// A key-value store kept in memory. Nothing is persisted, so it is meant
+/// for tests and ephemeral agents.
+#[derive(Debug, Default)]
+pub struct InMemoryStore(RwLock<BTreeMap<Vec<u8>, Vec<u8>>>);
+
+impl KeyValueStore for InMemoryStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let map = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(map.get(key).cloned())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut map = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        map.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn write(&self, batch: DbBatch) -> Result<()> {
+        let mut map = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        // Apply the batch to a copy of the touched entries first so that a
+        // failing write leaves the store unchanged
+        let mut pending: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for op in batch {
+            match op {
+                BatchOp::Put(key, value) => {
+                    pending.insert(key, value);
+                }
+                BatchOp::AddU256(key, amount) => {
+                    let existing = match pending.get(&key).or_else(|| map.get(&key)) {
+                        Some(value) => U256::read_from(&mut value.as_slice())?,
+                        None => U256::zero(),
+                    };
+                    pending.insert(key, existing.saturating_add(amount).to_vec());
+                }
+            }
+        }
+        map.extend(pending);
+        Ok(())
+    }
+
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a> {
+        let map = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        // Snapshot the entries so the lock is not held while iterating
+        let entries: Vec<_> = map
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Box::new(entries.into_iter())
+    }
+}
@@ -1,29 +1,130 @@
+use std::fmt::Debug;
 use std::path::PathBuf;
 use std::{io, path::Path, sync::Arc};
 
-use rocksdb::{DBIterator, Options, DB as Rocks};
+use rocksdb::{Options, DB as Rocks};
 use tracing::info;
 
 pub use hyperlane_db::*;
+pub use in_memory::*;
 pub use typed_db::*;
 
-use crate::{Decode, Encode, HyperlaneProtocolError};
+use crate::{Decode, Encode, HyperlaneProtocolError, U256};
 
-/// Shared functionality surrounding use of rocksdb
+/// Shared functionality surrounding iteration over the DB
 pub mod iterator;
 
 /// DB operations tied to specific Mailbox
 mod hyperlane_db;
+/// Key-value store kept in memory
+mod in_memory;
+/// Key-value store backed by rocksdb
+mod rocks;
 /// Type-specific db operations
 mod typed_db;
 
+/// A key-value store which can back a [`DB`]. RocksDB is used by default,
+/// see [`DB::from_path`].
+///
+/// Writes must be visible to subsequent reads from any thread, and batches
+/// must be applied atomically.
+pub trait KeyValueStore: Debug + Send + Sync {
+    /// Retrieve the value stored under `key`
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// Atomically apply all writes in `batch`
+    fn write(&self, batch: DbBatch) -> Result<()>;
+
+    /// Iterate over the entries whose keys start with `prefix`, in key order
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a>;
+}
+
+/// An iterator over key-value pairs of a [`KeyValueStore`]
+pub type KvIterator<'a> = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a>;
+
 #[derive(Debug, Clone)]
 /// A KV Store
-pub struct DB(Arc<Rocks>);
+pub struct DB(Arc<dyn KeyValueStore>);
 
+/// Wraps an already opened rocksdb instance. It must have been opened with
+/// [`DB::options`] for merges to work.
 impl From<Rocks> for DB {
     fn from(rocks: Rocks) -> Self {
-        Self(Arc::new(rocks))
+        Self::new(rocks)
+    }
+}
+
+/// A single write in a [`DbBatch`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    /// Store the value under the key
+    Put(Vec<u8>, Vec<u8>),
+    /// Add the amount to the `U256` stored under the key, treating a missing
+    /// value as zero
+    AddU256(Vec<u8>, U256),
+}
+
+/// A set of writes which are applied to the DB atomically by [`DB::write`].
+/// Dropping the batch without writing it discards all of them.
+#[derive(Debug, Default)]
+pub struct DbBatch(Vec<BatchOp>);
+
+fn prefixed_key(prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Vec<u8> {
+    let mut buf = vec![];
+    buf.extend(prefix.as_ref());
+    buf.extend(key.as_ref());
+    buf
+}
+
+impl DbBatch {
+    /// Number of writes in the batch
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the batch has no writes
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Add storing any encodable to the batch
+    pub fn store_encodable<V: Encode>(
+        &mut self,
+        prefix: impl AsRef<[u8]>,
+        key: impl AsRef<[u8]>,
+        value: &V,
+    ) {
+        self.0
+            .push(BatchOp::Put(prefixed_key(prefix, key), value.to_vec()))
+    }
+
+    /// Add storing any encodable with an encodable key to the batch
+    pub fn store_keyed_encodable<K: Encode, V: Encode>(
+        &mut self,
+        prefix: impl AsRef<[u8]>,
+        key: &K,
+        value: &V,
+    ) {
+        self.store_encodable(prefix, key.to_vec(), value)
+    }
+
+    /// Add adding `amount` to the `U256` stored under the key to the batch.
+    /// A missing value counts as zero.
+    pub fn add_keyed_u256<K: Encode>(&mut self, prefix: impl AsRef<[u8]>, key: &K, amount: U256) {
+        self.0
+            .push(BatchOp::AddU256(prefixed_key(prefix, key.to_vec()), amount))
+    }
+}
+
+impl IntoIterator for DbBatch {
+    type Item = BatchOp;
+    type IntoIter = std::vec::IntoIter<BatchOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
     }
 }
 
@@ -55,7 +156,18 @@ pub enum DbError {
 type Result<T> = std::result::Result<T, DbError>;
 
 impl DB {
-    /// Opens db at `db_path` and creates if missing
+    /// Use `store` as the backing key-value store
+    pub fn new(store: impl KeyValueStore + 'static) -> Self {
+        Self(Arc::new(store))
+    }
+
+    /// A DB kept in memory, which is lost when the last handle to it is
+    /// dropped. Useful for tests and ephemeral agents.
+    pub fn in_memory() -> Self {
+        Self::new(InMemoryStore::default())
+    }
+
+    /// Opens a rocksdb db at `db_path` and creates if missing
     #[tracing::instrument(err)]
     pub fn from_path(db_path: &str) -> Result<DB> {
         // Canonicalize ensures existence, so we have to do that, then extend
@@ -72,10 +184,7 @@ impl DB {
             false => info!("Creating db at {path}", path = path.to_str().unwrap()),
         }
 
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-
-        Rocks::open(&opts, &path)
+        Rocks::open(&Self::options(), &path)
             .map_err(|e| DbError::OpeningError {
                 source: e,
                 path: db_path.to_owned(),
@@ -84,14 +193,25 @@ impl DB {
             .map(Into::into)
     }
 
+    /// Options rocksdb is opened with, which create it if missing and
+    /// register the merge operators used by batches
+    pub fn options() -> Options {
+        rocks::options()
+    }
+
+    /// Atomically apply all writes in `batch`
+    pub fn write(&self, batch: DbBatch) -> Result<()> {
+        self.0.write(batch)
+    }
+
     /// Store a value in the DB
     fn _store(&self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
-        Ok(self.0.put(key, value)?)
+        self.0.put(key.as_ref(), value.as_ref())
     }
 
     /// Retrieve a value from the DB
     fn _retrieve(&self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
-        Ok(self.0.get(key)?)
+        self.0.get(key.as_ref())
     }
 
     /// Prefix a key and store in the DB
@@ -101,10 +221,7 @@ impl DB {
         key: impl AsRef<[u8]>,
         value: impl AsRef<[u8]>,
     ) -> Result<()> {
-        let mut buf = vec![];
-        buf.extend(prefix.as_ref());
-        buf.extend(key.as_ref());
-        self._store(buf, value)
+        self._store(prefixed_key(prefix, key), value)
     }
 
     /// Prefix the key and retrieve
@@ -113,10 +230,7 @@ impl DB {
         prefix: impl AsRef<[u8]>,
         key: impl AsRef<[u8]>,
     ) -> Result<Option<Vec<u8>>> {
-        let mut buf = vec![];
-        buf.extend(prefix.as_ref());
-        buf.extend(key.as_ref());
-        self._retrieve(buf)
+        self._retrieve(prefixed_key(prefix, key))
     }
 
     /// Store any encodeable
@@ -161,7 +275,140 @@ impl DB {
     }
 
     /// Get prefix db iterator for `prefix`
-    pub fn prefix_iterator(&self, prefix: impl AsRef<[u8]>) -> DBIterator {
-        self.0.prefix_iterator(prefix)
+    pub fn prefix_iterator(&self, prefix: impl AsRef<[u8]>) -> KvIterator {
+        self.0.prefix_iterator(prefix.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::TempDir;
+
+    use super::iterator::PrefixIterator;
+    use super::*;
+    use crate::{HyperlaneMessage, H256};
+
+    /// Run `test` against every key-value store implementation
+    fn for_each_store(test: impl Fn(DB)) {
+        test(DB::in_memory());
+
+        let dir = TempDir::new().unwrap();
+        test(DB::from_path(dir.path().to_str().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn stores_and_retrieves_values() {
+        for_each_store(|db| {
+            db.store_encodable("prefix_", "key", &7u32).unwrap();
+            assert_eq!(db.retrieve_decodable("prefix_", "key").unwrap(), Some(7u32));
+
+            db.store_keyed_encodable("prefix_", &1u32, &H256::repeat_byte(1))
+                .unwrap();
+            db.store_keyed_encodable("prefix_", &1u32, &H256::repeat_byte(2))
+                .unwrap();
+            assert_eq!(
+                db.retrieve_keyed_decodable("prefix_", &1u32).unwrap(),
+                Some(H256::repeat_byte(2))
+            );
+
+            assert_eq!(
+                db.retrieve_decodable::<u32>("prefix_", "missing").unwrap(),
+                None
+            );
+            assert_eq!(db.retrieve_decodable::<u32>("other_", "key").unwrap(), None);
+        })
+    }
+
+    #[test]
+    fn handles_share_the_store() {
+        for_each_store(|db| {
+            let handle = db.clone();
+            handle.store_encodable("", "key", &1u32).unwrap();
+            assert_eq!(db.retrieve_decodable("", "key").unwrap(), Some(1u32));
+        })
+    }
+
+    #[test]
+    fn applies_batches() {
+        for_each_store(|db| {
+            db.store_keyed_encodable("total_", &1u32, &U256::from(5))
+                .unwrap();
+
+            let mut batch = DbBatch::default();
+            batch.store_encodable("", "a", &1u32);
+            batch.store_encodable("", "a", &2u32);
+            batch.store_keyed_encodable("", &3u32, &4u32);
+            batch.add_keyed_u256("total_", &1u32, U256::from(2));
+            batch.add_keyed_u256("total_", &1u32, U256::from(3));
+            batch.add_keyed_u256("total_", &2u32, U256::from(4));
+            assert_eq!(batch.len(), 6);
+            assert_eq!(db.retrieve_decodable::<u32>("", "a").unwrap(), None);
+
+            db.write(batch).unwrap();
+            assert_eq!(db.retrieve_decodable("", "a").unwrap(), Some(2u32));
+            assert_eq!(db.retrieve_keyed_decodable("", &3u32).unwrap(), Some(4u32));
+            assert_eq!(
+                db.retrieve_keyed_decodable("total_", &1u32).unwrap(),
+                Some(U256::from(10))
+            );
+            assert_eq!(
+                db.retrieve_keyed_decodable("total_", &2u32).unwrap(),
+                Some(U256::from(4))
+            );
+
+            // Stores after adds overwrite the total
+            let mut batch = DbBatch::default();
+            batch.add_keyed_u256("total_", &2u32, U256::from(1));
+            batch.store_keyed_encodable("total_", &2u32, &U256::from(9));
+            db.write(batch).unwrap();
+            assert_eq!(
+                db.retrieve_keyed_decodable("total_", &2u32).unwrap(),
+                Some(U256::from(9))
+            );
+        })
+    }
+
+    #[test]
+    fn iterates_over_prefix_in_key_order() {
+        for_each_store(|db| {
+            db.store_keyed_encodable("b_", &2u32, &2u32).unwrap();
+            db.store_keyed_encodable("b_", &1u32, &1u32).unwrap();
+            db.store_keyed_encodable("a_", &0u32, &0u32).unwrap();
+            db.store_keyed_encodable("c_", &3u32, &3u32).unwrap();
+
+            let entries: Vec<_> = db.prefix_iterator("b_").collect();
+            assert_eq!(
+                entries,
+                vec![
+                    (prefixed_key("b_", 1u32.to_vec()), 1u32.to_vec()),
+                    (prefixed_key("b_", 2u32.to_vec()), 2u32.to_vec()),
+                ]
+            );
+            let values: Vec<u32> =
+                PrefixIterator::new(db.prefix_iterator("b_"), "b_".as_bytes()).collect();
+            assert_eq!(values, vec![1, 2]);
+            assert_eq!(db.prefix_iterator("d_").count(), 0);
+        })
+    }
+
+    #[test]
+    fn stores_messages() {
+        for_each_store(|db| {
+            let db = HyperlaneDB::new("mailbox", db);
+            let message = HyperlaneMessage {
+                nonce: 3,
+                destination: 12,
+                ..HyperlaneMessage::default()
+            };
+
+            db.store_messages(&[message.clone()]).unwrap();
+            assert_eq!(db.message_id_by_nonce(3).unwrap(), Some(message.id()));
+            assert!(db.message_by_nonce(3).unwrap().is_some());
+            assert_eq!(db.retrieve_latest_nonce().unwrap(), Some(3));
+            assert_eq!(
+                db.retrieve_latest_nonce_for_destination(12).unwrap(),
+                Some(3)
+            );
+        })
     }
 }
@@ -0,0 +1,63 @@
+use rocksdb::{MergeOperands, Options, WriteBatch, DB as Rocks};
+
+use crate::db::{BatchOp, DbBatch, DbError, KeyValueStore, KvIterator};
+use crate::{Decode, Encode, U256};
+
+type Result<T> = std::result::Result<T, DbError>;
+
+/// Name of the merge operator which sums `U256` values
+const SUM_U256_MERGE_OPERATOR: &str = "sum_u256";
+
+/// Options rocksdb is opened with, see `DB::options`
+pub(super) fn options() -> Options {
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    opts.set_merge_operator_associative(SUM_U256_MERGE_OPERATOR, sum_u256);
+    opts
+}
+
+/// Merge operator adding every operand to the existing value, all of which are
+/// encoded `U256`s. Values which cannot be decoded fail the merge, which
+/// surfaces as an error when the key is read.
+fn sum_u256(_key: &[u8], existing: Option<&[u8]>, operands: &mut MergeOperands) -> Option<Vec<u8>> {
+    let mut total = match existing {
+        Some(mut value) => U256::read_from(&mut value).ok()?,
+        None => U256::zero(),
+    };
+    for mut operand in operands {
+        total = total.saturating_add(U256::read_from(&mut operand).ok()?);
+    }
+    Some(total.to_vec())
+}
+
+impl KeyValueStore for Rocks {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(Rocks::get(self, key)?)
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        Ok(Rocks::put(self, key, value)?)
+    }
+
+    fn write(&self, batch: DbBatch) -> Result<()> {
+        let mut write_batch = WriteBatch::default();
+        for op in batch {
+            match op {
+                BatchOp::Put(key, value) => write_batch.put(key, value),
+                BatchOp::AddU256(key, amount) => write_batch.merge(key, amount.to_vec()),
+            }
+        }
+        Ok(Rocks::write(self, write_batch)?)
+    }
+
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a> {
+        let prefix = prefix.to_vec();
+        // Without a prefix extractor configured, rocksdb iterates from the
+        // prefix to the end of the keyspace
+        Box::new(
+            Rocks::prefix_iterator(self, &prefix)
+                .take_while(move |(key, _)| key.starts_with(&prefix))
+                .map(|(key, value)| (key.into_vec(), value.into_vec())),
+        )
+    }
+}
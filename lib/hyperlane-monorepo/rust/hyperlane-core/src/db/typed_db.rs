@@ -1,5 +1,5 @@
-use crate::db::{DbError, DB};
-use crate::{Decode, Encode};
+use crate::db::{DbBatch, DbError, DB};
+use crate::{Decode, Encode, U256};
 
 type Result<T> = std::result::Result<T, DbError>;
 
@@ -25,11 +25,22 @@ impl TypedDB {
     }
 
     fn full_prefix(&self, prefix: impl AsRef<[u8]>) -> Vec<u8> {
-        let mut full_prefix = vec![];
-        full_prefix.extend(self.entity.as_ref() as &[u8]);
-        full_prefix.extend("_".as_bytes());
-        full_prefix.extend(prefix.as_ref());
-        full_prefix
+        full_prefix(&self.entity, prefix)
+    }
+
+    /// Start a batch of writes, which are only applied once passed to
+    /// [`TypedDB::write`]
+    pub fn batch(&self) -> TypedBatch {
+        TypedBatch {
+            entity: self.entity.clone(),
+            batch: DbBatch::default(),
+        }
+    }
+
+    /// Atomically apply all writes in `batch`
+    pub fn write(&self, batch: TypedBatch) -> Result<()> {
+        debug_assert_eq!(batch.entity, self.entity);
+        self.db.write(batch.batch)
     }
 
     /// Store encodable value
@@ -73,3 +84,53 @@ impl TypedDB {
             .retrieve_keyed_decodable(self.full_prefix(prefix), key)
     }
 }
+
+fn full_prefix(entity: &str, prefix: impl AsRef<[u8]>) -> Vec<u8> {
+    let mut full_prefix = vec![];
+    full_prefix.extend(entity.as_bytes());
+    full_prefix.extend("_".as_bytes());
+    full_prefix.extend(prefix.as_ref());
+    full_prefix
+}
+
+/// A batch of writes to a `TypedDB`, using the same key structure.
+#[derive(Debug)]
+pub struct TypedBatch {
+    entity: String,
+    batch: DbBatch,
+}
+
+impl TypedBatch {
+    /// Whether the batch has no writes
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+
+    /// Add storing an encodable value to the batch
+    pub fn store_encodable<V: Encode>(
+        &mut self,
+        prefix: impl AsRef<[u8]>,
+        key: impl AsRef<[u8]>,
+        value: &V,
+    ) {
+        self.batch
+            .store_encodable(full_prefix(&self.entity, prefix), key, value)
+    }
+
+    /// Add storing an encodable kv pair to the batch
+    pub fn store_keyed_encodable<K: Encode, V: Encode>(
+        &mut self,
+        prefix: impl AsRef<[u8]>,
+        key: &K,
+        value: &V,
+    ) {
+        self.batch
+            .store_keyed_encodable(full_prefix(&self.entity, prefix), key, value)
+    }
+
+    /// Add adding `amount` to the `U256` stored under the key to the batch
+    pub fn add_keyed_u256<K: Encode>(&mut self, prefix: impl AsRef<[u8]>, key: &K, amount: U256) {
+        self.batch
+            .add_keyed_u256(full_prefix(&self.entity, prefix), key, amount)
+    }
+}
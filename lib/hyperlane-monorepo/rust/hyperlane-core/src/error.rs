@@ -4,9 +4,11 @@ use std::fmt::{Debug, Display, Formatter};
 use std::ops::Deref;
 
 use ethers::prelude::{ContractError, Middleware, ProviderError, SignatureError};
+use ethers::providers::JsonRpcError;
 
 use crate::db::DbError;
 use crate::HyperlaneProviderError;
+use crate::RevertReason;
 use crate::H256;
 
 /// The result of interacting with a chain.
@@ -80,6 +82,12 @@ pub enum ChainCommunicationError {
     /// A transaction submission timed out
     #[error("Transaction submission timed out")]
     TransactionTimeout(),
+    /// A dispatch transaction did not emit a dispatched message
+    #[error("Transaction {0:?} did not dispatch a message")]
+    MessageNotDispatched(H256),
+    /// A call or gas estimate reverted for a known reason
+    #[error("Call reverted: {0}")]
+    Reverted(RevertReason),
 }
 
 impl ChainCommunicationError {
@@ -92,6 +100,40 @@ impl ChainCommunicationError {
     pub fn from_other_boxed<E: HyperlaneCustomError>(err: Box<E>) -> Self {
         Self::Other(HyperlaneCustomErrorWrapper(err))
     }
+
+    /// What kind of failure this is
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            Self::HyperlaneProtocolError(
+                HyperlaneProtocolError::UnknownDomainId(_)
+                | HyperlaneProtocolError::ProcessGasLimitRequired,
+            ) => FailureKind::InvalidConfig,
+            Self::HyperlaneProtocolError(HyperlaneProtocolError::IoError(_)) => {
+                FailureKind::Transient
+            }
+            Self::HyperlaneProtocolError(HyperlaneProtocolError::SignatureError(_)) => {
+                FailureKind::Unknown
+            }
+            Self::ProviderError(err) => err.into(),
+            Self::TransactionDropped(_) | Self::TransactionTimeout() => FailureKind::Transient,
+            Self::DbError(_) => FailureKind::Unknown,
+            Self::MessageNotDispatched(_) | Self::Reverted(_) => FailureKind::Reverted,
+            Self::ContractError(err) | Self::Other(err) => FailureKind::of(err),
+        }
+    }
+
+    /// The reason a call reverted, if it did and the reason is known
+    pub fn revert_reason(&self) -> Option<&RevertReason> {
+        match self {
+            Self::Reverted(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the same operation could succeed
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
 }
 
 impl<M> From<ContractError<M>> for ChainCommunicationError
@@ -125,3 +167,313 @@ pub enum HyperlaneProtocolError {
     #[error("A gas limit was expected for `process` contract call")]
     ProcessGasLimitRequired,
 }
+
+/// The kind of failure behind an error, used to decide whether to retry,
+/// back off, alert or give up.
+#[derive(strum::Display, Copy, Clone, Eq, PartialEq, Debug)]
+#[strum(serialize_all = "snake_case")]
+pub enum FailureKind {
+    /// A temporary transport failure such as a timeout or dropped connection
+    Transient,
+    /// The node or service is rate limiting requests
+    RateLimited,
+    /// The transaction nonce was already used or is out of order
+    NonceConflict,
+    /// The signer cannot pay for the transaction
+    InsufficientFunds,
+    /// The contract call reverted
+    Reverted,
+    /// The request can never succeed with the current configuration, e.g. an
+    /// unknown domain, a bad API key or an unsupported RPC method
+    InvalidConfig,
+    /// The queried block range is too large or matches too many logs, so it
+    /// has to be split up
+    RangeTooLarge,
+    /// The error could not be classified
+    Unknown,
+}
+
+/// Error message fragments for each kind of failure, checked in order.
+/// Messages are lowercased before matching.
+const FAILURE_MESSAGES: &[(FailureKind, &[&str])] = &[
+    // The providers' exact wording, since looser patterns such as "block
+    // range" also match errors which shrinking the range cannot fix, like a
+    // range beyond the head block. These come first as some of them also
+    // mention timeouts.
+    (
+        FailureKind::RangeTooLarge,
+        &[
+            // Infura
+            "query returned more than",
+            // Alchemy
+            "log response size exceeded",
+            "query timeout exceeded",
+            // Ankr
+            "block range is too wide",
+            // Geth based chains such as BSC and Polygon
+            "exceed maximum block range",
+            // QuickNode
+            "eth_getlogs is limited to",
+            "block range too large",
+            "range is too large",
+            "too many blocks",
+        ],
+    ),
+    (
+        FailureKind::RateLimited,
+        &[
+            "rate limit",
+            "rate exceeded",
+            "too many requests",
+            "request limit",
+            "daily request count exceeded",
+            "exceeded the quota",
+            "compute units per second",
+            "capacity exceeded",
+            "slow down",
+            "slowdown",
+        ],
+    ),
+    (
+        FailureKind::NonceConflict,
+        &[
+            "nonce too low",
+            "nonce too high",
+            "nonce has already been used",
+            "already known",
+            "known transaction",
+            "replacement transaction underpriced",
+        ],
+    ),
+    (
+        FailureKind::InsufficientFunds,
+        &["insufficient funds", "insufficient balance"],
+    ),
+    (
+        FailureKind::Reverted,
+        &["revert", "invalid opcode", "gas required exceeds allowance"],
+    ),
+    (
+        FailureKind::InvalidConfig,
+        &[
+            "method not found",
+            "invalid argument",
+            "invalid params",
+            "does not exist/is not available",
+            "unsupported rpc",
+            "unauthorized",
+            "forbidden",
+            "invalid api key",
+            "access denied",
+            "no such bucket",
+            // S3 error codes and missing AWS credentials
+            "accessdenied",
+            "nosuchbucket",
+            "invalidaccesskeyid",
+            "signaturedoesnotmatch",
+            "aws_access_key_id",
+            "aws credentials",
+        ],
+    ),
+    (
+        FailureKind::Transient,
+        &[
+            "timeout",
+            "timed out",
+            "connection",
+            "bad gateway",
+            "service unavailable",
+            "temporarily unavailable",
+            "unexpected eof",
+            "broken pipe",
+            "dns error",
+            "dropped",
+        ],
+    ),
+];
+
+impl FailureKind {
+    /// Classify any error by its type where known and by its messages
+    /// otherwise. The whole source chain is considered.
+    pub fn of(err: &(dyn StdError + 'static)) -> Self {
+        let mut messages = String::new();
+        let mut current = Some(err);
+        while let Some(err) = current {
+            if let Some(err) = err.downcast_ref::<ChainCommunicationError>() {
+                return err.kind();
+            }
+            if let Some(err) = err.downcast_ref::<JsonRpcError>() {
+                return err.into();
+            }
+            messages.push_str(&err.to_string());
+            messages.push('\n');
+            current = err.source();
+        }
+        Self::from_message(&messages)
+    }
+
+    /// Classify an error message
+    pub fn from_message(message: &str) -> Self {
+        let message = message.to_lowercase();
+        FAILURE_MESSAGES
+            .iter()
+            .find(|(_, fragments)| fragments.iter().any(|f| message.contains(f)))
+            .map(|(kind, _)| *kind)
+            .unwrap_or(Self::Unknown)
+    }
+
+    /// Whether retrying the same operation could succeed. Transient failures
+    /// and rate limits should be retried with a backoff, and nonce conflicts
+    /// resolve once pending transactions are included.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Transient | Self::RateLimited | Self::NonceConflict | Self::Unknown
+        )
+    }
+
+    /// Whether the failure needs an operator to intervene, e.g. by funding a
+    /// signer or fixing the configuration
+    pub fn needs_attention(&self) -> bool {
+        matches!(self, Self::InsufficientFunds | Self::InvalidConfig)
+    }
+}
+
+impl From<&JsonRpcError> for FailureKind {
+    fn from(err: &JsonRpcError) -> Self {
+        match err.code {
+            // Limit exceeded, which Infura also returns for queries matching
+            // too many logs
+            -32005 => match Self::from_message(&err.message) {
+                Self::RangeTooLarge => Self::RangeTooLarge,
+                _ => Self::RateLimited,
+            },
+            429 => Self::RateLimited,
+            // Invalid request, method not found and invalid params
+            -32600 | -32601 | -32602 => Self::InvalidConfig,
+            // Execution reverted
+            3 => Self::Reverted,
+            _ => Self::from_message(&err.message),
+        }
+    }
+}
+
+impl From<&ProviderError> for FailureKind {
+    fn from(err: &ProviderError) -> Self {
+        match err {
+            ProviderError::HTTPError(err) => match err.status().map(|status| status.as_u16()) {
+                Some(429) => Self::RateLimited,
+                Some(401 | 403) => Self::InvalidConfig,
+                _ => Self::Transient,
+            },
+            ProviderError::SerdeJson(_) => Self::Transient,
+            ProviderError::UnsupportedRPC | ProviderError::UnsupportedNodeClient => {
+                Self::InvalidConfig
+            }
+            ProviderError::EnsError(_) | ProviderError::EnsNotOwned(_) => Self::InvalidConfig,
+            _ => Self::of(err),
+        }
+    }
+}
+
+impl<M> From<&ContractError<M>> for FailureKind
+where
+    M: Middleware + 'static,
+{
+    fn from(err: &ContractError<M>) -> Self {
+        match err {
+            ContractError::Revert(_) => Self::Reverted,
+            ContractError::DecodingError(_)
+            | ContractError::AbiError(_)
+            | ContractError::DetokenizationError(_)
+            | ContractError::ContractNotDeployed => Self::InvalidConfig,
+            _ => Self::of(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classifies_messages() {
+        let cases = [
+            ("429 Too Many Requests", FailureKind::RateLimited),
+            ("nonce too low", FailureKind::NonceConflict),
+            (
+                "insufficient funds for gas * price + value",
+                FailureKind::InsufficientFunds,
+            ),
+            ("execution reverted: !module", FailureKind::Reverted),
+            (
+                "the method eth_foo does not exist/is not available",
+                FailureKind::InvalidConfig,
+            ),
+            (
+                "Request ID: None Body: <Error><Code>AccessDenied</Code></Error>",
+                FailureKind::InvalidConfig,
+            ),
+            ("connection reset by peer", FailureKind::Transient),
+            (
+                "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range",
+                FailureKind::RangeTooLarge,
+            ),
+            (
+                "block range extends beyond current head block",
+                FailureKind::Unknown,
+            ),
+            ("something else", FailureKind::Unknown),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(FailureKind::from_message(message), kind, "{message}");
+        }
+    }
+
+    #[test]
+    fn classifies_chain_errors() {
+        let err = ChainCommunicationError::from(HyperlaneProtocolError::UnknownDomainId(7));
+        assert_eq!(err.kind(), FailureKind::InvalidConfig);
+        assert!(!err.is_retryable());
+
+        let err = ChainCommunicationError::TransactionTimeout();
+        assert_eq!(err.kind(), FailureKind::Transient);
+        assert!(err.is_retryable());
+
+        let err = ChainCommunicationError::from_other(JsonRpcError {
+            code: -32000,
+            message: "insufficient funds for transfer".into(),
+            data: None,
+        });
+        assert_eq!(err.kind(), FailureKind::InsufficientFunds);
+        assert!(err.kind().needs_attention());
+
+        let err = ChainCommunicationError::from(ProviderError::CustomError(
+            "Request rate limited".into(),
+        ));
+        assert_eq!(err.kind(), FailureKind::RateLimited);
+    }
+
+    #[test]
+    fn classifies_json_rpc_errors() {
+        let kind = |code, message: &str| {
+            FailureKind::from(&JsonRpcError {
+                code,
+                message: message.into(),
+                data: None,
+            })
+        };
+        assert_eq!(
+            kind(-32005, "project ID request rate exceeded"),
+            FailureKind::RateLimited
+        );
+        assert_eq!(
+            kind(-32005, "query returned more than 10000 results"),
+            FailureKind::RangeTooLarge
+        );
+        assert_eq!(kind(-32602, "invalid params"), FailureKind::InvalidConfig);
+        assert_eq!(kind(3, "execution reverted"), FailureKind::Reverted);
+        assert_eq!(kind(-32000, "header not found"), FailureKind::Unknown);
+        assert!(!FailureKind::RangeTooLarge.is_retryable());
+    }
+}
@@ -6,7 +6,7 @@
 #![forbid(where_clauses_object_safety)]
 
 pub use chain::*;
-pub use error::{ChainCommunicationError, ChainResult, HyperlaneProtocolError};
+pub use error::{ChainCommunicationError, ChainResult, FailureKind, HyperlaneProtocolError};
 pub use identifiers::HyperlaneIdentifier;
 pub use traits::*;
 pub use types::*;
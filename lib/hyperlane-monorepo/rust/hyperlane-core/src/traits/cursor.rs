@@ -1,4 +1,6 @@
-use crate::ChainResult;
+use std::time::Duration;
+
+use crate::{ChainCommunicationError, ChainResult};
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 
@@ -31,4 +33,18 @@ pub trait SyncBlockRangeCursor {
     /// so the next range fetched will be from `start_from`. Note that it is a
     /// no-op if a later block value is specified.
     fn backtrack(&mut self, start_from: u32);
+
+    /// Whether the last range returned by `next_range` reached the highest
+    /// block we may scrape.
+    fn is_caught_up(&self) -> bool;
+
+    /// Report that the range last returned by `next_range` was fetched,
+    /// finding `events` events in `elapsed`. Allows the cursor to adapt the
+    /// size of the ranges it returns.
+    fn range_fetched(&mut self, events: usize, elapsed: Duration);
+
+    /// Report that fetching the range last returned by `next_range` failed.
+    /// The range will be returned again, shrunk if the error indicates the
+    /// provider rejected it for being too large.
+    fn range_failed(&mut self, error: &ChainCommunicationError);
 }
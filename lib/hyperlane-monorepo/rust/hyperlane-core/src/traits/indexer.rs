@@ -8,9 +8,14 @@ use std::fmt::Debug;
 
 use async_trait::async_trait;
 use auto_impl::auto_impl;
+use futures_util::stream::BoxStream;
 
 use crate::{ChainResult, HyperlaneMessage, InterchainGasPaymentWithMeta, LogMeta, H256};
 
+/// A stream of finalized events delivered by a subscription. The stream ends
+/// when the subscription is closed, e.g. because the connection dropped.
+pub type EventStream<T> = BoxStream<'static, T>;
+
 /// Interface for an indexer.
 #[async_trait]
 #[auto_impl(&, Box, Arc)]
@@ -38,6 +43,15 @@ pub trait MailboxIndexer: Indexer {
         from: u32,
         to: u32,
     ) -> ChainResult<Vec<(H256, LogMeta)>>;
+
+    /// Subscribe to messages as they are dispatched, yielding them once they
+    /// have reached finality. Returns `None` if the indexer's connection
+    /// does not support subscriptions.
+    async fn subscribe_messages(
+        &self,
+    ) -> ChainResult<Option<EventStream<(HyperlaneMessage, LogMeta)>>> {
+        Ok(None)
+    }
 }
 
 /// Interface for InterchainGasPaymaster contract indexer.
@@ -51,4 +65,13 @@ pub trait InterchainGasPaymasterIndexer: Indexer {
         from_block: u32,
         to_block: u32,
     ) -> ChainResult<Vec<InterchainGasPaymentWithMeta>>;
+
+    /// Subscribe to gas payments as they are made, yielding them once they
+    /// have reached finality. Returns `None` if the indexer's connection
+    /// does not support subscriptions.
+    async fn subscribe_gas_payments(
+        &self,
+    ) -> ChainResult<Option<EventStream<InterchainGasPaymentWithMeta>>> {
+        Ok(None)
+    }
 }
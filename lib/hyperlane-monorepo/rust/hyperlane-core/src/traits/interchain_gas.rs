@@ -3,10 +3,39 @@ use std::fmt::Debug;
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 
-use crate::HyperlaneContract;
+use crate::{ChainResult, HyperlaneContract, TxOutcome, H256, U256};
 
 /// Interface for the InterchainGasPaymaster chain contract.
 /// Allows abstraction over different chains.
 #[async_trait]
 #[auto_impl(&, Box, Arc)]
-pub trait InterchainGasPaymaster: HyperlaneContract + Send + Sync + Debug {}
+pub trait InterchainGasPaymaster: HyperlaneContract + Send + Sync + Debug {
+    /// Quote the payment in origin chain native tokens required for
+    /// `gas_amount` of gas on the destination chain
+    async fn quote_gas_payment(
+        &self,
+        destination_domain: u32,
+        gas_amount: U256,
+    ) -> ChainResult<U256>;
+
+    /// Pay `payment` native tokens for `gas_amount` of destination gas for a
+    /// message. Any overpayment is refunded to `refund_address`.
+    async fn pay_for_gas(
+        &self,
+        message_id: H256,
+        destination_domain: u32,
+        gas_amount: U256,
+        payment: U256,
+        refund_address: H256,
+    ) -> ChainResult<TxOutcome>;
+
+    /// The native token balance of the contract, i.e. the fees which can be
+    /// claimed
+    async fn balance(&self) -> ChainResult<U256>;
+
+    /// The address claimed fees are sent to
+    async fn beneficiary(&self) -> ChainResult<H256>;
+
+    /// Send the contract's balance to the beneficiary
+    async fn claim(&self, tx_gas_limit: Option<U256>) -> ChainResult<TxOutcome>;
+}
@@ -1,11 +1,10 @@
 use std::fmt::Debug;
-use std::num::NonZeroU64;
 
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 
 use crate::{
-    traits::TxOutcome, utils::domain_hash, ChainResult, Checkpoint, HyperlaneContract,
+    traits::TxOutcome, utils::domain_hash, ChainResult, Checkpoint, Finality, HyperlaneContract,
     HyperlaneMessage, TxCostEstimate, H256, U256,
 };
 
@@ -27,9 +26,9 @@ pub trait Mailbox: HyperlaneContract + Send + Sync + Debug {
 
     /// Get the latest checkpoint.
     ///
-    /// - `lag` is how far behind the current block to query, if not specified
-    ///   it will query at the latest block.
-    async fn latest_checkpoint(&self, lag: Option<NonZeroU64>) -> ChainResult<Checkpoint>;
+    /// - `finality` determines the block to query, `Finality::Blocks(0)`
+    ///   queries at the latest block.
+    async fn latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint>;
 
     /// Fetch the current default interchain security module value
     async fn default_ism(&self) -> ChainResult<H256>;
@@ -37,6 +36,15 @@ pub trait Mailbox: HyperlaneContract + Send + Sync + Debug {
     /// Get the latest checkpoint.
     async fn recipient_ism(&self, recipient: H256) -> ChainResult<H256>;
 
+    /// Dispatch a message with `body` to `recipient` on the destination
+    /// domain. Returns the dispatched message.
+    async fn dispatch(
+        &self,
+        destination_domain: u32,
+        recipient: H256,
+        body: &[u8],
+    ) -> ChainResult<(HyperlaneMessage, TxOutcome)>;
+
     /// Process a message with a proof against the provided signed checkpoint
     async fn process(
         &self,
@@ -7,6 +7,7 @@ pub use mailbox::*;
 pub use multisig_ism::*;
 pub use provider::*;
 pub use signing::*;
+pub use validator_announce::*;
 
 mod cursor;
 mod deployed;
@@ -17,15 +18,36 @@ mod mailbox;
 mod multisig_ism;
 mod provider;
 mod signing;
+mod validator_announce;
 
 /// The result of a transaction
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Default)]
 pub struct TxOutcome {
     /// The txid
     pub txid: crate::H256,
     /// True if executed, false otherwise (reverted, etc.)
     pub executed: bool,
-    // TODO: more? What can be abstracted across all chains?
+    /// Amount of gas used by the transaction
+    pub gas_used: crate::U256,
+    /// Price paid per unit of gas, if known
+    pub effective_gas_price: Option<crate::U256>,
+    /// Number of the block the transaction was included in
+    pub block_number: Option<u64>,
+    /// Hash of the block the transaction was included in
+    pub block_hash: Option<crate::H256>,
+    /// Data returned by the transaction if it reverted, if known
+    pub revert_data: Option<Vec<u8>>,
+    /// The decoded reason the transaction reverted, if known
+    pub revert_reason: Option<crate::RevertReason>,
+}
+
+impl TxOutcome {
+    /// The amount of native tokens paid for the transaction, if the gas price
+    /// is known
+    pub fn cost(&self) -> Option<crate::U256> {
+        self.effective_gas_price
+            .map(|price| self.gas_used.saturating_mul(price))
+    }
 }
 
 impl From<ethers::prelude::TransactionReceipt> for TxOutcome {
@@ -33,6 +55,12 @@ impl From<ethers::prelude::TransactionReceipt> for TxOutcome {
         Self {
             txid: t.transaction_hash,
             executed: t.status.unwrap().low_u32() == 1,
+            gas_used: t.gas_used.unwrap_or_default(),
+            effective_gas_price: t.effective_gas_price,
+            block_number: t.block_number.map(|n| n.as_u64()),
+            block_hash: t.block_hash,
+            revert_data: None,
+            revert_reason: None,
         }
     }
 }
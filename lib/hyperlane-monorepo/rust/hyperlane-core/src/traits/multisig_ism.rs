@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 use async_trait::async_trait;
@@ -5,7 +6,7 @@ use auto_impl::auto_impl;
 
 use crate::{
     accumulator::merkle::Proof, ChainResult, HyperlaneContract, HyperlaneMessage,
-    MultisigSignedCheckpoint, H256,
+    MultisigSignedCheckpoint, SignatureWithSigner, H256,
 };
 
 /// Interface for the MultisigIsm chain contract. Allows abstraction over
@@ -28,3 +29,118 @@ pub trait MultisigIsm: HyperlaneContract + Send + Sync + Debug {
         proof: &Proof,
     ) -> Vec<u8>;
 }
+
+/// Multisig ISM metadata in the layout of the EVM multisig ISM, which the
+/// ISMs of other chains use as well: the checkpoint root and index, the
+/// mailbox, the merkle proof, the threshold, the signatures in validator
+/// order and the validators, all without padding.
+///
+/// Panics if the number of signatures is not the threshold or a signer is
+/// not one of `validators`.
+pub fn multisig_ism_metadata(
+    validators: &[H256],
+    threshold: u8,
+    checkpoint: &MultisigSignedCheckpoint,
+    proof: &Proof,
+) -> Vec<u8> {
+    assert_eq!(threshold as usize, checkpoint.signatures.len());
+    let proof_bytes: Vec<u8> = proof.path.iter().flat_map(|x| x.0).collect();
+    let signature_bytes = order_signatures(validators, &checkpoint.signatures).concat();
+    let validator_bytes: Vec<u8> = validators.iter().flat_map(|x| x.0).collect();
+
+    [
+        checkpoint.checkpoint.root.0.to_vec(),
+        checkpoint.checkpoint.index.to_be_bytes().to_vec(),
+        checkpoint.checkpoint.mailbox_address.0.to_vec(),
+        proof_bytes,
+        vec![threshold],
+        signature_bytes,
+        validator_bytes,
+    ]
+    .concat()
+}
+
+/// Orders `signatures` by the signers according to the `desired_order`.
+/// Returns a Vec of the signature raw bytes in the correct order.
+/// Panics if any signers in `signatures` are not present in `desired_order`
+pub fn order_signatures(
+    desired_order: &[H256],
+    signatures: &[SignatureWithSigner],
+) -> Vec<Vec<u8>> {
+    // Signer address => index to sort by
+    let ordering_map: HashMap<H256, usize> = desired_order
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, a)| (a, index))
+        .collect();
+
+    let mut ordered_signatures: Vec<(usize, &SignatureWithSigner)> = signatures
+        .iter()
+        .map(|s| (*ordering_map.get(&H256::from(s.signer)).unwrap(), s))
+        .collect();
+    ordered_signatures.sort_by_key(|s| s.0);
+    ordered_signatures
+        .iter()
+        .map(|s| s.1.signature.to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Checkpoint, Decode, H160};
+
+    use super::*;
+
+    fn signature(signer: H160, byte: u8) -> SignatureWithSigner {
+        let mut bytes = [byte; 65];
+        bytes[64] = 27;
+        SignatureWithSigner {
+            signature: Decode::read_from(&mut &bytes[..]).unwrap(),
+            signer,
+        }
+    }
+
+    #[test]
+    fn formats_metadata() {
+        let (first, second) = (H160::repeat_byte(1), H160::repeat_byte(2));
+        let validators = [H256::from(first), H256::from(second)];
+        let checkpoint = MultisigSignedCheckpoint {
+            checkpoint: Checkpoint {
+                mailbox_address: H256::repeat_byte(3),
+                mailbox_domain: 1000,
+                root: H256::repeat_byte(4),
+                index: 5,
+            },
+            // Signatures are ordered by validator
+            signatures: vec![signature(second, 0xbb), signature(first, 0xaa)],
+        };
+        let proof = Proof {
+            leaf: H256::zero(),
+            index: 5,
+            path: [H256::repeat_byte(6); 32],
+        };
+
+        let metadata = multisig_ism_metadata(&validators, 2, &checkpoint, &proof);
+        assert_eq!(metadata.len(), 32 + 4 + 32 + 32 * 32 + 1 + 2 * 65 + 2 * 32);
+        assert_eq!(&metadata[..32], &[4; 32]);
+        assert_eq!(&metadata[32..36], &5u32.to_be_bytes());
+        assert_eq!(&metadata[36..68], &[3; 32]);
+        assert_eq!(&metadata[68..100], &[6; 32]);
+        let rest = &metadata[68 + 32 * 32..];
+        assert_eq!(rest[0], 2);
+        assert_eq!(&rest[1..65], &[0xaa; 64]);
+        assert_eq!(&rest[66..130], &[0xbb; 64]);
+        assert_eq!(&rest[131..163], validators[0].as_bytes());
+        assert_eq!(&rest[163..], validators[1].as_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_signatures_of_unknown_validators() {
+        order_signatures(
+            &[H256::from(H160::repeat_byte(1))],
+            &[signature(H160::repeat_byte(2), 0xaa)],
+        );
+    }
+}
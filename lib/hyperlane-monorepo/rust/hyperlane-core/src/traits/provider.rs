@@ -22,6 +22,26 @@ pub trait HyperlaneProvider: HyperlaneChain + Send + Sync + Debug {
     /// Get txn info for a given txn hash
     async fn get_txn_by_hash(&self, hash: &H256) -> ChainResult<TxnInfo>;
 
+    /// Get block info for many block hashes, in the same order. Providers
+    /// which support batching fetch them with as few requests as possible.
+    async fn get_blocks_by_hash(&self, hashes: &[H256]) -> ChainResult<Vec<BlockInfo>> {
+        let mut blocks = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            blocks.push(self.get_block_by_hash(hash).await?);
+        }
+        Ok(blocks)
+    }
+
+    /// Get txn info for many txn hashes, in the same order. Providers which
+    /// support batching fetch them with as few requests as possible.
+    async fn get_txns_by_hash(&self, hashes: &[H256]) -> ChainResult<Vec<TxnInfo>> {
+        let mut txns = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            txns.push(self.get_txn_by_hash(hash).await?);
+        }
+        Ok(txns)
+    }
+
     /// Returns whether a contract exists at the provided address
     async fn is_contract(&self, address: &H256) -> ChainResult<bool>;
 }
@@ -38,4 +58,7 @@ pub enum HyperlaneProviderError {
     /// Could not find a transaction, block, or other object
     #[error("Could not find object from provider with hash {0:?}")]
     CouldNotFindObjectByHash(H256),
+    /// Could not find a block by its number
+    #[error("Could not find block {0} from provider")]
+    CouldNotFindBlockByNumber(u64),
 }
@@ -0,0 +1,90 @@
+use strum::{EnumString, IntoStaticStr};
+
+/// A block tag which nodes resolve to a recent block that is unlikely to be
+/// reorged.
+#[derive(EnumString, IntoStaticStr, strum::Display, Copy, Clone, Eq, PartialEq, Debug)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum FinalityTag {
+    /// The latest block which is safe from reorgs under honest majority
+    Safe,
+    /// The latest block which has been finalized by the consensus
+    Finalized,
+}
+
+/// How the latest final block of a chain is determined.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Finality {
+    /// A fixed number of blocks behind the head. `Blocks(0)` is the head.
+    Blocks(u32),
+    /// The block the node resolves `tag` to, or `fallback` blocks behind the
+    /// head if the node does not support the tag.
+    Tag {
+        /// The block tag to query
+        tag: FinalityTag,
+        /// Number of blocks behind the head to use if the tag is unsupported
+        fallback: u32,
+    },
+}
+
+impl Default for Finality {
+    fn default() -> Self {
+        Self::Blocks(0)
+    }
+}
+
+impl Finality {
+    /// Use `tag` if given and otherwise `blocks` behind the head. `blocks` is
+    /// also the fallback where the tag is unsupported.
+    pub fn new(blocks: u32, tag: Option<FinalityTag>) -> Self {
+        match tag {
+            Some(tag) => Self::Tag {
+                tag,
+                fallback: blocks,
+            },
+            None => Self::Blocks(blocks),
+        }
+    }
+
+    /// Whether the head of the chain is considered final
+    pub fn is_head(&self) -> bool {
+        *self == Self::Blocks(0)
+    }
+
+    /// The number of blocks behind the head which are considered final when
+    /// no tag is used
+    pub fn blocks(&self) -> u32 {
+        match *self {
+            Self::Blocks(blocks) => blocks,
+            Self::Tag { fallback, .. } => fallback,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_tags() {
+        assert_eq!("safe".parse(), Ok(FinalityTag::Safe));
+        assert_eq!("Finalized".parse(), Ok(FinalityTag::Finalized));
+        assert!("latest".parse::<FinalityTag>().is_err());
+        assert_eq!(FinalityTag::Finalized.to_string(), "finalized");
+    }
+
+    #[test]
+    fn falls_back_to_blocks() {
+        assert_eq!(Finality::new(20, None), Finality::Blocks(20));
+        let finality = Finality::new(20, Some(FinalityTag::Safe));
+        assert_eq!(
+            finality,
+            Finality::Tag {
+                tag: FinalityTag::Safe,
+                fallback: 20
+            }
+        );
+        assert_eq!(finality.blocks(), 20);
+        assert!(!finality.is_head());
+        assert!(Finality::default().is_head());
+    }
+}
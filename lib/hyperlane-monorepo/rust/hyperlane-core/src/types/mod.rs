@@ -3,16 +3,20 @@ pub use primitive_types::{H128, H160, H256, H512, U128, U256, U512};
 pub use announcement::*;
 pub use chain_data::*;
 pub use checkpoint::*;
+pub use finality::*;
 pub use log_metadata::*;
 pub use message::*;
+pub use revert::*;
 
 use crate::{Decode, Encode, HyperlaneProtocolError};
 
 mod announcement;
 mod chain_data;
 mod checkpoint;
+mod finality;
 mod log_metadata;
 mod message;
+mod revert;
 
 /// Unified 32-byte identifier with convenience tooling for handling
 /// 20-byte ids (e.g ethereum addresses)
@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+
+use crate::U256;
+
+/// The longest revert message used as a metrics label
+const MAX_LABEL_LEN: usize = 64;
+
+/// The decoded reason a call or transaction reverted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// `Error(string)`, from `require` or `revert` with a message
+    Message(String),
+    /// `Panic(uint256)`, from a failed assertion, arithmetic overflow, out of
+    /// bounds access etc.
+    Panic(U256),
+    /// A custom error from one of the known ABIs
+    Custom {
+        /// The name of the error
+        name: String,
+        /// The decoded arguments of the error
+        args: Vec<String>,
+    },
+    /// Revert data which could not be decoded, empty if the call reverted
+    /// without data
+    Unknown(Vec<u8>),
+}
+
+impl RevertReason {
+    /// A description of a `Panic(uint256)` code, see
+    /// https://docs.soliditylang.org/en/latest/control-structures.html#panic-via-assert-and-error-via-require
+    pub fn panic_description(code: U256) -> Option<&'static str> {
+        if code > U256::from(u8::MAX) {
+            return None;
+        }
+        Some(match code.low_u32() {
+            0x00 => "generic compiler panic",
+            0x01 => "assertion failed",
+            0x11 => "arithmetic overflow or underflow",
+            0x12 => "division or modulo by zero",
+            0x21 => "invalid enum value",
+            0x22 => "invalid storage byte array encoding",
+            0x31 => "pop on empty array",
+            0x32 => "array index out of bounds",
+            0x41 => "out of memory",
+            0x51 => "call to uninitialized internal function",
+            _ => return None,
+        })
+    }
+
+    /// A short description of the reason which is suitable as a metrics
+    /// label, i.e. it does not include arguments which vary between calls
+    pub fn label(&self) -> String {
+        match self {
+            Self::Message(message) => message.chars().take(MAX_LABEL_LEN).collect(),
+            Self::Panic(code) => format!("panic_{code:#x}"),
+            Self::Custom { name, .. } => name.clone(),
+            Self::Unknown(data) if data.len() >= 4 => format!("0x{}", hex::encode(&data[..4])),
+            Self::Unknown(_) => "unknown".to_owned(),
+        }
+    }
+}
+
+impl Display for RevertReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Message(message) => write!(f, "{message}"),
+            Self::Panic(code) => match Self::panic_description(*code) {
+                Some(description) => write!(f, "panic {code:#x} ({description})"),
+                None => write!(f, "panic {code:#x}"),
+            },
+            Self::Custom { name, args } => write!(f, "{name}({})", args.join(", ")),
+            Self::Unknown(data) if data.is_empty() => write!(f, "reverted without data"),
+            Self::Unknown(data) => write!(f, "unknown revert data 0x{}", hex::encode(data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn describes_reasons() {
+        let reason = RevertReason::Panic(U256::from(0x11));
+        assert_eq!(
+            reason.to_string(),
+            "panic 0x11 (arithmetic overflow or underflow)"
+        );
+        assert_eq!(reason.label(), "panic_0x11");
+
+        let reason = RevertReason::Custom {
+            name: "InsufficientCollateral".into(),
+            args: vec!["1".into(), "2".into()],
+        };
+        assert_eq!(reason.to_string(), "InsufficientCollateral(1, 2)");
+        assert_eq!(reason.label(), "InsufficientCollateral");
+
+        let reason = RevertReason::Unknown(vec![0xde, 0xad, 0xbe, 0xef, 0x01]);
+        assert_eq!(reason.to_string(), "unknown revert data 0xdeadbeef01");
+        assert_eq!(reason.label(), "0xdeadbeef");
+        assert_eq!(RevertReason::Unknown(vec![]).label(), "unknown");
+    }
+}
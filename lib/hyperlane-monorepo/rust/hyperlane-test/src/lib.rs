@@ -7,5 +7,8 @@
 /// Mock contracts
 pub mod mocks;
 
+/// In-memory mock chain
+pub mod mock_chain;
+
 /// Testing utilities
 pub mod test_utils;
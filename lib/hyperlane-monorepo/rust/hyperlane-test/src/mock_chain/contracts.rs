@@ -0,0 +1,715 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::prelude::Signature;
+
+use hyperlane_core::{
+    accumulator::{incremental::IncrementalMerkle, merkle::Proof, TREE_DEPTH},
+    multisig_ism_metadata, Announcement, BlockInfo, ChainCommunicationError, ChainResult,
+    Checkpoint, Finality, HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneMessage,
+    HyperlaneProvider, HyperlaneProviderError, Indexer, InterchainGasPaymaster,
+    InterchainGasPaymasterIndexer, InterchainGasPayment, InterchainGasPaymentMeta,
+    InterchainGasPaymentWithMeta, LogMeta, Mailbox, MailboxIndexer, MultisigIsm,
+    MultisigSignedCheckpoint, RawHyperlaneMessage, RevertReason, SignedType, TxCostEstimate,
+    TxOutcome, TxnInfo, TxnReceiptInfo, ValidatorAnnounce, H256, U256,
+};
+
+use super::ledger::{MockCall, MockChain, MockConfig, MockEvent, MockState};
+
+/// Gas used to dispatch a message, plus `GAS_PER_BYTE` per byte of its body
+const DISPATCH_GAS: u64 = 80_000;
+/// Gas used to process a message, plus `GAS_PER_BYTE` per byte of its body
+const PROCESS_GAS: u64 = 150_000;
+/// Gas used per byte of a message body
+const GAS_PER_BYTE: u64 = 16;
+/// Gas used to pay for gas
+const PAY_FOR_GAS_GAS: u64 = 60_000;
+/// Gas used to claim IGP fees
+const CLAIM_GAS: u64 = 30_000;
+/// Gas used to announce a storage location
+const ANNOUNCE_GAS: u64 = 70_000;
+
+/// Length of the fixed size part of multisig ISM metadata: the checkpoint
+/// root and index, the mailbox, the merkle proof and the threshold
+const METADATA_HEADER_LEN: usize = 32 + 4 + 32 + 32 * TREE_DEPTH + 1;
+/// Length of a signature in multisig ISM metadata
+const SIGNATURE_LEN: usize = 65;
+
+fn revert(reason: &str) -> RevertReason {
+    RevertReason::Message(reason.into())
+}
+
+/// A mailbox on a `MockChain`
+#[derive(Debug)]
+pub struct MockChainMailbox {
+    chain: Arc<MockChain>,
+    address: H256,
+    sender: H256,
+}
+
+impl MockChainMailbox {
+    /// Create a mailbox at `address`, which sends transactions from the zero
+    /// address
+    pub fn new(chain: Arc<MockChain>, address: H256) -> Self {
+        chain.deploy(address);
+        Self {
+            chain,
+            address,
+            sender: H256::zero(),
+        }
+    }
+
+    /// Send transactions from `sender`, which is also the sender of
+    /// dispatched messages
+    pub fn with_sender(self, sender: H256) -> Self {
+        Self { sender, ..self }
+    }
+
+    /// Check that `message` can be processed with `metadata`, like the
+    /// mailbox and a multisig ISM would
+    fn verify(
+        &self,
+        state: &MockState,
+        config: &MockConfig,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+    ) -> Result<(), RevertReason> {
+        if message.destination != self.chain.domain().id() {
+            return Err(revert("!destination"));
+        }
+        if state.delivered.contains(&message.id()) {
+            return Err(revert("delivered"));
+        }
+        let Some((validators, threshold)) = config.validators.get(&message.origin) else {
+            return Err(revert("!threshold"));
+        };
+        let metadata = MultisigMetadata::parse(metadata).ok_or_else(|| revert("!metadata"))?;
+        if metadata.validators != *validators || metadata.threshold != *threshold {
+            return Err(revert("!validators"));
+        }
+        let root =
+            IncrementalMerkle::branch_root(message.id(), metadata.proof, message.nonce as usize);
+        if root != metadata.checkpoint.root {
+            return Err(revert("!proof"));
+        }
+
+        // Signatures must be by distinct validators, in validator order
+        let checkpoint = Checkpoint {
+            mailbox_domain: message.origin,
+            ..metadata.checkpoint
+        };
+        let mut next_validator = 0;
+        for signature in metadata.signatures {
+            let signer = SignedType {
+                value: checkpoint,
+                signature,
+            }
+            .recover()
+            .map_err(|_| revert("!signature"))?;
+            let index = validators[next_validator..]
+                .iter()
+                .position(|validator| *validator == H256::from(signer))
+                .ok_or_else(|| revert("!threshold"))?;
+            next_validator += index + 1;
+        }
+        Ok(())
+    }
+
+    fn process_gas(message: &HyperlaneMessage) -> u64 {
+        PROCESS_GAS + GAS_PER_BYTE * message.body.len() as u64
+    }
+}
+
+impl HyperlaneContract for MockChainMailbox {
+    fn address(&self) -> H256 {
+        self.address
+    }
+}
+
+impl HyperlaneChain for MockChainMailbox {
+    fn domain(&self) -> &HyperlaneDomain {
+        self.chain.domain()
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        Box::new(MockChainProvider::new(self.chain.clone()))
+    }
+}
+
+#[async_trait]
+impl Mailbox for MockChainMailbox {
+    async fn count(&self) -> ChainResult<u32> {
+        self.chain.check(MockCall::Count)?;
+        Ok(self
+            .chain
+            .view(self.chain.head(), |state, _| state.tree.count() as u32))
+    }
+
+    async fn delivered(&self, id: H256) -> ChainResult<bool> {
+        self.chain.check(MockCall::Delivered)?;
+        Ok(self
+            .chain
+            .view(self.chain.head(), |state, _| state.delivered.contains(&id)))
+    }
+
+    async fn latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint> {
+        self.chain.check(MockCall::LatestCheckpoint)?;
+        let block = self.chain.head().saturating_sub(finality.blocks());
+        let (root, count) = self
+            .chain
+            .view(block, |state, _| (state.tree.root(), state.tree.count()));
+        // Like the mailbox contract, which returns the index of the last
+        // message and so reverts while there are none
+        let index = count
+            .checked_sub(1)
+            .ok_or_else(|| ChainCommunicationError::Reverted(revert("no messages")))?;
+        Ok(Checkpoint {
+            mailbox_address: self.address,
+            mailbox_domain: self.chain.domain().id(),
+            root,
+            index: index as u32,
+        })
+    }
+
+    async fn default_ism(&self) -> ChainResult<H256> {
+        self.chain.check(MockCall::Ism)?;
+        Ok(self
+            .chain
+            .view(self.chain.head(), |_, config| config.default_ism))
+    }
+
+    async fn recipient_ism(&self, recipient: H256) -> ChainResult<H256> {
+        self.chain.check(MockCall::Ism)?;
+        Ok(self.chain.view(self.chain.head(), |_, config| {
+            config
+                .recipient_isms
+                .get(&recipient)
+                .copied()
+                .unwrap_or(config.default_ism)
+        }))
+    }
+
+    async fn dispatch(
+        &self,
+        destination_domain: u32,
+        recipient: H256,
+        body: &[u8],
+    ) -> ChainResult<(HyperlaneMessage, TxOutcome)> {
+        self.chain.check(MockCall::Dispatch)?;
+        let origin = self.chain.domain().id();
+        let gas = DISPATCH_GAS + GAS_PER_BYTE * body.len() as u64;
+        let (result, outcome) =
+            self.chain
+                .transact(self.sender, self.address, gas, |state, _, events| {
+                    let message = HyperlaneMessage {
+                        version: 0,
+                        nonce: state.tree.count() as u32,
+                        origin,
+                        sender: self.sender,
+                        destination: destination_domain,
+                        recipient,
+                        body: body.to_vec(),
+                    };
+                    state.tree.ingest(message.id());
+                    events.push(MockEvent::Dispatch(message.clone()));
+                    Ok(message)
+                });
+        Ok((result.map_err(ChainCommunicationError::Reverted)?, outcome))
+    }
+
+    async fn process(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+        tx_gas_limit: Option<U256>,
+    ) -> ChainResult<TxOutcome> {
+        self.chain.check(MockCall::Process)?;
+        let gas = Self::process_gas(message);
+        let (_, outcome) =
+            self.chain
+                .transact(self.sender, self.address, gas, |state, config, events| {
+                    if tx_gas_limit.map_or(false, |limit| limit < gas.into()) {
+                        return Err(revert("out of gas"));
+                    }
+                    self.verify(state, config, message, metadata)?;
+                    state.delivered.insert(message.id());
+                    events.push(MockEvent::Process(message.id()));
+                    Ok(())
+                });
+        Ok(outcome)
+    }
+
+    async fn process_estimate_costs(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+    ) -> ChainResult<TxCostEstimate> {
+        self.chain.check(MockCall::EstimateProcess)?;
+        self.chain.view(self.chain.head(), |state, config| {
+            self.verify(state, config, message, metadata)
+                .map_err(ChainCommunicationError::Reverted)?;
+            Ok(TxCostEstimate {
+                gas_limit: Self::process_gas(message).into(),
+                gas_price: config.gas_price,
+            })
+        })
+    }
+
+    fn process_calldata(&self, message: &HyperlaneMessage, metadata: &[u8]) -> Vec<u8> {
+        [
+            (metadata.len() as u32).to_be_bytes().to_vec(),
+            metadata.to_vec(),
+            RawHyperlaneMessage::from(message),
+        ]
+        .concat()
+    }
+}
+
+/// An interchain gas paymaster on a `MockChain`
+#[derive(Debug)]
+pub struct MockChainInterchainGasPaymaster {
+    chain: Arc<MockChain>,
+    address: H256,
+}
+
+impl MockChainInterchainGasPaymaster {
+    /// Create an IGP at `address`
+    pub fn new(chain: Arc<MockChain>, address: H256) -> Self {
+        chain.deploy(address);
+        Self { chain, address }
+    }
+}
+
+impl HyperlaneContract for MockChainInterchainGasPaymaster {
+    fn address(&self) -> H256 {
+        self.address
+    }
+}
+
+impl HyperlaneChain for MockChainInterchainGasPaymaster {
+    fn domain(&self) -> &HyperlaneDomain {
+        self.chain.domain()
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        Box::new(MockChainProvider::new(self.chain.clone()))
+    }
+}
+
+#[async_trait]
+impl InterchainGasPaymaster for MockChainInterchainGasPaymaster {
+    async fn quote_gas_payment(
+        &self,
+        _destination_domain: u32,
+        gas_amount: U256,
+    ) -> ChainResult<U256> {
+        self.chain.check(MockCall::QuoteGasPayment)?;
+        Ok(self.chain.view(self.chain.head(), |_, config| {
+            gas_amount.saturating_mul(config.igp_gas_price)
+        }))
+    }
+
+    async fn pay_for_gas(
+        &self,
+        message_id: H256,
+        _destination_domain: u32,
+        gas_amount: U256,
+        payment: U256,
+        refund_address: H256,
+    ) -> ChainResult<TxOutcome> {
+        self.chain.check(MockCall::PayForGas)?;
+        let (_, outcome) = self.chain.transact(
+            refund_address,
+            self.address,
+            PAY_FOR_GAS_GAS,
+            |state, config, events| {
+                // Any overpayment is refunded
+                let required = gas_amount.saturating_mul(config.igp_gas_price);
+                if payment < required {
+                    return Err(revert("insufficient interchain gas payment"));
+                }
+                state.igp_balance += required;
+                events.push(MockEvent::GasPayment {
+                    message_id,
+                    payment: required,
+                });
+                Ok(())
+            },
+        );
+        Ok(outcome)
+    }
+
+    async fn balance(&self) -> ChainResult<U256> {
+        self.chain.check(MockCall::IgpState)?;
+        Ok(self
+            .chain
+            .view(self.chain.head(), |state, _| state.igp_balance))
+    }
+
+    async fn beneficiary(&self) -> ChainResult<H256> {
+        self.chain.check(MockCall::IgpState)?;
+        Ok(self
+            .chain
+            .view(self.chain.head(), |_, config| config.beneficiary))
+    }
+
+    async fn claim(&self, tx_gas_limit: Option<U256>) -> ChainResult<TxOutcome> {
+        self.chain.check(MockCall::Claim)?;
+        let (_, outcome) =
+            self.chain
+                .transact(H256::zero(), self.address, CLAIM_GAS, |state, _, _| {
+                    if tx_gas_limit.map_or(false, |limit| limit < CLAIM_GAS.into()) {
+                        return Err(revert("out of gas"));
+                    }
+                    state.igp_balance = U256::zero();
+                    Ok(())
+                });
+        Ok(outcome)
+    }
+}
+
+/// A multisig ISM on a `MockChain`. Its validators and thresholds are set
+/// with `MockChain::set_validators`.
+#[derive(Debug)]
+pub struct MockChainMultisigIsm {
+    chain: Arc<MockChain>,
+    address: H256,
+}
+
+impl MockChainMultisigIsm {
+    /// Create a multisig ISM at `address`
+    pub fn new(chain: Arc<MockChain>, address: H256) -> Self {
+        chain.deploy(address);
+        Self { chain, address }
+    }
+}
+
+impl HyperlaneContract for MockChainMultisigIsm {
+    fn address(&self) -> H256 {
+        self.address
+    }
+}
+
+impl HyperlaneChain for MockChainMultisigIsm {
+    fn domain(&self) -> &HyperlaneDomain {
+        self.chain.domain()
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        Box::new(MockChainProvider::new(self.chain.clone()))
+    }
+}
+
+#[async_trait]
+impl MultisigIsm for MockChainMultisigIsm {
+    async fn validators_and_threshold(
+        &self,
+        message: &HyperlaneMessage,
+    ) -> ChainResult<(Vec<H256>, u8)> {
+        self.chain.check(MockCall::ValidatorsAndThreshold)?;
+        Ok(self.chain.view(self.chain.head(), |_, config| {
+            config
+                .validators
+                .get(&message.origin)
+                .cloned()
+                .unwrap_or_default()
+        }))
+    }
+
+    /// Returns the metadata in the layout of the EVM multisig ISM
+    fn format_metadata(
+        &self,
+        validators: &[H256],
+        threshold: u8,
+        checkpoint: &MultisigSignedCheckpoint,
+        proof: &Proof,
+    ) -> Vec<u8> {
+        multisig_ism_metadata(validators, threshold, checkpoint, proof)
+    }
+}
+
+/// Multisig ISM metadata as produced by `multisig_ism_metadata`
+struct MultisigMetadata {
+    /// The signed checkpoint, whose domain is not part of the metadata and is
+    /// filled in by `parse`
+    checkpoint: Checkpoint,
+    proof: [H256; TREE_DEPTH],
+    threshold: u8,
+    signatures: Vec<Signature>,
+    validators: Vec<H256>,
+}
+
+impl MultisigMetadata {
+    fn parse(metadata: &[u8]) -> Option<Self> {
+        let header = metadata.get(..METADATA_HEADER_LEN)?;
+        let threshold = header[METADATA_HEADER_LEN - 1];
+        let signatures_len = threshold as usize * SIGNATURE_LEN;
+        let signatures = metadata.get(METADATA_HEADER_LEN..METADATA_HEADER_LEN + signatures_len)?;
+        let validators = &metadata[METADATA_HEADER_LEN + signatures_len..];
+        if validators.len() % 32 != 0 {
+            return None;
+        }
+
+        let mut proof = [H256::zero(); TREE_DEPTH];
+        for (node, bytes) in proof.iter_mut().zip(header[68..].chunks_exact(32)) {
+            *node = H256::from_slice(bytes);
+        }
+        Some(Self {
+            checkpoint: Checkpoint {
+                root: H256::from_slice(&header[..32]),
+                index: u32::from_be_bytes(header[32..36].try_into().unwrap()),
+                mailbox_address: H256::from_slice(&header[36..68]),
+                mailbox_domain: 0,
+            },
+            proof,
+            threshold,
+            signatures: signatures
+                .chunks_exact(SIGNATURE_LEN)
+                .map(Signature::try_from)
+                .collect::<Result<_, _>>()
+                .ok()?,
+            validators: validators.chunks_exact(32).map(H256::from_slice).collect(),
+        })
+    }
+}
+
+/// A validator announce contract on a `MockChain`
+#[derive(Debug)]
+pub struct MockChainValidatorAnnounce {
+    chain: Arc<MockChain>,
+    address: H256,
+}
+
+impl MockChainValidatorAnnounce {
+    /// Create a validator announce contract at `address`
+    pub fn new(chain: Arc<MockChain>, address: H256) -> Self {
+        chain.deploy(address);
+        Self { chain, address }
+    }
+}
+
+impl HyperlaneContract for MockChainValidatorAnnounce {
+    fn address(&self) -> H256 {
+        self.address
+    }
+}
+
+impl HyperlaneChain for MockChainValidatorAnnounce {
+    fn domain(&self) -> &HyperlaneDomain {
+        self.chain.domain()
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        Box::new(MockChainProvider::new(self.chain.clone()))
+    }
+}
+
+#[async_trait]
+impl ValidatorAnnounce for MockChainValidatorAnnounce {
+    async fn get_announced_storage_locations(
+        &self,
+        validators: &[H256],
+    ) -> ChainResult<Vec<Vec<String>>> {
+        self.chain.check(MockCall::AnnouncedLocations)?;
+        Ok(self.chain.view(self.chain.head(), |state, _| {
+            validators
+                .iter()
+                .map(|v| state.announcements.get(v).cloned().unwrap_or_default())
+                .collect()
+        }))
+    }
+
+    async fn announce(
+        &self,
+        announcement: SignedType<Announcement>,
+        tx_gas_limit: Option<U256>,
+    ) -> ChainResult<TxOutcome> {
+        self.chain.check(MockCall::Announce)?;
+        let validator = H256::from(announcement.value.validator);
+        let (_, outcome) =
+            self.chain
+                .transact(validator, self.address, ANNOUNCE_GAS, |state, _, _| {
+                    if tx_gas_limit.map_or(false, |limit| limit < ANNOUNCE_GAS.into()) {
+                        return Err(revert("out of gas"));
+                    }
+                    if announcement.verify(announcement.value.validator).is_err() {
+                        return Err(revert("!signature"));
+                    }
+                    let locations = state.announcements.entry(validator).or_default();
+                    if locations.contains(&announcement.value.storage_location) {
+                        return Err(revert("replay"));
+                    }
+                    locations.push(announcement.value.storage_location.clone());
+                    Ok(())
+                });
+        Ok(outcome)
+    }
+}
+
+/// A provider for a `MockChain`
+#[derive(Debug, Clone)]
+pub struct MockChainProvider {
+    chain: Arc<MockChain>,
+}
+
+impl MockChainProvider {
+    /// Create a provider for `chain`
+    pub fn new(chain: Arc<MockChain>) -> Self {
+        Self { chain }
+    }
+}
+
+impl HyperlaneChain for MockChainProvider {
+    fn domain(&self) -> &HyperlaneDomain {
+        self.chain.domain()
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        Box::new(self.clone())
+    }
+}
+
+#[async_trait]
+impl HyperlaneProvider for MockChainProvider {
+    async fn get_block_by_hash(&self, hash: &H256) -> ChainResult<BlockInfo> {
+        self.chain.check(MockCall::GetBlock)?;
+        let (number, timestamp) = self
+            .chain
+            .block(hash)
+            .ok_or(HyperlaneProviderError::CouldNotFindObjectByHash(*hash))?;
+        Ok(BlockInfo {
+            hash: *hash,
+            timestamp,
+            number,
+        })
+    }
+
+    async fn get_txn_by_hash(&self, hash: &H256) -> ChainResult<TxnInfo> {
+        self.chain.check(MockCall::GetTxn)?;
+        let transaction = self
+            .chain
+            .transaction(hash)
+            .ok_or(HyperlaneProviderError::CouldNotFindObjectByHash(*hash))?;
+        Ok(TxnInfo {
+            hash: *hash,
+            gas_limit: transaction.gas_used,
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+            gas_price: Some(transaction.gas_price),
+            nonce: transaction.nonce,
+            sender: transaction.sender,
+            recipient: Some(transaction.to),
+            receipt: Some(TxnReceiptInfo {
+                gas_used: transaction.gas_used,
+                // Every block holds a single transaction
+                cumulative_gas_used: transaction.gas_used,
+                effective_gas_price: Some(transaction.gas_price),
+            }),
+        })
+    }
+
+    async fn is_contract(&self, address: &H256) -> ChainResult<bool> {
+        self.chain.check(MockCall::IsContract)?;
+        Ok(self.chain.is_contract(address))
+    }
+}
+
+/// An indexer of the events of a contract on a `MockChain`, used for both
+/// mailboxes and IGPs
+#[derive(Debug)]
+pub struct MockChainIndexer {
+    chain: Arc<MockChain>,
+    address: H256,
+    finality: Finality,
+}
+
+impl MockChainIndexer {
+    /// Create an indexer of the contract at `address`. Only the block count
+    /// of `finality` is used.
+    pub fn new(chain: Arc<MockChain>, address: H256, finality: Finality) -> Self {
+        Self {
+            chain,
+            address,
+            finality,
+        }
+    }
+}
+
+#[async_trait]
+impl Indexer for MockChainIndexer {
+    async fn get_finalized_block_number(&self) -> ChainResult<u32> {
+        self.chain.check(MockCall::FinalizedBlockNumber)?;
+        Ok(self.chain.head().saturating_sub(self.finality.blocks()))
+    }
+}
+
+#[async_trait]
+impl MailboxIndexer for MockChainIndexer {
+    async fn fetch_sorted_messages(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> ChainResult<Vec<(HyperlaneMessage, LogMeta)>> {
+        self.chain.check(MockCall::FetchMessages)?;
+        let mut messages: Vec<(HyperlaneMessage, LogMeta)> = self
+            .chain
+            .events(self.address, from, to)
+            .into_iter()
+            .filter_map(|(event, meta)| match event {
+                MockEvent::Dispatch(message) => Some((message, meta)),
+                _ => None,
+            })
+            .collect();
+        messages.sort_by(|a, b| a.0.nonce.cmp(&b.0.nonce));
+        Ok(messages)
+    }
+
+    async fn fetch_delivered_messages(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> ChainResult<Vec<(H256, LogMeta)>> {
+        self.chain.check(MockCall::FetchDeliveries)?;
+        Ok(self
+            .chain
+            .events(self.address, from, to)
+            .into_iter()
+            .filter_map(|(event, meta)| match event {
+                MockEvent::Process(id) => Some((id, meta)),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl InterchainGasPaymasterIndexer for MockChainIndexer {
+    async fn fetch_gas_payments(
+        &self,
+        from_block: u32,
+        to_block: u32,
+    ) -> ChainResult<Vec<InterchainGasPaymentWithMeta>> {
+        self.chain.check(MockCall::FetchGasPayments)?;
+        Ok(self
+            .chain
+            .events(self.address, from_block, to_block)
+            .into_iter()
+            .filter_map(|(event, meta)| match event {
+                MockEvent::GasPayment {
+                    message_id,
+                    payment,
+                } => Some(InterchainGasPaymentWithMeta {
+                    payment: InterchainGasPayment {
+                        message_id,
+                        payment,
+                    },
+                    meta: InterchainGasPaymentMeta {
+                        transaction_hash: meta.transaction_hash,
+                        log_index: meta.log_index,
+                    },
+                }),
+                _ => None,
+            })
+            .collect())
+    }
+}
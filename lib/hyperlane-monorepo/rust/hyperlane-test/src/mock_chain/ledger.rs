@@ -0,0 +1,513 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ethers::providers::JsonRpcError;
+use ethers::utils::keccak256;
+use lazy_static::lazy_static;
+
+use hyperlane_core::{
+    accumulator::incremental::IncrementalMerkle, ChainCommunicationError, ChainResult, FailureKind,
+    HyperlaneDomain, HyperlaneMessage, LogMeta, RevertReason, TxOutcome, H256, U256,
+};
+
+/// Unix timestamp of the genesis block
+const GENESIS_TIMESTAMP: u64 = 1_600_000_000;
+
+/// Seconds between blocks
+const BLOCK_TIME: u64 = 12;
+
+lazy_static! {
+    /// Chains shared by name within the process
+    static ref CHAINS: Mutex<HashMap<String, Arc<MockChain>>> = Default::default();
+}
+
+/// The calls into a mock chain which can be made to fail with
+/// `MockChain::fail`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockCall {
+    /// `Mailbox::count`
+    Count,
+    /// `Mailbox::delivered`
+    Delivered,
+    /// `Mailbox::latest_checkpoint`
+    LatestCheckpoint,
+    /// `Mailbox::default_ism` and `Mailbox::recipient_ism`
+    Ism,
+    /// `Mailbox::dispatch`
+    Dispatch,
+    /// `Mailbox::process`
+    Process,
+    /// `Mailbox::process_estimate_costs`
+    EstimateProcess,
+    /// `Indexer::get_finalized_block_number`
+    FinalizedBlockNumber,
+    /// `MailboxIndexer::fetch_sorted_messages`
+    FetchMessages,
+    /// `MailboxIndexer::fetch_delivered_messages`
+    FetchDeliveries,
+    /// `InterchainGasPaymasterIndexer::fetch_gas_payments`
+    FetchGasPayments,
+    /// `InterchainGasPaymaster::quote_gas_payment`
+    QuoteGasPayment,
+    /// `InterchainGasPaymaster::pay_for_gas`
+    PayForGas,
+    /// `InterchainGasPaymaster::balance` and
+    /// `InterchainGasPaymaster::beneficiary`
+    IgpState,
+    /// `InterchainGasPaymaster::claim`
+    Claim,
+    /// `MultisigIsm::validators_and_threshold`
+    ValidatorsAndThreshold,
+    /// `ValidatorAnnounce::get_announced_storage_locations`
+    AnnouncedLocations,
+    /// `ValidatorAnnounce::announce`
+    Announce,
+    /// `HyperlaneProvider::get_block_by_hash`
+    GetBlock,
+    /// `HyperlaneProvider::get_txn_by_hash`
+    GetTxn,
+    /// `HyperlaneProvider::is_contract`
+    IsContract,
+}
+
+/// An event emitted by a mock contract
+#[derive(Debug, Clone)]
+pub enum MockEvent {
+    /// A message was dispatched by the mailbox
+    Dispatch(HyperlaneMessage),
+    /// The message with this id was processed by the mailbox
+    Process(H256),
+    /// Gas was paid for a message
+    GasPayment {
+        /// The id of the message
+        message_id: H256,
+        /// The payment in native tokens
+        payment: U256,
+    },
+}
+
+/// A transaction included in a mock block
+#[derive(Debug, Clone)]
+pub struct MockTransaction {
+    /// Hash of the transaction
+    pub hash: H256,
+    /// The account which sent it
+    pub sender: H256,
+    /// The nonce of the sender
+    pub nonce: u64,
+    /// The contract which was called
+    pub to: H256,
+    /// Gas used by the transaction
+    pub gas_used: U256,
+    /// Price paid per unit of gas
+    pub gas_price: U256,
+    /// The reason the transaction reverted, if it did
+    pub revert_reason: Option<RevertReason>,
+    /// Events emitted by the called contract
+    pub events: Vec<MockEvent>,
+}
+
+/// The state of the contracts on a mock chain, which is rolled back by reorgs
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockState {
+    /// The mailbox merkle tree of dispatched message ids
+    pub tree: IncrementalMerkle,
+    /// Ids of processed messages
+    pub delivered: HashSet<H256>,
+    /// Native tokens held by the IGP
+    pub igp_balance: U256,
+    /// Storage locations announced by each validator
+    pub announcements: HashMap<H256, Vec<String>>,
+}
+
+/// Settings of the contracts on a mock chain. Unlike `MockState` these are
+/// set by tests directly and are not affected by reorgs.
+#[derive(Debug, Clone)]
+pub(crate) struct MockConfig {
+    /// The ISM of recipients without one of their own
+    pub default_ism: H256,
+    /// ISMs of specific recipients
+    pub recipient_isms: HashMap<H256, H256>,
+    /// Validators and threshold of the multisig ISM by origin domain
+    pub validators: HashMap<u32, (Vec<H256>, u8)>,
+    /// Native tokens the IGP charges per unit of destination gas
+    pub igp_gas_price: U256,
+    /// Price paid per unit of gas by transactions on this chain
+    pub gas_price: U256,
+    /// Recipient of the fees claimed from the IGP
+    pub beneficiary: H256,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            default_ism: H256::zero(),
+            recipient_isms: HashMap::new(),
+            validators: HashMap::new(),
+            igp_gas_price: U256::one(),
+            gas_price: U256::one(),
+            beneficiary: H256::zero(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockBlock {
+    hash: H256,
+    number: u64,
+    timestamp: u64,
+    transactions: Vec<MockTransaction>,
+    /// State after the block's transactions
+    state: MockState,
+}
+
+#[derive(Debug)]
+struct Ledger {
+    /// The canonical chain, starting at genesis
+    blocks: Vec<MockBlock>,
+    config: MockConfig,
+    contracts: HashSet<H256>,
+    nonces: HashMap<H256, u64>,
+    /// Failures to inject, with the number of times left to inject them
+    failures: Vec<(MockCall, FailureKind, usize)>,
+    /// Number of reorgs so far, which makes replaced blocks' hashes differ
+    reorgs: u64,
+    /// Number of transactions ever sent, including reorged ones
+    transactions: u64,
+}
+
+impl Ledger {
+    fn head(&self) -> &MockBlock {
+        self.blocks.last().expect("chain has a genesis block")
+    }
+
+    /// Append a block with `transactions` which leave the chain in `state`
+    fn mine(&mut self, domain: u32, transactions: Vec<MockTransaction>, state: MockState) {
+        let number = self.blocks.len() as u64;
+        let hash = keccak256(
+            [
+                domain.to_be_bytes().as_slice(),
+                &number.to_be_bytes(),
+                &self.reorgs.to_be_bytes(),
+            ]
+            .concat(),
+        );
+        self.blocks.push(MockBlock {
+            hash: hash.into(),
+            number,
+            timestamp: GENESIS_TIMESTAMP + number * BLOCK_TIME,
+            transactions,
+            state,
+        });
+    }
+}
+
+/// An in-memory chain which mines every transaction into a block of its own,
+/// like a development node with automining.
+///
+/// A chain holds a single deployment of each Hyperlane contract: contracts
+/// built at different addresses on the same chain share their state. Tests
+/// configure the contracts, inject reorgs and failures, and mine blocks
+/// through the methods of this type.
+#[derive(Debug)]
+pub struct MockChain {
+    domain: HyperlaneDomain,
+    ledger: Mutex<Ledger>,
+}
+
+impl MockChain {
+    /// Create a chain with only a genesis block
+    pub fn new(domain: HyperlaneDomain) -> Self {
+        let mut ledger = Ledger {
+            blocks: vec![],
+            config: MockConfig::default(),
+            contracts: HashSet::new(),
+            nonces: HashMap::new(),
+            failures: vec![],
+            reorgs: 0,
+            transactions: 0,
+        };
+        ledger.mine(domain.id(), vec![], MockState::default());
+        Self {
+            domain,
+            ledger: Mutex::new(ledger),
+        }
+    }
+
+    /// The chain shared under `name` within this process, created on first
+    /// use. This lets agents and tests running in the same process connect
+    /// to the same chain.
+    ///
+    /// Panics if the chain already exists with a different domain, which
+    /// means two configurations disagree about the chain.
+    pub fn named(name: &str, domain: HyperlaneDomain) -> Arc<Self> {
+        let chain = CHAINS
+            .lock()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(Self::new(domain.clone())))
+            .clone();
+        assert_eq!(
+            chain.domain, domain,
+            "mock chain {name} already exists with a different domain"
+        );
+        chain
+    }
+
+    /// The domain of the chain
+    pub fn domain(&self) -> &HyperlaneDomain {
+        &self.domain
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap()
+    }
+
+    /// The number of the latest block
+    pub fn head(&self) -> u32 {
+        self.ledger().head().number as u32
+    }
+
+    /// The hash of the canonical block `number`, if it has been mined
+    pub fn block_hash(&self, number: u32) -> Option<H256> {
+        self.ledger()
+            .blocks
+            .get(number as usize)
+            .map(|block| block.hash)
+    }
+
+    /// Mine `count` empty blocks
+    pub fn mine(&self, count: u32) {
+        let mut ledger = self.ledger();
+        for _ in 0..count {
+            let state = ledger.head().state.clone();
+            ledger.mine(self.domain.id(), vec![], state);
+        }
+    }
+
+    /// Replace the latest `depth` blocks with as many empty blocks, rolling
+    /// back the state to before them. The genesis block is never replaced.
+    /// Returns the transactions which were dropped.
+    pub fn reorg(&self, depth: u32) -> Vec<MockTransaction> {
+        let mut ledger = self.ledger();
+        let depth = (depth as usize).min(ledger.blocks.len() - 1);
+        let fork = ledger.blocks.len() - depth;
+        let dropped = ledger.blocks.split_off(fork);
+        ledger.reorgs += 1;
+        for _ in 0..depth {
+            let state = ledger.head().state.clone();
+            ledger.mine(self.domain.id(), vec![], state);
+        }
+        dropped
+            .into_iter()
+            .flat_map(|block| block.transactions)
+            .collect()
+    }
+
+    /// Make the next `times` calls of `call` fail with an error of `kind`,
+    /// in the form a JSON-RPC node would return it
+    pub fn fail(&self, call: MockCall, kind: FailureKind, times: usize) {
+        self.ledger().failures.push((call, kind, times));
+    }
+
+    /// Set the default ISM of the mailbox
+    pub fn set_default_ism(&self, ism: H256) {
+        self.ledger().config.default_ism = ism;
+    }
+
+    /// Set the ISM of a recipient
+    pub fn set_recipient_ism(&self, recipient: H256, ism: H256) {
+        self.ledger().config.recipient_isms.insert(recipient, ism);
+    }
+
+    /// Set the validators and threshold of the multisig ISM for messages
+    /// from `origin`
+    pub fn set_validators(&self, origin: u32, validators: Vec<H256>, threshold: u8) {
+        self.ledger()
+            .config
+            .validators
+            .insert(origin, (validators, threshold));
+    }
+
+    /// Set the native tokens the IGP charges per unit of destination gas
+    pub fn set_igp_gas_price(&self, price: U256) {
+        self.ledger().config.igp_gas_price = price;
+    }
+
+    /// Set the recipient of fees claimed from the IGP
+    pub fn set_beneficiary(&self, beneficiary: H256) {
+        self.ledger().config.beneficiary = beneficiary;
+    }
+
+    /// Record a contract as deployed at `address`
+    pub(crate) fn deploy(&self, address: H256) {
+        self.ledger().contracts.insert(address);
+    }
+
+    /// Whether a contract was deployed at `address`
+    pub(crate) fn is_contract(&self, address: &H256) -> bool {
+        self.ledger().contracts.contains(address)
+    }
+
+    /// Fail if a failure of `call` was injected
+    pub(crate) fn check(&self, call: MockCall) -> ChainResult<()> {
+        let mut ledger = self.ledger();
+        let Some(position) = ledger
+            .failures
+            .iter()
+            .position(|(c, _, times)| *c == call && *times > 0)
+        else {
+            return Ok(());
+        };
+        let failure = &mut ledger.failures[position];
+        failure.2 -= 1;
+        let kind = failure.1;
+        if failure.2 == 0 {
+            ledger.failures.remove(position);
+        }
+        Err(injected_error(kind))
+    }
+
+    /// Read the state at block `number`, or at the head if it has not been
+    /// mined yet
+    pub(crate) fn view<T>(&self, number: u32, f: impl FnOnce(&MockState, &MockConfig) -> T) -> T {
+        let ledger = self.ledger();
+        let block = ledger
+            .blocks
+            .get(number as usize)
+            .unwrap_or_else(|| ledger.head());
+        f(&block.state, &ledger.config)
+    }
+
+    /// Send a transaction from `sender` to the contract at `to` and mine it.
+    /// `f` executes the transaction on a copy of the state at the head,
+    /// pushing any events it emits, and the copy becomes the new state
+    /// unless `f` reverts.
+    pub(crate) fn transact<T>(
+        &self,
+        sender: H256,
+        to: H256,
+        gas_used: u64,
+        f: impl FnOnce(&mut MockState, &MockConfig, &mut Vec<MockEvent>) -> Result<T, RevertReason>,
+    ) -> (Result<T, RevertReason>, TxOutcome) {
+        let mut ledger = self.ledger();
+        let mut state = ledger.head().state.clone();
+        let mut events = vec![];
+        let result = f(&mut state, &ledger.config, &mut events);
+        if result.is_err() {
+            state = ledger.head().state.clone();
+            events.clear();
+        }
+
+        let nonce = ledger.nonces.entry(sender).or_default();
+        let sender_nonce = *nonce;
+        *nonce += 1;
+        let transaction = MockTransaction {
+            hash: keccak256(
+                [
+                    self.domain.id().to_be_bytes().as_slice(),
+                    &ledger.transactions.to_be_bytes(),
+                ]
+                .concat(),
+            )
+            .into(),
+            sender,
+            nonce: sender_nonce,
+            to,
+            gas_used: gas_used.into(),
+            gas_price: ledger.config.gas_price,
+            revert_reason: result.as_ref().err().cloned(),
+            events,
+        };
+        ledger.transactions += 1;
+
+        let outcome = TxOutcome {
+            txid: transaction.hash,
+            executed: result.is_ok(),
+            gas_used: transaction.gas_used,
+            effective_gas_price: Some(transaction.gas_price),
+            block_number: Some(ledger.blocks.len() as u64),
+            revert_reason: transaction.revert_reason.clone(),
+            ..Default::default()
+        };
+        ledger.mine(self.domain.id(), vec![transaction], state);
+        let outcome = TxOutcome {
+            block_hash: Some(ledger.head().hash),
+            ..outcome
+        };
+        (result, outcome)
+    }
+
+    /// The events emitted by the contract at `address` in canonical blocks
+    /// `from` to `to`, with the metadata of each
+    pub(crate) fn events(&self, address: H256, from: u32, to: u32) -> Vec<(MockEvent, LogMeta)> {
+        let ledger = self.ledger();
+        let blocks = ledger
+            .blocks
+            .iter()
+            .skip(from as usize)
+            .take((to as usize + 1).saturating_sub(from as usize));
+        let mut events = vec![];
+        for block in blocks {
+            let mut log_index = 0;
+            for (transaction_index, transaction) in block.transactions.iter().enumerate() {
+                for event in &transaction.events {
+                    if transaction.to == address {
+                        let meta = LogMeta {
+                            address,
+                            block_number: block.number,
+                            block_hash: block.hash,
+                            transaction_hash: transaction.hash,
+                            transaction_index: transaction_index as u64,
+                            log_index: log_index.into(),
+                        };
+                        events.push((event.clone(), meta));
+                    }
+                    log_index += 1;
+                }
+            }
+        }
+        events
+    }
+
+    /// The number and timestamp of the canonical block with `hash`
+    pub(crate) fn block(&self, hash: &H256) -> Option<(u64, u64)> {
+        self.ledger()
+            .blocks
+            .iter()
+            .find(|block| block.hash == *hash)
+            .map(|block| (block.number, block.timestamp))
+    }
+
+    /// The canonical transaction with `hash`
+    pub(crate) fn transaction(&self, hash: &H256) -> Option<MockTransaction> {
+        self.ledger()
+            .blocks
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .find(|transaction| transaction.hash == *hash)
+            .cloned()
+    }
+}
+
+/// The error a JSON-RPC node would return for a failure of `kind`
+fn injected_error(kind: FailureKind) -> ChainCommunicationError {
+    let (code, message) = match kind {
+        FailureKind::Transient => (-32000, "connection timed out"),
+        FailureKind::RateLimited => (-32005, "rate limit exceeded"),
+        FailureKind::NonceConflict => (-32000, "nonce too low"),
+        FailureKind::InsufficientFunds => (-32000, "insufficient funds for gas * price + value"),
+        FailureKind::InvalidConfig => (-32601, "method not found"),
+        FailureKind::Reverted => {
+            return ChainCommunicationError::Reverted(RevertReason::Message(
+                "injected failure".into(),
+            ))
+        }
+        FailureKind::Unknown => (-32603, "internal error"),
+    };
+    ChainCommunicationError::from_other(JsonRpcError {
+        code,
+        message: message.into(),
+        data: None,
+    })
+}
@@ -0,0 +1,253 @@
+//! An in-memory chain implementing the Hyperlane contracts, provider and
+//! indexers, so agents can be run end-to-end in tests without a node.
+//!
+//! Chains are selected in agent settings with the `mock` protocol and are
+//! shared within the process by name, so tests can configure a chain, inject
+//! reorgs and failures, and inspect it through `MockChain::named` while
+//! agents use it.
+
+pub use contracts::*;
+pub use ledger::{MockCall, MockChain, MockEvent, MockTransaction};
+
+mod contracts;
+mod ledger;
+
+/// Mock chain connection configuration
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionConf {
+    /// Name the chain is shared under within the process. Defaults to the
+    /// chain name from the settings.
+    #[serde(default)]
+    pub chain: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use hyperlane_core::{
+        accumulator::{merkle::MerkleTree, TREE_DEPTH},
+        FailureKind, Finality, HyperlaneChain, HyperlaneContract, HyperlaneDomain,
+        HyperlaneDomainProtocol, HyperlaneDomainType, HyperlaneMessage, HyperlaneProvider,
+        HyperlaneSigner, Indexer, InterchainGasPaymaster, InterchainGasPaymasterIndexer, Mailbox,
+        MailboxIndexer, MultisigIsm, MultisigSignedCheckpoint, Signable, SignatureWithSigner, H256,
+        U256,
+    };
+
+    use super::*;
+    use crate::test_utils::TestSigner;
+
+    fn domain(domain_id: u32) -> HyperlaneDomain {
+        HyperlaneDomain::Unknown {
+            domain_id,
+            chain_name: format!("mock{domain_id}"),
+            domain_type: HyperlaneDomainType::LocalTestChain,
+            domain_protocol: HyperlaneDomainProtocol::Mock,
+        }
+    }
+
+    /// Dispatch a message from a new origin chain and return it with the
+    /// metadata to process it on `destination`
+    async fn dispatch_and_sign(destination: &Arc<MockChain>) -> (HyperlaneMessage, Vec<u8>) {
+        let origin = Arc::new(MockChain::new(domain(1000)));
+        let mailbox = MockChainMailbox::new(origin, H256::repeat_byte(1));
+        let (message, _) = mailbox
+            .dispatch(destination.domain().id(), H256::repeat_byte(2), &[1, 2, 3])
+            .await
+            .unwrap();
+
+        let validator = TestSigner::default();
+        let validators = vec![H256::from(validator.eth_address())];
+        destination.set_validators(1000, validators.clone(), 1);
+        let checkpoint = mailbox
+            .latest_checkpoint(Finality::default())
+            .await
+            .unwrap();
+        let signature = validator
+            .sign_hash(&checkpoint.signing_hash())
+            .await
+            .unwrap();
+        let signed = MultisigSignedCheckpoint {
+            checkpoint,
+            signatures: vec![SignatureWithSigner {
+                signature,
+                signer: validator.eth_address(),
+            }],
+        };
+        let proof = MerkleTree::create(&[message.id()], TREE_DEPTH).prove_against_current(0);
+        let ism = MockChainMultisigIsm::new(destination.clone(), H256::repeat_byte(3));
+        let metadata = ism.format_metadata(&validators, 1, &signed, &proof);
+        (message, metadata)
+    }
+
+    #[tokio::test]
+    async fn dispatches_and_indexes_messages() {
+        let chain = Arc::new(MockChain::new(domain(1000)));
+        let mailbox = MockChainMailbox::new(chain.clone(), H256::repeat_byte(1));
+        let indexer = MockChainIndexer::new(chain.clone(), mailbox.address(), Finality::Blocks(1));
+
+        assert!(mailbox
+            .latest_checkpoint(Finality::default())
+            .await
+            .is_err());
+        let (message, outcome) = mailbox.dispatch(2000, H256::zero(), &[1]).await.unwrap();
+        assert!(outcome.executed);
+        assert_eq!(outcome.block_number, Some(1));
+        assert_eq!(message.nonce, 0);
+        assert_eq!(mailbox.count().await.unwrap(), 1);
+
+        // The message is only final once another block is mined
+        assert_eq!(indexer.get_finalized_block_number().await.unwrap(), 0);
+        assert!(mailbox
+            .latest_checkpoint(Finality::Blocks(1))
+            .await
+            .is_err());
+        chain.mine(1);
+        let tip = indexer.get_finalized_block_number().await.unwrap();
+        let messages = indexer.fetch_sorted_messages(0, tip).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0.id(), message.id());
+        assert_eq!(messages[0].1.transaction_hash, outcome.txid);
+
+        let provider = mailbox.provider();
+        let block = provider
+            .get_block_by_hash(&messages[0].1.block_hash)
+            .await
+            .unwrap();
+        assert_eq!(block.number, 1);
+        let txn = provider.get_txn_by_hash(&outcome.txid).await.unwrap();
+        assert_eq!(txn.recipient, Some(mailbox.address()));
+        assert!(provider.is_contract(&mailbox.address()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn processes_messages_with_valid_metadata() {
+        let chain = Arc::new(MockChain::new(domain(2000)));
+        let mailbox = MockChainMailbox::new(chain.clone(), H256::repeat_byte(1));
+        let indexer = MockChainIndexer::new(chain.clone(), mailbox.address(), Finality::default());
+        let (message, metadata) = dispatch_and_sign(&chain).await;
+
+        let mut bad_metadata = metadata.clone();
+        bad_metadata[0] ^= 1;
+        assert!(mailbox
+            .process_estimate_costs(&message, &bad_metadata)
+            .await
+            .unwrap_err()
+            .revert_reason()
+            .is_some());
+        let outcome = mailbox
+            .process(&message, &bad_metadata, None)
+            .await
+            .unwrap();
+        assert!(!outcome.executed);
+        assert!(outcome.revert_reason.is_some());
+
+        let estimate = mailbox
+            .process_estimate_costs(&message, &metadata)
+            .await
+            .unwrap();
+        let outcome = mailbox
+            .process(&message, &metadata, Some(estimate.gas_limit))
+            .await
+            .unwrap();
+        assert!(outcome.executed);
+        assert!(mailbox.delivered(message.id()).await.unwrap());
+
+        let tip = indexer.get_finalized_block_number().await.unwrap();
+        let delivered = indexer.fetch_delivered_messages(0, tip).await.unwrap();
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].0, message.id());
+
+        // Messages can only be processed once
+        let outcome = mailbox.process(&message, &metadata, None).await.unwrap();
+        assert!(!outcome.executed);
+    }
+
+    #[tokio::test]
+    async fn pays_for_gas() {
+        let chain = Arc::new(MockChain::new(domain(1000)));
+        let igp = MockChainInterchainGasPaymaster::new(chain.clone(), H256::repeat_byte(4));
+        let indexer = MockChainIndexer::new(chain.clone(), igp.address(), Finality::default());
+        chain.set_igp_gas_price(2.into());
+
+        let quote = igp.quote_gas_payment(2000, 100.into()).await.unwrap();
+        assert_eq!(quote, U256::from(200));
+        let underpaid = igp
+            .pay_for_gas(H256::zero(), 2000, 100.into(), 199.into(), H256::zero())
+            .await
+            .unwrap();
+        assert!(!underpaid.executed);
+        let outcome = igp
+            .pay_for_gas(H256::zero(), 2000, 100.into(), 300.into(), H256::zero())
+            .await
+            .unwrap();
+        assert!(outcome.executed);
+        assert_eq!(igp.balance().await.unwrap(), quote);
+
+        let tip = indexer.get_finalized_block_number().await.unwrap();
+        let payments = indexer.fetch_gas_payments(0, tip).await.unwrap();
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].payment.payment, quote);
+
+        assert!(igp.claim(None).await.unwrap().executed);
+        assert_eq!(igp.balance().await.unwrap(), U256::zero());
+    }
+
+    #[tokio::test]
+    async fn reorgs_roll_back_state() {
+        let chain = Arc::new(MockChain::new(domain(1000)));
+        let mailbox = MockChainMailbox::new(chain.clone(), H256::repeat_byte(1));
+        let indexer = MockChainIndexer::new(chain.clone(), mailbox.address(), Finality::default());
+
+        mailbox.dispatch(2000, H256::zero(), &[1]).await.unwrap();
+        let (reorged, _) = mailbox.dispatch(2000, H256::zero(), &[2]).await.unwrap();
+        let hash = chain.block_hash(2).unwrap();
+
+        let dropped = chain.reorg(1);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(chain.head(), 2);
+        assert_ne!(chain.block_hash(2), Some(hash));
+        assert_eq!(mailbox.count().await.unwrap(), 1);
+        assert!(mailbox.provider().get_block_by_hash(&hash).await.is_err());
+
+        // The nonce of the dropped message is reused
+        let (message, _) = mailbox.dispatch(2000, H256::zero(), &[3]).await.unwrap();
+        assert_eq!(message.nonce, reorged.nonce);
+        let messages = indexer
+            .fetch_sorted_messages(0, chain.head())
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].0.id(), message.id());
+    }
+
+    #[tokio::test]
+    async fn injects_failures() {
+        let chain = MockChain::named("injects_failures", domain(1000));
+        let mailbox = MockChainMailbox::new(chain.clone(), H256::repeat_byte(1));
+        chain.fail(MockCall::Count, FailureKind::RateLimited, 2);
+        chain.fail(MockCall::Dispatch, FailureKind::InsufficientFunds, 1);
+
+        for _ in 0..2 {
+            let err = mailbox.count().await.unwrap_err();
+            assert_eq!(err.kind(), FailureKind::RateLimited);
+        }
+        assert_eq!(mailbox.count().await.unwrap(), 0);
+
+        let err = mailbox.dispatch(2000, H256::zero(), &[]).await.unwrap_err();
+        assert_eq!(err.kind(), FailureKind::InsufficientFunds);
+        assert_eq!(chain.head(), 0);
+
+        // Chains are shared by name
+        let shared = MockChain::named("injects_failures", domain(1000));
+        assert!(Arc::ptr_eq(&chain, &shared));
+    }
+
+    #[test]
+    #[should_panic(expected = "already exists with a different domain")]
+    fn rejects_named_chain_with_other_domain() {
+        MockChain::named("rejects_other_domain", domain(1000));
+        MockChain::named("rejects_other_domain", domain(2000));
+    }
+}
@@ -1,11 +1,12 @@
 #![allow(non_snake_case)]
 
 use std::future::Future;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use mockall::mock;
 
-use hyperlane_core::{ChainResult, SyncBlockRangeCursor};
+use hyperlane_core::{ChainCommunicationError, ChainResult, SyncBlockRangeCursor};
 
 mock! {
     pub SyncBlockRangeCursor {
@@ -14,6 +15,12 @@ mock! {
         pub fn _current_position(&self) -> u32 {}
 
         pub fn _backtrack(&mut self, start_from: u32) {}
+
+        pub fn _is_caught_up(&self) -> bool {}
+
+        pub fn _range_fetched(&mut self, events: usize, elapsed: Duration) {}
+
+        pub fn _range_failed(&mut self, error: &ChainCommunicationError) {}
     }
 }
 
@@ -30,4 +37,16 @@ impl SyncBlockRangeCursor for MockSyncBlockRangeCursor {
     fn backtrack(&mut self, start_from: u32) {
         self._backtrack(start_from)
     }
+
+    fn is_caught_up(&self) -> bool {
+        self._is_caught_up()
+    }
+
+    fn range_fetched(&mut self, events: usize, elapsed: Duration) {
+        self._range_fetched(events, elapsed)
+    }
+
+    fn range_failed(&mut self, error: &ChainCommunicationError) {
+        self._range_failed(error)
+    }
 }
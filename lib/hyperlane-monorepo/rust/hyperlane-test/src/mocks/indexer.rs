@@ -3,7 +3,9 @@
 use async_trait::async_trait;
 use mockall::*;
 
-use hyperlane_core::{ChainResult, HyperlaneMessage, Indexer, LogMeta, MailboxIndexer, H256};
+use hyperlane_core::{
+    ChainResult, EventStream, HyperlaneMessage, Indexer, LogMeta, MailboxIndexer, H256,
+};
 
 mock! {
     pub Indexer {
@@ -24,6 +26,7 @@ mock! {
         pub fn _get_finalized_block_number(&self) -> ChainResult<u32> {}
         pub fn _fetch_sorted_messages(&self, from: u32, to: u32) -> ChainResult<Vec<(HyperlaneMessage, LogMeta)>> {}
         pub fn _fetch_delivered_messages(&self, from: u32, to: u32) -> ChainResult<Vec<(H256, LogMeta)>> {}
+        pub fn _subscribe_messages(&self) -> ChainResult<Option<EventStream<(HyperlaneMessage, LogMeta)>>> {}
     }
 }
 
@@ -57,4 +60,10 @@ impl MailboxIndexer for MockHyperlaneIndexer {
     ) -> ChainResult<Vec<(H256, LogMeta)>> {
         self._fetch_delivered_messages(from, to)
     }
+
+    async fn subscribe_messages(
+        &self,
+    ) -> ChainResult<Option<EventStream<(HyperlaneMessage, LogMeta)>>> {
+        self._subscribe_messages()
+    }
 }
@@ -0,0 +1,100 @@
+#![allow(non_snake_case)]
+
+use async_trait::async_trait;
+use mockall::*;
+
+use hyperlane_core::*;
+
+mock! {
+    pub InterchainGasPaymasterContract {
+        // InterchainGasPaymaster
+        pub fn _address(&self) -> H256 {}
+
+        pub fn _domain(&self) -> &HyperlaneDomain {}
+
+        pub fn _provider(&self) -> Box<dyn HyperlaneProvider> {}
+
+        pub fn _quote_gas_payment(
+            &self,
+            destination_domain: u32,
+            gas_amount: U256,
+        ) -> ChainResult<U256> {}
+
+        pub fn _pay_for_gas(
+            &self,
+            message_id: H256,
+            destination_domain: u32,
+            gas_amount: U256,
+            payment: U256,
+            refund_address: H256,
+        ) -> ChainResult<TxOutcome> {}
+
+        pub fn _balance(&self) -> ChainResult<U256> {}
+
+        pub fn _beneficiary(&self) -> ChainResult<H256> {}
+
+        pub fn _claim(&self, tx_gas_limit: Option<U256>) -> ChainResult<TxOutcome> {}
+    }
+}
+
+impl std::fmt::Debug for MockInterchainGasPaymasterContract {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MockInterchainGasPaymasterContract")
+    }
+}
+
+#[async_trait]
+impl InterchainGasPaymaster for MockInterchainGasPaymasterContract {
+    async fn quote_gas_payment(
+        &self,
+        destination_domain: u32,
+        gas_amount: U256,
+    ) -> ChainResult<U256> {
+        self._quote_gas_payment(destination_domain, gas_amount)
+    }
+
+    async fn pay_for_gas(
+        &self,
+        message_id: H256,
+        destination_domain: u32,
+        gas_amount: U256,
+        payment: U256,
+        refund_address: H256,
+    ) -> ChainResult<TxOutcome> {
+        self._pay_for_gas(
+            message_id,
+            destination_domain,
+            gas_amount,
+            payment,
+            refund_address,
+        )
+    }
+
+    async fn balance(&self) -> ChainResult<U256> {
+        self._balance()
+    }
+
+    async fn beneficiary(&self) -> ChainResult<H256> {
+        self._beneficiary()
+    }
+
+    async fn claim(&self, tx_gas_limit: Option<U256>) -> ChainResult<TxOutcome> {
+        self._claim(tx_gas_limit)
+    }
+}
+
+impl HyperlaneChain for MockInterchainGasPaymasterContract {
+    fn domain(&self) -> &HyperlaneDomain {
+        self._domain()
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        self._provider()
+    }
+}
+
+impl HyperlaneContract for MockInterchainGasPaymasterContract {
+    fn address(&self) -> H256 {
+        self._address()
+    }
+}
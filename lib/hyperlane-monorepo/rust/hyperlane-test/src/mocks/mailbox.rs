@@ -1,7 +1,5 @@
 #![allow(non_snake_case)]
 
-use std::num::NonZeroU64;
-
 use async_trait::async_trait;
 use mockall::*;
 
@@ -30,13 +28,20 @@ mock! {
 
         pub fn _count(&self) -> ChainResult<u32> {}
 
-        pub fn _latest_checkpoint(&self, maybe_lag: Option<NonZeroU64>) -> ChainResult<Checkpoint> {}
+        pub fn _latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint> {}
 
         pub fn _default_ism(&self) -> ChainResult<H256> {}
         pub fn _recipient_ism(&self, recipient: H256) -> ChainResult<H256> {}
 
         pub fn _delivered(&self, id: H256) -> ChainResult<bool> {}
 
+        pub fn _dispatch(
+            &self,
+            destination_domain: u32,
+            recipient: H256,
+            body: &[u8],
+        ) -> ChainResult<(HyperlaneMessage, TxOutcome)> {}
+
         pub fn process(
             &self,
             message: &HyperlaneMessage,
@@ -70,8 +75,8 @@ impl Mailbox for MockMailboxContract {
         self._count()
     }
 
-    async fn latest_checkpoint(&self, maybe_lag: Option<NonZeroU64>) -> ChainResult<Checkpoint> {
-        self._latest_checkpoint(maybe_lag)
+    async fn latest_checkpoint(&self, finality: Finality) -> ChainResult<Checkpoint> {
+        self._latest_checkpoint(finality)
     }
 
     async fn default_ism(&self) -> ChainResult<H256> {
@@ -86,6 +91,15 @@ impl Mailbox for MockMailboxContract {
         self._delivered(id)
     }
 
+    async fn dispatch(
+        &self,
+        destination_domain: u32,
+        recipient: H256,
+        body: &[u8],
+    ) -> ChainResult<(HyperlaneMessage, TxOutcome)> {
+        self._dispatch(destination_domain, recipient, body)
+    }
+
     async fn process(
         &self,
         message: &HyperlaneMessage,
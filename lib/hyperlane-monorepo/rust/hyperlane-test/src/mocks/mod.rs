@@ -1,6 +1,9 @@
 /// Mock mailbox contract
 pub mod mailbox;
 
+/// Mock interchain gas paymaster contract
+pub mod interchain_gas;
+
 /// Mock indexer
 pub mod indexer;
 
@@ -8,4 +11,5 @@ pub mod indexer;
 pub mod cursor;
 
 pub use indexer::MockIndexer;
+pub use interchain_gas::MockInterchainGasPaymasterContract;
 pub use mailbox::MockMailboxContract;
@@ -1,13 +1,64 @@
+use async_trait::async_trait;
+use ethers::prelude::Signature;
+use ethers::signers::LocalWallet;
 use futures_util::Future;
 use rocksdb::Options;
 use tempfile::TempDir;
 
 use hyperlane_core::db::DB;
+use hyperlane_core::{
+    Checkpoint, HyperlaneSigner, HyperlaneSignerError, HyperlaneSignerExt, SignedCheckpoint, H160,
+    H256,
+};
+use hyperlane_ethereum::Signers;
+
+/// Private key of the validator used by tests
+pub const TEST_VALIDATOR_KEY: &str =
+    "1111111111111111111111111111111111111111111111111111111111111111";
+
+/// A local signer which signs with the agents' Ethereum signer. Uses
+/// `TEST_VALIDATOR_KEY` by default.
+#[derive(Debug, Clone)]
+pub struct TestSigner(Signers);
+
+impl TestSigner {
+    /// Create a signer with the given hex private key
+    pub fn from_key(key: &str) -> Self {
+        Self(key.parse::<LocalWallet>().expect("invalid test key").into())
+    }
+}
+
+impl Default for TestSigner {
+    fn default() -> Self {
+        Self::from_key(TEST_VALIDATOR_KEY)
+    }
+}
+
+#[async_trait]
+impl HyperlaneSigner for TestSigner {
+    fn eth_address(&self) -> H160 {
+        self.0.eth_address()
+    }
+
+    async fn sign_hash(&self, hash: &H256) -> Result<Signature, HyperlaneSignerError> {
+        self.0.sign_hash(hash).await
+    }
+}
+
+/// A checkpoint at `index` of a mailbox on the test1 domain, signed by the
+/// default `TestSigner`
+pub async fn signed_checkpoint(index: u32) -> SignedCheckpoint {
+    let checkpoint = Checkpoint {
+        mailbox_address: H256::repeat_byte(1),
+        mailbox_domain: 13371,
+        root: H256::repeat_byte(2),
+        index,
+    };
+    TestSigner::default().sign(checkpoint).await.unwrap()
+}
 
 pub fn setup_db(db_path: String) -> DB {
-    let mut opts = Options::default();
-    opts.create_if_missing(true);
-    rocksdb::DB::open(&opts, db_path)
+    rocksdb::DB::open(&DB::options(), db_path)
         .expect("Failed to open db path")
         .into()
 }
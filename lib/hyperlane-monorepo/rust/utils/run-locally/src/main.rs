@@ -9,6 +9,8 @@
 //! - `E2E_KATHY_ROUNDS`: Number of rounds to run kathy for. Defaults to 4 if CI mode is enabled.
 //! - `E2E_LOG_ALL`: Log all output instead of writing to log files. Defaults to true if CI mode,
 //! else false.
+//! - `E2E_ANVIL`: true/false, runs a local anvil node instead of the hardhat node. Defaults to
+//! false.
 
 use std::{
     env,
@@ -109,6 +111,10 @@ fn main() -> ExitCode {
     // TODO: Plumb via environment variable or something.
     let kathy_messages_per_round = 10;
 
+    let use_anvil = env::var("E2E_ANVIL")
+        .map(|k| k.parse::<bool>().unwrap())
+        .unwrap_or_default();
+
     let log_all = env::var("E2E_LOG_ALL")
         .map(|k| k.parse::<bool>().unwrap())
         .unwrap_or(ci_mode);
@@ -162,8 +168,7 @@ fn main() -> ExitCode {
         "HYP_RELAYER_ORIGINCHAINNAME" => "test1",
         "HYP_RELAYER_DESTINATIONCHAINNAMES" => "test2,test3",
         "HYP_RELAYER_WHITELIST" => r#"[{"senderAddress": "*", "destinationDomain": ["13372", "13373"], "recipientAddress": "*"}]"#,
-        "HYP_RELAYER_MULTISIGCHECKPOINTSYNCER_CHECKPOINTSYNCERS_0x70997970c51812dc3a010c7d01b50e0d17dc79c8_TYPE" => "localStorage",
-        "HYP_RELAYER_MULTISIGCHECKPOINTSYNCER_CHECKPOINTSYNCERS_0x70997970c51812dc3a010c7d01b50e0d17dc79c8_PATH" => checkpoints_dir.path().to_str().unwrap(),
+        "HYP_RELAYER_ALLOWLOCALCHECKPOINTSYNCERS" => "true",
     };
 
     let validator_env = hashmap! {
@@ -179,6 +184,8 @@ fn main() -> ExitCode {
         "HYP_BASE_TRACING_FMT" => "pretty",
         "HYP_BASE_TRACING_LEVEL" => "info",
         "HYP_BASE_DB" => validator_db.to_str().unwrap(),
+        "HYP_BASE_CHAINS_TEST1_SIGNER_KEY" => "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+        "HYP_BASE_CHAINS_TEST1_SIGNER_TYPE" => "hexKey",
         "HYP_VALIDATOR_ORIGINCHAINNAME" => "test1",
         "HYP_VALIDATOR_VALIDATOR_KEY" => "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
         "HYP_VALIDATOR_VALIDATOR_TYPE" => "hexKey",
@@ -217,10 +224,16 @@ fn main() -> ExitCode {
     );
 
     let mut state = State::default();
-    println!("Launching hardhat...");
-    let mut node = Command::new("yarn");
-    node.args(["hardhat", "node"])
-        .current_dir("../typescript/infra");
+    let mut node = if use_anvil {
+        println!("Launching anvil...");
+        Command::new("anvil")
+    } else {
+        println!("Launching hardhat...");
+        let mut node = Command::new("yarn");
+        node.args(["hardhat", "node"]);
+        node
+    };
+    node.current_dir("../typescript/infra");
     if log_all {
         // TODO: should we log this? It seems way too verbose to be useful
         // node.stdout(Stdio::piped());
@@ -322,22 +335,6 @@ fn main() -> ExitCode {
         Some("../typescript/sdk"),
     );
 
-    // Register the validator announcement
-    println!("Announcing validator...");
-    let mut announce = Command::new("yarn");
-    announce.arg("announce");
-    announce.args([
-        "--checkpointsdir",
-        checkpoints_dir.path().to_str().unwrap(),
-        "--chain",
-        "test1",
-    ]);
-    announce
-        .current_dir("../typescript/infra")
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("Failed to announce validator");
-
     println!("Setup complete! Agents running in background...");
     println!("Ctrl+C to end execution...");
 